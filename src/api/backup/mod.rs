@@ -4,11 +4,17 @@ extern crate bodyparser;
 extern crate rustc_serialize;
 extern crate chrono;
 
+pub mod restore;
+pub mod sandbox;
+pub mod scheduler;
+pub mod verify;
+
 use chrono::*;
 use iron::status;
 use iron::prelude::*;
 use iron::mime::Mime;
 use router::Router;
+use urlencoded::{UrlEncodedQuery, QueryMap, UrlDecodingError};
 use rustc_serialize::json;
 use rusqlite::DatabaseName;
 
@@ -18,12 +24,27 @@ use std::error::Error;
 use std::path::{Path, PathBuf};
 
 use ::api::{GrokDB, ErrorResponse};
+use ::api::decks::restify::deck_exists;
+use ::api::export::ExportError;
+use ::api::export::extract::extract_deck;
+use self::restore::{BackupError, BackupsResponse, list_backups, find_backup, restore_backup};
+use self::sandbox::{sandboxed_path, ensure_within};
+use self::scheduler::{BackupKind, BackupLogResponse, record_backup, get_backup_log};
+use self::verify::{finalize_backup, verify_backup};
+
+// timestamp of backups named with a timestamp
+pub static BACKUP_TIMESTAMP_FORMAT: &'static str = "%a-%b-%e--%H-%M-%S-%Y";
+
+static DEFAULT_BACKUP_LOG_LIMIT: i64 = 100;
 
 #[derive(Debug, Clone, RustcDecodable)]
 pub struct BackupRequest {
     name: Option<String>,
     dest_path: Option<String>,
-    with_timestamp: Option<bool>
+    with_timestamp: Option<bool>,
+
+    // write the backup as a gzip archive (see verify module)
+    compress: Option<bool>
 }
 
 impl BackupRequest {
@@ -37,6 +58,15 @@ impl BackupRequest {
         return true;
     }
 
+    fn compress(&self) -> bool {
+
+        if self.compress.is_some() {
+            return self.compress.unwrap();
+        }
+
+        return false;
+    }
+
     fn get_name(&self, default_name: &String) -> String {
 
         if self.name.is_some() {
@@ -55,20 +85,22 @@ impl BackupRequest {
         return format!("./");
     }
 
-    fn get_path(&self, default_name: &String) -> String {
+    fn get_file_name(&self, default_name: &String) -> String {
 
-        let name: String = {
+        let name: String = self.get_name(default_name);
 
-            let __name: String = self.get_name(default_name);
+        if self.with_timestamp() {
+            return format!("{}-{}.db",
+                name,
+                UTC::now().format(BACKUP_TIMESTAMP_FORMAT).to_string());
+        }
 
-            if self.with_timestamp() {
-                format!("{}-{}.db",
-                    __name,
-                    UTC::now().format("%a-%b-%e--%H-%M-%S-%Y").to_string())
-            } else {
-                format!("{}.db", __name)
-            }
-        };
+        return format!("{}.db", name);
+    }
+
+    fn get_path(&self, default_name: &String) -> String {
+
+        let name: String = self.get_file_name(default_name);
 
         let path = format!("{}/{}", self.get_dest(), name);
         let path = Path::new(&path);
@@ -78,6 +110,32 @@ impl BackupRequest {
 
         return normalized.to_string();
     }
+
+    // path of the requested backup.
+    // dest_path is confined to the backup directory, unless --unsafe-backup-paths is given
+    // (see sandbox module); where it's any directory, and defaults to the backup directory.
+    fn resolve_path(&self, grokdb: &GrokDB, default_name: &String) -> Result<String, BackupError> {
+
+        if grokdb.unsafe_backup_paths {
+
+            let mut request = self.clone();
+
+            if request.dest_path.is_none() {
+                request.dest_path = Some(get_backup_dir(grokdb));
+            }
+
+            return Ok(request.get_path(default_name));
+        }
+
+        let backup_dir: String = get_backup_dir(grokdb);
+        let file_name: String = self.get_file_name(default_name);
+
+        let dest_path: Option<&str> = self.dest_path.as_ref().map(|dest_path| dest_path.trim());
+
+        let path: PathBuf = try!(sandboxed_path(&backup_dir, dest_path, &file_name));
+
+        return Ok(path.to_string_lossy().into_owned());
+    }
 }
 
 #[derive(Debug, RustcEncodable)]
@@ -112,8 +170,9 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                 Ok(None) => {
                     BackupRequest {
                         name: Some(format!("{}", grokdb.base_db_name)),
-                        dest_path: Some(format!("./")),
-                        with_timestamp: Some(true)
+                        dest_path: None,
+                        with_timestamp: Some(true),
+                        compress: None
                     }
                 },
 
@@ -132,34 +191,260 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                 }
             };
 
-            let mut backup_request = backup_request;
+            let dest_path: String = match backup_request.resolve_path(grokdb, &grokdb.base_db_name) {
+                Err(why) => {
+                    return backup_error_response(why);
+                },
+                Ok(dest_path) => dest_path
+            };
+
+            // back up database
+
+            let started_at: i64 = UTC::now().timestamp();
 
-            // if no dest_path is given, fallback to any default backup_base_dest
+            let copied: Result<(), BackupError> = {
+                let db_conn_guard = grokdb.decks.db.lock().unwrap();
+                let ref db_conn = *db_conn_guard;
 
-            match grokdb.backup_base_dest {
-                None => {/* continue */},
-                Some(ref backup_base_dest) => {
+                match db_conn.backup(DatabaseName::Main, &dest_path, None) {
+                    Err(why) => Err(BackupError::Sqlite(why)),
+                    Ok(_) => Ok(())
+                }
+            };
+
+            // the backup is verified once taken, and may be compressed; without holding the db
+            // connection lock, since the backup is a copy
+            let backup_result: Result<String, BackupError> = match copied {
+                Err(why) => Err(why),
+                Ok(_) => finalize_backup(&dest_path, backup_request.compress())
+            };
 
-                    if backup_request.dest_path.is_none() {
-                        backup_request.dest_path = Some(format!("{}", backup_base_dest));
+            let recorded = {
+                let db_conn_guard = grokdb.decks.db.lock().unwrap();
+                let ref db_conn = *db_conn_guard;
+
+                match backup_result {
+                    Err(ref why) => record_backup(db_conn, BackupKind::Manual, &dest_path, started_at, Some(format!("{}", why))),
+                    Ok(ref dest_file) => record_backup(db_conn, BackupKind::Manual, dest_file, started_at, None)
+                }
+            };
+
+            match recorded {
+                Err(why) => {
+                    return backup_error_response(BackupError::Query(why));
+                },
+                _ => {/* backup recorded */}
+            }
+
+            let dest_path: String = match backup_result {
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let ref user_message = format!("{}", why);
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: user_message,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(dest_file) => dest_file
+            };
+
+            let response = BackupResponse {
+                dest_file: dest_path
+            }.to_json();
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response)));
+        }
+    });
+
+    // extract the deck and its descendents into a standalone grokdb database.
+    // request body is the same as of PUT /backup; the default name is <database>-deck-<deck_id>.
+    router.post("/decks/:deck_id/extract", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            // parse json
+
+            let extract_request = req.get::<bodyparser::Struct<BackupRequest>>();
+
+            let extract_request: BackupRequest = match extract_request {
+                Ok(Some(extract_request)) => extract_request,
+
+                Ok(None) => {
+                    BackupRequest {
+                        name: None,
+                        dest_path: None,
+                        with_timestamp: Some(true),
+                        compress: None
                     }
+                },
+
+                Err(err) => {
+
+                    let ref reason = format!("{:?}", err);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: err.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
                 }
+            };
+
+            // fetch and parse requested deck id
+
+            let deck_id = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
             }
 
-            let backup_request = backup_request;
+            let default_name: String = format!("{}-deck-{}", grokdb.base_db_name, deck_id);
 
-            // back up database
+            let dest_path: String = match extract_request.resolve_path(grokdb, &default_name) {
+                Err(why) => {
+                    return backup_error_response(why);
+                },
+                Ok(dest_path) => dest_path
+            };
+
+            // extract deck
 
             let db_conn_guard = grokdb.decks.db.lock().unwrap();
             let ref db_conn = *db_conn_guard;
 
-            let dest_path: String = backup_request.get_path(&grokdb.base_db_name);
+            let response = match extract_deck(db_conn, deck_id, &dest_path) {
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+
+                    let res_code = match why {
+                        ExportError::Invalid(_) => status::Conflict,
+                        _ => status::InternalServerError
+                    };
+
+                    let ref user_message = format!("{}", why);
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: user_message,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(response) => response
+            };
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response.to_json())));
+        }
+    });
+
+    // list backups within the default backup directory
+    router.get("/backups", {
+        let grokdb = grokdb.clone();
+        move |_: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let backup_dir: String = get_backup_dir(grokdb);
+
+            let backups = match list_backups(&backup_dir, &grokdb.decks.db.database_name) {
+                Err(why) => {
+                    return backup_error_response(why);
+                },
+                Ok(backups) => backups
+            };
+
+            let response = BackupsResponse {
+                backups: backups
+            }.to_json();
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response)));
+        }
+    });
+
+    // latest backups that were taken, whether scheduled or requested.
+    // queries:
+    // - limit: number of entries (default 100)
+    router.get("/backups/log", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let limit: i64 = match req.get_ref::<UrlEncodedQuery>() {
+
+                Ok(ref hashmap) => {
+
+                    let hashmap: &QueryMap = hashmap;
+
+                    match hashmap.get("limit") {
+                        Some(values) if values.len() > 0 => {
+                            match values[0].trim().parse::<u64>() {
+                                Ok(limit) if limit > 0 => limit as i64,
+                                _ => {
+
+                                    let ref reason = format!("invalid limit query; expected positive integer");
+                                    let res_code = status::BadRequest;
+
+                                    let err_response = ErrorResponse {
+                                        status: res_code,
+                                        developerMessage: reason,
+                                        userMessage: reason,
+                                    }.to_json();
+
+                                    return Ok(Response::with((res_code, err_response)));
+                                }
+                            }
+                        },
+                        _ => DEFAULT_BACKUP_LOG_LIMIT
+                    }
+                },
+
+                Err(UrlDecodingError::EmptyQuery) => {
+                    DEFAULT_BACKUP_LOG_LIMIT
+                },
 
-            match db_conn.backup(DatabaseName::Main, &dest_path, None) {
                 Err(why) => {
 
                     let ref reason = format!("{:?}", why);
-                    let res_code = status::InternalServerError;
+                    let res_code = status::BadRequest;
 
                     let err_response = ErrorResponse {
                         status: res_code,
@@ -168,14 +453,23 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                     }.to_json();
 
                     return Ok(Response::with((res_code, err_response)));
-                },
-                _ => {
-                    // backup complete
                 }
             };
 
-            let response = BackupResponse {
-                dest_file: dest_path
+            let entries = {
+                let db_conn_guard = grokdb.decks.db.lock().unwrap();
+                let ref db_conn = *db_conn_guard;
+
+                match get_backup_log(db_conn, limit) {
+                    Err(why) => {
+                        return backup_error_response(BackupError::Query(why));
+                    },
+                    Ok(entries) => entries
+                }
+            };
+
+            let response = BackupLogResponse {
+                entries: entries
             }.to_json();
 
             let content_type = "application/json".parse::<Mime>().unwrap();
@@ -183,4 +477,115 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
             return Ok(Response::with((content_type, status::Ok, response)));
         }
     });
+
+    // verify a backup within the default backup directory (see verify module)
+    router.get("/backups/:name/verify", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let name: &str = req.extensions.get::<Router>().unwrap().find("name").unwrap();
+
+            let backup_dir: String = get_backup_dir(grokdb);
+
+            let backup_path: PathBuf = match find_backup(&backup_dir, name) {
+                Err(why) => {
+                    return backup_error_response(why);
+                },
+                Ok(backup_path) => backup_path
+            };
+
+            if !grokdb.unsafe_backup_paths {
+                match ensure_within(&backup_dir, &backup_path) {
+                    Err(why) => {
+                        return backup_error_response(why);
+                    },
+                    _ => {/* continue */}
+                }
+            }
+
+            let response = match verify_backup(&backup_path) {
+                Err(why) => {
+                    return backup_error_response(why);
+                },
+                Ok(response) => response
+            };
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response.to_json())));
+        }
+    });
+
+    // restore the database from a backup within the default backup directory.
+    // a safety backup of the database is taken beforehand.
+    router.post("/backups/:name/restore", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let name: &str = req.extensions.get::<Router>().unwrap().find("name").unwrap();
+
+            let backup_dir: String = get_backup_dir(grokdb);
+
+            let backup_path: PathBuf = match find_backup(&backup_dir, name) {
+                Err(why) => {
+                    return backup_error_response(why);
+                },
+                Ok(backup_path) => backup_path
+            };
+
+            // a backup may be a symlink to a file outside of the backup directory
+            if !grokdb.unsafe_backup_paths {
+                match ensure_within(&backup_dir, &backup_path) {
+                    Err(why) => {
+                        return backup_error_response(why);
+                    },
+                    _ => {/* continue */}
+                }
+            }
+
+            let response = match restore_backup(&grokdb.decks.db, &backup_path, &backup_dir, &grokdb.base_db_name) {
+                Err(why) => {
+                    return backup_error_response(why);
+                },
+                Ok(response) => response
+            };
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response.to_json())));
+        }
+    });
+}
+
+/* helpers */
+
+// default backup directory; the current directory, unless a backup directory is given
+pub fn get_backup_dir(grokdb: &GrokDB) -> String {
+    return match grokdb.backup_base_dest {
+        Some(ref backup_base_dest) => format!("{}", backup_base_dest),
+        None => format!("./")
+    };
+}
+
+fn backup_error_response(why: BackupError) -> IronResult<Response> {
+
+    let ref reason = format!("{:?}", why);
+
+    let res_code = match why {
+        BackupError::Invalid(_) => status::BadRequest,
+        BackupError::Forbidden(_) => status::Forbidden,
+        _ => status::InternalServerError
+    };
+
+    let ref user_message = format!("{}", why);
+
+    let err_response = ErrorResponse {
+        status: res_code,
+        developerMessage: reason,
+        userMessage: user_message,
+    }.to_json();
+
+    return Ok(Response::with((res_code, err_response)));
 }
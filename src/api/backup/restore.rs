@@ -0,0 +1,361 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+extern crate chrono;
+
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use chrono::*;
+use rusqlite::{Connection, DatabaseName, Error as SqliteError};
+use rustc_serialize::json;
+
+use ::database::{self, DB, QueryError, BootstrapError, create_schema};
+use ::migrations;
+use ::api::tempfile::TempFile;
+use ::api::backup::BACKUP_TIMESTAMP_FORMAT;
+use ::api::backup::verify::{is_compressed, decompress_file};
+
+
+// listing and restoring of backups (see PUT /backup).
+//
+// a backup is restored into a temporary file next to the database file, migrated and validated
+// there, and then renamed over the database file; a new connection to it is swapped for the
+// connection held by DB. every api shares that connection, so requests wait on its lock until
+// the swap is done. a backup that fails to restore or migrate leaves the database untouched.
+// before restoring, a safety backup of the database is taken alongside the other backups.
+// compressed backups (.db.gz) are decompressed into a temporary file to be restored.
+
+// src: https://www.sqlite.org/fileformat.html#the_database_header
+static SQLITE_HEADER: &'static [u8] = b"SQLite format 3\0";
+
+pub static BACKUP_EXTENSION: &'static str = "db";
+pub static COMPRESSED_BACKUP_EXTENSION: &'static str = "db.gz";
+
+// tables a backup should have to be restored; it's migrated once restored
+static REQUIRED_TABLES: [&'static str; 8] = [
+    "Configs",
+    "Decks",
+    "DecksClosure",
+    "Cards",
+    "CardsScore",
+    "CardsScoreHistory",
+    "Stashes",
+    "StashCards"
+];
+
+#[derive(Debug)]
+pub enum BackupError {
+    Io(io::Error),
+    Sqlite(SqliteError),
+    Query(QueryError),
+    Bootstrap(BootstrapError),
+
+    // backup is malformed, or doesn't exist
+    Invalid(String),
+
+    // backup path is outside of the backup directory
+    Forbidden(String)
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            BackupError::Io(ref err) => write!(f, "{}", err),
+            BackupError::Sqlite(ref err) => write!(f, "{}", err),
+            BackupError::Query(ref err) => write!(f, "{}", err),
+            BackupError::Bootstrap(ref err) => write!(f, "{}", err),
+            BackupError::Invalid(ref reason) => write!(f, "{}", reason),
+            BackupError::Forbidden(ref reason) => write!(f, "{}", reason),
+        };
+    }
+}
+
+impl error::Error for BackupError {
+    fn description(&self) -> &str {
+        return match *self {
+            BackupError::Io(ref err) => err.description(),
+            BackupError::Sqlite(ref err) => err.description(),
+            BackupError::Query(ref err) => err.description(),
+            BackupError::Bootstrap(ref err) => err.description(),
+            BackupError::Invalid(ref reason) => reason,
+            BackupError::Forbidden(ref reason) => reason,
+        };
+    }
+}
+
+impl From<io::Error> for BackupError {
+    fn from(err: io::Error) -> BackupError {
+        return BackupError::Io(err);
+    }
+}
+
+impl From<SqliteError> for BackupError {
+    fn from(err: SqliteError) -> BackupError {
+        return BackupError::Sqlite(err);
+    }
+}
+
+impl From<QueryError> for BackupError {
+    fn from(err: QueryError) -> BackupError {
+        return BackupError::Query(err);
+    }
+}
+
+impl From<BootstrapError> for BackupError {
+    fn from(err: BootstrapError) -> BackupError {
+        return BackupError::Bootstrap(err);
+    }
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct BackupEntry {
+    pub name: String,
+    pub path: String,
+    pub size: u64, // bytes
+    pub modified_at: u64 // unix timestamp
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct BackupsResponse {
+    pub backups: Vec<BackupEntry>
+}
+
+impl BackupsResponse {
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct RestoreResponse {
+    pub restored_from: String,
+    pub safety_backup: String
+}
+
+impl RestoreResponse {
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+}
+
+// list the backups within the directory, most recent first.
+// the database file itself is skipped (e.g. if backups are kept alongside it).
+pub fn list_backups(backup_dir: &str, database_name: &str) -> Result<Vec<BackupEntry>, BackupError> {
+
+    let database_path: Option<PathBuf> = fs::canonicalize(database_name).ok();
+
+    let mut backups: Vec<BackupEntry> = vec![];
+
+    for entry in try!(fs::read_dir(backup_dir)) {
+
+        let path: PathBuf = try!(entry).path();
+
+        let file_name: String = match path.file_name() {
+            Some(file_name) => file_name.to_string_lossy().into_owned(),
+            None => {
+                continue;
+            }
+        };
+
+        let is_backup: bool = if file_name.ends_with(&format!(".{}", COMPRESSED_BACKUP_EXTENSION)) {
+            true
+        } else {
+            file_name.ends_with(&format!(".{}", BACKUP_EXTENSION)) && try!(is_sqlite_file(&path))
+        };
+
+        if !is_backup {
+            continue;
+        }
+
+        if database_path.is_some() && fs::canonicalize(&path).ok() == database_path {
+            continue;
+        }
+
+        let metadata = try!(fs::metadata(&path));
+
+        let modified_at: u64 = match metadata.modified() {
+            Ok(modified) => {
+                match modified.duration_since(UNIX_EPOCH) {
+                    Ok(duration) => duration.as_secs(),
+                    Err(_) => 0
+                }
+            },
+            Err(_) => 0
+        };
+
+        backups.push(BackupEntry {
+            name: file_name,
+            path: path.to_string_lossy().into_owned(),
+            size: metadata.len(),
+            modified_at: modified_at
+        });
+    }
+
+    backups.sort_by(|a, b| {
+        match b.modified_at.cmp(&a.modified_at) {
+            Ordering::Equal => a.name.cmp(&b.name),
+            ordering => ordering
+        }
+    });
+
+    return Ok(backups);
+}
+
+// resolve the name of a backup within the backup directory; the .db (or .db.gz) extension is optional
+pub fn find_backup(backup_dir: &str, name: &str) -> Result<PathBuf, BackupError> {
+
+    let name = name.trim();
+
+    if name.len() <= 0 || name.contains('/') || name.contains('\\') || name.starts_with(".") {
+        return Err(BackupError::Invalid(format!("invalid backup name: {}", name)));
+    }
+
+    let lowercase_name: String = name.to_lowercase();
+
+    let file_names: Vec<String> = if lowercase_name.ends_with(&format!(".{}", BACKUP_EXTENSION)) ||
+        lowercase_name.ends_with(&format!(".{}", COMPRESSED_BACKUP_EXTENSION)) {
+        vec![name.to_string()]
+    } else {
+        vec![
+            format!("{}.{}", name, BACKUP_EXTENSION),
+            format!("{}.{}", name, COMPRESSED_BACKUP_EXTENSION)
+        ]
+    };
+
+    for file_name in file_names {
+
+        let path: PathBuf = Path::new(backup_dir).join(file_name);
+
+        if fs::metadata(&path).is_ok() {
+            return Ok(path);
+        }
+    }
+
+    return Err(BackupError::Invalid(format!("no such backup: {}", name)));
+}
+
+// ensure the file is a grokdb database
+pub fn validate_backup(backup_path: &Path) -> Result<(), BackupError> {
+
+    if !try!(is_sqlite_file(backup_path)) {
+        return Err(BackupError::Invalid(format!("{} is not a SQLite database", backup_path.display())));
+    }
+
+    let backup_conn: Connection = try!(Connection::open(backup_path));
+
+    for table in REQUIRED_TABLES.iter() {
+
+        let ref query = format!("
+            SELECT COUNT(1) FROM sqlite_master WHERE type = 'table' AND name = $1;
+        ");
+
+        let count = backup_conn.query_row(query, &[table], |row| -> i64 {
+            return row.get(0);
+        });
+
+        match count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(BackupError::Query(err));
+            },
+            Ok(0) => {
+                return Err(BackupError::Invalid(format!("{} is not a grokdb database; it has no {} table",
+                    backup_path.display(), table)));
+            },
+            Ok(_) => {/* continue */}
+        }
+    }
+
+    let version: i64 = try!(migrations::get_version(&backup_conn));
+
+    if version > migrations::latest_version() {
+        return Err(BackupError::Invalid(format!("{} has schema version {}; this grokdb supports up to version {}",
+            backup_path.display(), version, migrations::latest_version())));
+    }
+
+    return Ok(());
+}
+
+// restore the backup into the database; a safety backup is taken into backup_dir beforehand.
+pub fn restore_backup(db: &DB, backup_path: &Path, backup_dir: &str, base_db_name: &str)
+    -> Result<RestoreResponse, BackupError> {
+
+    // the decompressed backup is deleted once restored
+    let temp_file: Option<TempFile> = if is_compressed(backup_path) {
+        let temp_file = TempFile::new("grokdb-restore", BACKUP_EXTENSION);
+        try!(decompress_file(backup_path, &temp_file.path));
+        Some(temp_file)
+    } else {
+        None
+    };
+
+    let restored_from: &Path = backup_path;
+
+    let backup_path: &Path = match temp_file {
+        Some(ref temp_file) => &temp_file.path,
+        None => backup_path
+    };
+
+    try!(validate_backup(backup_path));
+
+    let database_path: &Path = Path::new(&db.database_name);
+
+    let database_dir: &Path = match database_path.parent() {
+        Some(database_dir) if database_dir != Path::new("") => database_dir,
+        _ => Path::new(".")
+    };
+
+    // renamed over the database file once migrated and validated; deleted otherwise
+    let restored_file = TempFile::new_in(database_dir, "grokdb-restore", BACKUP_EXTENSION);
+
+    {
+        let mut restored_conn: Connection = try!(database::open(&restored_file.path.to_string_lossy()));
+
+        try!(restored_conn.restore(DatabaseName::Main, backup_path, None));
+
+        // migrate backups taken by an older grokdb
+        try!(create_schema(&restored_conn));
+    }
+
+    try!(validate_backup(&restored_file.path));
+
+    let safety_backup: PathBuf = Path::new(backup_dir).join(format!("{}-before-restore-{}.{}",
+        base_db_name,
+        UTC::now().format(BACKUP_TIMESTAMP_FORMAT).to_string(),
+        BACKUP_EXTENSION));
+
+    let mut db_conn_guard = db.lock().unwrap();
+
+    try!(db_conn_guard.backup(DatabaseName::Main, &safety_backup, None));
+
+    try!(fs::rename(&restored_file.path, database_path));
+
+    let new_conn: Connection = try!(database::open(&db.database_name));
+
+    // the previous connection is closed once dropped
+    *db_conn_guard = new_conn;
+
+    return Ok(RestoreResponse {
+        restored_from: restored_from.to_string_lossy().into_owned(),
+        safety_backup: safety_backup.to_string_lossy().into_owned()
+    });
+}
+
+fn is_sqlite_file(path: &Path) -> Result<bool, io::Error> {
+
+    let mut header: Vec<u8> = vec![];
+
+    try!(try!(fs::File::open(path)).take(SQLITE_HEADER.len() as u64).read_to_end(&mut header));
+
+    return Ok(&header[..] == SQLITE_HEADER);
+}
@@ -0,0 +1,184 @@
+use std::fs;
+use std::path::{Path, PathBuf, Component};
+
+use ::api::backup::restore::BackupError;
+
+
+// confinement of backups requested over http to the backup directory.
+//
+// dest_path of a request is a subdirectory of the backup directory; absolute paths, and paths
+// with .. are rejected. the directory is resolved (following symlinks) to ensure it's still
+// within the backup directory. a backup never replaces a symlink.
+//
+// unless --unsafe-backup-paths is given, where dest_path may be any directory.
+
+// path of the file within dest_path (a subdirectory of backup_dir)
+pub fn sandboxed_path(backup_dir: &str, dest_path: Option<&str>, file_name: &str) -> Result<PathBuf, BackupError> {
+
+    try!(validate_file_name(file_name));
+
+    let dest_dir: PathBuf = match dest_path {
+        None => Path::new(backup_dir).to_path_buf(),
+        Some(dest_path) => {
+            try!(validate_relative_path(dest_path));
+            Path::new(backup_dir).join(dest_path)
+        }
+    };
+
+    let dest_dir: PathBuf = try!(ensure_within(backup_dir, &dest_dir));
+
+    let path: PathBuf = dest_dir.join(file_name);
+
+    try!(ensure_not_symlink(&path));
+
+    return Ok(path);
+}
+
+// files written alongside a backup (e.g. its archive or manifest) never replace a symlink either
+pub fn ensure_not_symlink(path: &Path) -> Result<(), BackupError> {
+
+    match fs::symlink_metadata(path) {
+        Ok(ref metadata) if metadata.file_type().is_symlink() => {
+            let file_name: String = match path.file_name() {
+                Some(file_name) => file_name.to_string_lossy().into_owned(),
+                None => path.to_string_lossy().into_owned()
+            };
+            return Err(BackupError::Forbidden(format!("{} is a symlink", file_name)));
+        },
+        _ => {/* continue */}
+    }
+
+    return Ok(());
+}
+
+// resolve path, and ensure it's within backup_dir
+pub fn ensure_within(backup_dir: &str, path: &Path) -> Result<PathBuf, BackupError> {
+
+    let backup_dir: PathBuf = match fs::canonicalize(backup_dir) {
+        Ok(backup_dir) => backup_dir,
+        Err(_) => {
+            return Err(BackupError::Invalid(format!("backup directory doesn't exist: {}", backup_dir)));
+        }
+    };
+
+    let resolved: PathBuf = match fs::canonicalize(path) {
+        Ok(resolved) => resolved,
+        Err(_) => {
+            return Err(BackupError::Invalid(format!("no such directory: {}", path.display())));
+        }
+    };
+
+    if !resolved.starts_with(&backup_dir) {
+        return Err(BackupError::Forbidden(format!("{} is outside of the backup directory", path.display())));
+    }
+
+    return Ok(resolved);
+}
+
+fn validate_file_name(file_name: &str) -> Result<(), BackupError> {
+
+    if file_name.len() <= 0 || file_name.starts_with(".") ||
+        file_name.contains('/') || file_name.contains('\\') || file_name.contains('\0') {
+        return Err(BackupError::Invalid(format!("invalid backup name: {}", file_name)));
+    }
+
+    return Ok(());
+}
+
+fn validate_relative_path(dest_path: &str) -> Result<(), BackupError> {
+
+    if dest_path.contains('\0') {
+        return Err(BackupError::Invalid(format!("invalid backup path: {}", dest_path)));
+    }
+
+    for component in Path::new(dest_path).components() {
+        match component {
+            Component::Normal(_) | Component::CurDir => {/* continue */},
+            Component::ParentDir => {
+                return Err(BackupError::Forbidden(format!("backup path shouldn't contain ..: {}", dest_path)));
+            },
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(BackupError::Forbidden(format!("backup path should be relative to the backup directory: {}", dest_path)));
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs::{self, File};
+    use std::os::unix::fs::symlink;
+    use std::path::PathBuf;
+
+    use super::sandboxed_path;
+    use ::api::backup::restore::BackupError;
+    use ::api::tempfile::TempDir;
+
+    fn is_forbidden(result: Result<PathBuf, BackupError>) -> bool {
+        return match result {
+            Err(BackupError::Forbidden(_)) => true,
+            _ => false
+        };
+    }
+
+    #[test]
+    fn path_within_backup_dir_is_allowed() {
+
+        let backup_dir = TempDir::new("grokdb-sandbox").unwrap();
+
+        fs::create_dir(backup_dir.path.join("nested")).unwrap();
+
+        let path: PathBuf = sandboxed_path(backup_dir.path.to_str().unwrap(), Some("nested"), "backup.db").unwrap();
+
+        let expected: PathBuf = fs::canonicalize(&backup_dir.path).unwrap().join("nested").join("backup.db");
+
+        assert_eq!(path, expected);
+    }
+
+    #[test]
+    fn absolute_path_is_forbidden() {
+
+        let backup_dir = TempDir::new("grokdb-sandbox").unwrap();
+
+        assert!(is_forbidden(sandboxed_path(backup_dir.path.to_str().unwrap(), Some("/tmp"), "backup.db")));
+    }
+
+    #[test]
+    fn parent_dir_is_forbidden() {
+
+        let backup_dir = TempDir::new("grokdb-sandbox").unwrap();
+
+        fs::create_dir(backup_dir.path.join("nested")).unwrap();
+
+        assert!(is_forbidden(sandboxed_path(backup_dir.path.to_str().unwrap(), Some(".."), "backup.db")));
+        assert!(is_forbidden(sandboxed_path(backup_dir.path.to_str().unwrap(), Some("nested/../.."), "backup.db")));
+    }
+
+    #[test]
+    fn symlinked_dir_outside_of_backup_dir_is_forbidden() {
+
+        let backup_dir = TempDir::new("grokdb-sandbox").unwrap();
+        let outside_dir = TempDir::new("grokdb-sandbox-outside").unwrap();
+
+        symlink(&outside_dir.path, backup_dir.path.join("escape")).unwrap();
+
+        assert!(is_forbidden(sandboxed_path(backup_dir.path.to_str().unwrap(), Some("escape"), "backup.db")));
+    }
+
+    #[test]
+    fn symlinked_file_is_forbidden() {
+
+        let backup_dir = TempDir::new("grokdb-sandbox").unwrap();
+        let outside_dir = TempDir::new("grokdb-sandbox-outside").unwrap();
+
+        let target: PathBuf = outside_dir.path.join("target.db");
+
+        File::create(&target).unwrap();
+        symlink(&target, backup_dir.path.join("backup.db")).unwrap();
+
+        assert!(is_forbidden(sandboxed_path(backup_dir.path.to_str().unwrap(), None, "backup.db")));
+    }
+}
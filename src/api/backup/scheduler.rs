@@ -0,0 +1,568 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+extern crate chrono;
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use chrono::*;
+use rusqlite::{Connection, DatabaseName, SqliteStatement};
+use rusqlite::types::ToSql;
+use rustc_serialize::json;
+
+use ::database::{QueryError, get_config};
+use ::api::GrokDB;
+use ::api::backup::{BackupRequest, BACKUP_TIMESTAMP_FORMAT, get_backup_dir};
+use ::api::backup::restore::{BackupError, BACKUP_EXTENSION, COMPRESSED_BACKUP_EXTENSION};
+use ::api::backup::verify::{finalize_backup, manifest_path};
+
+
+// scheduled backups.
+//
+// a background thread takes a backup (named <database>-scheduled-<timestamp>.db) whenever the
+// backup interval has passed since the last successful scheduled backup. the interval is given by the
+// --backup-interval flag, otherwise by the backup_interval setting (see Configs); without either,
+// no backups are scheduled. settings are read on every check; so they can be changed while
+// grokdb is running.
+//
+// once a scheduled backup is taken, older scheduled backups are pruned by the retention policy:
+//
+// - backup_keep_last: the latest n backups are kept
+// - backup_keep_daily: the latest backup of each of the latest n days is kept
+// - backup_keep_weekly: the latest backup of each of the latest n weeks is kept
+//
+// a backup is kept if any rule keeps it. scheduled backups are found by listing the backup
+// directory, and are dated by the timestamp in their names; backups that were requested
+// (PUT /backup) are never pruned. every backup is recorded in BackupLog.
+//
+// scheduled backups are compressed if the backup_compress setting is true.
+
+pub static BACKUP_INTERVAL_CONFIG: &'static str = "backup_interval";
+pub static BACKUP_KEEP_LAST_CONFIG: &'static str = "backup_keep_last";
+pub static BACKUP_KEEP_DAILY_CONFIG: &'static str = "backup_keep_daily";
+pub static BACKUP_KEEP_WEEKLY_CONFIG: &'static str = "backup_keep_weekly";
+pub static BACKUP_COMPRESS_CONFIG: &'static str = "backup_compress";
+
+static DEFAULT_KEEP_LAST: i64 = 10;
+static DEFAULT_KEEP_DAILY: i64 = 7;
+static DEFAULT_KEEP_WEEKLY: i64 = 4;
+
+// how often the scheduler checks whether a backup is due
+static CHECK_INTERVAL_SECS: u64 = 60;
+
+// within the names of scheduled backups
+static SCHEDULED_BACKUP_TAG: &'static str = "scheduled";
+
+// see backup_keep_* settings
+struct RetentionPolicy {
+    keep_last: i64,
+    keep_daily: i64,
+    keep_weekly: i64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackupKind {
+    Scheduled,
+    Manual
+}
+
+impl BackupKind {
+
+    fn as_str(&self) -> &'static str {
+        return match *self {
+            BackupKind::Scheduled => "scheduled",
+            BackupKind::Manual => "manual"
+        };
+    }
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct BackupLogEntry {
+    backup_id: i64,
+    kind: String,
+    dest_file: String,
+    status: String,
+    error: String,
+    size: Option<i64>, // bytes
+    started_at: i64,
+    finished_at: i64,
+    pruned_at: Option<i64>
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct BackupLogResponse {
+    pub entries: Vec<BackupLogEntry>
+}
+
+impl BackupLogResponse {
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+}
+
+// parse a backup interval; returns seconds.
+// an interval is either hourly, daily, weekly, or a number of minutes.
+pub fn parse_interval(interval: &str) -> Option<i64> {
+    return match interval.trim().to_lowercase().as_ref() {
+        "hourly" => Some(60 * 60),
+        "daily" => Some(24 * 60 * 60),
+        "weekly" => Some(7 * 24 * 60 * 60),
+        minutes => {
+            match minutes.parse::<u64>() {
+                Ok(minutes) if minutes > 0 => Some((minutes as i64) * 60),
+                _ => None
+            }
+        }
+    };
+}
+
+// start the scheduler thread; interval (in seconds) overrides the backup_interval setting
+pub fn start(grokdb: GrokDB, interval: Option<i64>) {
+
+    thread::spawn(move || {
+        loop {
+
+            match run_if_due(&grokdb, interval) {
+                Err(why) => {
+                    println!("Scheduled backup failed:\n{}", why);
+                },
+                Ok(Some(dest_file)) => {
+                    println!("Scheduled backup at: {}", dest_file);
+                },
+                Ok(None) => {/* no backup is due */}
+            }
+
+            thread::sleep(Duration::from_secs(CHECK_INTERVAL_SECS));
+        }
+    });
+}
+
+// returns the path of the backup if one was taken
+fn run_if_due(grokdb: &GrokDB, interval: Option<i64>) -> Result<Option<String>, BackupError> {
+
+    let backup_dir: String = get_backup_dir(grokdb);
+
+    // the database is copied while holding the db connection lock; the copy is then verified
+    // (and maybe compressed) without it.
+    let (dest_path, compress, started_at): (String, bool, i64) = {
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let interval: i64 = match interval {
+            Some(interval) => interval,
+            None => {
+                match try!(get_config(db_conn, BACKUP_INTERVAL_CONFIG)).and_then(|interval| parse_interval(&interval)) {
+                    Some(interval) => interval,
+                    None => {
+                        return Ok(None);
+                    }
+                }
+            }
+        };
+
+        let now: i64 = UTC::now().timestamp();
+
+        // a failed backup is retried on the next check
+        let ref query = format!("
+            SELECT COALESCE(MAX(started_at), 0) FROM BackupLog WHERE kind = 'scheduled' AND status = 'success';
+        ");
+
+        let last_backup_at = db_conn.query_row(query, &[], |row| -> i64 {
+            return row.get(0);
+        });
+
+        let last_backup_at: i64 = match last_backup_at {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(BackupError::Query(err));
+            },
+            Ok(last_backup_at) => last_backup_at
+        };
+
+        if now - last_backup_at < interval {
+            return Ok(None);
+        }
+
+        let backup_request = BackupRequest {
+            name: Some(scheduled_backup_name(&grokdb.base_db_name)),
+            dest_path: Some(backup_dir.clone()),
+            with_timestamp: Some(true),
+            compress: None
+        };
+
+        let dest_path: String = backup_request.get_path(&grokdb.base_db_name);
+
+        let compress: bool = match try!(get_config(db_conn, BACKUP_COMPRESS_CONFIG)) {
+            Some(compress) => compress.trim() == "true",
+            None => false
+        };
+
+        let started_at: i64 = UTC::now().timestamp();
+
+        match db_conn.backup(DatabaseName::Main, &dest_path, None) {
+            Err(why) => {
+                try!(record_backup(db_conn, BackupKind::Scheduled, &dest_path, started_at,
+                    Some(format!("{}", BackupError::Sqlite(why)))));
+                return Ok(None);
+            },
+            Ok(_) => {}
+        }
+
+        (dest_path, compress, started_at)
+    };
+
+    let result: Result<String, BackupError> = finalize_backup(&dest_path, compress);
+
+    let (dest_file, policy): (String, RetentionPolicy) = {
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let dest_file: String = match result {
+            Err(why) => {
+                try!(record_backup(db_conn, BackupKind::Scheduled, &dest_path, started_at, Some(format!("{}", why))));
+                return Ok(None);
+            },
+            Ok(dest_file) => {
+                try!(record_backup(db_conn, BackupKind::Scheduled, &dest_file, started_at, None));
+                dest_file
+            }
+        };
+
+        (dest_file, try!(get_retention_policy(db_conn)))
+    };
+
+    let pruned: Vec<String> = try!(prune(&backup_dir, &grokdb.base_db_name, &policy));
+
+    if pruned.len() > 0 {
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(mark_pruned(db_conn, &pruned));
+    }
+
+    return Ok(Some(dest_file));
+}
+
+// scheduled backups are named apart from requested backups; so that only they are pruned
+fn scheduled_backup_name(base_db_name: &str) -> String {
+    return format!("{}-{}", base_db_name, SCHEDULED_BACKUP_TAG);
+}
+
+// when the scheduled backup of the given file name was taken (unix timestamp); it's read from the
+// timestamp within the name. any other file is not a scheduled backup.
+fn parse_scheduled_backup(file_name: &str, base_db_name: &str) -> Option<i64> {
+
+    let prefix: String = format!("{}-", scheduled_backup_name(base_db_name));
+
+    if !file_name.starts_with(&prefix) {
+        return None;
+    }
+
+    let rest: &str = &file_name[prefix.len()..];
+
+    let compressed_suffix: String = format!(".{}", COMPRESSED_BACKUP_EXTENSION);
+    let suffix: String = format!(".{}", BACKUP_EXTENSION);
+
+    let timestamp: &str = if rest.ends_with(&compressed_suffix) {
+        &rest[..(rest.len() - compressed_suffix.len())]
+    } else if rest.ends_with(&suffix) {
+        &rest[..(rest.len() - suffix.len())]
+    } else {
+        return None;
+    };
+
+    return match NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP_FORMAT) {
+        Ok(taken_at) => Some(taken_at.timestamp()),
+        Err(_) => None
+    };
+}
+
+// scheduled backups within the backup directory, latest first; as (path, taken at).
+// the backup directory is listed rather than BackupLog, since restoring a backup rewinds BackupLog.
+fn list_scheduled_backups(backup_dir: &str, base_db_name: &str) -> Result<Vec<(String, i64)>, BackupError> {
+
+    let mut backups: Vec<(String, i64)> = vec![];
+
+    for entry in try!(fs::read_dir(backup_dir)) {
+
+        let path: PathBuf = try!(entry).path();
+
+        let file_name: String = match path.file_name() {
+            Some(file_name) => file_name.to_string_lossy().into_owned(),
+            None => {
+                continue;
+            }
+        };
+
+        match parse_scheduled_backup(&file_name, base_db_name) {
+            None => {},
+            Some(taken_at) => {
+                backups.push((path.to_string_lossy().into_owned(), taken_at));
+            }
+        }
+    }
+
+    backups.sort_by(|a, b| {
+        match b.1.cmp(&a.1) {
+            Ordering::Equal => b.0.cmp(&a.0),
+            ordering => ordering
+        }
+    });
+
+    return Ok(backups);
+}
+
+// record a backup that was started at started_at (unix timestamp) in BackupLog.
+// error is given if the backup failed.
+// caller should hold the db connection lock.
+pub fn record_backup(db_conn: &Connection, kind: BackupKind, dest_path: &str, started_at: i64, error: Option<String>)
+    -> Result<(), QueryError> {
+
+    let finished_at: i64 = UTC::now().timestamp();
+
+    let kind: &str = kind.as_str();
+
+    let (status, error, size): (&str, String, Option<i64>) = match error {
+        Some(error) => ("failed", error, None),
+        None => {
+            let size: Option<i64> = fs::metadata(dest_path).ok().map(|metadata| metadata.len() as i64);
+            ("success", String::new(), size)
+        }
+    };
+
+    let ref query = format!("
+        INSERT INTO BackupLog(kind, dest_file, status, error, size, started_at, finished_at)
+        VALUES (:kind, :dest_file, :status, :error, :size, :started_at, :finished_at);
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":kind", &kind),
+        (":dest_file", &dest_path),
+        (":status", &status),
+        (":error", &error),
+        (":size", &size),
+        (":started_at", &started_at),
+        (":finished_at", &finished_at)
+    ];
+
+    match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
+
+// caller should hold the db connection lock.
+fn get_retention_policy(db_conn: &Connection) -> Result<RetentionPolicy, QueryError> {
+    return Ok(RetentionPolicy {
+        keep_last: try!(get_config_count(db_conn, BACKUP_KEEP_LAST_CONFIG, DEFAULT_KEEP_LAST)),
+        keep_daily: try!(get_config_count(db_conn, BACKUP_KEEP_DAILY_CONFIG, DEFAULT_KEEP_DAILY)),
+        keep_weekly: try!(get_config_count(db_conn, BACKUP_KEEP_WEEKLY_CONFIG, DEFAULT_KEEP_WEEKLY))
+    });
+}
+
+// delete scheduled backups within the backup directory that the retention policy doesn't keep.
+// returns the paths of the deleted backups.
+fn prune(backup_dir: &str, base_db_name: &str, policy: &RetentionPolicy) -> Result<Vec<String>, BackupError> {
+
+    // a policy that keeps nothing would delete every backup
+    if policy.keep_last <= 0 && policy.keep_daily <= 0 && policy.keep_weekly <= 0 {
+        return Ok(vec![]);
+    }
+
+    // latest first
+    let backups: Vec<(String, i64)> = try!(list_scheduled_backups(backup_dir, base_db_name));
+
+    let mut kept: HashSet<usize> = HashSet::new();
+
+    let mut days: HashSet<(i32, u32)> = HashSet::new();
+    let mut weeks: HashSet<(i32, u32)> = HashSet::new();
+
+    for (index, &(_, taken_at)) in backups.iter().enumerate() {
+
+        if (index as i64) < policy.keep_last {
+            kept.insert(index);
+        }
+
+        let date = NaiveDateTime::from_timestamp(taken_at, 0).date();
+
+        let day: (i32, u32) = (date.year(), date.ordinal());
+
+        if !days.contains(&day) && (days.len() as i64) < policy.keep_daily {
+            days.insert(day);
+            kept.insert(index);
+        }
+
+        let (week_year, week, _) = date.isoweekdate();
+
+        let week: (i32, u32) = (week_year, week);
+
+        if !weeks.contains(&week) && (weeks.len() as i64) < policy.keep_weekly {
+            weeks.insert(week);
+            kept.insert(index);
+        }
+    }
+
+    let mut pruned: Vec<String> = vec![];
+
+    for (index, &(ref dest_file, _)) in backups.iter().enumerate() {
+
+        if kept.contains(&index) {
+            continue;
+        }
+
+        match fs::remove_file(dest_file) {
+            Ok(_) => {},
+            Err(why) => {
+                println!("Unable to prune backup {}: {}", dest_file, why);
+                continue;
+            }
+        }
+
+        let _ = fs::remove_file(manifest_path(Path::new(dest_file)));
+
+        pruned.push(dest_file.clone());
+    }
+
+    return Ok(pruned);
+}
+
+// mark pruned backups in BackupLog; backups taken after a restored backup aren't logged.
+// caller should hold the db connection lock.
+fn mark_pruned(db_conn: &Connection, pruned: &Vec<String>) -> Result<(), QueryError> {
+
+    let pruned_at: i64 = UTC::now().timestamp();
+
+    for dest_file in pruned.iter() {
+
+        let ref query = format!("
+            UPDATE BackupLog SET pruned_at = :pruned_at
+            WHERE kind = 'scheduled' AND dest_file = :dest_file AND pruned_at IS NULL;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":pruned_at", &pruned_at),
+            (":dest_file", dest_file)
+        ];
+
+        match db_conn.execute_named(query, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */}
+        }
+    }
+
+    return Ok(());
+}
+
+// latest entries of BackupLog.
+// caller should hold the db connection lock.
+pub fn get_backup_log(db_conn: &Connection, limit: i64) -> Result<Vec<BackupLogEntry>, QueryError> {
+
+    let ref query = format!("
+        SELECT
+            backup_id, kind, dest_file, status, error, size, started_at, finished_at, pruned_at
+        FROM BackupLog
+        ORDER BY started_at DESC, backup_id DESC
+        LIMIT :limit;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":limit", &limit)
+    ];
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut entries: Vec<BackupLogEntry> = vec![];
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                entries.push(BackupLogEntry {
+                    backup_id: row.get(0),
+                    kind: row.get(1),
+                    dest_file: row.get(2),
+                    status: row.get(3),
+                    error: row.get(4),
+                    size: row.get(5),
+                    started_at: row.get(6),
+                    finished_at: row.get(7),
+                    pruned_at: row.get(8)
+                });
+            }
+
+            return Ok(entries);
+        }
+    };
+}
+
+// ignore malformed counts
+fn get_config_count(db_conn: &Connection, setting: &str, default: i64) -> Result<i64, QueryError> {
+    return match try!(get_config(db_conn, setting)) {
+        None => Ok(default),
+        Some(value) => {
+            match value.trim().parse::<i64>() {
+                Ok(count) if count >= 0 => Ok(count),
+                _ => Ok(default)
+            }
+        }
+    };
+}
@@ -0,0 +1,287 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+extern crate chrono;
+extern crate sha2;
+extern crate libflate;
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::*;
+use libflate::gzip;
+use rusqlite::{Connection, SqliteStatement};
+use rustc_serialize::json;
+use sha2::{Sha256, Digest};
+
+use ::api::tempfile::TempFile;
+use ::api::backup::restore::{BackupError, validate_backup};
+use ::api::backup::sandbox::ensure_not_symlink;
+
+
+// verification of backups.
+//
+// once taken, a backup is opened and checked (PRAGMA integrity_check, and the tables of a grokdb
+// database). a backup may then be compressed (gzip), where the .db file is replaced by a .db.gz
+// archive. the sha-256 checksum of the backup (or archive) is recorded in a sidecar manifest,
+// <backup>.manifest.json, alongside it.
+//
+// existing backups are verified by checking them again, and comparing their checksum with the
+// one within their manifest, if any.
+
+static COMPRESSED_EXTENSION: &'static str = "gz";
+static MANIFEST_SUFFIX: &'static str = ".manifest.json";
+
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct BackupManifest {
+    pub file: String, // file name of the backup
+    pub sha256: String,
+    pub size: u64, // bytes
+    pub compressed: bool,
+    pub created_at: i64 // unix timestamp
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct VerifyResponse {
+    pub file: String,
+    pub sha256: String,
+    pub manifest_sha256: Option<String>,
+    pub compressed: bool,
+    pub valid: bool,
+    pub problems: Vec<String>
+}
+
+impl VerifyResponse {
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+}
+
+pub fn is_compressed(path: &Path) -> bool {
+    return match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => extension == COMPRESSED_EXTENSION,
+        None => false
+    };
+}
+
+pub fn manifest_path(path: &Path) -> PathBuf {
+
+    let file_name: String = match path.file_name() {
+        Some(file_name) => file_name.to_string_lossy().into_owned(),
+        None => String::new()
+    };
+
+    return path.with_file_name(format!("{}{}", file_name, MANIFEST_SUFFIX));
+}
+
+// check the backup that was just taken at dest_path, compress it if requested, and write its
+// manifest; returns the path of the backup (i.e. of the archive if compressed).
+// a backup that fails the checks is left as it is. neither the archive nor the manifest replace a
+// symlink (see sandbox module).
+pub fn finalize_backup(dest_path: &str, compress: bool) -> Result<String, BackupError> {
+
+    let problems: Vec<String> = check_database(Path::new(dest_path));
+
+    if problems.len() > 0 {
+        return Err(BackupError::Invalid(format!("backup at {} failed verification: {}",
+            dest_path, problems.join("; "))));
+    }
+
+    let path: PathBuf = if compress {
+
+        let archive_path: PathBuf = PathBuf::from(format!("{}.{}", dest_path, COMPRESSED_EXTENSION));
+
+        try!(ensure_not_symlink(&archive_path));
+
+        match compress_file(Path::new(dest_path), &archive_path) {
+            Err(why) => {
+                let _ = fs::remove_file(&archive_path);
+                return Err(BackupError::Io(why));
+            },
+            Ok(_) => {}
+        }
+
+        try!(fs::remove_file(dest_path));
+
+        archive_path
+    } else {
+        PathBuf::from(dest_path)
+    };
+
+    let manifest = BackupManifest {
+        file: path.file_name().unwrap().to_string_lossy().into_owned(),
+        sha256: try!(sha256_file(&path)),
+        size: try!(fs::metadata(&path)).len(),
+        compressed: compress,
+        created_at: UTC::now().timestamp()
+    };
+
+    let manifest_file_path: PathBuf = manifest_path(&path);
+
+    try!(ensure_not_symlink(&manifest_file_path));
+
+    let mut manifest_file = try!(fs::File::create(&manifest_file_path));
+    try!(manifest_file.write_all(json::encode(&manifest).unwrap().as_bytes()));
+
+    return Ok(path.to_string_lossy().into_owned());
+}
+
+// check an existing backup (or archive of a backup)
+pub fn verify_backup(path: &Path) -> Result<VerifyResponse, BackupError> {
+
+    let sha256: String = try!(sha256_file(path));
+
+    let mut problems: Vec<String> = vec![];
+
+    let manifest: Option<BackupManifest> = match fs::File::open(manifest_path(path)) {
+        Err(_) => None,
+        Ok(mut manifest_file) => {
+
+            let mut contents = String::new();
+            try!(manifest_file.read_to_string(&mut contents));
+
+            match json::decode::<BackupManifest>(&contents) {
+                Ok(manifest) => Some(manifest),
+                Err(why) => {
+                    problems.push(format!("malformed manifest: {}", why));
+                    None
+                }
+            }
+        }
+    };
+
+    let manifest_sha256: Option<String> = manifest.map(|manifest| manifest.sha256);
+
+    match manifest_sha256 {
+        Some(ref manifest_sha256) if *manifest_sha256 != sha256 => {
+            problems.push(format!("checksum doesn't match the manifest"));
+        },
+        _ => {}
+    }
+
+    let compressed: bool = is_compressed(path);
+
+    if compressed {
+
+        let temp_file = TempFile::new("grokdb-verify", "db");
+
+        match decompress_file(path, &temp_file.path) {
+            Err(why) => {
+                problems.push(format!("unable to decompress: {}", why));
+            },
+            Ok(_) => {
+                problems.extend(check_database(&temp_file.path));
+            }
+        }
+    } else {
+        problems.extend(check_database(path));
+    }
+
+    return Ok(VerifyResponse {
+        file: path.to_string_lossy().into_owned(),
+        sha256: sha256,
+        manifest_sha256: manifest_sha256,
+        compressed: compressed,
+        valid: problems.len() <= 0,
+        problems: problems
+    });
+}
+
+// decompress an archive of a backup
+pub fn decompress_file(path: &Path, dest_path: &Path) -> Result<(), io::Error> {
+
+    let mut decoder = try!(gzip::Decoder::new(try!(fs::File::open(path))));
+    let mut dest_file = try!(fs::File::create(dest_path));
+
+    try!(io::copy(&mut decoder, &mut dest_file));
+
+    return Ok(());
+}
+
+fn compress_file(path: &Path, dest_path: &Path) -> Result<(), io::Error> {
+
+    let mut source_file = try!(fs::File::open(path));
+    let mut encoder = try!(gzip::Encoder::new(try!(fs::File::create(dest_path))));
+
+    try!(io::copy(&mut source_file, &mut encoder));
+    try!(encoder.finish().into_result());
+
+    return Ok(());
+}
+
+// problems with the database at path; none if it's a sound grokdb database
+fn check_database(path: &Path) -> Vec<String> {
+
+    match validate_backup(path) {
+        Err(why) => {
+            return vec![format!("{}", why)];
+        },
+        _ => {/* continue */}
+    }
+
+    let db_conn: Connection = match Connection::open(path) {
+        Err(why) => {
+            return vec![format!("unable to open: {}", why)];
+        },
+        Ok(db_conn) => db_conn
+    };
+
+    let ref query = format!("PRAGMA integrity_check;");
+
+    let mut stmt: SqliteStatement = match db_conn.prepare(query) {
+        Err(why) => {
+            return vec![format!("integrity check failed: {}", why)];
+        },
+        Ok(stmt) => stmt
+    };
+
+    let maybe_iter = stmt.query_map(&[], |row| -> String {
+        return row.get(0);
+    });
+
+    let mut problems: Vec<String> = vec![];
+
+    match maybe_iter {
+        Err(why) => {
+            problems.push(format!("integrity check failed: {}", why));
+        },
+        Ok(iter) => {
+            for result in iter {
+                match result {
+                    Err(why) => {
+                        problems.push(format!("integrity check failed: {}", why));
+                    },
+                    Ok(ref result) if result == "ok" => {},
+                    Ok(result) => {
+                        problems.push(result);
+                    }
+                }
+            }
+        }
+    }
+
+    return problems;
+}
+
+fn sha256_file(path: &Path) -> Result<String, io::Error> {
+
+    let mut file = try!(fs::File::open(path));
+
+    let mut hasher = Sha256::default();
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        let read: usize = try!(file.read(&mut buffer));
+
+        if read <= 0 {
+            break;
+        }
+
+        hasher.input(&buffer[..read]);
+    }
+
+    let digest: String = hasher.result().iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    return Ok(digest);
+}
@@ -10,7 +10,7 @@ use rusqlite::{Statement};
 use rustc_serialize::json;
 
 use ::api::{GrokDB};
-use ::api::review::ReviewResponse;
+use ::api::review::{ReviewResponse, REVIEWABLE_CARDS_FILTER};
 use ::database::{DB, QueryError};
 pub use self::restify::restify;
 
@@ -30,12 +30,46 @@ pub enum SortOrder {
     Ascending
 }
 
+// filter cards by whether they can be reviewed
+pub enum CardState {
+    Active, // neither suspended nor buried
+    Suspended,
+    Buried
+}
+
+// restricts a selection of cards to those with the tag bound to :tag.
+// the card should be aliased as c within the query.
+const TAG_WHERE_COND: &'static str = "
+    AND c.card_id IN (
+        SELECT ct.card
+        FROM CardsTags AS ct
+        INNER JOIN Tags AS t
+        ON t.tag_id = ct.tag
+        WHERE t.name = :tag
+    )
+";
+
+impl CardState {
+
+    pub fn where_cond(&self) -> String {
+        return match *self {
+            CardState::Active => format!("AND {}", REVIEWABLE_CARDS_FILTER),
+            CardState::Suspended => format!("AND c.card_id IN (SELECT card FROM SuspendedCards)"),
+            CardState::Buried => {
+                format!("AND c.card_id IN (SELECT card FROM BuriedCards WHERE buried_until > strftime('%s', 'now'))")
+            }
+        };
+    }
+}
+
 pub struct CardsPageRequest {
     page: i64,
     per_page: i64,
     sort_by: SortBy,
     order: SortOrder,
-    search: Option<String>
+    search: Option<String>,
+    state: Option<CardState>,
+    tag: Option<String> // name of a tag the cards should have (see tags)
 }
 
 impl CardsPageRequest {
@@ -70,12 +104,29 @@ pub struct UpdateCard {
     front: Option<String>,
     back: Option<String>,
     deck: Option<i64>,
+
+    // suspended cards are excluded from review until they're resumed
+    suspended: Option<bool>,
+
+    // card is excluded from review until this unix timestamp.
+    // a timestamp that isn't in the future unburies the card.
+    buried_until: Option<i64>
 }
 
 impl UpdateCard {
 
     #[allow(unused_parens)]
     pub fn should_update(&self) -> bool {
+        return (
+            self.should_update_fields() ||
+            self.suspended.is_some() ||
+            self.buried_until.is_some()
+        );
+    }
+
+    // check if any column of Cards is updated
+    #[allow(unused_parens)]
+    pub fn should_update_fields(&self) -> bool {
         return (
             self.title.is_some() ||
             self.description.is_some() ||
@@ -160,6 +211,8 @@ struct CardResponse {
     deck: i64,
     created_at: i64, // unix timestamp
     updated_at: i64,  // unix timestamp
+    suspended: bool,
+    buried_until: Option<i64>, // unix timestamp; null if not buried
     review_stat: ReviewResponse
     // TODO: needed?
     // stashes: Vec<i64>
@@ -200,6 +253,14 @@ impl CardsAPI {
             Ok(review_stat) => review_stat
         };
 
+        let (suspended, buried_until): (bool, Option<i64>) = match self.get_state(card_id) {
+            Err(why) => {
+                // why: QueryError
+                return Err(why);
+            },
+            Ok(state) => state
+        };
+
         let response = CardResponse {
             id: card.id,
             title: card.title,
@@ -209,6 +270,8 @@ impl CardsAPI {
             deck: card.deck,
             created_at: card.created_at,
             updated_at: card.updated_at,
+            suspended: suspended,
+            buried_until: buried_until,
             review_stat: review_stat
         };
 
@@ -254,7 +317,42 @@ impl CardsAPI {
         };
     }
 
-    pub fn count_by_deck(&self, deck_id: i64, maybe_search_query: Option<String>) -> Result<i64, QueryError> {
+    // returns whether the card is suspended, and until when the card is buried (if it is)
+    pub fn get_state(&self, card_id: i64) -> Result<(bool, Option<i64>), QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                (SELECT COUNT(1) FROM SuspendedCards WHERE card = :card_id),
+                (
+                    SELECT buried_until FROM BuriedCards
+                    WHERE card = :card_id AND buried_until > strftime('%s', 'now')
+                );
+        ");
+
+        let results = db_conn.query_row_named(query, &[(":card_id", &card_id)], |row| -> (bool, Option<i64>) {
+            let suspended: i64 = row.get(0);
+            return (suspended >= 1, row.get(1));
+        });
+
+        match results {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(state) => {
+                return Ok(state);
+            }
+        };
+    }
+
+    pub fn count_by_deck(&self, deck_id: i64, maybe_search_query: Option<String>,
+        maybe_state: Option<CardState>, maybe_tag: Option<String>) -> Result<i64, QueryError> {
 
         let db_conn_guard = self.db.lock().unwrap();
         let ref db_conn = *db_conn_guard;
@@ -276,6 +374,16 @@ impl CardsAPI {
             }
         };
 
+        let state_where_cond: String = match maybe_state {
+            None => "".to_string(),
+            Some(ref state) => state.where_cond()
+        };
+
+        let tag_where_cond: &str = match maybe_tag {
+            None => "",
+            Some(_) => TAG_WHERE_COND
+        };
+
         let ref query = format!("
             SELECT
                 COUNT(1)
@@ -289,12 +397,17 @@ impl CardsAPI {
             WHERE
             dc.ancestor = :deck_id
             {search_where_cond}
+            {state_where_cond}
+            {tag_where_cond}
             ;
         ",
         search_inner_join = search_inner_join,
-        search_where_cond = search_where_cond);
+        search_where_cond = search_where_cond,
+        state_where_cond = state_where_cond,
+        tag_where_cond = tag_where_cond);
 
         let mut search_query: &str = "";
+        let mut tag: &str = "";
 
         let mut params: Vec<(&str, &ToSql)> = vec![
             (":deck_id", &deck_id)
@@ -306,6 +419,12 @@ impl CardsAPI {
         }
         let search_query = search_query;
 
+        if maybe_tag.is_some() {
+            tag = maybe_tag.as_ref().unwrap();
+            params.push((":tag", &tag));
+        }
+        let tag = tag;
+
         let params: &[(&str, &ToSql)] = params.as_slice();
 
 
@@ -339,6 +458,7 @@ impl CardsAPI {
         let offset = page_query.get_offset();
         let per_page = page_query.per_page;
         let mut search_query: &str = "";
+        let mut tag: &str = "";
 
 
         let mut params: Vec<(&str, &ToSql)> = vec![
@@ -353,6 +473,12 @@ impl CardsAPI {
         }
         let search_query = search_query;
 
+        if page_query.tag.is_some() {
+            tag = page_query.tag.as_ref().unwrap();
+            params.push((":tag", &tag));
+        }
+        let tag = tag;
+
         let params: &[(&str, &ToSql)] = params.as_slice();
 
         let maybe_stmt = db_conn.prepare(query);
@@ -508,28 +634,127 @@ impl CardsAPI {
 
         try!(DB::prepare_query(db_conn));
 
-        let (fields, values): (String, Vec<(&str, &ToSql)>) = update_card_request.sqlize();
+        let tx = match db_conn.transaction() {
+
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: format!("creating transaction"),
+                };
+                return Err(err);
+            },
+
+            Ok(tx) => {
+                /* new transaction created */
+                tx
+            }
+        };
+
+        if update_card_request.should_update_fields() {
 
-        let mut values = values;
-        values.push((":card_id", &card_id));
-        let values = values;
+            let (fields, values): (String, Vec<(&str, &ToSql)>) = update_card_request.sqlize();
 
-        let ref query_update = format!("
-            UPDATE Cards
-            SET
-            {fields}
-            WHERE card_id = :card_id;
-        ", fields = fields);
+            let mut values = values;
+            values.push((":card_id", &card_id));
+            let values = values;
 
-        match db_conn.execute_named(query_update, &values[..]) {
+            let ref query_update = format!("
+                UPDATE Cards
+                SET
+                {fields}
+                WHERE card_id = :card_id;
+            ", fields = fields);
+
+            match db_conn.execute_named(query_update, &values[..]) {
+                Err(why) => {
+                    let err = QueryError {
+                        sqlite_error: why,
+                        query: query_update.clone(),
+                    };
+                    return Err(err);
+                },
+                _ => {/* query sucessfully executed */},
+            }
+        }
+
+        let mut queries: Vec<(String, Vec<(&str, &ToSql)>)> = vec![];
+
+        match update_card_request.suspended {
+            Some(true) => {
+                let params: Vec<(&str, &ToSql)> = vec![(":card_id", &card_id)];
+                queries.push((format!("
+                    INSERT OR IGNORE INTO SuspendedCards(card) VALUES (:card_id);
+                "), params));
+            },
+            Some(false) => {
+                let params: Vec<(&str, &ToSql)> = vec![(":card_id", &card_id)];
+                queries.push((format!("
+                    DELETE FROM SuspendedCards WHERE card = :card_id;
+                "), params));
+            },
+            None => {/* noop */}
+        }
+
+        match update_card_request.buried_until {
+            Some(ref buried_until) => {
+                let params: Vec<(&str, &ToSql)> = vec![(":card_id", &card_id)];
+                queries.push((format!("
+                    DELETE FROM BuriedCards WHERE card = :card_id;
+                "), params));
+
+                // bury the card only if buried_until is in the future
+                let params: Vec<(&str, &ToSql)> = vec![(":card_id", &card_id), (":buried_until", buried_until)];
+                queries.push((format!("
+                    INSERT INTO BuriedCards(card, buried_until)
+                    SELECT :card_id, :buried_until
+                    WHERE :buried_until > strftime('%s', 'now');
+                "), params));
+            },
+            None => {/* noop */}
+        }
+
+        if update_card_request.suspended.is_some() || update_card_request.buried_until.is_some() {
+
+            // card may no longer be reviewable; remove it from any review cache
+
+            let params: Vec<(&str, &ToSql)> = vec![(":card_id", &card_id)];
+            queries.push((format!("
+                DELETE FROM CachedDeckReview WHERE card = :card_id;
+            "), params));
+
+            let params: Vec<(&str, &ToSql)> = vec![(":card_id", &card_id)];
+            queries.push((format!("
+                DELETE FROM CachedStashReview WHERE card = :card_id;
+            "), params));
+
+            let params: Vec<(&str, &ToSql)> = vec![(":card_id", &card_id)];
+            queries.push((format!("
+                DELETE FROM CachedTagReview WHERE card = :card_id;
+            "), params));
+        }
+
+        for &(ref query, ref params) in queries.iter() {
+            match db_conn.execute_named(query, &params[..]) {
+                Err(why) => {
+                    let err = QueryError {
+                        sqlite_error: why,
+                        query: query.clone(),
+                    };
+                    return Err(err);
+                },
+                _ => {/* query sucessfully executed */},
+            }
+        }
+
+        match tx.commit() {
             Err(why) => {
                 let err = QueryError {
                     sqlite_error: why,
-                    query: query_update.clone(),
+                    query: format!("committing transaction"),
                 };
                 return Err(err);
             },
-            _ => {/* query sucessfully executed */},
+            _ => {/* commit successful */}
         }
 
         return Ok(());
@@ -651,18 +876,29 @@ impl CardsAPI {
 
     }
 
-    pub fn count_by_stash(&self, stash_id: i64) -> Result<i64, QueryError> {
+    pub fn count_by_stash(&self, stash_id: i64, maybe_state: Option<CardState>) -> Result<i64, QueryError> {
 
         let db_conn_guard = self.db.lock().unwrap();
         let ref db_conn = *db_conn_guard;
 
+        let state_where_cond: String = match maybe_state {
+            None => "".to_string(),
+            Some(ref state) => state.where_cond()
+        };
+
         let ref query = format!("
             SELECT
                 COUNT(1)
-            FROM
-                StashCards
-            WHERE stash = :stash_id;
-        ");
+            FROM StashCards AS sc
+
+            INNER JOIN Cards AS c
+            ON c.card_id = sc.card
+
+            WHERE
+            sc.stash = :stash_id
+            {state_where_cond}
+            ;
+        ", state_where_cond = state_where_cond);
 
         let params: &[(&str, &ToSql)] = &[
             (":stash_id", &stash_id)
@@ -753,6 +989,131 @@ impl CardsAPI {
             }
         };
     }
+
+    pub fn count_by_tag(&self, tag_id: i64, maybe_state: Option<CardState>) -> Result<i64, QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let state_where_cond: String = match maybe_state {
+            None => "".to_string(),
+            Some(ref state) => state.where_cond()
+        };
+
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM CardsTags AS ct
+
+            INNER JOIN Cards AS c
+            ON c.card_id = ct.card
+
+            WHERE
+            ct.tag = :tag_id
+            {state_where_cond}
+            ;
+        ", state_where_cond = state_where_cond);
+
+        let params: &[(&str, &ToSql)] = &[
+            (":tag_id", &tag_id)
+        ];
+
+        let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(count) => {
+                return Ok(count);
+            }
+        };
+    }
+
+    pub fn get_by_tag(&self, tag_id: i64, page_query: CardsPageRequest) -> Result<Vec<i64>, QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        // invariant: page_query.offset is legal
+
+        let ref page_query = page_query;
+
+        let ref query = get_by_tag_query(page_query);
+
+        let offset = page_query.get_offset();
+        let per_page = page_query.per_page;
+        let mut search_query: &str = "";
+
+        let mut params: Vec<(&str, &ToSql)> = vec![
+            (":tag_id", &tag_id),
+            (":offset", &offset),
+            (":per_page", &per_page)
+        ];
+
+        if page_query.search.is_some() {
+            search_query = page_query.search.as_ref().unwrap();
+            params.push((":search_query", &search_query));
+        }
+        let search_query = search_query;
+
+        let params: &[(&str, &ToSql)] = params.as_slice();
+
+        let maybe_stmt = db_conn.prepare(query);
+
+        if maybe_stmt.is_err() {
+
+            let why = maybe_stmt.unwrap_err();
+
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        }
+
+        let mut stmt: Statement = maybe_stmt.unwrap();
+
+        let maybe_iter = stmt.query_named(params);
+
+        match maybe_iter {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(iter) => {
+
+                let mut vec_of_card_id: Vec<i64> = Vec::new();
+
+                for result_row in iter {
+
+                    let card_id: i64 = match result_row {
+                        Err(why) => {
+                            let err = QueryError {
+                                sqlite_error: why,
+                                query: query.clone(),
+                            };
+                            return Err(err);
+                        },
+                        Ok(row) => row.get(0)
+                    };
+
+                    vec_of_card_id.push(card_id);
+                }
+
+                return Ok(vec_of_card_id);
+            }
+        };
+    }
 }
 
 /* helpers */
@@ -781,6 +1142,17 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
         }
     };
 
+    let state_where_cond: String = match page_query.state {
+        None => "".to_string(),
+        Some(ref state) => state.where_cond()
+    };
+
+    // cards are filtered by tag along with their state
+    let state_where_cond: String = match page_query.tag {
+        None => state_where_cond,
+        Some(_) => format!("{} {}", state_where_cond, TAG_WHERE_COND)
+    };
+
     let query = match page_query.sort_by {
 
         SortBy::CreatedAt => {
@@ -809,6 +1181,7 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
                     dc.ancestor = :deck_id
 
                     {search_where_cond}
+                    {state_where_cond}
 
                     ORDER BY c.created_at {sort_order} LIMIT :offset
                 )
@@ -817,12 +1190,14 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
                 dc.ancestor = :deck_id
 
                 {search_where_cond}
+                {state_where_cond}
 
                 ORDER BY c.created_at {sort_order} LIMIT :per_page;
             ",
             sort_order = sort_order,
             search_inner_join = search_inner_join,
-            search_where_cond = search_where_cond)
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
         },
 
         SortBy::UpdatedAt => {
@@ -851,6 +1226,7 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
                     dc.ancestor = :deck_id
 
                     {search_where_cond}
+                    {state_where_cond}
 
                     ORDER BY c.updated_at {sort_order} LIMIT :offset
                 )
@@ -858,12 +1234,14 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
                 dc.ancestor = :deck_id
 
                 {search_where_cond}
+                {state_where_cond}
 
                 ORDER BY c.updated_at {sort_order} LIMIT :per_page;
             ",
             sort_order = sort_order,
             search_inner_join = search_inner_join,
-            search_where_cond = search_where_cond)
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
         },
 
         SortBy::Title => {
@@ -892,6 +1270,7 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
                     dc.ancestor = :deck_id
 
                     {search_where_cond}
+                    {state_where_cond}
 
                     ORDER BY c.title {sort_order} LIMIT :offset
                 )
@@ -899,12 +1278,14 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
                 dc.ancestor = :deck_id
 
                 {search_where_cond}
+                {state_where_cond}
 
                 ORDER BY c.title {sort_order} LIMIT :per_page;
             ",
             sort_order = sort_order,
             search_inner_join = search_inner_join,
-            search_where_cond = search_where_cond)
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
         },
 
         SortBy::ReviewedDate => {
@@ -940,6 +1321,7 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
                                     cs.times_reviewed > 0
 
                                 {search_where_cond}
+                                {state_where_cond}
 
                                 ORDER  BY cs.reviewed_at {sort_order}
                             )
@@ -971,6 +1353,7 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
                                     cs.times_reviewed = 0
 
                                 {search_where_cond}
+                                {state_where_cond}
 
                                 ORDER  BY cs.reviewed_at {sort_order}
                             )
@@ -996,6 +1379,7 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
                                     cs.times_reviewed > 0
 
                                 {search_where_cond}
+                                {state_where_cond}
 
                                 ORDER  BY cs.reviewed_at {sort_order}
                             )
@@ -1019,6 +1403,7 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
                                     cs.times_reviewed = 0
 
                                 {search_where_cond}
+                                {state_where_cond}
 
                                 ORDER  BY cs.reviewed_at {sort_order}
                             )
@@ -1028,7 +1413,8 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
             ",
             sort_order = sort_order,
             search_inner_join = search_inner_join,
-            search_where_cond = search_where_cond)
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
 
         },
 
@@ -1064,6 +1450,7 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
                     dc.ancestor = :deck_id
 
                     {search_where_cond}
+                    {state_where_cond}
 
                     ORDER BY cs.times_reviewed {sort_order} LIMIT :offset
                 )
@@ -1071,12 +1458,14 @@ fn get_by_deck_query(page_query: &CardsPageRequest) -> String {
                 dc.ancestor = :deck_id
 
                 {search_where_cond}
+                {state_where_cond}
 
                 ORDER BY cs.times_reviewed {sort_order} LIMIT :per_page;
             ",
             sort_order = sort_order,
             search_inner_join = search_inner_join,
-            search_where_cond = search_where_cond)
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
         },
     };
 
@@ -1107,6 +1496,11 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
         }
     };
 
+    let state_where_cond: String = match page_query.state {
+        None => "".to_string(),
+        Some(ref state) => state.where_cond()
+    };
+
     let query = match page_query.sort_by {
 
         SortBy::CreatedAt => {
@@ -1135,6 +1529,7 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
                     sc.stash = :stash_id
 
                     {search_where_cond}
+                    {state_where_cond}
 
                     ORDER BY c.created_at {sort_order} LIMIT :offset
                 )
@@ -1142,12 +1537,14 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
                 sc.stash = :stash_id
 
                 {search_where_cond}
+                {state_where_cond}
 
                 ORDER BY c.created_at {sort_order} LIMIT :per_page;
             ",
             sort_order = sort_order,
             search_inner_join = search_inner_join,
-            search_where_cond = search_where_cond)
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
         },
 
         SortBy::UpdatedAt => {
@@ -1176,6 +1573,7 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
                     sc.stash = :stash_id
 
                     {search_where_cond}
+                    {state_where_cond}
 
                     ORDER BY c.updated_at {sort_order} LIMIT :offset
                 )
@@ -1183,12 +1581,14 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
                 sc.stash = :stash_id
 
                 {search_where_cond}
+                {state_where_cond}
 
                 ORDER BY c.updated_at {sort_order} LIMIT :per_page;
             ",
             sort_order = sort_order,
             search_inner_join = search_inner_join,
-            search_where_cond = search_where_cond)
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
         },
 
         SortBy::Title => {
@@ -1217,6 +1617,7 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
                     sc.stash = :stash_id
 
                     {search_where_cond}
+                    {state_where_cond}
 
                     ORDER BY c.title {sort_order} LIMIT :offset
                 )
@@ -1224,12 +1625,14 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
                 sc.stash = :stash_id
 
                 {search_where_cond}
+                {state_where_cond}
 
                 ORDER BY c.title {sort_order} LIMIT :per_page;
             ",
             sort_order = sort_order,
             search_inner_join = search_inner_join,
-            search_where_cond = search_where_cond)
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
         },
 
         SortBy::ReviewedDate => {
@@ -1263,6 +1666,7 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
                                     cs.times_reviewed > 0
 
                                     {search_where_cond}
+                                    {state_where_cond}
 
                                 ORDER  BY cs.reviewed_at {sort_order}
                             )
@@ -1294,6 +1698,7 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
                                     cs.times_reviewed = 0
 
                                     {search_where_cond}
+                                    {state_where_cond}
 
                                 ORDER  BY cs.reviewed_at {sort_order}
                             )
@@ -1319,6 +1724,7 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
                                         cs.times_reviewed > 0
 
                                         {search_where_cond}
+                                        {state_where_cond}
 
                                     ORDER  BY cs.reviewed_at {sort_order}
                                 )
@@ -1342,6 +1748,7 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
                                         cs.times_reviewed = 0
 
                                         {search_where_cond}
+                                        {state_where_cond}
 
                                     ORDER  BY cs.reviewed_at {sort_order}
                                 )
@@ -1351,7 +1758,8 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
             ",
             sort_order = sort_order,
             search_inner_join = search_inner_join,
-            search_where_cond = search_where_cond)
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
         },
 
         SortBy::TimesReviewed => {
@@ -1386,6 +1794,7 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
                     sc.stash = :stash_id
 
                     {search_where_cond}
+                    {state_where_cond}
 
                     ORDER BY cs.times_reviewed {sort_order} LIMIT :offset
                 )
@@ -1393,12 +1802,358 @@ fn get_by_stash_query(page_query: &CardsPageRequest) -> String {
                 sc.stash = :stash_id
 
                 {search_where_cond}
+                {state_where_cond}
+
+                ORDER BY cs.times_reviewed {sort_order} LIMIT :per_page;
+            ",
+            sort_order = sort_order,
+            search_inner_join = search_inner_join,
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
+        },
+    };
+
+    return query;
+}
+
+fn get_by_tag_query(page_query: &CardsPageRequest) -> String {
+
+    let sort_order: &str = match page_query.order {
+        SortOrder::Descending => "DESC",
+        SortOrder::Ascending => "ASC"
+    };
+
+    let search_inner_join: &str = match page_query.search {
+        None => "",
+        Some(_) => {
+            "
+            INNER JOIN CardsFTS
+            ON CardsFTS.docid = c.card_id
+            "
+        }
+    };
+
+    let search_where_cond: &str = match page_query.search {
+        None => "",
+        Some(_) => {
+            "AND CardsFTS MATCH :search_query"
+        }
+    };
+
+    let state_where_cond: String = match page_query.state {
+        None => "".to_string(),
+        Some(ref state) => state.where_cond()
+    };
+
+    let query = match page_query.sort_by {
+
+        SortBy::CreatedAt => {
+            format!("
+                SELECT
+                    c.card_id, c.title, c.description, c.front, c.back, c.deck, c.created_at, c.updated_at
+                FROM CardsTags AS ct
+
+                INNER JOIN Cards AS c
+                ON c.card_id = ct.card
+
+                {search_inner_join}
+
+                WHERE
+                c.oid NOT IN (
+                    SELECT
+                        c.oid
+                    FROM CardsTags AS ct
+
+                    INNER JOIN Cards AS c
+                    ON c.card_id = ct.card
+
+                    {search_inner_join}
+
+                    WHERE
+                    ct.tag = :tag_id
+
+                    {search_where_cond}
+                    {state_where_cond}
+
+                    ORDER BY c.created_at {sort_order} LIMIT :offset
+                )
+                AND
+                ct.tag = :tag_id
+
+                {search_where_cond}
+                {state_where_cond}
+
+                ORDER BY c.created_at {sort_order} LIMIT :per_page;
+            ",
+            sort_order = sort_order,
+            search_inner_join = search_inner_join,
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
+        },
+
+        SortBy::UpdatedAt => {
+            format!("
+                SELECT
+                    c.card_id, c.title, c.description, c.front, c.back, c.deck, c.created_at, c.updated_at
+                FROM CardsTags AS ct
+
+                INNER JOIN Cards AS c
+                ON c.card_id = ct.card
+
+                {search_inner_join}
+
+                WHERE
+                c.oid NOT IN (
+                    SELECT
+                        c.oid
+                    FROM CardsTags AS ct
+
+                    INNER JOIN Cards AS c
+                    ON c.card_id = ct.card
+
+                    {search_inner_join}
+
+                    WHERE
+                    ct.tag = :tag_id
+
+                    {search_where_cond}
+                    {state_where_cond}
+
+                    ORDER BY c.updated_at {sort_order} LIMIT :offset
+                )
+                AND
+                ct.tag = :tag_id
+
+                {search_where_cond}
+                {state_where_cond}
+
+                ORDER BY c.updated_at {sort_order} LIMIT :per_page;
+            ",
+            sort_order = sort_order,
+            search_inner_join = search_inner_join,
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
+        },
+
+        SortBy::Title => {
+            format!("
+                SELECT
+                    c.card_id, c.title, c.description, c.front, c.back, c.deck, c.created_at, c.updated_at
+                FROM CardsTags AS ct
+
+                INNER JOIN Cards AS c
+                ON c.card_id = ct.card
+
+                {search_inner_join}
+
+                WHERE
+                c.oid NOT IN (
+                    SELECT
+                        c.oid
+                    FROM CardsTags AS ct
+
+                    INNER JOIN Cards AS c
+                    ON c.card_id = ct.card
+
+                    {search_inner_join}
+
+                    WHERE
+                    ct.tag = :tag_id
+
+                    {search_where_cond}
+                    {state_where_cond}
+
+                    ORDER BY c.title {sort_order} LIMIT :offset
+                )
+                AND
+                ct.tag = :tag_id
+
+                {search_where_cond}
+                {state_where_cond}
+
+                ORDER BY c.title {sort_order} LIMIT :per_page;
+            ",
+            sort_order = sort_order,
+            search_inner_join = search_inner_join,
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
+        },
+
+        SortBy::ReviewedDate => {
+            format!("
+                SELECT * FROM
+                    (
+                        SELECT * FROM
+                            (
+                                SELECT
+                                    c.card_id,
+                                    c.title,
+                                    c.description,
+                                    c.front,
+                                    c.back,
+                                    c.deck,
+                                    c.created_at,
+                                    c.updated_at
+
+                                FROM   CardsTags AS ct
+
+                                INNER JOIN cards AS c
+                                    ON c.card_id = ct.card
+                                INNER JOIN cardsscore AS cs
+                                    ON cs.card = c.card_id
+
+                                    {search_inner_join}
+
+                                WHERE
+                                    ct.tag = :tag_id
+                                AND
+                                    cs.times_reviewed > 0
+
+                                    {search_where_cond}
+                                    {state_where_cond}
+
+                                ORDER  BY cs.reviewed_at {sort_order}
+                            )
+                            UNION ALL
+                        SELECT * FROM
+                            (
+                                SELECT
+                                    c.card_id,
+                                    c.title,
+                                    c.description,
+                                    c.front,
+                                    c.back,
+                                    c.deck,
+                                    c.created_at,
+                                    c.updated_at
+
+                                FROM   CardsTags AS ct
+
+                                INNER JOIN cards AS c
+                                    ON c.card_id = ct.card
+                                INNER JOIN cardsscore AS cs
+                                    ON cs.card = c.card_id
+
+                                    {search_inner_join}
+
+                                WHERE
+                                    ct.tag = :tag_id
+                                AND
+                                    cs.times_reviewed = 0
+
+                                    {search_where_cond}
+                                    {state_where_cond}
+
+                                ORDER  BY cs.reviewed_at {sort_order}
+                            )
+                        ) AS res
+
+                        WHERE  res.card_id NOT IN (
+                            SELECT * FROM
+                                (
+                                    SELECT
+                                        c.oid
+                                    FROM   CardsTags AS ct
+
+                                    INNER JOIN cards AS c
+                                        ON c.card_id = ct.card
+                                    INNER JOIN cardsscore AS cs
+                                        ON cs.card = c.card_id
+
+                                        {search_inner_join}
+
+                                    WHERE
+                                        ct.tag = :tag_id
+                                    AND
+                                        cs.times_reviewed > 0
+
+                                        {search_where_cond}
+                                        {state_where_cond}
+
+                                    ORDER  BY cs.reviewed_at {sort_order}
+                                )
+                                UNION ALL
+                            SELECT * FROM
+                                (
+                                    SELECT
+                                        c.oid
+                                    FROM   CardsTags AS ct
+
+                                    INNER JOIN cards AS c
+                                        ON c.card_id = ct.card
+                                    INNER JOIN cardsscore AS cs
+                                        ON cs.card = c.card_id
+
+                                        {search_inner_join}
+
+                                    WHERE
+                                        ct.tag = :tag_id
+                                    AND
+                                        cs.times_reviewed = 0
+
+                                        {search_where_cond}
+                                        {state_where_cond}
+
+                                    ORDER  BY cs.reviewed_at {sort_order}
+                                )
+                            LIMIT :offset
+                        )
+                        LIMIT  :per_page;
+            ",
+            sort_order = sort_order,
+            search_inner_join = search_inner_join,
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
+        },
+
+        SortBy::TimesReviewed => {
+            format!("
+                SELECT
+                    c.card_id, c.title, c.description, c.front, c.back, c.deck, c.created_at, c.updated_at
+                FROM CardsTags AS ct
+
+                INNER JOIN Cards AS c
+                ON c.card_id = ct.card
+
+                INNER JOIN CardsScore AS cs
+                ON cs.card = c.card_id
+
+                {search_inner_join}
+
+                WHERE
+                c.oid NOT IN (
+                    SELECT
+                        c.oid
+                    FROM CardsTags AS ct
+
+                    INNER JOIN Cards AS c
+                    ON c.card_id = ct.card
+
+                    INNER JOIN CardsScore AS cs
+                    ON cs.card = c.card_id
+
+                    {search_inner_join}
+
+                    WHERE
+                    ct.tag = :tag_id
+
+                    {search_where_cond}
+                    {state_where_cond}
+
+                    ORDER BY cs.times_reviewed {sort_order} LIMIT :offset
+                )
+                AND
+                ct.tag = :tag_id
+
+                {search_where_cond}
+                {state_where_cond}
 
                 ORDER BY cs.times_reviewed {sort_order} LIMIT :per_page;
             ",
             sort_order = sort_order,
             search_inner_join = search_inner_join,
-            search_where_cond = search_where_cond)
+            search_where_cond = search_where_cond,
+            state_where_cond = state_where_cond)
         },
     };
 
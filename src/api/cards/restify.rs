@@ -16,9 +16,10 @@ use std::ops::Deref;
 use std::error::Error;
 
 use ::api::{GrokDB, ErrorResponse};
-use ::api::cards::{CreateCard, CreateCardForDeck, UpdateCard, CardResponse, CardPaginationInfo, CardsPageRequest, SortBy, SortOrder};
+use ::api::cards::{CreateCard, CreateCardForDeck, UpdateCard, CardResponse, CardPaginationInfo, CardsPageRequest, SortBy, SortOrder, CardState};
 use ::api::decks::restify::deck_exists;
 use ::api::stashes::restify::stash_exists;
+use ::api::tags::restify::tag_exists;
 use ::database::QueryError;
 
 
@@ -563,6 +564,22 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
         move |req: &mut Request| -> IronResult<Response> {
             let ref grokdb = grokdb.deref();
 
+            // fetch any card state filter
+            let state: Option<CardState> = match get_card_state_query(req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(state) => state
+            };
+
+            // fetch any tag filter
+            let tag: Option<String> = match get_card_tag_query(req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(tag) => tag
+            };
+
             let page_query: CardsPageRequest = match req.get_ref::<UrlEncodedQuery>() {
 
                 Ok(ref hashmap) => {
@@ -732,7 +749,9 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                         per_page: per_page,
                         sort_by: sort_by,
                         order: order,
-                        search: search
+                        search: search,
+                        state: state,
+                        tag: tag
                     }
                 },
 
@@ -742,7 +761,9 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                         per_page: 25,
                         sort_by: SortBy::UpdatedAt,
                         order: SortOrder::Descending,
-                        search: None
+                        search: None,
+                        state: state,
+                        tag: tag
                     }
                 },
 
@@ -790,7 +811,7 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                 _ => {/* noop; continue */}
             }
 
-            match grokdb.cards.count_by_deck(deck_id, None) {
+            match grokdb.cards.count_by_deck(deck_id, None, None, page_query.tag.clone()) {
 
                 Err(why) => {
                     // why: QueryError
@@ -902,6 +923,22 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
         move |req: &mut Request| -> IronResult<Response> {
             let ref grokdb = grokdb.deref();
 
+            // fetch any card state filter
+            let state: Option<CardState> = match get_card_state_query(req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(state) => state
+            };
+
+            // fetch any tag filter
+            let tag: Option<String> = match get_card_tag_query(req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(tag) => tag
+            };
+
 
             // fetch any search query
             let search_query: Option<String> = match req.get_ref::<UrlEncodedQuery>() {
@@ -978,7 +1015,7 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                 _ => {/* noop; continue */}
             }
 
-            let count = match grokdb.cards.count_by_deck(deck_id, search_query) {
+            let count = match grokdb.cards.count_by_deck(deck_id, search_query, state, tag) {
                 Err(why) => {
                     // why: QueryError
 
@@ -1109,6 +1146,14 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
         move |req: &mut Request| -> IronResult<Response> {
             let ref grokdb = grokdb.deref();
 
+            // fetch any card state filter
+            let state: Option<CardState> = match get_card_state_query(req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(state) => state
+            };
+
             let page_query: CardsPageRequest = match req.get_ref::<UrlEncodedQuery>() {
                 Ok(ref hashmap) => {
                     let hashmap: &QueryMap = hashmap;
@@ -1275,7 +1320,9 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                         per_page: per_page,
                         sort_by: sort_by,
                         order: order,
-                        search: search
+                        search: search,
+                        state: state,
+                        tag: None
                     }
                 },
 
@@ -1285,7 +1332,9 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                         per_page: 25,
                         sort_by: SortBy::UpdatedAt,
                         order: SortOrder::Descending,
-                        search: None
+                        search: None,
+                        state: state,
+                        tag: None
                     }
                 },
 
@@ -1333,7 +1382,7 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                 _ => {/* stash exists; continue */}
             }
 
-            match grokdb.cards.count_by_stash(stash_id) {
+            match grokdb.cards.count_by_stash(stash_id, None) {
                 Err(why) => {
                     // why: QueryError
 
@@ -1436,11 +1485,346 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
         }
     });
 
+    router.get("/tags/:tag/cards", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            // fetch any card state filter
+            let state: Option<CardState> = match get_card_state_query(req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(state) => state
+            };
+
+            let page_query: CardsPageRequest = match req.get_ref::<UrlEncodedQuery>() {
+                Ok(ref hashmap) => {
+                    let hashmap: &QueryMap = hashmap;
+
+                    let page: i64 = match hashmap.contains_key("page") {
+                        true => {
+                            let maybe_page: &Vec<String> = hashmap.get("page").unwrap();
+
+                            if maybe_page.len() <= 0 {
+                                1
+                            } else {
+
+                                let ref page: String = maybe_page[0];
+
+                                match page.parse::<i64>() {
+                                    Ok(page) => {
+                                        let page: i64 = page;
+
+                                        if page <= 0 {
+                                            let ref reason = format!("page query should be at least 1");
+                                            let res_code = status::BadRequest;
+
+                                            let err_response = ErrorResponse {
+                                                status: res_code,
+                                                developerMessage: reason,
+                                                userMessage: reason,
+                                            }.to_json();
+
+                                            return Ok(Response::with((res_code, err_response)));
+                                        }
+
+                                        page
+                                    },
+                                    Err(why) => {
+                                        let ref reason = format!("invalid page query");
+                                        let res_code = status::BadRequest;
+
+                                        let err_response = ErrorResponse {
+                                            status: res_code,
+                                            developerMessage: why.description(),
+                                            userMessage: reason,
+                                        }.to_json();
+
+                                        return Ok(Response::with((res_code, err_response)));
+                                    }
+                                }
+                            }
+                        },
+                        _ => 1
+                    };
+
+                    let per_page: i64 = match hashmap.contains_key("per_page") {
+                        true => {
+                            let maybe_per_page: &Vec<String> = hashmap.get("per_page").unwrap();
+
+                            if maybe_per_page.len() <= 0 {
+                                25
+                            } else {
+
+                                let ref per_page: String = maybe_per_page[0];
+
+                                match per_page.parse::<i64>() {
+                                    Ok(per_page) => {
+                                        let per_page: i64 = per_page;
+
+                                        if per_page <= 0 {
+                                            let ref reason = format!("per_page query should be at least 1");
+                                            let res_code = status::BadRequest;
+
+                                            let err_response = ErrorResponse {
+                                                status: res_code,
+                                                developerMessage: reason,
+                                                userMessage: reason,
+                                            }.to_json();
+
+                                            return Ok(Response::with((res_code, err_response)));
+                                        }
+
+                                        per_page
+                                    },
+                                    Err(why) => {
+                                        let ref reason = format!("invalid per_page query");
+                                        let res_code = status::BadRequest;
+
+                                        let err_response = ErrorResponse {
+                                            status: res_code,
+                                            developerMessage: why.description(),
+                                            userMessage: reason,
+                                        }.to_json();
+
+                                        return Ok(Response::with((res_code, err_response)));
+                                    }
+                                }
+                            }
+                        },
+                        _ => 25
+                    };
+
+                    let sort_by: SortBy = match hashmap.contains_key("sort_by") {
+                        true => {
+                            let maybe_sort_by: &Vec<String> = hashmap.get("sort_by").unwrap();
+
+                            if maybe_sort_by.len() <= 0 {
+                                SortBy::UpdatedAt
+                            } else {
+
+                                let ref sort_by: String = maybe_sort_by[0];
+
+                                match sort_by.to_lowercase().as_ref() {
+                                    "created_at" => SortBy::CreatedAt,
+                                    "updated_at" => SortBy::UpdatedAt,
+                                    "title" => SortBy::Title,
+                                    "reviewed_at" => SortBy::ReviewedDate,
+                                    "times_reviewed" => SortBy::TimesReviewed,
+                                    // "raw_score" => SortBy::RawScore,
+                                    _ => SortBy::UpdatedAt
+                                }
+                            }
+                        },
+                        _ => SortBy::UpdatedAt
+                    };
+
+                    let order: SortOrder = match hashmap.contains_key("order_by") {
+                        true => {
+                            let maybe_order_by: &Vec<String> = hashmap.get("order_by").unwrap();
+
+                            if maybe_order_by.len() <= 0 {
+                                SortOrder::Descending
+                            } else {
+
+                                let ref order_by: String = maybe_order_by[0];
+
+                                match order_by.to_lowercase().as_ref() {
+                                    "desc" => SortOrder::Descending,
+                                    "descending" => SortOrder::Descending,
+                                    "asc" => SortOrder::Ascending,
+                                    "ascending" => SortOrder::Ascending,
+                                    _ => SortOrder::Descending
+                                }
+                            }
+                        },
+                        _ => SortOrder::Descending
+                    };
+
+                    let search: Option<String> = match hashmap.contains_key("search") {
+                        true => {
+                            let maybe_search: &Vec<String> = hashmap.get("search").unwrap();
+
+                            if maybe_search.len() <= 0 {
+                                None
+                            } else {
+
+                                let ref search: String = maybe_search[0];
+
+                                let search: String = search.trim().to_string();
+                                Some(search)
+                            }
+                        },
+                        _ => None
+                    };
+
+                    CardsPageRequest {
+                        page: page,
+                        per_page: per_page,
+                        sort_by: sort_by,
+                        order: order,
+                        search: search,
+                        state: state,
+                        tag: None
+                    }
+                },
+
+                Err(UrlDecodingError::EmptyQuery) => {
+                    CardsPageRequest {
+                        page: 1,
+                        per_page: 25,
+                        sort_by: SortBy::UpdatedAt,
+                        order: SortOrder::Descending,
+                        search: None,
+                        state: state,
+                        tag: None
+                    }
+                },
+
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // fetch requested tag
+
+            let tag: String = req.extensions.get::<Router>().unwrap().find("tag").unwrap().trim().to_string();
+
+            // ensure tag exists
+            let tag_id: i64 = match tag_exists(grokdb, &tag) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(tag_id) => tag_id
+            };
+
+            match grokdb.cards.count_by_tag(tag_id, None) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+
+                Ok(count) => {
+
+                    if count <= 0 {
+                        let ref v: Vec<CardResponse> = vec![];
+                        let response: String = json::encode(v).unwrap();
+
+                        let content_type = "application/json".parse::<Mime>().unwrap();
+
+                        return Ok(Response::with((content_type, status::Ok, response)));
+                    }
+
+                    if page_query.get_offset() >= count {
+                        let ref reason = format!("page out of bounds");
+                        let res_code = status::BadRequest;
+
+                        let err_response = ErrorResponse {
+                            status: res_code,
+                            developerMessage: reason,
+                            userMessage: reason,
+                        }.to_json();
+
+                        return Ok(Response::with((res_code, err_response)));
+                    }
+                }
+            }
+
+            let response: String = match grokdb.cards.get_by_tag(tag_id, page_query) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+
+                Ok(list) => {
+
+                    let mut collected_list: Vec<CardResponse> = vec![];
+
+                    for card_id in &list {
+
+                        let card_id: i64 = *card_id;
+
+                        let maybe_card: Result<CardResponse, QueryError> = grokdb.cards.get_response(grokdb, card_id);
+
+                        let card: CardResponse = match maybe_card {
+
+                            Err(why) => {
+                                // why: QueryError
+
+                                let ref reason = format!("{:?}", why);
+                                let res_code = status::NotFound;
+
+                                let err_response = ErrorResponse {
+                                    status: res_code,
+                                    developerMessage: reason,
+                                    userMessage: why.description(),
+                                }.to_json();
+
+                                return Ok(Response::with((res_code, err_response)));
+                            },
+
+                            Ok(card) => card,
+                        };
+
+                        collected_list.push(card);
+                    }
+
+                    let ref collected_list = collected_list;
+
+                    json::encode(collected_list).unwrap()
+                }
+            };
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response)));
+        }
+    });
+
     router.get("/stashes/:stash_id/cards/count", {
         let grokdb = grokdb.clone();
         move |req: &mut Request| -> IronResult<Response> {
             let ref grokdb = grokdb.deref();
 
+            // fetch any card state filter
+            let state: Option<CardState> = match get_card_state_query(req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(state) => state
+            };
+
             // fetch and parse requested stash id
 
             let stash_id = req.extensions.get::<Router>().unwrap().find("stash_id").unwrap();
@@ -1470,7 +1854,7 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                 _ => {/* stash exists; continue */}
             }
 
-            let count = match grokdb.cards.count_by_stash(stash_id) {
+            let count = match grokdb.cards.count_by_stash(stash_id, state) {
                 Err(why) => {
                     // why: QueryError
 
@@ -1669,3 +2053,115 @@ pub fn card_exists(grokdb: &GrokDB, card_id: i64) -> Result<(), IronResult<Respo
         }
     }
 }
+
+// parse card state filter from the state query (if any)
+fn get_card_state_query(req: &mut Request) -> Result<Option<CardState>, IronResult<Response>> {
+
+    match req.get_ref::<UrlEncodedQuery>() {
+
+        Ok(ref hashmap) => {
+
+            let hashmap: &QueryMap = hashmap;
+
+            if !hashmap.contains_key("state") {
+                return Ok(None);
+            }
+
+            let maybe_state: &Vec<String> = hashmap.get("state").unwrap();
+
+            if maybe_state.len() <= 0 {
+                return Ok(None);
+            }
+
+            let ref state: String = maybe_state[0];
+
+            return match state.trim().to_lowercase().as_ref() {
+                "active" => Ok(Some(CardState::Active)),
+                "suspended" => Ok(Some(CardState::Suspended)),
+                "buried" => Ok(Some(CardState::Buried)),
+                _ => {
+
+                    let ref reason = format!("invalid state query; should be one of: active, suspended, buried");
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: reason,
+                    }.to_json();
+
+                    let res = Ok(Response::with((res_code, err_response)));
+                    return Err(res);
+                }
+            };
+        },
+
+        Err(UrlDecodingError::EmptyQuery) => {
+            return Ok(None);
+        },
+
+        Err(why) => {
+
+            let ref reason = format!("{:?}", why);
+            let res_code = status::BadRequest;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: why.description(),
+            }.to_json();
+
+            let res = Ok(Response::with((res_code, err_response)));
+            return Err(res);
+        }
+    }
+}
+
+// parse tag filter from the tag query (if any)
+fn get_card_tag_query(req: &mut Request) -> Result<Option<String>, IronResult<Response>> {
+
+    match req.get_ref::<UrlEncodedQuery>() {
+
+        Ok(ref hashmap) => {
+
+            let hashmap: &QueryMap = hashmap;
+
+            if !hashmap.contains_key("tag") {
+                return Ok(None);
+            }
+
+            let maybe_tag: &Vec<String> = hashmap.get("tag").unwrap();
+
+            if maybe_tag.len() <= 0 {
+                return Ok(None);
+            }
+
+            let tag: String = maybe_tag[0].trim().to_string();
+
+            if tag.len() <= 0 {
+                return Ok(None);
+            }
+
+            return Ok(Some(tag));
+        },
+
+        Err(UrlDecodingError::EmptyQuery) => {
+            return Ok(None);
+        },
+
+        Err(why) => {
+
+            let ref reason = format!("{:?}", why);
+            let res_code = status::BadRequest;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: why.description(),
+            }.to_json();
+
+            let res = Ok(Response::with((res_code, err_response)));
+            return Err(res);
+        }
+    }
+}
@@ -52,6 +52,10 @@ impl ConfigResponse {
     pub fn to_json(&self) -> String {
         return json::encode(self).unwrap();
     }
+
+    pub fn get_value(&self) -> String {
+        return self.value.clone();
+    }
 }
 
 #[derive(Debug, Clone)]
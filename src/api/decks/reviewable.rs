@@ -7,11 +7,15 @@ use rusqlite::types::ToSql;
 
 use ::database::{DB, QueryError};
 use ::api::GrokDB;
-use ::api::review::ReviewableSelection;
+use ::api::review::{ReviewableSelection, reviewable_cards_filter};
+use ::api::review::settings::ReviewSettings;
 
 pub struct ReviewableDeck {
     pub deck_id: i64,
-    pub grokdb: Arc<GrokDB>
+    pub grokdb: Arc<GrokDB>,
+
+    // if false, new cards are hidden (e.g. daily limit of new cards of a review session is reached)
+    pub allow_new_cards: bool
 }
 
 impl ReviewableDeck {
@@ -96,6 +100,142 @@ impl ReviewableDeck {
         };
     }
 
+    // choose the scheduler for this deck
+    pub fn set_scheduler_name(&self, scheduler_name: &str) -> Result<(), QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query = format!("
+            INSERT OR REPLACE INTO DecksScheduler(deck, scheduler)
+            VALUES (:deck_id, :scheduler);
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &(self.deck_id)),
+            (":scheduler", &scheduler_name)
+        ];
+
+        match db_conn.execute_named(query, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        return Ok(());
+    }
+
+    // remove the scheduler chosen for this deck
+    pub fn remove_scheduler_name(&self) -> Result<(), QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query_delete = format!("
+            DELETE FROM DecksScheduler WHERE deck = :deck_id;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &(self.deck_id))
+        ];
+
+        match db_conn.execute_named(query_delete, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_delete.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        return Ok(());
+    }
+
+    // choose the review settings for this deck
+    pub fn set_review_settings(&self, settings: &ReviewSettings) -> Result<(), QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query = format!("
+            INSERT OR REPLACE INTO DecksReviewSettings(deck, new_cards, old_enough, least_recent, min_score, age_in_hours, purgatory_size)
+            VALUES (:deck_id, :new_cards, :old_enough, :least_recent, :min_score, :age_in_hours, :purgatory_size);
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &(self.deck_id)),
+            (":new_cards", &(settings.new_cards)),
+            (":old_enough", &(settings.old_enough)),
+            (":least_recent", &(settings.least_recent)),
+            (":min_score", &(settings.min_score)),
+            (":age_in_hours", &(settings.age_in_hours)),
+            (":purgatory_size", &(settings.purgatory_size))
+        ];
+
+        match db_conn.execute_named(query, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        return Ok(());
+    }
+
+    // remove the review settings chosen for this deck
+    pub fn remove_review_settings(&self) -> Result<(), QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query_delete = format!("
+            DELETE FROM DecksReviewSettings WHERE deck = :deck_id;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &(self.deck_id))
+        ];
+
+        match db_conn.execute_named(query_delete, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_delete.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        return Ok(());
+    }
 }
 
 impl ReviewableSelection for ReviewableDeck {
@@ -115,14 +255,46 @@ impl ReviewableSelection for ReviewableDeck {
 
     fn number_of_cards(&self) -> Result<i64, QueryError> {
 
-        match self.grokdb.deref().cards.count_by_deck(self.deck_id, None) {
-            Err(err) => {
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &(self.deck_id))
+        ];
+
+        // suspended cards are excluded
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM DecksClosure AS dc
+
+            INNER JOIN Cards AS c
+            ON c.deck = dc.descendent
+
+            WHERE
+                dc.ancestor = :deck_id
+            AND
+                {reviewable};
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
                 return Err(err);
             },
-            Ok(num_cards) => {
-                return Ok(num_cards);
+            Ok(count) => {
+                return Ok(count);
             }
-        }
+        };
     }
 
     fn cache_card(&self, card_id: i64) -> Result<(), QueryError> {
@@ -258,6 +430,24 @@ impl ReviewableSelection for ReviewableDeck {
         return Ok(());
     }
 
+    fn allows_new_cards(&self) -> bool {
+        return self.allow_new_cards;
+    }
+
+    fn is_new_card(&self, card_id: i64) -> Result<bool, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        match grokdb.review.get_review_stat(card_id) {
+            Err(why) => {
+                return Err(why);
+            },
+            Ok(review_stat) => {
+                return Ok(review_stat.is_new());
+            }
+        }
+    }
+
     fn has_new_cards(&self) -> Result<bool, QueryError> {
 
         match self.number_of_new_cards() {
@@ -272,6 +462,10 @@ impl ReviewableSelection for ReviewableDeck {
 
     fn number_of_new_cards(&self) -> Result<i64, QueryError> {
 
+        if !self.allow_new_cards {
+            return Ok(0);
+        }
+
         let ref grokdb = self.grokdb.deref();
 
         let db_conn_guard = grokdb.decks.db.lock().unwrap();
@@ -294,9 +488,11 @@ impl ReviewableSelection for ReviewableDeck {
 
             WHERE
                 dc.ancestor = :deck_id
+            AND
+                {reviewable}
             AND
                 (c.created_at - cs.seen_at) = 0;
-        ");
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
 
         let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
             return row.get(0);
@@ -319,6 +515,8 @@ impl ReviewableSelection for ReviewableDeck {
     // returns card id
     fn get_new_card(&self, index: i64) -> Result<i64, QueryError> {
 
+        assert!(self.allow_new_cards);
+
         let ref grokdb = self.grokdb.deref();
 
         let db_conn_guard = grokdb.decks.db.lock().unwrap();
@@ -337,11 +535,13 @@ impl ReviewableSelection for ReviewableDeck {
 
             WHERE
                 dc.ancestor = :deck_id
+            AND
+                {reviewable}
             AND
                 (c.created_at - cs.seen_at) = 0
             LIMIT 1
             OFFSET :offset;
-        ");
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
 
         let params: &[(&str, &ToSql)] = &[
             (":deck_id", &(self.deck_id)),
@@ -398,11 +598,13 @@ impl ReviewableSelection for ReviewableDeck {
 
             WHERE
                 dc.ancestor = :deck_id
+            AND
+                {reviewable}
             AND
                 (strftime('%s','now') - cs.seen_at) >= :age_of_consent
             AND
                 raw_score(cs.success, cs.fail) >= :min_score;
-        ");
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
 
         let age_in_seconds: i64 = age_in_hours * 3600;
 
@@ -451,6 +653,8 @@ impl ReviewableSelection for ReviewableDeck {
 
             WHERE
                 dc.ancestor = :deck_id
+            AND
+                {reviewable}
             AND
                 (strftime('%s','now') - cs.seen_at) >= :age_of_consent
             AND
@@ -459,7 +663,7 @@ impl ReviewableSelection for ReviewableDeck {
                 rank_score(cs.success, cs.fail, strftime('%s','now') - cs.seen_at, cs.times_reviewed) DESC
             LIMIT 1
             OFFSET :index;
-        ");
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
 
         let age_in_seconds: i64 = age_in_hours * 3600;
 
@@ -524,6 +728,8 @@ impl ReviewableSelection for ReviewableDeck {
 
                 WHERE
                     dc.ancestor = :deck_id
+                AND
+                    {reviewable}
                 ORDER BY
                     (strftime('%s','now') - cs.seen_at) DESC
                 LIMIT :purgatory_size
@@ -533,7 +739,7 @@ impl ReviewableSelection for ReviewableDeck {
                 raw_score(sub.success, sub.fail) >= :min_score
             {sort_by_score}
             ;
-        ", sort_by_score = {
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards), sort_by_score = {
             if sort_by_score {
                 "ORDER BY rank_score(sub.success, sub.fail, strftime('%s','now') - sub.seen_at, sub.times_reviewed) DESC"
             } else {
@@ -590,6 +796,8 @@ impl ReviewableSelection for ReviewableDeck {
 
                 WHERE
                     dc.ancestor = :deck_id
+                AND
+                    {reviewable}
                 ORDER BY
                     (strftime('%s','now') - cs.seen_at) DESC
                 LIMIT :purgatory_size
@@ -599,7 +807,7 @@ impl ReviewableSelection for ReviewableDeck {
                 raw_score(sub.success, sub.fail) >= :min_score
             {sort_by_score}
             LIMIT 1 OFFSET :index;
-        ", sort_by_score = {
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards), sort_by_score = {
             if sort_by_score {
                 "ORDER BY rank_score(sub.success, sub.fail, strftime('%s','now') - sub.seen_at, sub.times_reviewed) DESC"
             } else {
@@ -632,4 +840,401 @@ impl ReviewableSelection for ReviewableDeck {
         };
     }
 
+    fn has_due_cards(&self) -> Result<bool, QueryError> {
+
+        match self.number_of_due_cards() {
+            Err(err) => {
+                return Err(err);
+            },
+            Ok(num_cards) => {
+                return Ok(num_cards > 0);
+            }
+        }
+    }
+
+    fn number_of_due_cards(&self) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &(self.deck_id))
+        ];
+
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM DecksClosure AS dc
+
+            INNER JOIN Cards AS c
+            ON c.deck = dc.descendent
+
+            INNER JOIN CardsSM2 AS sm
+            ON sm.card = c.card_id
+
+            WHERE
+                dc.ancestor = :deck_id
+            AND
+                {reviewable}
+            AND
+                sm.due_at <= strftime('%s','now');
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(count) => {
+                return Ok(count);
+            }
+        };
+    }
+
+    // returns card id
+    fn get_due_card(&self, index: i64) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                c.card_id
+            FROM DecksClosure AS dc
+
+            INNER JOIN Cards AS c
+            ON c.deck = dc.descendent
+
+            INNER JOIN CardsSM2 AS sm
+            ON sm.card = c.card_id
+
+            WHERE
+                dc.ancestor = :deck_id
+            AND
+                {reviewable}
+            AND
+                sm.due_at <= strftime('%s','now')
+            ORDER BY
+                sm.due_at ASC
+            LIMIT 1
+            OFFSET :index;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &(self.deck_id)),
+            (":index", &index)
+        ];
+
+        let maybe_card_id = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_card_id {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(card_id) => {
+                return Ok(card_id);
+            }
+        };
+    }
+
+    fn has_fading_cards(&self, retention: f64) -> Result<bool, QueryError> {
+
+        match self.number_of_fading_cards(retention) {
+            Err(err) => {
+                return Err(err);
+            },
+            Ok(num_cards) => {
+                return Ok(num_cards > 0);
+            }
+        }
+    }
+
+    fn number_of_fading_cards(&self, retention: f64) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &(self.deck_id)),
+            (":retention", &retention)
+        ];
+
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM DecksClosure AS dc
+
+            INNER JOIN Cards AS c
+            ON c.deck = dc.descendent
+
+            INNER JOIN CardsFSRS AS f
+            ON f.card = c.card_id
+
+            WHERE
+                dc.ancestor = :deck_id
+            AND
+                {reviewable}
+            AND
+                fsrs_retrievability(CAST(strftime('%s','now') - f.reviewed_at AS REAL), f.stability) < :retention;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(count) => {
+                return Ok(count);
+            }
+        };
+    }
+
+    // returns card id
+    fn get_fading_card(&self, retention: f64, index: i64) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                c.card_id
+            FROM DecksClosure AS dc
+
+            INNER JOIN Cards AS c
+            ON c.deck = dc.descendent
+
+            INNER JOIN CardsFSRS AS f
+            ON f.card = c.card_id
+
+            WHERE
+                dc.ancestor = :deck_id
+            AND
+                {reviewable}
+            AND
+                fsrs_retrievability(CAST(strftime('%s','now') - f.reviewed_at AS REAL), f.stability) < :retention
+            ORDER BY
+                fsrs_retrievability(CAST(strftime('%s','now') - f.reviewed_at AS REAL), f.stability) ASC
+            LIMIT 1
+            OFFSET :index;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &(self.deck_id)),
+            (":retention", &retention),
+            (":index", &index)
+        ];
+
+        let maybe_card_id = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_card_id {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(card_id) => {
+                return Ok(card_id);
+            }
+        };
+    }
+
+    fn get_scheduler_name(&self) -> Result<Option<String>, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &(self.deck_id))
+        ];
+
+        // a deck inherits the scheduler of its nearest ancestor (including itself)
+
+        // TODO: can be simplified if this is fixed: https://github.com/jgallagher/rusqlite/issues/79
+        let ref query_count = format!("
+            SELECT
+                COUNT(1)
+            FROM DecksClosure AS dc
+
+            INNER JOIN DecksScheduler AS ds
+            ON ds.deck = dc.ancestor
+
+            WHERE
+                dc.descendent = :deck_id
+            LIMIT 1;
+        ");
+
+        let has_entry = db_conn.query_row_named(query_count, params, |row| -> bool {
+            let count: i64 = row.get(0);
+            return count >= 1;
+        });
+
+        match has_entry {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_count.clone(),
+                };
+                return Err(err);
+            },
+            Ok(has_entry) => {
+                if !has_entry {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let ref query = format!("
+            SELECT
+                ds.scheduler
+            FROM DecksClosure AS dc
+
+            INNER JOIN DecksScheduler AS ds
+            ON ds.deck = dc.ancestor
+
+            WHERE
+                dc.descendent = :deck_id
+            ORDER BY
+                dc.depth ASC
+            LIMIT 1;
+        ");
+
+        let scheduler_name = db_conn.query_row_named(query, params, |row| -> String {
+            return row.get(0);
+        });
+
+        match scheduler_name {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(scheduler_name) => {
+                return Ok(Some(scheduler_name));
+            }
+        };
+    }
+
+    fn get_review_settings(&self) -> Result<Option<ReviewSettings>, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &(self.deck_id))
+        ];
+
+        // a deck inherits the review settings of its nearest ancestor (including itself)
+
+        // TODO: can be simplified if this is fixed: https://github.com/jgallagher/rusqlite/issues/79
+        let ref query_count = format!("
+            SELECT
+                COUNT(1)
+            FROM DecksClosure AS dc
+
+            INNER JOIN DecksReviewSettings AS rs
+            ON rs.deck = dc.ancestor
+
+            WHERE
+                dc.descendent = :deck_id
+            LIMIT 1;
+        ");
+
+        let has_entry = db_conn.query_row_named(query_count, params, |row| -> bool {
+            let count: i64 = row.get(0);
+            return count >= 1;
+        });
+
+        match has_entry {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_count.clone(),
+                };
+                return Err(err);
+            },
+            Ok(has_entry) => {
+                if !has_entry {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let ref query = format!("
+            SELECT
+                rs.new_cards, rs.old_enough, rs.least_recent, rs.min_score, rs.age_in_hours, rs.purgatory_size
+            FROM DecksClosure AS dc
+
+            INNER JOIN DecksReviewSettings AS rs
+            ON rs.deck = dc.ancestor
+
+            WHERE
+                dc.descendent = :deck_id
+            ORDER BY
+                dc.depth ASC
+            LIMIT 1;
+        ");
+
+        let settings = db_conn.query_row_named(query, params, |row| -> ReviewSettings {
+            return ReviewSettings {
+                new_cards: row.get(0),
+                old_enough: row.get(1),
+                least_recent: row.get(2),
+                min_score: row.get(3),
+                age_in_hours: row.get(4),
+                purgatory_size: row.get(5)
+            };
+        });
+
+        match settings {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(settings) => {
+                return Ok(Some(settings));
+            }
+        };
+    }
+
 }
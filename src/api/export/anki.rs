@@ -0,0 +1,855 @@
+extern crate chrono;
+extern crate rusqlite;
+extern crate rustc_serialize;
+extern crate sha1;
+extern crate zip;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Write, Seek};
+
+use chrono::*;
+use rusqlite::{Connection, SqliteStatement};
+use rusqlite::types::ToSql;
+use rustc_serialize::json::Json;
+use zip::{ZipWriter, CompressionMethod};
+
+use ::database::QueryError;
+use ::api::tempfile::TempFile;
+use ::api::export::{ExportError, strip_html};
+
+
+// export of a deck (and the deck's descendents) as an Anki package (.apkg).
+//
+// the package holds an Anki collection in the legacy format (collection.anki2), which any
+// version of Anki is able to open.
+// src: https://github.com/ankitects/anki/blob/main/rslib/src/storage/schema11.sql
+//
+// - every deck is mapped onto an Anki deck; nested decks are named by joining deck names with ::
+// - every card is mapped onto a note of a basic note type, with a Front and a Back field
+// - every review event (that wasn't undone) in CardsScoreHistory is mapped onto a revlog entry;
+//   a review that isn't a success is answered with 'again', and any other with 'good'.
+//
+// cards that were never reviewed are exported as new cards. reviewed cards are exported as review
+// cards; they're scheduled by their SM-2 schedule (see review::sm2) if they have one, otherwise
+// they're due the day of the export.
+
+static COLLECTION_FILE: &'static str = "collection.anki2";
+static MEDIA_FILE: &'static str = "media";
+
+static DECK_SEPARATOR: &'static str = "::";
+static FIELD_SEPARATOR: &'static str = "\x1f";
+
+// id of Anki's default deck, and of the default deck options
+static DEFAULT_DECK_ID: i64 = 1;
+static DEFAULT_DECK_CONFIG_ID: i64 = 1;
+
+// fixed, so that re-importing an export reuses the note type
+static BASIC_MODEL_ID: i64 = 1447200000000;
+static BASIC_MODEL_NAME: &'static str = "Basic (grokdb)";
+
+// revlog answer buttons
+static EASE_AGAIN: i64 = 1;
+static EASE_GOOD: i64 = 3;
+
+// revlog entry type of a review
+static REVLOG_REVIEW: i64 = 1;
+
+// card type and queue of new and review cards
+static CARD_NEW: i64 = 0;
+static CARD_REVIEW: i64 = 2;
+
+// ease factor of review cards, in permille
+static DEFAULT_FACTOR: i64 = 2500;
+
+// Anki caps the time taken to answer a card at 60 seconds
+static MAX_ANSWER_TIME_MS: i64 = 60000;
+
+static ANKI_SCHEMA: &'static str = "
+CREATE TABLE col (
+    id integer primary key,
+    crt integer not null,
+    mod integer not null,
+    scm integer not null,
+    ver integer not null,
+    dty integer not null,
+    usn integer not null,
+    ls integer not null,
+    conf text not null,
+    models text not null,
+    decks text not null,
+    dconf text not null,
+    tags text not null
+);
+CREATE TABLE notes (
+    id integer primary key,
+    guid text not null,
+    mid integer not null,
+    mod integer not null,
+    usn integer not null,
+    tags text not null,
+    flds text not null,
+    sfld integer not null,
+    csum integer not null,
+    flags integer not null,
+    data text not null
+);
+CREATE TABLE cards (
+    id integer primary key,
+    nid integer not null,
+    did integer not null,
+    ord integer not null,
+    mod integer not null,
+    usn integer not null,
+    type integer not null,
+    queue integer not null,
+    due integer not null,
+    ivl integer not null,
+    factor integer not null,
+    reps integer not null,
+    lapses integer not null,
+    left integer not null,
+    odue integer not null,
+    odid integer not null,
+    flags integer not null,
+    data text not null
+);
+CREATE TABLE revlog (
+    id integer primary key,
+    cid integer not null,
+    usn integer not null,
+    ease integer not null,
+    ivl integer not null,
+    lastIvl integer not null,
+    factor integer not null,
+    time integer not null,
+    type integer not null
+);
+CREATE TABLE graves (
+    usn integer not null,
+    oid integer not null,
+    type integer not null
+);
+CREATE INDEX ix_notes_usn ON notes (usn);
+CREATE INDEX ix_cards_usn ON cards (usn);
+CREATE INDEX ix_revlog_usn ON revlog (usn);
+CREATE INDEX ix_cards_nid ON cards (nid);
+CREATE INDEX ix_cards_sched ON cards (did, queue, due);
+CREATE INDEX ix_revlog_cid ON revlog (cid);
+CREATE INDEX ix_notes_csum ON notes (csum);
+";
+
+static BASIC_MODEL_CSS: &'static str = ".card {
+    font-family: arial;
+    font-size: 20px;
+    text-align: center;
+    color: black;
+    background-color: white;
+}
+";
+
+#[derive(Debug)]
+pub struct AnkiExportResponse {
+    pub decks_exported: i64,
+    pub cards_exported: i64,
+    pub reviews_exported: i64
+}
+
+struct ExportedDeck {
+    deck_id: i64,
+    parent: Option<i64>,
+    name: String,
+    description: String
+}
+
+struct ExportedCard {
+    card_id: i64,
+    deck: i64,
+    front: String,
+    back: String,
+    created_at: i64, // unix timestamp
+    updated_at: i64, // unix timestamp
+
+    // SM-2 schedule; null if the card was never graded
+    ease_factor: Option<f64>,
+    interval_days: Option<i64>,
+    due_at: Option<i64> // unix timestamp
+}
+
+struct ExportedReview {
+    card: i64,
+    occurred_at: i64, // unix timestamp
+    success: i64,
+    answer_time_ms: Option<i64>
+}
+
+// export the deck and the deck's descendents as an .apkg package into writer.
+// guid_prefix identifies the database the cards are exported from; Anki uses note guids to
+// find notes that were previously imported.
+// caller should hold the db connection lock.
+pub fn export_apkg<W: Write + Seek>(db_conn: &Connection, deck_id: i64, guid_prefix: &str, writer: W)
+    -> Result<AnkiExportResponse, ExportError> {
+
+    let decks: Vec<ExportedDeck> = try!(get_decks(db_conn, deck_id));
+    let cards: Vec<ExportedCard> = try!(get_cards(db_conn, deck_id));
+    let reviews: Vec<ExportedReview> = try!(get_reviews(db_conn, deck_id));
+
+    let now: i64 = UTC::now().timestamp();
+    let now_ms: i64 = now * 1000;
+
+    // map decks onto Anki decks
+
+    let mut anki_decks: BTreeMap<String, Json> = BTreeMap::new();
+    let mut deck_map: HashMap<i64, (i64, String)> = HashMap::new();
+
+    anki_decks.insert(format!("{}", DEFAULT_DECK_ID), anki_deck(DEFAULT_DECK_ID, "Default", "", now));
+
+    for (index, deck) in decks.iter().enumerate() {
+
+        let name: String = deck.name.replace(DECK_SEPARATOR, ":").trim().to_string();
+
+        // the exported deck is a root deck within Anki
+        let name: String = match deck.parent.and_then(|parent| deck_map.get(&parent)) {
+            Some(&(_, ref parent_name)) if index > 0 => format!("{}{}{}", parent_name, DECK_SEPARATOR, name),
+            _ => name
+        };
+
+        let anki_deck_id: i64 = now_ms + (index as i64);
+
+        anki_decks.insert(format!("{}", anki_deck_id), anki_deck(anki_deck_id, &name, &deck.description, now));
+        deck_map.insert(deck.deck_id, (anki_deck_id, name));
+    }
+
+    // count reviews and lapses of cards
+
+    let mut card_reviews: HashMap<i64, (i64, i64)> = HashMap::new();
+
+    for review in reviews.iter() {
+
+        let entry = card_reviews.entry(review.card).or_insert((0, 0));
+
+        entry.0 = entry.0 + 1;
+
+        if review.success <= 0 {
+            entry.1 = entry.1 + 1;
+        }
+    }
+
+    let temp_file = TempFile::new("grokdb-anki", "anki2");
+
+    let reviews_exported: i64 = {
+        let anki_conn = try!(Connection::open(&temp_file.path));
+
+        try!(anki_conn.execute_batch(ANKI_SCHEMA));
+
+        let anki_tx = try!(anki_conn.transaction());
+
+        let conf: String = anki_collection_config((cards.len() as i64) + 1).to_string();
+        let models: String = anki_models(now).to_string();
+        let decks_json: String = Json::Object(anki_decks).to_string();
+        let dconf: String = anki_deck_configs(now).to_string();
+
+        let params: &[(&str, &ToSql)] = &[
+            (":crt", &now),
+            (":mod", &now_ms),
+            (":conf", &conf),
+            (":models", &models),
+            (":decks", &decks_json),
+            (":dconf", &dconf)
+        ];
+
+        try!(anki_conn.execute_named("
+            INSERT INTO col(id, crt, mod, scm, ver, dty, usn, ls, conf, models, decks, dconf, tags)
+            VALUES (1, :crt, :mod, :mod, 11, 0, 0, 0, :conf, :models, :decks, :dconf, '{}');
+        ", params));
+
+        // map cards onto notes
+
+        let mut used_ids: HashSet<i64> = HashSet::new();
+        let mut anki_card_ids: HashMap<i64, i64> = HashMap::new();
+
+        for (index, card) in cards.iter().enumerate() {
+
+            let anki_deck_id: i64 = match deck_map.get(&card.deck) {
+                Some(&(anki_deck_id, _)) => anki_deck_id,
+                None => DEFAULT_DECK_ID
+            };
+
+            // Anki ids are the creation time in milliseconds
+            let mut anki_id: i64 = card.created_at * 1000;
+
+            while used_ids.contains(&anki_id) {
+                anki_id = anki_id + 1;
+            }
+
+            used_ids.insert(anki_id);
+            anki_card_ids.insert(card.card_id, anki_id);
+
+            let guid: String = format!("{}-{}", guid_prefix, card.card_id);
+            let fields: String = format!("{}{}{}", card.front, FIELD_SEPARATOR, card.back);
+            let sort_field: String = strip_html(&card.front, "");
+            let checksum: i64 = field_checksum(&sort_field);
+
+            let params: &[(&str, &ToSql)] = &[
+                (":id", &anki_id),
+                (":guid", &guid),
+                (":mid", &BASIC_MODEL_ID),
+                (":mod", &(card.updated_at)),
+                (":flds", &fields),
+                (":sfld", &sort_field),
+                (":csum", &checksum)
+            ];
+
+            try!(anki_conn.execute_named("
+                INSERT INTO notes(id, guid, mid, mod, usn, tags, flds, sfld, csum, flags, data)
+                VALUES (:id, :guid, :mid, :mod, -1, '', :flds, :sfld, :csum, 0, '');
+            ", params));
+
+            let (reps, lapses) = match card_reviews.get(&card.card_id) {
+                Some(&(reps, lapses)) => (reps, lapses),
+                None => (0, 0)
+            };
+
+            let (card_type, due, interval, factor): (i64, i64, i64, i64) = if reps <= 0 {
+
+                // new cards are due in the order they're exported
+                (CARD_NEW, (index as i64) + 1, 0, 0)
+
+            } else {
+
+                // review cards are due on a day counted from the collection's creation (i.e. now);
+                // overdue cards are due today
+                let due: i64 = match card.due_at {
+                    Some(due_at) if due_at > now => (due_at - now) / (24 * 60 * 60),
+                    _ => 0
+                };
+
+                let interval: i64 = match card.interval_days {
+                    Some(interval_days) if interval_days > 0 => interval_days,
+                    _ => 1
+                };
+
+                let factor: i64 = match card.ease_factor {
+                    Some(ease_factor) => (ease_factor * 1000f64).round() as i64,
+                    None => DEFAULT_FACTOR
+                };
+
+                (CARD_REVIEW, due, interval, factor)
+            };
+
+            let params: &[(&str, &ToSql)] = &[
+                (":id", &anki_id),
+                (":nid", &anki_id),
+                (":did", &anki_deck_id),
+                (":mod", &(card.updated_at)),
+                (":type", &card_type),
+                (":due", &due),
+                (":ivl", &interval),
+                (":factor", &factor),
+                (":reps", &reps),
+                (":lapses", &lapses)
+            ];
+
+            try!(anki_conn.execute_named("
+                INSERT INTO cards(
+                    id, nid, did, ord, mod, usn, type, queue, due, ivl, factor,
+                    reps, lapses, left, odue, odid, flags, data
+                )
+                VALUES (
+                    :id, :nid, :did, 0, :mod, -1, :type, :type, :due, :ivl, :factor,
+                    :reps, :lapses, 0, 0, 0, 0, ''
+                );
+            ", params));
+        }
+
+        // map review events onto the review log
+
+        let mut last_revlog_id: i64 = 0;
+        let mut reviews_exported: i64 = 0;
+
+        for review in reviews.iter() {
+
+            let anki_card_id: i64 = match anki_card_ids.get(&review.card) {
+                Some(anki_card_id) => *anki_card_id,
+                None => continue
+            };
+
+            // revlog ids are the review time in milliseconds; reviews are ordered by time
+            let mut revlog_id: i64 = review.occurred_at * 1000;
+
+            if revlog_id <= last_revlog_id {
+                revlog_id = last_revlog_id + 1;
+            }
+
+            last_revlog_id = revlog_id;
+
+            let ease: i64 = if review.success > 0 {
+                EASE_GOOD
+            } else {
+                EASE_AGAIN
+            };
+
+            let time: i64 = match review.answer_time_ms {
+                Some(answer_time_ms) if answer_time_ms > MAX_ANSWER_TIME_MS => MAX_ANSWER_TIME_MS,
+                Some(answer_time_ms) if answer_time_ms > 0 => answer_time_ms,
+                _ => 0
+            };
+
+            let params: &[(&str, &ToSql)] = &[
+                (":id", &revlog_id),
+                (":cid", &anki_card_id),
+                (":ease", &ease),
+                (":time", &time),
+                (":type", &REVLOG_REVIEW)
+            ];
+
+            try!(anki_conn.execute_named("
+                INSERT INTO revlog(id, cid, usn, ease, ivl, lastIvl, factor, time, type)
+                VALUES (:id, :cid, -1, :ease, 0, 0, 0, :time, :type);
+            ", params));
+
+            reviews_exported = reviews_exported + 1;
+        }
+
+        try!(anki_tx.commit());
+
+        reviews_exported
+    };
+
+    // package the collection
+
+    let mut package = ZipWriter::new(writer);
+
+    try!(package.start_file(COLLECTION_FILE, CompressionMethod::Deflated));
+
+    {
+        let mut collection = try!(File::open(&temp_file.path));
+        try!(io::copy(&mut collection, &mut package));
+    }
+
+    // no media files are exported
+    try!(package.start_file(MEDIA_FILE, CompressionMethod::Deflated));
+    try!(package.write_all(b"{}"));
+
+    try!(package.finish());
+
+    let response = AnkiExportResponse {
+        decks_exported: decks.len() as i64,
+        cards_exported: cards.len() as i64,
+        reviews_exported: reviews_exported
+    };
+
+    return Ok(response);
+}
+
+// returns the deck and its descendents; ancestors before descendents
+fn get_decks(db_conn: &Connection, deck_id: i64) -> Result<Vec<ExportedDeck>, QueryError> {
+
+    let ref query = format!("
+        SELECT
+            d.deck_id, d.name, d.description,
+            (SELECT p.ancestor FROM DecksClosure AS p WHERE p.descendent = d.deck_id AND p.depth = 1)
+        FROM DecksClosure AS dc
+
+        INNER JOIN Decks AS d
+        ON d.deck_id = dc.descendent
+
+        WHERE dc.ancestor = :deck_id
+        ORDER BY dc.depth ASC, d.deck_id ASC;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":deck_id", &deck_id)
+    ];
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut list: Vec<ExportedDeck> = Vec::new();
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                list.push(ExportedDeck {
+                    deck_id: row.get(0),
+                    name: row.get(1),
+                    description: row.get(2),
+                    parent: row.get(3)
+                });
+            }
+
+            return Ok(list);
+        }
+    };
+}
+
+// returns cards within the deck and its descendents
+fn get_cards(db_conn: &Connection, deck_id: i64) -> Result<Vec<ExportedCard>, QueryError> {
+
+    let ref query = format!("
+        SELECT
+            c.card_id, c.deck, c.front, c.back, c.created_at, c.updated_at,
+            sm.ease_factor, sm.interval_days, sm.due_at
+        FROM DecksClosure AS dc
+
+        INNER JOIN Cards AS c
+        ON c.deck = dc.descendent
+
+        LEFT JOIN CardsSM2 AS sm
+        ON sm.card = c.card_id
+
+        WHERE dc.ancestor = :deck_id
+        ORDER BY c.card_id ASC;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":deck_id", &deck_id)
+    ];
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut list: Vec<ExportedCard> = Vec::new();
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                list.push(ExportedCard {
+                    card_id: row.get(0),
+                    deck: row.get(1),
+                    front: row.get(2),
+                    back: row.get(3),
+                    created_at: row.get(4),
+                    updated_at: row.get(5),
+                    ease_factor: row.get(6),
+                    interval_days: row.get(7),
+                    due_at: row.get(8)
+                });
+            }
+
+            return Ok(list);
+        }
+    };
+}
+
+// returns review events of cards within the deck and its descendents; oldest first.
+// undone review actions are excluded.
+fn get_reviews(db_conn: &Connection, deck_id: i64) -> Result<Vec<ExportedReview>, QueryError> {
+
+    let ref query = format!("
+        SELECT
+            h.card, h.occurred_at, h.success, ctx.answer_time_ms
+        FROM DecksClosure AS dc
+
+        INNER JOIN Cards AS c
+        ON c.deck = dc.descendent
+
+        INNER JOIN CardsScoreHistory AS h
+        ON h.card = c.card_id
+
+        LEFT JOIN CardsScoreHistoryContext AS ctx
+        ON ctx.history = h.history_id
+
+        WHERE
+            dc.ancestor = :deck_id
+        AND
+            h.is_review_event = 1
+        AND
+            h.history_id NOT IN (SELECT history FROM CardsScoreUndo WHERE reverted = 1)
+        ORDER BY h.occurred_at ASC, h.history_id ASC;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":deck_id", &deck_id)
+    ];
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut list: Vec<ExportedReview> = Vec::new();
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                list.push(ExportedReview {
+                    card: row.get(0),
+                    occurred_at: row.get(1),
+                    success: row.get(2),
+                    answer_time_ms: row.get(3)
+                });
+            }
+
+            return Ok(list);
+        }
+    };
+}
+
+/* Anki collection json */
+
+fn json_object(entries: Vec<(&str, Json)>) -> Json {
+
+    let mut object: BTreeMap<String, Json> = BTreeMap::new();
+
+    for (key, value) in entries.into_iter() {
+        object.insert(key.to_string(), value);
+    }
+
+    return Json::Object(object);
+}
+
+fn json_i64_array(values: &[i64]) -> Json {
+    return Json::Array(values.iter().map(|value| Json::I64(*value)).collect());
+}
+
+fn anki_deck(id: i64, name: &str, description: &str, modified: i64) -> Json {
+    return json_object(vec![
+        ("id", Json::I64(id)),
+        ("name", Json::String(name.to_string())),
+        ("desc", Json::String(description.to_string())),
+        ("mod", Json::I64(modified)),
+        ("usn", Json::I64(-1)),
+        ("dyn", Json::I64(0)),
+        ("conf", Json::I64(DEFAULT_DECK_CONFIG_ID)),
+        ("collapsed", Json::Boolean(false)),
+        ("browserCollapsed", Json::Boolean(false)),
+        ("extendNew", Json::I64(10)),
+        ("extendRev", Json::I64(50)),
+        ("newToday", json_i64_array(&[0, 0])),
+        ("revToday", json_i64_array(&[0, 0])),
+        ("lrnToday", json_i64_array(&[0, 0])),
+        ("timeToday", json_i64_array(&[0, 0]))
+    ]);
+}
+
+fn anki_models(modified: i64) -> Json {
+
+    let field = |name: &str, ord: i64| -> Json {
+        return json_object(vec![
+            ("name", Json::String(name.to_string())),
+            ("ord", Json::I64(ord)),
+            ("sticky", Json::Boolean(false)),
+            ("rtl", Json::Boolean(false)),
+            ("font", Json::String(format!("Arial"))),
+            ("size", Json::I64(20)),
+            ("media", Json::Array(vec![]))
+        ]);
+    };
+
+    let template = json_object(vec![
+        ("name", Json::String(format!("Card 1"))),
+        ("ord", Json::I64(0)),
+        ("qfmt", Json::String(format!("{{{{Front}}}}"))),
+        ("afmt", Json::String(format!("{{{{FrontSide}}}}\n\n<hr id=answer>\n\n{{{{Back}}}}"))),
+        ("did", Json::Null),
+        ("bqfmt", Json::String(format!(""))),
+        ("bafmt", Json::String(format!("")))
+    ]);
+
+    let model = json_object(vec![
+        ("id", Json::I64(BASIC_MODEL_ID)),
+        ("name", Json::String(BASIC_MODEL_NAME.to_string())),
+        ("type", Json::I64(0)),
+        ("mod", Json::I64(modified)),
+        ("usn", Json::I64(-1)),
+        ("sortf", Json::I64(0)),
+        ("did", Json::I64(DEFAULT_DECK_ID)),
+        ("tmpls", Json::Array(vec![template])),
+        ("flds", Json::Array(vec![field("Front", 0), field("Back", 1)])),
+        ("css", Json::String(BASIC_MODEL_CSS.to_string())),
+        ("latexPre", Json::String(format!("\\documentclass[12pt]{{article}}\n\\special{{papersize=3in,5in}}\n\\usepackage[utf8]{{inputenc}}\n\\usepackage{{amssymb,amsmath}}\n\\pagestyle{{empty}}\n\\setlength{{\\parindent}}{{0in}}\n\\begin{{document}}\n"))),
+        ("latexPost", Json::String(format!("\\end{{document}}"))),
+        ("tags", Json::Array(vec![])),
+        ("vers", Json::Array(vec![])),
+        // the card is generated whenever the front isn't empty
+        ("req", Json::Array(vec![
+            Json::Array(vec![Json::I64(0), Json::String(format!("all")), json_i64_array(&[0])])
+        ]))
+    ]);
+
+    let model_id: String = format!("{}", BASIC_MODEL_ID);
+
+    return json_object(vec![
+        (&model_id[..], model)
+    ]);
+}
+
+// Anki's default deck options
+fn anki_deck_configs(modified: i64) -> Json {
+
+    let config = json_object(vec![
+        ("id", Json::I64(DEFAULT_DECK_CONFIG_ID)),
+        ("name", Json::String(format!("Default"))),
+        ("mod", Json::I64(modified)),
+        ("usn", Json::I64(-1)),
+        ("dyn", Json::Boolean(false)),
+        ("maxTaken", Json::I64(60)),
+        ("timer", Json::I64(0)),
+        ("autoplay", Json::Boolean(true)),
+        ("replayq", Json::Boolean(true)),
+        ("new", json_object(vec![
+            ("bury", Json::Boolean(true)),
+            ("delays", Json::Array(vec![Json::F64(1.0), Json::F64(10.0)])),
+            ("initialFactor", Json::I64(2500)),
+            ("ints", json_i64_array(&[1, 4, 7])),
+            ("order", Json::I64(1)),
+            ("perDay", Json::I64(20)),
+            ("separate", Json::Boolean(true))
+        ])),
+        ("rev", json_object(vec![
+            ("bury", Json::Boolean(true)),
+            ("ease4", Json::F64(1.3)),
+            ("fuzz", Json::F64(0.05)),
+            ("ivlFct", Json::F64(1.0)),
+            ("maxIvl", Json::I64(36500)),
+            ("minSpace", Json::I64(1)),
+            ("perDay", Json::I64(200))
+        ])),
+        ("lapse", json_object(vec![
+            ("delays", Json::Array(vec![Json::F64(10.0)])),
+            ("leechAction", Json::I64(1)),
+            ("leechFails", Json::I64(8)),
+            ("minInt", Json::I64(1)),
+            ("mult", Json::F64(0.0))
+        ]))
+    ]);
+
+    let config_id: String = format!("{}", DEFAULT_DECK_CONFIG_ID);
+
+    return json_object(vec![
+        (&config_id[..], config)
+    ]);
+}
+
+fn anki_collection_config(next_position: i64) -> Json {
+    return json_object(vec![
+        ("nextPos", Json::I64(next_position)),
+        ("estTimes", Json::Boolean(true)),
+        ("activeDecks", json_i64_array(&[DEFAULT_DECK_ID])),
+        ("sortType", Json::String(format!("noteFld"))),
+        ("timeLim", Json::I64(0)),
+        ("sortBackwards", Json::Boolean(false)),
+        ("addToCur", Json::Boolean(true)),
+        ("curDeck", Json::I64(DEFAULT_DECK_ID)),
+        ("newSpread", Json::I64(0)),
+        ("dueCounts", Json::Boolean(true)),
+        ("curModel", Json::String(format!("{}", BASIC_MODEL_ID))),
+        ("collapseTime", Json::I64(1200))
+    ]);
+}
+
+// checksum of a note's sort field used by Anki to find duplicates;
+// the first 8 hex digits of the field's sha1 digest.
+fn field_checksum(sort_field: &str) -> i64 {
+
+    let mut digest = sha1::Sha1::new();
+    digest.update(sort_field.as_bytes());
+
+    let digest: String = digest.hexdigest();
+
+    return match i64::from_str_radix(&digest[..8], 16) {
+        Ok(checksum) => checksum,
+        Err(_) => unreachable!() // sha1 digest is hex
+    };
+}
@@ -0,0 +1,342 @@
+extern crate csv;
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+use std::io::Write;
+
+use rusqlite::{Connection, SqliteStatement};
+use rusqlite::types::ToSql;
+use rustc_serialize::json;
+
+use ::database::{DB, QueryError};
+use ::api::export::ExportError;
+
+
+// export of cards along with their review statistics; for analysis of study progress elsewhere.
+//
+// csv and tsv files have a header row, and a row per card. json is an array of cards.
+
+#[derive(Debug, Clone, Copy)]
+pub enum ExportFormat {
+    Csv,
+    Tsv,
+    Json
+}
+
+impl ExportFormat {
+
+    pub fn parse(format: &str) -> Option<ExportFormat> {
+        return match format.trim().to_lowercase().as_ref() {
+            "csv" => Some(ExportFormat::Csv),
+            "tsv" => Some(ExportFormat::Tsv),
+            "json" => Some(ExportFormat::Json),
+            _ => None
+        };
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        return match *self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Tsv => "text/tab-separated-values",
+            ExportFormat::Json => "application/json"
+        };
+    }
+
+    pub fn extension(&self) -> &'static str {
+        return match *self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Tsv => "tsv",
+            ExportFormat::Json => "json"
+        };
+    }
+}
+
+static HEADERS: [&'static str; 14] = [
+    "card_id", "title", "description", "front", "back", "deck", "created_at", "updated_at",
+    "success", "fail", "score", "times_reviewed", "reviewed_at", "seen_at"
+];
+
+#[derive(Debug, Clone, RustcEncodable)]
+pub struct ExportedReviewStat {
+    success: i64,
+    fail: i64,
+    score: f64,
+    times_reviewed: i64,
+    reviewed_at: i64, // unix timestamp
+    seen_at: i64 // unix timestamp
+}
+
+#[derive(Debug, Clone, RustcEncodable)]
+pub struct ExportedCard {
+    card_id: i64,
+    title: String,
+    description: String,
+    front: String,
+    back: String,
+    deck: i64,
+    created_at: i64, // unix timestamp
+    updated_at: i64, // unix timestamp
+    review_stat: ExportedReviewStat
+}
+
+impl ExportedCard {
+
+    // in the order of HEADERS
+    fn to_record(&self) -> Vec<String> {
+        return vec![
+            format!("{}", self.card_id),
+            self.title.clone(),
+            self.description.clone(),
+            self.front.clone(),
+            self.back.clone(),
+            format!("{}", self.deck),
+            format!("{}", self.created_at),
+            format!("{}", self.updated_at),
+            format!("{}", self.review_stat.success),
+            format!("{}", self.review_stat.fail),
+            format!("{}", self.review_stat.score),
+            format!("{}", self.review_stat.times_reviewed),
+            format!("{}", self.review_stat.reviewed_at),
+            format!("{}", self.review_stat.seen_at)
+        ];
+    }
+}
+
+// cards the export is of
+#[derive(Debug, Clone, Copy)]
+pub enum CardsOf {
+    Deck(i64), // the deck and the deck's descendents
+    Stash(i64)
+}
+
+// number of cards read at a time
+static EXPORT_BATCH_SIZE: i64 = 500;
+
+// export every card of the deck or stash into writer.
+// cards are read in batches (by card id) while holding the db connection lock; each batch is
+// written once the lock is released. so the lock is never held while writing (e.g. to a slow
+// client); and the export is never held in memory as a whole.
+pub fn export_cards<W: Write>(db: &DB, cards_of: CardsOf, format: ExportFormat, writer: W) -> Result<(), ExportError> {
+
+    let mut encoder: CardsEncoder<W> = try!(CardsEncoder::new(format, writer));
+
+    let mut after_card_id: i64 = 0;
+
+    loop {
+
+        let cards: Vec<ExportedCard> = {
+            let db_conn_guard = db.lock().unwrap();
+            let ref db_conn = *db_conn_guard;
+
+            try!(get_cards(db_conn, cards_of, after_card_id, EXPORT_BATCH_SIZE))
+        };
+
+        for card in cards.iter() {
+            try!(encoder.write(card));
+        }
+
+        match cards.last() {
+            Some(card) if (cards.len() as i64) >= EXPORT_BATCH_SIZE => {
+                after_card_id = card.card_id;
+            },
+            _ => {
+                break;
+            }
+        }
+    }
+
+    return encoder.finish();
+}
+
+// returns at most limit cards of the deck or stash whose id is after after_card_id; by card id.
+// caller should hold the db connection lock.
+fn get_cards(db_conn: &Connection, cards_of: CardsOf, after_card_id: i64, limit: i64) -> Result<Vec<ExportedCard>, QueryError> {
+
+    let (ref query, container_id): (String, i64) = match cards_of {
+        CardsOf::Deck(deck_id) => {
+            let query = format!("
+                SELECT
+                    c.card_id, c.title, c.description, c.front, c.back, c.deck, c.created_at, c.updated_at,
+                    cs.success, cs.fail, cs.times_reviewed, cs.reviewed_at, cs.seen_at
+                FROM DecksClosure AS dc
+
+                INNER JOIN Cards AS c
+                ON c.deck = dc.descendent
+
+                INNER JOIN CardsScore AS cs
+                ON cs.card = c.card_id
+
+                WHERE dc.ancestor = :container_id AND c.card_id > :after_card_id
+                ORDER BY c.card_id ASC
+                LIMIT :limit;
+            ");
+
+            (query, deck_id)
+        },
+        CardsOf::Stash(stash_id) => {
+            let query = format!("
+                SELECT
+                    c.card_id, c.title, c.description, c.front, c.back, c.deck, c.created_at, c.updated_at,
+                    cs.success, cs.fail, cs.times_reviewed, cs.reviewed_at, cs.seen_at
+                FROM StashCards AS sc
+
+                INNER JOIN Cards AS c
+                ON c.card_id = sc.card
+
+                INNER JOIN CardsScore AS cs
+                ON cs.card = c.card_id
+
+                WHERE sc.stash = :container_id AND c.card_id > :after_card_id
+                ORDER BY c.card_id ASC
+                LIMIT :limit;
+            ");
+
+            (query, stash_id)
+        }
+    };
+
+    let params: &[(&str, &ToSql)] = &[
+        (":container_id", &container_id),
+        (":after_card_id", &after_card_id),
+        (":limit", &limit)
+    ];
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut list: Vec<ExportedCard> = Vec::new();
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                let success: i64 = row.get(8);
+                let fail: i64 = row.get(9);
+                let total: i64 = success + fail;
+
+                list.push(ExportedCard {
+                    card_id: row.get(0),
+                    title: row.get(1),
+                    description: row.get(2),
+                    front: row.get(3),
+                    back: row.get(4),
+                    deck: row.get(5),
+                    created_at: row.get(6),
+                    updated_at: row.get(7),
+                    review_stat: ExportedReviewStat {
+                        success: success,
+                        fail: fail,
+                        // see ReviewAPI::get_review_stat
+                        score: (fail as f64 + 0.5f64) / (total as f64 + 1.0f64),
+                        times_reviewed: row.get(10),
+                        reviewed_at: row.get(11),
+                        seen_at: row.get(12)
+                    }
+                });
+            }
+
+            return Ok(list);
+        }
+    };
+}
+
+// writes cards one at a time in the given format
+enum CardsEncoder<W: Write> {
+    Delimited(csv::Writer<W>),
+
+    // has_cards is true once a card is written; cards after the first are preceded by a comma
+    Json(W, bool)
+}
+
+impl<W: Write> CardsEncoder<W> {
+
+    fn new(format: ExportFormat, writer: W) -> Result<CardsEncoder<W>, ExportError> {
+
+        let delimiter: u8 = match format {
+            ExportFormat::Json => {
+                let mut writer = writer;
+                try!(writer.write_all(b"["));
+                return Ok(CardsEncoder::Json(writer, false));
+            },
+            ExportFormat::Csv => b',',
+            ExportFormat::Tsv => b'\t'
+        };
+
+        let mut writer = csv::Writer::from_writer(writer).delimiter(delimiter);
+
+        let headers: Vec<String> = HEADERS.iter().map(|header| header.to_string()).collect();
+
+        try!(writer.write(headers.into_iter()));
+
+        return Ok(CardsEncoder::Delimited(writer));
+    }
+
+    fn write(&mut self, card: &ExportedCard) -> Result<(), ExportError> {
+
+        match *self {
+            CardsEncoder::Delimited(ref mut writer) => {
+                try!(writer.write(card.to_record().into_iter()));
+            },
+            CardsEncoder::Json(ref mut writer, ref mut has_cards) => {
+
+                if *has_cards {
+                    try!(writer.write_all(b","));
+                }
+
+                try!(writer.write_all(json::encode(card).unwrap().as_bytes()));
+
+                *has_cards = true;
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn finish(self) -> Result<(), ExportError> {
+
+        match self {
+            CardsEncoder::Delimited(mut writer) => {
+                try!(writer.flush());
+            },
+            CardsEncoder::Json(mut writer, _) => {
+                try!(writer.write_all(b"]"));
+                try!(writer.flush());
+            }
+        }
+
+        return Ok(());
+    }
+}
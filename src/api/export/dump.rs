@@ -0,0 +1,174 @@
+extern crate chrono;
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+use std::collections::BTreeMap;
+
+use chrono::*;
+use rusqlite::{Connection, SqliteStatement};
+use rustc_serialize::json::{self, Json};
+
+use ::database::QueryError;
+use ::api::export::ExportError;
+
+
+// full database dump as a versioned json document.
+//
+// the dump holds every row of every table (except for the full-text search index, which is
+// rebuilt from Cards); ids are kept as they are, so that a restored database is identical.
+// rows of a table are objects keyed by column name, ordered by rowid.
+//
+// {
+//     "format": "grokdb-dump",
+//     "version": 1,
+//     "dumped_at": 1450000000,
+//     "tables": {
+//         "Decks": [{"deck_id": 1, "name": "Library", ...}, ...],
+//         ...
+//     }
+// }
+//
+// see import::dump for the matching restore.
+
+pub static DUMP_FORMAT: &'static str = "grokdb-dump";
+
+// bump whenever the document structure changes; tables gaining columns don't change the version
+pub static DUMP_FORMAT_VERSION: i64 = 1;
+
+// tables in the order they're restored; referenced tables come first.
+// tables whose rows are referenced by rowid keep their rowid as the rowid key.
+pub static DUMP_TABLES: [(&'static str, bool); 26] = [
+    ("Configs", false),
+    ("Decks", false),
+    ("DecksClosure", false),
+    ("Cards", false),
+    ("CardsScore", false),
+    ("CardsScoreHistory", true),
+    ("Stashes", false),
+    ("StashCards", false),
+    ("CachedDeckReview", false),
+    ("CachedStashReview", false),
+    ("DecksScheduler", false),
+    ("StashesScheduler", false),
+    ("CardsSM2", false),
+    ("CardsFSRS", false),
+    ("DecksReviewSettings", false),
+    ("StashesReviewSettings", false),
+    ("ReviewSessions", false),
+    ("CardsScoreUndo", false),
+    ("CardsScoreHistoryContext", false),
+    ("CardsLeech", false),
+    ("SuspendedCards", false),
+    ("BuriedCards", false),
+    ("BackupLog", false),
+    ("Tags", false),
+    ("CardsTags", false),
+    ("CachedTagReview", false)
+];
+
+pub static ROWID_KEY: &'static str = "rowid";
+
+// dump the whole database; returns the pretty-printed json document.
+// caller should hold the db connection lock.
+pub fn dump_database(db_conn: &Connection) -> Result<String, ExportError> {
+
+    let mut tables: BTreeMap<String, Json> = BTreeMap::new();
+
+    for &(table, with_rowid) in DUMP_TABLES.iter() {
+        let rows: Vec<Json> = try!(dump_table(db_conn, table, with_rowid));
+        tables.insert(table.to_string(), Json::Array(rows));
+    }
+
+    let mut document: BTreeMap<String, Json> = BTreeMap::new();
+
+    document.insert(format!("format"), Json::String(DUMP_FORMAT.to_string()));
+    document.insert(format!("version"), Json::I64(DUMP_FORMAT_VERSION));
+    document.insert(format!("dumped_at"), Json::I64(UTC::now().timestamp()));
+    document.insert(format!("tables"), Json::Object(tables));
+
+    return Ok(format!("{}", json::as_pretty_json(&Json::Object(document))));
+}
+
+fn dump_table(db_conn: &Connection, table: &str, with_rowid: bool) -> Result<Vec<Json>, QueryError> {
+
+    let ref query = if with_rowid {
+        format!("SELECT oid AS {rowid}, * FROM {table} ORDER BY oid ASC;", rowid = ROWID_KEY, table = table)
+    } else {
+        format!("SELECT * FROM {table} ORDER BY oid ASC;", table = table)
+    };
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let columns: Vec<String> = stmt.column_names().iter().map(|column| column.to_string()).collect();
+
+    let maybe_iter = stmt.query(&[]);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut list: Vec<Json> = Vec::new();
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                let mut object: BTreeMap<String, Json> = BTreeMap::new();
+
+                for (index, column) in columns.iter().enumerate() {
+
+                    let index = index as i32;
+
+                    // sqlite values are dynamically typed; no table has blob columns
+                    let value: Json = match row.get_checked::<Option<i64>>(index) {
+                        Ok(None) => Json::Null,
+                        Ok(Some(value)) => Json::I64(value),
+                        Err(_) => {
+                            match row.get_checked::<f64>(index) {
+                                Ok(value) => Json::F64(value),
+                                Err(_) => {
+                                    let value: String = row.get(index);
+                                    Json::String(value)
+                                }
+                            }
+                        }
+                    };
+
+                    object.insert(column.clone(), value);
+                }
+
+                list.push(Json::Object(object));
+            }
+
+            return Ok(list);
+        }
+    };
+}
@@ -0,0 +1,316 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+use std::fs;
+use std::path::Path;
+
+use rusqlite::{Connection, SqliteStatement};
+use rusqlite::types::ToSql;
+use rustc_serialize::json;
+
+use ::database::{DB, QueryError, create_schema};
+use ::api::export::ExportError;
+use ::api::export::dump::DUMP_TABLES;
+use ::api::import::dump::get_columns;
+
+
+// extraction of a deck and its descendents into a standalone grokdb database.
+//
+// the extracted database has the full schema; it holds the decks of the subtree, their cards
+// (along with their scores, review history, schedules and tags), and the stashes of those cards
+// (restricted to those cards). ids are kept as they are. the extracted deck becomes a root deck.
+//
+// references to decks, stashes, or review sessions outside of the subtree (e.g. the context of
+// a review within an ancestor deck) are cleared.
+
+// schema name of the extracted database while it's attached
+static EXTRACTED_SCHEMA: &'static str = "extracted";
+
+#[derive(Debug, RustcEncodable)]
+pub struct ExtractResponse {
+    pub dest_file: String,
+
+    pub decks_extracted: i64,
+    pub cards_extracted: i64,
+    pub stashes_extracted: i64
+}
+
+impl ExtractResponse {
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+}
+
+// extract the deck into a new database at dest_path; dest_path shouldn't exist.
+// caller should hold the db connection lock.
+pub fn extract_deck(db_conn: &Connection, deck_id: i64, dest_path: &str) -> Result<ExtractResponse, ExportError> {
+
+    if fs::metadata(dest_path).is_ok() {
+        return Err(ExportError::Invalid(format!("{} already exists", dest_path)));
+    }
+
+    // set up the schema of the new database; this connection is closed once dropped
+    {
+        let dest_conn: Connection = try!(Connection::open(Path::new(dest_path)));
+        try!(create_schema(&dest_conn));
+    }
+
+    let result = extract_into(db_conn, deck_id, dest_path);
+
+    // foreign keys are turned off while extracting
+    let restored = DB::prepare_query(db_conn);
+
+    if result.is_err() {
+        let _ = fs::remove_file(dest_path);
+    }
+
+    let response: ExtractResponse = try!(result);
+    try!(restored);
+
+    return Ok(response);
+}
+
+fn extract_into(db_conn: &Connection, deck_id: i64, dest_path: &str) -> Result<ExtractResponse, ExportError> {
+
+    // rows are copied table by table; so references are checked once every table is copied
+    try!(execute(db_conn, "PRAGMA foreign_keys=OFF;", &[]));
+
+    let ref query_attach = format!("ATTACH DATABASE :dest_path AS {};", EXTRACTED_SCHEMA);
+
+    let params: &[(&str, &ToSql)] = &[
+        (":dest_path", &dest_path)
+    ];
+
+    match db_conn.execute_named(query_attach, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_attach.clone(),
+            };
+            return Err(ExportError::Query(err));
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    let result = copy_subtree(db_conn, deck_id, dest_path);
+
+    let detached = execute(db_conn, &format!("DETACH DATABASE {};", EXTRACTED_SCHEMA), &[]);
+
+    let response: ExtractResponse = try!(result);
+    try!(detached);
+
+    return Ok(response);
+}
+
+fn copy_subtree(db_conn: &Connection, deck_id: i64, dest_path: &str) -> Result<ExtractResponse, ExportError> {
+
+    let tx = match db_conn.transaction() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("creating transaction"),
+            };
+            return Err(ExportError::Query(err));
+        },
+        Ok(tx) => tx
+    };
+
+    for &(table, with_rowid) in DUMP_TABLES.iter() {
+
+        let filter: String = match subtree_filter(table) {
+            Some(filter) => filter,
+            None => {
+                continue;
+            }
+        };
+
+        let mut columns: Vec<String> = try!(get_columns(db_conn, table));
+
+        if with_rowid {
+            columns.insert(0, format!("oid"));
+        }
+
+        // rows created by triggers are replaced (e.g. a deck's own DecksClosure row)
+        let ref query = format!("
+            INSERT OR REPLACE INTO {schema}.{table}({columns})
+            SELECT {columns} FROM main.{table}
+            WHERE {filter};
+        ", schema = EXTRACTED_SCHEMA, table = table, columns = columns.join(", "), filter = filter);
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &deck_id)
+        ];
+
+        try!(execute(db_conn, query, params));
+    }
+
+    // clear references to rows outside of the subtree
+    for table in ["CardsScoreUndo", "CardsScoreHistoryContext"].iter() {
+        for &(column, referenced_table, referenced_column) in [
+            ("deck", "Decks", "deck_id"),
+            ("stash", "Stashes", "stash_id"),
+            ("session", "ReviewSessions", "session_id")
+        ].iter() {
+
+            let ref query = format!("
+                UPDATE {schema}.{table}
+                SET {column} = NULL
+                WHERE {column} IS NOT NULL
+                AND {column} NOT IN (SELECT {referenced_column} FROM {schema}.{referenced_table});
+            ", schema = EXTRACTED_SCHEMA, table = table, column = column,
+                referenced_table = referenced_table, referenced_column = referenced_column);
+
+            try!(execute(db_conn, query, &[]));
+        }
+    }
+
+    try!(check_foreign_keys(db_conn));
+
+    let response = ExtractResponse {
+        dest_file: dest_path.to_string(),
+        decks_extracted: try!(count_rows(db_conn, "Decks")),
+        cards_extracted: try!(count_rows(db_conn, "Cards")),
+        stashes_extracted: try!(count_rows(db_conn, "Stashes"))
+    };
+
+    match tx.commit() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("committing transaction"),
+            };
+            return Err(ExportError::Query(err));
+        },
+        _ => {/* commit successful */}
+    }
+
+    return Ok(response);
+}
+
+// condition on the rows of the table that belong to the subtree of :deck_id.
+// returns None for tables that aren't extracted.
+fn subtree_filter(table: &str) -> Option<String> {
+
+    let decks: String = format!("SELECT descendent FROM main.DecksClosure WHERE ancestor = :deck_id");
+    let cards: String = format!("SELECT card_id FROM main.Cards WHERE deck IN ({})", decks);
+    let stashes: String = format!("SELECT stash FROM main.StashCards WHERE card IN ({})", cards);
+    let tags: String = format!("SELECT tag FROM main.CardsTags WHERE card IN ({})", cards);
+
+    let filter: String = match table {
+        "Decks" => format!("deck_id IN ({})", decks),
+        "DecksClosure" => format!("ancestor IN ({decks}) AND descendent IN ({decks})", decks = decks),
+        "Cards" => format!("card_id IN ({})", cards),
+        "Stashes" => format!("stash_id IN ({})", stashes),
+        "StashCards" => format!("card IN ({})", cards),
+        "Tags" => format!("tag_id IN ({})", tags),
+        "CardsTags" => format!("card IN ({})", cards),
+        "CachedTagReview" => format!("tag IN ({}) AND card IN ({})", tags, cards),
+        "CachedDeckReview" => format!("deck IN ({}) AND card IN ({})", decks, cards),
+        "CachedStashReview" => format!("stash IN ({}) AND card IN ({})", stashes, cards),
+        "DecksScheduler" | "DecksReviewSettings" => format!("deck IN ({})", decks),
+        "StashesScheduler" | "StashesReviewSettings" => format!("stash IN ({})", stashes),
+        "ReviewSessions" => format!("deck IN ({}) OR stash IN ({})", decks, stashes),
+        "CardsScore" | "CardsScoreHistory" | "CardsSM2" | "CardsFSRS" | "CardsScoreUndo" |
+        "CardsScoreHistoryContext" | "CardsLeech" | "SuspendedCards" | "BuriedCards" => {
+            format!("card IN ({})", cards)
+        },
+
+        // settings of this grokdb aren't handed over
+        _ => {
+            return None;
+        }
+    };
+
+    return Some(filter);
+}
+
+fn check_foreign_keys(db_conn: &Connection) -> Result<(), ExportError> {
+
+    let ref query = format!("PRAGMA {}.foreign_key_check;", EXTRACTED_SCHEMA);
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(ExportError::Query(err));
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_map(&[], |row| -> String {
+        return row.get(0);
+    });
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(ExportError::Query(err));
+        },
+        Ok(iter) => {
+            for table in iter {
+                match table {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(ExportError::Query(err));
+                    },
+                    Ok(table) => {
+                        return Err(ExportError::Invalid(format!("extracted {} has rows referencing rows outside of the deck", table)));
+                    }
+                }
+            }
+        }
+    };
+
+    return Ok(());
+}
+
+fn count_rows(db_conn: &Connection, table: &str) -> Result<i64, QueryError> {
+
+    let ref query = format!("SELECT COUNT(1) FROM {}.{};", EXTRACTED_SCHEMA, table);
+
+    let count = db_conn.query_row(query, &[], |row| -> i64 {
+        return row.get(0);
+    });
+
+    match count {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(count) => {
+            return Ok(count);
+        }
+    };
+}
+
+fn execute(db_conn: &Connection, query: &str, params: &[(&str, &ToSql)]) -> Result<(), QueryError> {
+
+    match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.to_string(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
@@ -0,0 +1,708 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, SqliteStatement};
+use rusqlite::types::ToSql;
+use rustc_serialize::json::{self, Json};
+
+use ::database::QueryError;
+use ::api::export::ExportError;
+
+
+// export of a deck (and the deck's descendents) as a directory of markdown files; for keeping
+// decks under version control.
+//
+// every deck is a directory holding a _deck.md file, a markdown file per card, and a directory
+// per child deck. the exported deck is the directory itself.
+//
+// a markdown file starts with front-matter (a small subset of yaml) holding the id, title (or
+// name) and description, and timestamps. the front and back of a card are separated by a line
+// holding only <!-- back -->; such lines within the front or back are escaped with a backslash
+// (\<!-- back -->). e.g.
+//
+// ---
+// id: 42
+// title: "Pythagorean theorem"
+// description: ""
+// created_at: 1450000000
+// updated_at: 1450000000
+// ---
+// a^2 + b^2 = ?
+//
+// <!-- back -->
+//
+// c^2
+//
+// see import::markdown for reconciling such a directory back into decks and cards.
+
+pub static DECK_FILE: &'static str = "_deck.md";
+pub static MARKDOWN_EXTENSION: &'static str = "md";
+
+pub static FRONT_MATTER_FENCE: &'static str = "---";
+pub static BACK_SEPARATOR: &'static str = "<!-- back -->";
+
+// maximum number of characters of file and directory names derived from titles and names
+static MAX_SLUG_LENGTH: usize = 50;
+
+#[derive(Debug)]
+pub struct MarkdownExportResponse {
+    pub decks_exported: i64,
+    pub cards_exported: i64
+}
+
+// value of a front-matter entry
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrontMatterValue {
+    Integer(i64),
+    Text(String)
+}
+
+// markdown file split into its front-matter and body
+#[derive(Debug, Clone)]
+pub struct MarkdownFile {
+    pub front_matter: Vec<(String, FrontMatterValue)>,
+    pub body: String
+}
+
+impl MarkdownFile {
+
+    pub fn get_integer(&self, key: &str) -> Option<i64> {
+        for &(ref entry_key, ref value) in self.front_matter.iter() {
+            if entry_key == key {
+                return match *value {
+                    FrontMatterValue::Integer(value) => Some(value),
+                    FrontMatterValue::Text(ref value) => value.trim().parse::<i64>().ok()
+                };
+            }
+        }
+
+        return None;
+    }
+
+    pub fn get_text(&self, key: &str) -> Option<String> {
+        for &(ref entry_key, ref value) in self.front_matter.iter() {
+            if entry_key == key {
+                return match *value {
+                    FrontMatterValue::Integer(value) => Some(format!("{}", value)),
+                    FrontMatterValue::Text(ref value) => Some(value.clone())
+                };
+            }
+        }
+
+        return None;
+    }
+
+    // set the entry; entries that are new are put first
+    pub fn set(&mut self, key: &str, value: FrontMatterValue) {
+        for entry in self.front_matter.iter_mut() {
+            if entry.0 == key {
+                entry.1 = value;
+                return;
+            }
+        }
+
+        self.front_matter.insert(0, (key.to_string(), value));
+    }
+
+    pub fn parse(content: &str) -> MarkdownFile {
+
+        let content: &str = content.trim_left_matches('\u{feff}');
+
+        // lines with the offset of where each line starts; the body is kept as it is
+        let mut lines: Vec<(usize, &str)> = vec![];
+        let mut offset: usize = 0;
+
+        for line in content.split('\n') {
+            lines.push((offset, line));
+            offset = offset + line.len() + 1;
+        }
+
+        let mut front_matter: Vec<(String, FrontMatterValue)> = vec![];
+
+        // no front-matter
+        if lines[0].1.trim_right() != FRONT_MATTER_FENCE {
+            return MarkdownFile {
+                front_matter: front_matter,
+                body: content.to_string()
+            };
+        }
+
+        let mut body_start: Option<usize> = None;
+
+        for (index, &(_, line)) in lines.iter().enumerate().skip(1) {
+
+            if line.trim_right() == FRONT_MATTER_FENCE {
+                body_start = Some(index + 1);
+                break;
+            }
+
+            let separator: usize = match line.find(':') {
+                Some(separator) => separator,
+                None => continue
+            };
+
+            let key: String = line[..separator].trim().to_string();
+            let value: &str = line[(separator + 1)..].trim();
+
+            if key.len() <= 0 {
+                continue;
+            }
+
+            let value: FrontMatterValue = if value.starts_with('"') {
+                // double-quoted strings are json strings
+                match Json::from_str(value) {
+                    Ok(Json::String(value)) => FrontMatterValue::Text(value),
+                    _ => FrontMatterValue::Text(value.trim_matches('"').to_string())
+                }
+            } else {
+                match value.parse::<i64>() {
+                    Ok(value) => FrontMatterValue::Integer(value),
+                    Err(_) => FrontMatterValue::Text(value.to_string())
+                }
+            };
+
+            front_matter.push((key, value));
+        }
+
+        let body: String = match body_start {
+            // unterminated front-matter is taken as the body
+            None => {
+                return MarkdownFile {
+                    front_matter: vec![],
+                    body: content.to_string()
+                };
+            },
+            Some(body_start) => {
+                if body_start < lines.len() {
+                    content[lines[body_start].0..].to_string()
+                } else {
+                    String::new()
+                }
+            }
+        };
+
+        return MarkdownFile {
+            front_matter: front_matter,
+            body: body
+        };
+    }
+
+    pub fn to_string(&self) -> String {
+
+        let mut content: String = format!("{}\n", FRONT_MATTER_FENCE);
+
+        for &(ref key, ref value) in self.front_matter.iter() {
+
+            let value: String = match *value {
+                FrontMatterValue::Integer(value) => format!("{}", value),
+                FrontMatterValue::Text(ref value) => json::encode(value).unwrap()
+            };
+
+            content.push_str(&format!("{}: {}\n", key, value));
+        }
+
+        content.push_str(&format!("{}\n", FRONT_MATTER_FENCE));
+        content.push_str(&self.body);
+
+        if !content.ends_with("\n") {
+            content.push('\n');
+        }
+
+        return content;
+    }
+
+    pub fn read(path: &Path) -> Result<MarkdownFile, io::Error> {
+
+        let mut content: String = String::new();
+
+        let mut file = try!(File::open(path));
+        try!(file.read_to_string(&mut content));
+
+        return Ok(MarkdownFile::parse(&content));
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), io::Error> {
+
+        let mut file = try!(File::create(path));
+        try!(file.write_all(self.to_string().as_bytes()));
+
+        return Ok(());
+    }
+}
+
+// split the body of a card's markdown file into the front and back; the body is split at the
+// first line holding only <!-- back -->. the blank lines around the separator (see
+// join_card_body) are dropped, and escaped separators are unescaped.
+pub fn split_card_body(body: &str) -> (String, String) {
+
+    let body: &str = strip_suffix(body, "\n");
+
+    let mut front: Vec<String> = vec![];
+    let mut back: Vec<String> = vec![];
+    let mut is_back: bool = false;
+
+    for line in body.split('\n') {
+
+        if !is_back && line.trim() == BACK_SEPARATOR {
+            is_back = true;
+            continue;
+        }
+
+        if is_back {
+            back.push(unescape_separator(line));
+        } else {
+            front.push(unescape_separator(line));
+        }
+    }
+
+    if !is_back {
+        return (front.join("\n"), String::new());
+    }
+
+    let front: String = front.join("\n");
+    let back: String = back.join("\n");
+
+    return (strip_suffix(&front, "\n").to_string(), strip_prefix(&back, "\n").to_string());
+}
+
+// lines of the front and back that would be taken as the separator are escaped with a backslash
+pub fn join_card_body(front: &str, back: &str) -> String {
+    return format!("{}\n\n{}\n\n{}\n", escape_separator(front), BACK_SEPARATOR, escape_separator(back));
+}
+
+// a line holding only <!-- back -->, preceded by the given number of backslashes
+fn separator_escapes(line: &str) -> Option<usize> {
+
+    let line: &str = line.trim();
+    let unescaped: &str = line.trim_left_matches('\\');
+
+    if unescaped.trim() != BACK_SEPARATOR {
+        return None;
+    }
+
+    return Some(line.len() - unescaped.len());
+}
+
+fn escape_separator(text: &str) -> String {
+
+    let lines: Vec<String> = text.split('\n').map(|line| {
+        match separator_escapes(line) {
+            Some(_) => format!("\\{}", line),
+            None => line.to_string()
+        }
+    }).collect();
+
+    return lines.join("\n");
+}
+
+fn unescape_separator(line: &str) -> String {
+    return match separator_escapes(line) {
+        Some(escapes) if escapes > 0 => {
+            let index: usize = line.find('\\').unwrap();
+            format!("{}{}", &line[..index], &line[(index + 1)..])
+        },
+        _ => line.to_string()
+    };
+}
+
+fn strip_prefix<'a>(text: &'a str, prefix: &str) -> &'a str {
+    if text.starts_with(prefix) {
+        return &text[prefix.len()..];
+    }
+
+    return text;
+}
+
+fn strip_suffix<'a>(text: &'a str, suffix: &str) -> &'a str {
+    if text.ends_with(suffix) {
+        return &text[..(text.len() - suffix.len())];
+    }
+
+    return text;
+}
+
+// file or directory name derived from a title or name
+pub fn slugify(name: &str, fallback: &str) -> String {
+
+    let mut slug: String = String::new();
+
+    for c in name.chars() {
+        if c.is_alphanumeric() {
+            for c in c.to_lowercase() {
+                slug.push(c);
+            }
+        } else if slug.len() > 0 && !slug.ends_with("-") {
+            slug.push('-');
+        }
+    }
+
+    let slug: String = slug.chars().take(MAX_SLUG_LENGTH).collect();
+    let slug: String = slug.trim_matches('-').to_string();
+
+    if slug.len() <= 0 {
+        return fallback.to_string();
+    }
+
+    return slug;
+}
+
+struct ExportedDeck {
+    deck_id: i64,
+    parent: Option<i64>,
+    name: String,
+    description: String,
+    created_at: i64, // unix timestamp
+    updated_at: i64 // unix timestamp
+}
+
+struct ExportedCard {
+    card_id: i64,
+    deck: i64,
+    title: String,
+    description: String,
+    front: String,
+    back: String,
+    created_at: i64, // unix timestamp
+    updated_at: i64 // unix timestamp
+}
+
+// export the deck and the deck's descendents into dir.
+// markdown files of a previous export of these decks and cards within dir are replaced; any other
+// file is left as is.
+// caller should hold the db connection lock.
+pub fn export_markdown(db_conn: &Connection, deck_id: i64, dir: &Path) -> Result<MarkdownExportResponse, ExportError> {
+
+    let decks: Vec<ExportedDeck> = try!(get_decks(db_conn, deck_id));
+    let cards: Vec<ExportedCard> = try!(get_cards(db_conn, deck_id));
+
+    try!(fs::create_dir_all(dir));
+
+    let mut written: HashSet<PathBuf> = HashSet::new();
+
+    // map decks onto directories
+
+    let mut deck_dirs: HashMap<i64, PathBuf> = HashMap::new();
+    let mut used_names: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+
+    for (index, deck) in decks.iter().enumerate() {
+
+        let deck_dir: PathBuf = match deck.parent.and_then(|parent| deck_dirs.get(&parent)) {
+            Some(parent_dir) if index > 0 => {
+                let name: String = unique_name(&mut used_names, parent_dir, slugify(&deck.name, "deck"), deck.deck_id, "");
+                parent_dir.join(name)
+            },
+            // the exported deck is dir itself
+            _ => dir.to_path_buf()
+        };
+
+        try!(fs::create_dir_all(&deck_dir));
+
+        let deck_file = MarkdownFile {
+            front_matter: vec![
+                (format!("id"), FrontMatterValue::Integer(deck.deck_id)),
+                (format!("name"), FrontMatterValue::Text(deck.name.clone())),
+                (format!("description"), FrontMatterValue::Text(deck.description.clone())),
+                (format!("created_at"), FrontMatterValue::Integer(deck.created_at)),
+                (format!("updated_at"), FrontMatterValue::Integer(deck.updated_at))
+            ],
+            body: String::new()
+        };
+
+        let deck_path: PathBuf = deck_dir.join(DECK_FILE);
+
+        try!(deck_file.write(&deck_path));
+        written.insert(deck_path);
+
+        deck_dirs.insert(deck.deck_id, deck_dir);
+    }
+
+    // map cards onto markdown files
+
+    for card in cards.iter() {
+
+        let deck_dir: &PathBuf = match deck_dirs.get(&card.deck) {
+            Some(deck_dir) => deck_dir,
+            None => unreachable!() // cards are within the exported decks
+        };
+
+        let extension: String = format!(".{}", MARKDOWN_EXTENSION);
+        let name: String = unique_name(&mut used_names, deck_dir, slugify(&card.title, "card"), card.card_id, &extension);
+
+        let card_file = MarkdownFile {
+            front_matter: vec![
+                (format!("id"), FrontMatterValue::Integer(card.card_id)),
+                (format!("title"), FrontMatterValue::Text(card.title.clone())),
+                (format!("description"), FrontMatterValue::Text(card.description.clone())),
+                (format!("created_at"), FrontMatterValue::Integer(card.created_at)),
+                (format!("updated_at"), FrontMatterValue::Integer(card.updated_at))
+            ],
+            body: join_card_body(&card.front, &card.back)
+        };
+
+        let card_path: PathBuf = deck_dir.join(name);
+
+        try!(card_file.write(&card_path));
+        written.insert(card_path);
+    }
+
+    // files of a previous export that weren't replaced (e.g. of a renamed card) are removed
+
+    let deck_ids: HashSet<i64> = decks.iter().map(|deck| deck.deck_id).collect();
+    let card_ids: HashSet<i64> = cards.iter().map(|card| card.card_id).collect();
+
+    try!(remove_stale_files(dir, &deck_ids, &card_ids, &written));
+
+    let response = MarkdownExportResponse {
+        decks_exported: decks.len() as i64,
+        cards_exported: cards.len() as i64
+    };
+
+    return Ok(response);
+}
+
+// returns name (with the given suffix) that is unique within the directory;
+// names that are taken are suffixed with the id.
+fn unique_name(used_names: &mut HashMap<PathBuf, HashSet<String>>, dir: &PathBuf, slug: String, id: i64, suffix: &str) -> String {
+
+    let names = used_names.entry(dir.clone()).or_insert(HashSet::new());
+
+    let mut name: String = format!("{}{}", slug, suffix);
+
+    if names.contains(&name) || name == DECK_FILE {
+        name = format!("{}-{}{}", slug, id, suffix);
+    }
+
+    names.insert(name.clone());
+
+    return name;
+}
+
+// remove markdown files of the given decks (_deck.md files) and cards that weren't just written,
+// and directories left empty by their removal. files that can't be read are left as is.
+// hidden files and directories (e.g. .git) are skipped.
+// returns true if any file was removed.
+fn remove_stale_files(dir: &Path, deck_ids: &HashSet<i64>, card_ids: &HashSet<i64>, written: &HashSet<PathBuf>)
+    -> Result<bool, io::Error> {
+
+    let mut removed: bool = false;
+
+    for entry in try!(fs::read_dir(dir)) {
+
+        let entry = try!(entry);
+        let path: PathBuf = entry.path();
+
+        let file_name: String = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue
+        };
+
+        if file_name.starts_with(".") {
+            continue;
+        }
+
+        if try!(entry.file_type()).is_dir() {
+
+            if try!(remove_stale_files(&path, deck_ids, card_ids, written)) {
+
+                removed = true;
+
+                // only succeeds if the directory is empty
+                let _ = fs::remove_dir(&path);
+            }
+
+            continue;
+        }
+
+        let is_markdown: bool = match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) => extension == MARKDOWN_EXTENSION,
+            None => false
+        };
+
+        if !is_markdown || written.contains(&path) {
+            continue;
+        }
+
+        let id: Option<i64> = match MarkdownFile::read(&path) {
+            Ok(markdown_file) => markdown_file.get_integer("id"),
+            Err(_) => None
+        };
+
+        let is_stale: bool = match id {
+            None => false,
+            Some(id) => {
+                if file_name == DECK_FILE {
+                    deck_ids.contains(&id)
+                } else {
+                    card_ids.contains(&id)
+                }
+            }
+        };
+
+        if is_stale {
+            try!(fs::remove_file(&path));
+            removed = true;
+        }
+    }
+
+    return Ok(removed);
+}
+
+// returns the deck and its descendents; ancestors before descendents
+fn get_decks(db_conn: &Connection, deck_id: i64) -> Result<Vec<ExportedDeck>, QueryError> {
+
+    let ref query = format!("
+        SELECT
+            d.deck_id, d.name, d.description, d.created_at, d.updated_at,
+            (SELECT p.ancestor FROM DecksClosure AS p WHERE p.descendent = d.deck_id AND p.depth = 1)
+        FROM DecksClosure AS dc
+
+        INNER JOIN Decks AS d
+        ON d.deck_id = dc.descendent
+
+        WHERE dc.ancestor = :deck_id
+        ORDER BY dc.depth ASC, d.deck_id ASC;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":deck_id", &deck_id)
+    ];
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut list: Vec<ExportedDeck> = Vec::new();
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                list.push(ExportedDeck {
+                    deck_id: row.get(0),
+                    name: row.get(1),
+                    description: row.get(2),
+                    created_at: row.get(3),
+                    updated_at: row.get(4),
+                    parent: row.get(5)
+                });
+            }
+
+            return Ok(list);
+        }
+    };
+}
+
+// returns cards within the deck and its descendents
+fn get_cards(db_conn: &Connection, deck_id: i64) -> Result<Vec<ExportedCard>, QueryError> {
+
+    let ref query = format!("
+        SELECT
+            c.card_id, c.deck, c.title, c.description, c.front, c.back, c.created_at, c.updated_at
+        FROM DecksClosure AS dc
+
+        INNER JOIN Cards AS c
+        ON c.deck = dc.descendent
+
+        WHERE dc.ancestor = :deck_id
+        ORDER BY c.card_id ASC;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":deck_id", &deck_id)
+    ];
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut list: Vec<ExportedCard> = Vec::new();
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                list.push(ExportedCard {
+                    card_id: row.get(0),
+                    deck: row.get(1),
+                    title: row.get(2),
+                    description: row.get(3),
+                    front: row.get(4),
+                    back: row.get(5),
+                    created_at: row.get(6),
+                    updated_at: row.get(7)
+                });
+            }
+
+            return Ok(list);
+        }
+    };
+}
@@ -0,0 +1,124 @@
+extern crate rusqlite;
+extern crate zip;
+extern crate csv;
+
+pub mod anki;
+pub mod cards;
+pub mod dump;
+pub mod extract;
+pub mod markdown;
+mod restify;
+
+use std::error;
+use std::fmt;
+use std::io;
+
+use rusqlite::Error as SqliteError;
+use zip::result::ZipError;
+use csv::Error as CsvError;
+
+use ::database::{QueryError, BootstrapError};
+pub use self::restify::restify;
+
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Zip(ZipError),
+    Csv(CsvError),
+    Sqlite(SqliteError),
+    Query(QueryError),
+    Bootstrap(BootstrapError),
+
+    // exported data would be inconsistent, or can't be written
+    Invalid(String)
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            ExportError::Io(ref err) => write!(f, "{}", err),
+            ExportError::Zip(ref err) => write!(f, "{}", err),
+            ExportError::Csv(ref err) => write!(f, "{}", err),
+            ExportError::Sqlite(ref err) => write!(f, "{}", err),
+            ExportError::Query(ref err) => write!(f, "{}", err),
+            ExportError::Bootstrap(ref err) => write!(f, "{}", err),
+            ExportError::Invalid(ref reason) => write!(f, "{}", reason),
+        };
+    }
+}
+
+impl error::Error for ExportError {
+    fn description(&self) -> &str {
+        return match *self {
+            ExportError::Io(ref err) => err.description(),
+            ExportError::Zip(ref err) => err.description(),
+            ExportError::Csv(ref err) => err.description(),
+            ExportError::Sqlite(ref err) => err.description(),
+            ExportError::Query(ref err) => err.description(),
+            ExportError::Bootstrap(ref err) => err.description(),
+            ExportError::Invalid(ref reason) => reason,
+        };
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(err: io::Error) -> ExportError {
+        return ExportError::Io(err);
+    }
+}
+
+impl From<ZipError> for ExportError {
+    fn from(err: ZipError) -> ExportError {
+        return ExportError::Zip(err);
+    }
+}
+
+impl From<CsvError> for ExportError {
+    fn from(err: CsvError) -> ExportError {
+        return ExportError::Csv(err);
+    }
+}
+
+impl From<SqliteError> for ExportError {
+    fn from(err: SqliteError) -> ExportError {
+        return ExportError::Sqlite(err);
+    }
+}
+
+impl From<QueryError> for ExportError {
+    fn from(err: QueryError) -> ExportError {
+        return ExportError::Query(err);
+    }
+}
+
+impl From<BootstrapError> for ExportError {
+    fn from(err: BootstrapError) -> ExportError {
+        return ExportError::Bootstrap(err);
+    }
+}
+
+// strip html tags from the content; every tag is replaced by tag_separator
+pub fn strip_html(content: &str, tag_separator: &str) -> String {
+
+    let mut text: String = String::new();
+    let mut in_tag: bool = false;
+
+    for c in content.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+            },
+            '>' if in_tag => {
+                in_tag = false;
+                text.push_str(tag_separator);
+            },
+            _ if in_tag => {/* skip */},
+            _ => {
+                text.push(c);
+            }
+        }
+    }
+
+    return text;
+}
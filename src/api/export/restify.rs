@@ -0,0 +1,344 @@
+extern crate iron;
+extern crate router;
+extern crate rustc_serialize;
+
+use iron::status;
+use iron::prelude::*;
+use iron::mime::Mime;
+use iron::response::{WriteBody, ResponseBody};
+use router::Router;
+use urlencoded::{UrlEncodedQuery, QueryMap, UrlDecodingError};
+
+use std::io::{self, Cursor};
+use std::sync::Arc;
+use std::ops::Deref;
+use std::error::Error;
+
+use ::database::DB;
+use ::api::{GrokDB, ErrorResponse};
+use ::api::decks::restify::deck_exists;
+use ::api::stashes::restify::stash_exists;
+use ::api::export::ExportError;
+use ::api::export::anki::export_apkg;
+use ::api::export::cards::{export_cards, CardsOf, ExportFormat};
+use ::api::export::dump::dump_database;
+
+
+// attach export REST endpoints to given router
+pub fn restify(router: &mut Router, grokdb: GrokDB) {
+
+    let grokdb = Arc::new(grokdb);
+
+    // dump the whole database as a json document
+    router.get("/dump", {
+        let grokdb = grokdb.clone();
+        move |_: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let dump: String = {
+                let db_conn_guard = grokdb.decks.db.lock().unwrap();
+                let ref db_conn = *db_conn_guard;
+
+                match dump_database(db_conn) {
+                    Err(why) => {
+                        return export_error_response(why);
+                    },
+                    Ok(dump) => dump
+                }
+            };
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            let mut response = Response::with((content_type, status::Ok, dump));
+
+            response.headers.set_raw("Content-Disposition",
+                vec![format!("attachment; filename=\"{}-dump.json\"", grokdb.base_db_name).into_bytes()]);
+
+            return Ok(response);
+        }
+    });
+
+    // export the deck and its descendents as an Anki package (.apkg)
+    router.get("/decks/:deck_id/export/anki", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            // fetch and parse requested deck id
+
+            let deck_id = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
+            }
+
+            let mut package: Cursor<Vec<u8>> = Cursor::new(vec![]);
+
+            {
+                let db_conn_guard = grokdb.decks.db.lock().unwrap();
+                let ref db_conn = *db_conn_guard;
+
+                match export_apkg(db_conn, deck_id, &format!("grokdb:{}", grokdb.base_db_name), &mut package) {
+                    Err(why) => {
+                        return export_error_response(why);
+                    },
+                    _ => {/* package written */}
+                }
+            }
+
+            let content_type = "application/octet-stream".parse::<Mime>().unwrap();
+
+            let mut response = Response::with((content_type, status::Ok, package.into_inner()));
+
+            response.headers.set_raw("Content-Disposition",
+                vec![format!("attachment; filename=\"deck-{}.apkg\"", deck_id).into_bytes()]);
+
+            return Ok(response);
+        }
+    });
+
+    // export every card within the deck and its descendents, along with their review statistics.
+    // queries:
+    // - format: csv (default), tsv, or json
+    router.get("/decks/:deck_id/cards/export", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let format: ExportFormat = match parse_export_format(req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(format) => format
+            };
+
+            // fetch and parse requested deck id
+
+            let deck_id = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
+            }
+
+            let body = ExportedCardsBody {
+                db: grokdb.decks.db.clone(),
+                cards_of: CardsOf::Deck(deck_id),
+                format: format
+            };
+
+            let file_name: String = format!("deck-{}-cards.{}", deck_id, format.extension());
+
+            return Ok(exported_cards_response(format, &file_name, body));
+        }
+    });
+
+    // export every card within the stash, along with their review statistics.
+    // queries:
+    // - format: csv (default), tsv, or json
+    router.get("/stashes/:stash_id/cards/export", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let format: ExportFormat = match parse_export_format(req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(format) => format
+            };
+
+            // fetch and parse requested stash id
+
+            let stash_id = req.extensions.get::<Router>().unwrap().find("stash_id").unwrap();
+
+            let stash_id: i64 = match stash_id.parse::<u64>() {
+                Ok(stash_id) => stash_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure stash exists
+            match stash_exists(grokdb, stash_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* stash exists; continue */}
+            }
+
+            let body = ExportedCardsBody {
+                db: grokdb.stashes.db.clone(),
+                cards_of: CardsOf::Stash(stash_id),
+                format: format
+            };
+
+            let file_name: String = format!("stash-{}-cards.{}", stash_id, format.extension());
+
+            return Ok(exported_cards_response(format, &file_name, body));
+        }
+    });
+}
+
+/* helpers */
+
+fn parse_export_format(req: &mut Request) -> Result<ExportFormat, IronResult<Response>> {
+
+    match req.get_ref::<UrlEncodedQuery>() {
+
+        Ok(ref hashmap) => {
+
+            let hashmap: &QueryMap = hashmap;
+
+            let format: Option<&String> = match hashmap.get("format") {
+                Some(values) if values.len() > 0 => Some(&values[0]),
+                _ => None
+            };
+
+            match format {
+                None => {
+                    return Ok(ExportFormat::Csv);
+                },
+                Some(format) => {
+                    match ExportFormat::parse(format) {
+                        Some(format) => {
+                            return Ok(format);
+                        },
+                        None => {
+
+                            let ref reason = format!("invalid format query; expected csv, tsv, or json");
+                            let res_code = status::BadRequest;
+
+                            let err_response = ErrorResponse {
+                                status: res_code,
+                                developerMessage: reason,
+                                userMessage: reason,
+                            }.to_json();
+
+                            return Err(Ok(Response::with((res_code, err_response))));
+                        }
+                    }
+                }
+            };
+        },
+
+        Err(UrlDecodingError::EmptyQuery) => {
+            return Ok(ExportFormat::Csv);
+        },
+
+        Err(why) => {
+
+            let ref reason = format!("{:?}", why);
+            let res_code = status::BadRequest;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: why.description(),
+            }.to_json();
+
+            return Err(Ok(Response::with((res_code, err_response))));
+        }
+    };
+}
+
+// exported cards are streamed into the response body in batches (see export::cards::export_cards)
+struct ExportedCardsBody {
+    db: Arc<DB>,
+    cards_of: CardsOf,
+    format: ExportFormat
+}
+
+impl WriteBody for ExportedCardsBody {
+
+    fn write_body(&mut self, res: &mut ResponseBody) -> io::Result<()> {
+
+        let result: Result<(), ExportError> = export_cards(&self.db, self.cards_of, self.format, res);
+
+        // the response has already begun; so errors can only cut it short
+        return match result {
+            Err(ExportError::Io(why)) => Err(why),
+            Err(why) => Err(io::Error::new(io::ErrorKind::Other, format!("{}", why))),
+            Ok(_) => Ok(())
+        };
+    }
+}
+
+fn exported_cards_response(format: ExportFormat, file_name: &str, body: ExportedCardsBody) -> Response {
+
+    let content_type = format.content_type().parse::<Mime>().unwrap();
+
+    let body: Box<WriteBody + Send> = Box::new(body);
+
+    let mut response = Response::with((content_type, status::Ok, body));
+
+    response.headers.set_raw("Content-Disposition",
+        vec![format!("attachment; filename=\"{}\"", file_name).into_bytes()]);
+
+    return response;
+}
+
+fn export_error_response(why: ExportError) -> IronResult<Response> {
+
+    let ref reason = format!("{:?}", why);
+    let res_code = status::InternalServerError;
+
+    let err_response = ErrorResponse {
+        status: res_code,
+        developerMessage: reason,
+        userMessage: why.description(),
+    }.to_json();
+
+    return Ok(Response::with((res_code, err_response)));
+}
@@ -0,0 +1,431 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+extern crate zip;
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek};
+use std::path::Path;
+
+use rusqlite::{Connection, Statement};
+use rustc_serialize::json::{self, Json};
+use zip::ZipArchive;
+
+use ::database::QueryError;
+use ::api::tempfile::TempFile;
+use ::api::import::{ImportError, find_or_create_deck_path, insert_card, seed_card_score, derive_title};
+
+
+// import of Anki packages (.apkg).
+//
+// an .apkg file is a zip archive holding an Anki collection (a SQLite database) and media files.
+// src: https://github.com/ankitects/anki/blob/main/rslib/src/import_export/package
+//
+// - every Anki deck is mapped onto a deck; nested decks are named by joining deck names with ::
+// - every Anki note is mapped onto a card; the first field is the front, and the remaining
+//   fields are the back. the card is put in the deck of the note's first card.
+// - success and fail counts are optionally seeded from the review log (revlog); a review
+//   answered with 'again' is a fail, and any other answer is a success.
+
+// Anki collection file names; newest supported format first
+static COLLECTION_FILES: [&'static str; 2] = ["collection.anki21", "collection.anki2"];
+
+// collections compressed with zstd by newer Anki versions aren't supported
+static UNSUPPORTED_COLLECTION_FILE: &'static str = "collection.anki21b";
+
+static MEDIA_FILE: &'static str = "media";
+
+static DECK_SEPARATOR: &'static str = "::";
+static FIELD_SEPARATOR: &'static str = "\x1f";
+
+// id of Anki's default deck
+static DEFAULT_DECK_ID: i64 = 1;
+
+static IMPORT_CHANGELOG: &'static str = "Imported from Anki.";
+
+pub struct AnkiImportOptions {
+    // imported decks are created under this deck; otherwise they're created as root decks
+    pub parent: Option<i64>,
+
+    // seed CardsScore from the review log
+    pub with_scores: bool,
+
+    // directory to extract media files into; media files are skipped if not given
+    pub media_dir: Option<String>
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct AnkiImportResponse {
+    pub decks_created: i64,
+    pub cards_created: i64,
+    pub cards_scored: i64,
+    pub media_files: i64,
+
+    // deck id of every imported Anki deck
+    pub decks: Vec<i64>
+}
+
+impl AnkiImportResponse {
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+}
+
+struct AnkiNote {
+    id: i64,
+    fields: String,
+    tags: String,
+    deck: Option<i64> // Anki deck id
+}
+
+struct AnkiScore {
+    success: i64,
+    fail: i64,
+    reviewed_at: i64 // unix timestamp
+}
+
+// import an .apkg package.
+// caller should hold the db connection lock; the import is done within a single transaction.
+pub fn import_apkg<R: Read + Seek>(db_conn: &Connection, reader: R, options: &AnkiImportOptions)
+    -> Result<AnkiImportResponse, ImportError> {
+
+    let mut archive = try!(ZipArchive::new(reader));
+
+    // extract the collection; rusqlite can only open databases from files
+
+    let mut collection_file: Option<&str> = None;
+
+    for file_name in COLLECTION_FILES.iter() {
+        if archive.by_name(file_name).is_ok() {
+            collection_file = Some(*file_name);
+            break;
+        }
+    }
+
+    let collection_file: &str = match collection_file {
+        Some(collection_file) => collection_file,
+        None => {
+
+            let reason = if archive.by_name(UNSUPPORTED_COLLECTION_FILE).is_ok() {
+                format!("unsupported Anki package; export it with 'Support older Anki versions' enabled")
+            } else {
+                format!("invalid Anki package; no collection found")
+            };
+
+            return Err(ImportError::Invalid(reason));
+        }
+    };
+
+    // deleted once the extracted collection is no longer needed
+    let temp_file = TempFile::new("grokdb-anki", "anki2");
+
+    {
+        let mut collection = try!(archive.by_name(collection_file));
+        let mut out = try!(File::create(&temp_file.path));
+        try!(io::copy(&mut collection, &mut out));
+    }
+
+    let anki_conn = try!(Connection::open(&temp_file.path));
+
+    let anki_decks: Vec<(i64, String)> = try!(get_anki_decks(&anki_conn));
+    let notes: Vec<AnkiNote> = try!(get_anki_notes(&anki_conn));
+
+    let scores: HashMap<i64, AnkiScore> = if options.with_scores {
+        try!(get_anki_scores(&anki_conn))
+    } else {
+        HashMap::new()
+    };
+
+    let mut response = AnkiImportResponse {
+        decks_created: 0,
+        cards_created: 0,
+        cards_scored: 0,
+        media_files: 0,
+        decks: vec![]
+    };
+
+    let tx = match db_conn.transaction() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("creating transaction"),
+            };
+            return Err(ImportError::Query(err));
+        },
+        Ok(tx) => tx
+    };
+
+    // map Anki decks onto decks
+
+    let mut deck_map: HashMap<i64, i64> = HashMap::new();
+
+    for &(anki_deck_id, ref name) in anki_decks.iter() {
+
+        // skip Anki's default deck when it's unused
+        if anki_deck_id == DEFAULT_DECK_ID && !notes.iter().any(|note| note.deck == Some(anki_deck_id)) {
+            continue;
+        }
+
+        let path: Vec<String> = name.split(DECK_SEPARATOR)
+            .map(|name| name.trim().to_string())
+            .filter(|name| name.len() > 0)
+            .collect();
+
+        if path.len() <= 0 {
+            continue;
+        }
+
+        let (deck_id, num_created) = try!(find_or_create_deck_path(db_conn, options.parent, &path[..]));
+
+        response.decks_created = response.decks_created + num_created;
+        response.decks.push(deck_id);
+        deck_map.insert(anki_deck_id, deck_id);
+    }
+
+    // map Anki notes onto cards
+
+    for note in notes.iter() {
+
+        let deck_id: i64 = match note.deck.and_then(|anki_deck_id| deck_map.get(&anki_deck_id).map(|id| *id)) {
+            Some(deck_id) => deck_id,
+            None => {
+                // note's cards are in a deck that no longer exists
+                let path: Vec<String> = vec![format!("Default")];
+                let (deck_id, num_created) = try!(find_or_create_deck_path(db_conn, options.parent, &path[..]));
+                response.decks_created = response.decks_created + num_created;
+                deck_id
+            }
+        };
+
+        let fields: Vec<&str> = note.fields.split(FIELD_SEPARATOR).collect();
+
+        let front: &str = fields[0];
+        let back: String = fields[1..].iter()
+            .map(|field| field.trim())
+            .filter(|field| field.len() > 0)
+            .collect::<Vec<&str>>()
+            .join("\n\n");
+
+        let title: String = derive_title(front, &format!("Anki note {}", note.id));
+
+        let card_id: i64 = try!(insert_card(db_conn, deck_id, &title, note.tags.trim(), front, &back));
+
+        response.cards_created = response.cards_created + 1;
+
+        match scores.get(&note.id) {
+            None => {/* note was never reviewed */},
+            Some(score) => {
+                try!(seed_card_score(db_conn, card_id, score.success, score.fail, score.reviewed_at, IMPORT_CHANGELOG));
+                response.cards_scored = response.cards_scored + 1;
+            }
+        }
+    }
+
+    // extract media files
+
+    match options.media_dir {
+        None => {/* media files skipped */},
+        Some(ref media_dir) => {
+            response.media_files = try!(extract_media(&mut archive, Path::new(media_dir)));
+        }
+    }
+
+    match tx.commit() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("committing transaction"),
+            };
+            return Err(ImportError::Query(err));
+        },
+        _ => {/* commit successful */}
+    }
+
+    return Ok(response);
+}
+
+// returns Anki deck ids and names
+fn get_anki_decks(anki_conn: &Connection) -> Result<Vec<(i64, String)>, ImportError> {
+
+    // older collections store decks as json within the col table;
+    // newer collections have a decks table whose names are separated by \x1f.
+
+    let has_decks_table: i64 = try!(anki_conn.query_row("
+        SELECT COUNT(1) FROM sqlite_master WHERE type = 'table' AND name = 'decks';
+    ", &[], |row| -> i64 {
+        return row.get(0);
+    }));
+
+    let mut decks: Vec<(i64, String)> = vec![];
+
+    if has_decks_table > 0 {
+
+        let mut stmt: Statement = try!(anki_conn.prepare("SELECT id, name FROM decks;"));
+
+        let rows = try!(stmt.query_map(&[], |row| -> (i64, String) {
+            let name: String = row.get(1);
+            return (row.get(0), name.replace(FIELD_SEPARATOR, DECK_SEPARATOR));
+        }));
+
+        for row in rows {
+            decks.push(try!(row));
+        }
+
+    } else {
+
+        let decks_json: String = try!(anki_conn.query_row("SELECT decks FROM col LIMIT 1;", &[], |row| -> String {
+            return row.get(0);
+        }));
+
+        let decks_json = match Json::from_str(&decks_json) {
+            Ok(Json::Object(decks_json)) => decks_json,
+            _ => {
+                return Err(ImportError::Invalid(format!("invalid Anki collection; malformed decks")));
+            }
+        };
+
+        for (_, deck) in decks_json.iter() {
+
+            let id: Option<i64> = deck.find("id").and_then(|id| id.as_i64());
+            let name: Option<&str> = deck.find("name").and_then(|name| name.as_string());
+
+            match (id, name) {
+                (Some(id), Some(name)) => {
+                    decks.push((id, name.to_string()));
+                },
+                _ => {
+                    return Err(ImportError::Invalid(format!("invalid Anki collection; malformed deck")));
+                }
+            }
+        }
+    }
+
+    // parent decks first
+    decks.sort_by(|a, b| a.1.cmp(&b.1));
+
+    return Ok(decks);
+}
+
+fn get_anki_notes(anki_conn: &Connection) -> Result<Vec<AnkiNote>, ImportError> {
+
+    let mut stmt: Statement = try!(anki_conn.prepare("
+        SELECT
+            n.id, n.flds, n.tags,
+            (SELECT c.did FROM cards AS c WHERE c.nid = n.id ORDER BY c.ord ASC LIMIT 1)
+        FROM notes AS n
+        ORDER BY n.id ASC;
+    "));
+
+    let rows = try!(stmt.query_map(&[], |row| -> AnkiNote {
+        return AnkiNote {
+            id: row.get(0),
+            fields: row.get(1),
+            tags: row.get(2),
+            deck: row.get(3)
+        };
+    }));
+
+    let mut notes: Vec<AnkiNote> = vec![];
+
+    for row in rows {
+        notes.push(try!(row));
+    }
+
+    return Ok(notes);
+}
+
+// returns scores by Anki note id
+fn get_anki_scores(anki_conn: &Connection) -> Result<HashMap<i64, AnkiScore>, ImportError> {
+
+    // ease is the answer button; 1 is 'again'.
+    // ease of 0 denotes the card was manually rescheduled, which isn't a review.
+    // revlog id is the time of the review in milliseconds.
+    let mut stmt: Statement = try!(anki_conn.prepare("
+        SELECT
+            c.nid,
+            SUM(CASE WHEN r.ease > 1 THEN 1 ELSE 0 END),
+            SUM(CASE WHEN r.ease = 1 THEN 1 ELSE 0 END),
+            MAX(r.id) / 1000
+        FROM revlog AS r
+
+        INNER JOIN cards AS c
+        ON c.id = r.cid
+
+        WHERE r.ease > 0
+        GROUP BY c.nid;
+    "));
+
+    let rows = try!(stmt.query_map(&[], |row| -> (i64, AnkiScore) {
+        return (row.get(0), AnkiScore {
+            success: row.get(1),
+            fail: row.get(2),
+            reviewed_at: row.get(3)
+        });
+    }));
+
+    let mut scores: HashMap<i64, AnkiScore> = HashMap::new();
+
+    for row in rows {
+        let (note_id, score) = try!(row);
+        scores.insert(note_id, score);
+    }
+
+    return Ok(scores);
+}
+
+// extract media files listed in the package's media file; returns number of files extracted
+fn extract_media<R: Read + Seek>(archive: &mut ZipArchive<R>, media_dir: &Path) -> Result<i64, ImportError> {
+
+    // media file maps the archived file names onto the original file names.
+    // e.g. {"0": "image.jpg"}
+
+    let mut media_json: String = String::new();
+
+    match archive.by_name(MEDIA_FILE) {
+        Err(_) => {
+            // package has no media
+            return Ok(0);
+        },
+        Ok(mut media) => {
+            try!(media.read_to_string(&mut media_json));
+        }
+    }
+
+    let media_json = match Json::from_str(&media_json) {
+        Ok(Json::Object(media_json)) => media_json,
+        _ => {
+            return Err(ImportError::Invalid(format!("invalid Anki package; malformed media file")));
+        }
+    };
+
+    try!(fs::create_dir_all(media_dir));
+
+    let mut num_extracted: i64 = 0;
+
+    for (archived_name, file_name) in media_json.iter() {
+
+        // only the file name is kept; paths could otherwise escape media_dir
+        let file_name: &str = match file_name.as_string().and_then(|name| Path::new(name).file_name()) {
+            Some(file_name) => {
+                match file_name.to_str() {
+                    Some(file_name) => file_name,
+                    None => continue
+                }
+            },
+            None => continue
+        };
+
+        let mut media = match archive.by_name(archived_name) {
+            Err(_) => continue,
+            Ok(media) => media
+        };
+
+        let mut out = try!(File::create(media_dir.join(file_name)));
+        try!(io::copy(&mut media, &mut out));
+
+        num_extracted = num_extracted + 1;
+    }
+
+    return Ok(num_extracted);
+}
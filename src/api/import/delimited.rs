@@ -0,0 +1,390 @@
+extern crate csv;
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+use std::io::Read;
+
+use rusqlite::Connection;
+use rustc_serialize::json;
+
+use ::database::QueryError;
+use ::api::import::{ImportError, find_or_create_deck_path, insert_card, derive_title};
+
+
+// import of spreadsheets as CSV or TSV files.
+//
+// every row is mapped onto a card within the deck the file is imported into. columns are mapped
+// onto the card's title, description, front, and back; a column is referred to by its index
+// (starting at 0), or by its name if the file has a header row.
+//
+// a deck path column (deck names from outermost to innermost, separated by ::) puts the card
+// within a descendent of the deck; missing decks are created.
+//
+// the whole file is imported within a single transaction; if any row is rejected, nothing is
+// imported. in a dry run, the rows that would be rejected are reported, and nothing is imported.
+
+static DECK_SEPARATOR: &'static str = "::";
+
+// excel prepends a byte order mark to utf-8 files
+static BYTE_ORDER_MARK: char = '\u{feff}';
+
+#[derive(Debug, Clone)]
+pub enum Column {
+    Index(usize),
+    Name(String)
+}
+
+impl Column {
+
+    // a column is an index if it's a number; otherwise it's a name
+    pub fn parse(column: &str) -> Column {
+        return match column.trim().parse::<usize>() {
+            Ok(index) => Column::Index(index),
+            Err(_) => Column::Name(column.trim().to_string())
+        };
+    }
+}
+
+pub struct DelimitedImportOptions {
+    // b',' for csv; b'\t' for tsv
+    pub delimiter: u8,
+
+    // first row is a header row
+    pub has_headers: bool,
+
+    // column mapping.
+    // if no column is mapped, columns are mapped by header names (title, description, front,
+    // back, and deck); without a header row, the first two columns are the front and back.
+    // if no title column is mapped, titles are derived from the front.
+    pub title: Option<Column>,
+    pub description: Option<Column>,
+    pub front: Option<Column>,
+    pub back: Option<Column>,
+    pub deck_path: Option<Column>,
+
+    pub dry_run: bool
+}
+
+impl DelimitedImportOptions {
+
+    #[allow(unused_parens)]
+    fn has_mapping(&self) -> bool {
+        return (
+            self.title.is_some() ||
+            self.description.is_some() ||
+            self.front.is_some() ||
+            self.back.is_some() ||
+            self.deck_path.is_some()
+        );
+    }
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct RejectedRow {
+    // row number within the file, starting at 1 (a header row is row 1)
+    row: i64,
+    reason: String
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct DelimitedImportResponse {
+    dry_run: bool,
+
+    // cards and decks were created
+    imported: bool,
+
+    rows: i64,
+    cards_created: i64,
+    decks_created: i64,
+
+    rejected: Vec<RejectedRow>
+}
+
+impl DelimitedImportResponse {
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+
+    pub fn has_rejected(&self) -> bool {
+        return self.rejected.len() > 0;
+    }
+}
+
+// column indices
+struct ColumnMapping {
+    title: Option<usize>,
+    description: Option<usize>,
+    front: Option<usize>,
+    back: Option<usize>,
+    deck_path: Option<usize>
+}
+
+// import the file into the deck.
+// caller should hold the db connection lock; the import is done within a single transaction.
+pub fn import_delimited<R: Read>(db_conn: &Connection, deck_id: i64, reader: R, options: &DelimitedImportOptions)
+    -> Result<DelimitedImportResponse, ImportError> {
+
+    let mut csv_reader = csv::Reader::from_reader(reader)
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(options.delimiter);
+
+    let mut records: Vec<Vec<String>> = vec![];
+
+    for record in csv_reader.records() {
+        records.push(try!(record));
+    }
+
+    let headers: Option<Vec<String>> = if options.has_headers && records.len() > 0 {
+
+        let mut headers: Vec<String> = records.remove(0);
+
+        if headers.len() > 0 {
+            headers[0] = headers[0].trim_left_matches(BYTE_ORDER_MARK).to_string();
+        }
+
+        Some(headers)
+    } else {
+        None
+    };
+
+    // row number of the first record
+    let first_row: i64 = if headers.is_some() {
+        2
+    } else {
+        1
+    };
+
+    let mapping: ColumnMapping = try!(resolve_mapping(&headers, options));
+
+    let mut response = DelimitedImportResponse {
+        dry_run: options.dry_run,
+        imported: false,
+        rows: 0,
+        cards_created: 0,
+        decks_created: 0,
+        rejected: vec![]
+    };
+
+    let tx = match db_conn.transaction() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("creating transaction"),
+            };
+            return Err(ImportError::Query(err));
+        },
+        Ok(tx) => tx
+    };
+
+    for (index, record) in records.iter().enumerate() {
+
+        let row: i64 = first_row + (index as i64);
+
+        // skip blank rows
+        if record.iter().all(|cell| cell.trim().len() <= 0) {
+            continue;
+        }
+
+        response.rows = response.rows + 1;
+
+        let card = match map_row(record, &mapping) {
+            Err(reason) => {
+                response.rejected.push(RejectedRow {
+                    row: row,
+                    reason: reason
+                });
+                continue;
+            },
+            Ok(card) => card
+        };
+
+        if response.has_rejected() {
+            // nothing is imported; keep looking for rows to reject
+            continue;
+        }
+
+        let (title, description, front, back, path) = card;
+
+        let card_deck_id: i64 = if path.len() > 0 {
+            let (card_deck_id, num_created) = try!(find_or_create_deck_path(db_conn, Some(deck_id), &path[..]));
+            response.decks_created = response.decks_created + num_created;
+            card_deck_id
+        } else {
+            deck_id
+        };
+
+        try!(insert_card(db_conn, card_deck_id, &title, &description, &front, &back));
+
+        response.cards_created = response.cards_created + 1;
+    }
+
+    if response.has_rejected() {
+        // rolled back once dropped
+        response.cards_created = 0;
+        response.decks_created = 0;
+        return Ok(response);
+    }
+
+    if options.dry_run {
+        // rolled back once dropped; report what would have been created
+        return Ok(response);
+    }
+
+    match tx.commit() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("committing transaction"),
+            };
+            return Err(ImportError::Query(err));
+        },
+        _ => {/* commit successful */}
+    }
+
+    response.imported = true;
+
+    return Ok(response);
+}
+
+fn resolve_mapping(headers: &Option<Vec<String>>, options: &DelimitedImportOptions) -> Result<ColumnMapping, ImportError> {
+
+    let mapping = if options.has_mapping() {
+        ColumnMapping {
+            title: try!(resolve_column(headers, &options.title)),
+            description: try!(resolve_column(headers, &options.description)),
+            front: try!(resolve_column(headers, &options.front)),
+            back: try!(resolve_column(headers, &options.back)),
+            deck_path: try!(resolve_column(headers, &options.deck_path))
+        }
+    } else {
+        match *headers {
+            Some(ref headers) => {
+                ColumnMapping {
+                    title: find_header(headers, "title"),
+                    description: find_header(headers, "description"),
+                    front: find_header(headers, "front"),
+                    back: find_header(headers, "back"),
+                    deck_path: find_header(headers, "deck")
+                }
+            },
+            None => {
+                ColumnMapping {
+                    title: None,
+                    description: None,
+                    front: Some(0),
+                    back: Some(1),
+                    deck_path: None
+                }
+            }
+        }
+    };
+
+    if mapping.title.is_none() && mapping.front.is_none() {
+        return Err(ImportError::Invalid(format!("either a title or a front column should be mapped")));
+    }
+
+    return Ok(mapping);
+}
+
+fn resolve_column(headers: &Option<Vec<String>>, column: &Option<Column>) -> Result<Option<usize>, ImportError> {
+
+    let column: &Column = match *column {
+        None => {
+            return Ok(None);
+        },
+        Some(ref column) => column
+    };
+
+    match *column {
+        Column::Index(index) => {
+            return Ok(Some(index));
+        },
+        Column::Name(ref name) => {
+
+            let index: Option<usize> = match *headers {
+                Some(ref headers) => find_header(headers, name),
+                None => {
+                    return Err(ImportError::Invalid(format!("columns can only be named if the file has a header row")));
+                }
+            };
+
+            return match index {
+                Some(index) => Ok(Some(index)),
+                None => Err(ImportError::Invalid(format!("no such column: {}", name)))
+            };
+        }
+    };
+}
+
+// case insensitive
+fn find_header(headers: &Vec<String>, name: &str) -> Option<usize> {
+
+    let name: String = name.trim().to_lowercase();
+
+    for (index, header) in headers.iter().enumerate() {
+        if header.trim().to_lowercase() == name {
+            return Some(index);
+        }
+    }
+
+    return None;
+}
+
+// returns the card's title, description, front, back, and deck path; or the reason the row is rejected
+fn map_row(record: &Vec<String>, mapping: &ColumnMapping)
+    -> Result<(String, String, String, String, Vec<String>), String> {
+
+    let front: String = try!(get_cell(record, mapping.front));
+    let back: String = try!(get_cell(record, mapping.back));
+    let description: String = try!(get_cell(record, mapping.description));
+
+    let title: String = match mapping.title {
+        Some(_) => {
+
+            let title: String = try!(get_cell(record, mapping.title)).trim().to_string();
+
+            if title.len() <= 0 {
+                return Err(format!("card title should be non-empty string"));
+            }
+
+            title
+        },
+        None => {
+
+            let title: String = derive_title(&front, "");
+
+            if title.len() <= 0 {
+                return Err(format!("card title can't be derived from an empty front"));
+            }
+
+            title
+        }
+    };
+
+    let path: Vec<String> = try!(get_cell(record, mapping.deck_path))
+        .split(DECK_SEPARATOR)
+        .map(|name| name.trim().to_string())
+        .filter(|name| name.len() > 0)
+        .collect();
+
+    return Ok((title, description, front, back, path));
+}
+
+// empty if the column isn't mapped
+fn get_cell(record: &Vec<String>, column: Option<usize>) -> Result<String, String> {
+
+    let index: usize = match column {
+        None => {
+            return Ok(String::new());
+        },
+        Some(index) => index
+    };
+
+    if index >= record.len() {
+        return Err(format!("missing column {}; row has {} columns", index, record.len()));
+    }
+
+    return Ok(record[index].clone());
+}
@@ -0,0 +1,282 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use rusqlite::Connection;
+use rusqlite::types::ToSql;
+use rustc_serialize::json::{self, Json};
+
+use ::database::QueryError;
+use ::api::import::ImportError;
+use ::api::export::dump::{DUMP_FORMAT, DUMP_FORMAT_VERSION, DUMP_TABLES, ROWID_KEY};
+
+
+// restore of a full database dump (see export::dump) into a fresh database.
+//
+// rows are restored with their ids. dumps of older versions of grokdb are restored as long as
+// their columns still exist; columns added since are set to their defaults.
+
+#[derive(Debug, RustcEncodable)]
+pub struct RestoreDumpResponse {
+    pub dumped_at: i64, // unix timestamp
+    pub rows_restored: i64
+}
+
+impl RestoreDumpResponse {
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+}
+
+// restore the dump into the database; the database should have no rows.
+// caller should hold the db connection lock; the restore is done within a single transaction.
+pub fn restore_dump<R: Read>(db_conn: &Connection, reader: R) -> Result<RestoreDumpResponse, ImportError> {
+
+    let mut reader = reader;
+
+    let document: Json = match Json::from_reader(&mut reader) {
+        Ok(document) => document,
+        Err(why) => {
+            return Err(ImportError::Invalid(format!("invalid dump; {}", why)));
+        }
+    };
+
+    match document.find("format").and_then(|format| format.as_string()) {
+        Some(format) if format == DUMP_FORMAT => {/* continue */},
+        _ => {
+            return Err(ImportError::Invalid(format!("invalid dump; not a grokdb dump")));
+        }
+    }
+
+    match document.find("version").and_then(|version| version.as_i64()) {
+        Some(version) if version >= 1 && version <= DUMP_FORMAT_VERSION => {/* continue */},
+        Some(version) => {
+            return Err(ImportError::Invalid(format!("unsupported dump version {}; this grokdb supports up to version {}",
+                version, DUMP_FORMAT_VERSION)));
+        },
+        None => {
+            return Err(ImportError::Invalid(format!("invalid dump; no version")));
+        }
+    }
+
+    let dumped_at: i64 = match document.find("dumped_at").and_then(|dumped_at| dumped_at.as_i64()) {
+        Some(dumped_at) => dumped_at,
+        None => 0
+    };
+
+    let tables: &BTreeMap<String, Json> = match document.find("tables").and_then(|tables| tables.as_object()) {
+        Some(tables) => tables,
+        None => {
+            return Err(ImportError::Invalid(format!("invalid dump; no tables")));
+        }
+    };
+
+    for (table, _) in tables.iter() {
+        if !DUMP_TABLES.iter().any(|&(dump_table, _)| dump_table == table) {
+            return Err(ImportError::Invalid(format!("invalid dump; unknown table {}", table)));
+        }
+    }
+
+    // existing rows would otherwise be overwritten by the dumped rows
+    for &(table, _) in DUMP_TABLES.iter() {
+        if try!(count_rows(db_conn, table)) > 0 {
+            return Err(ImportError::Invalid(format!("dumps can only be restored into an empty database; {} has rows", table)));
+        }
+    }
+
+    let mut response = RestoreDumpResponse {
+        dumped_at: dumped_at,
+        rows_restored: 0
+    };
+
+    let tx = match db_conn.transaction() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("creating transaction"),
+            };
+            return Err(ImportError::Query(err));
+        },
+        Ok(tx) => tx
+    };
+
+    for &(table, with_rowid) in DUMP_TABLES.iter() {
+
+        let rows: &Vec<Json> = match tables.get(table) {
+            None => {
+                // table was added after the dump
+                continue;
+            },
+            Some(&Json::Array(ref rows)) => rows,
+            Some(_) => {
+                return Err(ImportError::Invalid(format!("invalid dump; rows of {} should be an array", table)));
+            }
+        };
+
+        let mut columns: Vec<String> = try!(get_columns(db_conn, table));
+
+        if with_rowid {
+            columns.push(ROWID_KEY.to_string());
+        }
+
+        for row in rows.iter() {
+            try!(restore_row(db_conn, table, &columns, row));
+            response.rows_restored = response.rows_restored + 1;
+        }
+    }
+
+    match tx.commit() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("committing transaction"),
+            };
+            return Err(ImportError::Query(err));
+        },
+        _ => {/* commit successful */}
+    }
+
+    return Ok(response);
+}
+
+fn restore_row(db_conn: &Connection, table: &str, columns: &Vec<String>, row: &Json) -> Result<(), ImportError> {
+
+    let row: &BTreeMap<String, Json> = match row.as_object() {
+        Some(row) => row,
+        None => {
+            return Err(ImportError::Invalid(format!("invalid dump; rows of {} should be objects", table)));
+        }
+    };
+
+    let mut names: Vec<String> = vec![];
+    let mut values: Vec<Box<ToSql>> = vec![];
+
+    for (column, value) in row.iter() {
+
+        // column names are interpolated into the query; only known columns are allowed
+        if !columns.contains(column) {
+            return Err(ImportError::Invalid(format!("invalid dump; unknown column {} of {}", column, table)));
+        }
+
+        let value: Box<ToSql> = match *value {
+            Json::Null => Box::new(None::<i64>),
+            Json::I64(value) => Box::new(value),
+            Json::U64(value) => Box::new(value as i64),
+            Json::F64(value) => Box::new(value),
+            Json::String(ref value) => Box::new(value.clone()),
+            _ => {
+                return Err(ImportError::Invalid(format!("invalid dump; unsupported value of {}.{}", table, column)));
+            }
+        };
+
+        names.push(column.clone());
+        values.push(value);
+    }
+
+    if names.len() <= 0 {
+        return Err(ImportError::Invalid(format!("invalid dump; empty row of {}", table)));
+    }
+
+    let placeholders: Vec<String> = (1..(names.len() + 1)).map(|index| format!("?{}", index)).collect();
+
+    // rows created by triggers are replaced by the dumped rows (e.g. a new card's CardsScore)
+    let ref query = format!("
+        INSERT OR REPLACE INTO {table}({columns}) VALUES ({placeholders});
+    ", table = table, columns = names.join(", "), placeholders = placeholders.join(", "));
+
+    let params: Vec<&ToSql> = values.iter().map(|value| &**value).collect();
+
+    match db_conn.execute(query, &params[..]) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(ImportError::Query(err));
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
+
+// returns column names of the table
+pub fn get_columns(db_conn: &Connection, table: &str) -> Result<Vec<String>, QueryError> {
+
+    let ref query = format!("PRAGMA table_info({});", table);
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_map(&[], |row| -> String {
+        return row.get(1);
+    });
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut columns: Vec<String> = vec![];
+
+            for column in iter {
+                match column {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(column) => {
+                        columns.push(column);
+                    }
+                }
+            }
+
+            return Ok(columns);
+        }
+    };
+}
+
+fn count_rows(db_conn: &Connection, table: &str) -> Result<i64, QueryError> {
+
+    let ref query = format!("SELECT COUNT(1) FROM {};", table);
+
+    let count = db_conn.query_row(query, &[], |row| -> i64 {
+        return row.get(0);
+    });
+
+    match count {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(count) => {
+            return Ok(count);
+        }
+    };
+}
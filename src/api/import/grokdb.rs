@@ -0,0 +1,811 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+
+use rusqlite::{Connection, SqliteStatement, SqliteRow};
+use rusqlite::types::ToSql;
+use rustc_serialize::json;
+
+use ::database::{self, QueryError, BootstrapError};
+use ::migrations;
+use ::api::tempfile::TempFile;
+use ::api::import::{ImportError, create_deck, insert_card};
+
+
+// merge of another grokdb database into this one.
+//
+// decks, cards (along with their scores and score history), and stashes of the other database are
+// imported with new ids; its root decks are put under the given parent deck (if any).
+//
+// a deck, card, or stash of the other database conflicts with one of this database if both have
+// the same id and were created at the same time; i.e. they're the same deck, card, or stash (e.g.
+// the other database was extracted from this one). conflicts are resolved by the merge strategy:
+//
+// - keep both: conflicting decks, cards, and stashes are imported as new ones
+// - newer: the existing one is updated if the imported one was updated after it; score history
+//   of a card reviewed since is appended, and its score is merged rather than replaced (whether
+//   or not the card was updated)
+// - skip: the existing one is kept as is
+//
+// decks, cards, and stashes that conflict (and aren't kept as both) are merged into the existing
+// ones; e.g. new cards of a conflicting deck are imported into the existing deck.
+//
+// the other database is migrated to the schema version of this database before it's merged;
+// databases newer than this grokdb are refused.
+//
+// the merge is done within a single transaction.
+
+// schema name of the other database while it's attached
+static MERGED_SCHEMA: &'static str = "merged";
+
+// src: https://www.sqlite.org/fileformat.html#the_database_header
+static SQLITE_HEADER: &'static [u8] = b"SQLite format 3\0";
+
+// tables merged from the other database
+static MERGED_TABLES: [&'static str; 7] = [
+    "Decks",
+    "DecksClosure",
+    "Cards",
+    "CardsScore",
+    "CardsScoreHistory",
+    "Stashes",
+    "StashCards"
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeStrategy {
+    KeepBoth,
+    Newer,
+    Skip
+}
+
+impl MergeStrategy {
+
+    pub fn parse(strategy: &str) -> Option<MergeStrategy> {
+        return match strategy.trim().to_lowercase().as_ref() {
+            "keep_both" => Some(MergeStrategy::KeepBoth),
+            "newer" => Some(MergeStrategy::Newer),
+            "skip" => Some(MergeStrategy::Skip),
+            _ => None
+        };
+    }
+}
+
+pub struct GrokDBImportOptions {
+    // deck to put root decks under; otherwise they're root decks
+    pub parent: Option<i64>,
+
+    pub strategy: MergeStrategy
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct GrokDBImportResponse {
+    pub decks_created: i64,
+    pub decks_updated: i64,
+    pub decks_skipped: i64,
+
+    pub cards_created: i64,
+    pub cards_updated: i64,
+    pub cards_skipped: i64,
+
+    pub stashes_created: i64,
+    pub stashes_updated: i64,
+    pub stashes_skipped: i64,
+
+    pub history_merged: i64
+}
+
+impl GrokDBImportResponse {
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+}
+
+// a deck, card, or stash of the other database
+struct MergedRow {
+    id: i64,
+    name: String,
+    description: String,
+    created_at: i64,
+    updated_at: i64,
+
+    // deck: parent deck (if any); card: deck; stash: unused
+    parent: Option<i64>,
+
+    // card only
+    front: String,
+    back: String
+}
+
+enum Resolution {
+    // import as a new row
+    Create,
+    // existing row of the same id; updated if true
+    Merge(bool)
+}
+
+// merge the database file into the database.
+// caller should hold the db connection lock; the merge is done within a single transaction.
+pub fn import_grokdb<R: Read>(db_conn: &Connection, reader: R, options: &GrokDBImportOptions)
+    -> Result<GrokDBImportResponse, ImportError> {
+
+    let mut reader = reader;
+
+    // deleted once the merge is done
+    let temp_file = TempFile::new("grokdb-merge", "db");
+
+    {
+        let mut out = try!(File::create(&temp_file.path));
+        try!(io::copy(&mut reader, &mut out));
+    }
+
+    {
+        let mut header: Vec<u8> = vec![];
+        try!(try!(File::open(&temp_file.path)).take(SQLITE_HEADER.len() as u64).read_to_end(&mut header));
+
+        if &header[..] != SQLITE_HEADER {
+            return Err(ImportError::Invalid(format!("not a grokdb database")));
+        }
+    }
+
+    let path: String = match temp_file.path.to_str() {
+        Some(path) => path.to_string(),
+        None => {
+            return Err(ImportError::Invalid(format!("invalid temporary file path")));
+        }
+    };
+
+    try!(migrate_merged(&path));
+
+    let ref query_attach = format!("ATTACH DATABASE :path AS {};", MERGED_SCHEMA);
+
+    let params: &[(&str, &ToSql)] = &[
+        (":path", &path)
+    ];
+
+    try!(execute(db_conn, query_attach, params));
+
+    let result = merge(db_conn, options);
+
+    let detached = execute(db_conn, &format!("DETACH DATABASE {};", MERGED_SCHEMA), &[]);
+
+    let response: GrokDBImportResponse = try!(result);
+    try!(detached);
+
+    return Ok(response);
+}
+
+// migrate the database file to the latest schema version before it's attached.
+// databases with a newer schema version are refused.
+fn migrate_merged(path: &str) -> Result<(), ImportError> {
+
+    let merged_conn: Connection = match database::open(path) {
+        Err(BootstrapError::Sqlite(why)) => {
+            return Err(ImportError::Sqlite(why));
+        },
+        Err(why) => {
+            return Err(ImportError::Invalid(format!("unable to open the database: {}", why)));
+        },
+        Ok(merged_conn) => merged_conn
+    };
+
+    let version: i64 = try!(migrations::get_version(&merged_conn));
+
+    if version > migrations::latest_version() {
+        return Err(ImportError::Invalid(format!("database has schema version {}; this grokdb supports up to version {}",
+            version, migrations::latest_version())));
+    }
+
+    // checked before migrating; otherwise the baseline migration would create the missing tables
+    for table in MERGED_TABLES.iter() {
+        if !try!(has_table(&merged_conn, table)) {
+            return Err(ImportError::Invalid(format!("not a grokdb database; it has no {} table", table)));
+        }
+    }
+
+    match migrations::migrate(&merged_conn, None) {
+        Err(why) => {
+            return Err(ImportError::Invalid(format!("unable to migrate the database: {}", why)));
+        },
+        Ok(_) => {}
+    };
+
+    return Ok(());
+}
+
+fn merge(db_conn: &Connection, options: &GrokDBImportOptions) -> Result<GrokDBImportResponse, ImportError> {
+
+    let mut response = GrokDBImportResponse {
+        decks_created: 0,
+        decks_updated: 0,
+        decks_skipped: 0,
+        cards_created: 0,
+        cards_updated: 0,
+        cards_skipped: 0,
+        stashes_created: 0,
+        stashes_updated: 0,
+        stashes_skipped: 0,
+        history_merged: 0
+    };
+
+    let tx = match db_conn.transaction() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("creating transaction"),
+            };
+            return Err(ImportError::Query(err));
+        },
+        Ok(tx) => tx
+    };
+
+    // ids of the other database to ids of this database
+    let mut deck_ids: HashMap<i64, i64> = HashMap::new();
+    let mut card_ids: HashMap<i64, i64> = HashMap::new();
+    let mut stash_ids: HashMap<i64, i64> = HashMap::new();
+
+    /* decks */
+
+    // parent decks come before their descendents
+    let ref query_decks = format!("
+        SELECT
+            d.deck_id, d.name, d.description, d.created_at, d.updated_at,
+            (SELECT p.ancestor FROM {schema}.DecksClosure AS p WHERE p.descendent = d.deck_id AND p.depth = 1)
+        FROM {schema}.Decks AS d
+        ORDER BY
+            (SELECT MAX(dc.depth) FROM {schema}.DecksClosure AS dc WHERE dc.descendent = d.deck_id) ASC,
+            d.deck_id ASC;
+    ", schema = MERGED_SCHEMA);
+
+    for deck in try!(get_merged_rows(db_conn, query_decks, false)).iter() {
+
+        match try!(resolve(db_conn, "Decks", "deck_id", deck, options.strategy)) {
+            Resolution::Create => {
+
+                let parent: Option<i64> = match deck.parent {
+                    Some(parent) => deck_ids.get(&parent).map(|parent| *parent),
+                    None => options.parent
+                };
+
+                let deck_id: i64 = try!(create_deck(db_conn, &deck.name, &deck.description, parent));
+
+                try!(set_timestamps(db_conn, "Decks", "deck_id", deck_id, deck));
+
+                deck_ids.insert(deck.id, deck_id);
+                response.decks_created = response.decks_created + 1;
+            },
+            Resolution::Merge(true) => {
+
+                try!(update_named(db_conn, "Decks", "deck_id", deck));
+
+                deck_ids.insert(deck.id, deck.id);
+                response.decks_updated = response.decks_updated + 1;
+            },
+            Resolution::Merge(false) => {
+                deck_ids.insert(deck.id, deck.id);
+                response.decks_skipped = response.decks_skipped + 1;
+            }
+        }
+    }
+
+    /* cards */
+
+    let ref query_cards = format!("
+        SELECT
+            card_id, title, description, created_at, updated_at, deck, front, back
+        FROM {schema}.Cards
+        ORDER BY card_id ASC;
+    ", schema = MERGED_SCHEMA);
+
+    for card in try!(get_merged_rows(db_conn, query_cards, true)).iter() {
+
+        let deck_id: i64 = match card.parent.and_then(|deck| deck_ids.get(&deck)) {
+            Some(deck_id) => *deck_id,
+            None => {
+                return Err(ImportError::Invalid(format!("card #{} of the database has no deck", card.id)));
+            }
+        };
+
+        match try!(resolve(db_conn, "Cards", "card_id", card, options.strategy)) {
+            Resolution::Create => {
+
+                let card_id: i64 = try!(insert_card(db_conn, deck_id, &card.name, &card.description,
+                    &card.front, &card.back));
+
+                try!(set_timestamps(db_conn, "Cards", "card_id", card_id, card));
+
+                let num_history: i64 = try!(copy_history(db_conn, card.id, card_id, false));
+                response.history_merged = response.history_merged + num_history;
+
+                try!(copy_score(db_conn, card.id, card_id, false));
+
+                card_ids.insert(card.id, card_id);
+                response.cards_created = response.cards_created + 1;
+            },
+            Resolution::Merge(true) => {
+
+                try!(update_card(db_conn, card));
+
+                let num_history: i64 = try!(merge_score(db_conn, card.id));
+                response.history_merged = response.history_merged + num_history;
+
+                card_ids.insert(card.id, card.id);
+                response.cards_updated = response.cards_updated + 1;
+            },
+            Resolution::Merge(false) => {
+
+                // reviews don't update a card; so its score is merged even if its content isn't
+                if options.strategy == MergeStrategy::Newer {
+                    let num_history: i64 = try!(merge_score(db_conn, card.id));
+                    response.history_merged = response.history_merged + num_history;
+                }
+
+                card_ids.insert(card.id, card.id);
+                response.cards_skipped = response.cards_skipped + 1;
+            }
+        }
+    }
+
+    /* stashes */
+
+    let ref query_stashes = format!("
+        SELECT
+            stash_id, name, description, created_at, updated_at, NULL
+        FROM {schema}.Stashes
+        ORDER BY stash_id ASC;
+    ", schema = MERGED_SCHEMA);
+
+    for stash in try!(get_merged_rows(db_conn, query_stashes, false)).iter() {
+
+        match try!(resolve(db_conn, "Stashes", "stash_id", stash, options.strategy)) {
+            Resolution::Create => {
+
+                let stash_id: i64 = try!(create_stash(db_conn, stash));
+
+                stash_ids.insert(stash.id, stash_id);
+                response.stashes_created = response.stashes_created + 1;
+            },
+            Resolution::Merge(true) => {
+
+                try!(update_named(db_conn, "Stashes", "stash_id", stash));
+
+                stash_ids.insert(stash.id, stash.id);
+                response.stashes_updated = response.stashes_updated + 1;
+            },
+            Resolution::Merge(false) => {
+                stash_ids.insert(stash.id, stash.id);
+                response.stashes_skipped = response.stashes_skipped + 1;
+            }
+        }
+    }
+
+    // cards of a conflicting stash are added to the existing stash
+    for (stash, card, added_at) in try!(get_merged_stash_cards(db_conn)).into_iter() {
+
+        match (stash_ids.get(&stash), card_ids.get(&card)) {
+            (Some(stash_id), Some(card_id)) => {
+                try!(add_stash_card(db_conn, *stash_id, *card_id, added_at));
+            },
+            _ => {
+                return Err(ImportError::Invalid(format!("stash #{} of the database has an unknown card #{}", stash, card)));
+            }
+        }
+    }
+
+    match tx.commit() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("committing transaction"),
+            };
+            return Err(ImportError::Query(err));
+        },
+        _ => {/* commit successful */}
+    }
+
+    return Ok(response);
+}
+
+// resolve the row of the other database against the row of the same id of this database (if any)
+fn resolve(db_conn: &Connection, table: &str, id_column: &str, row: &MergedRow, strategy: MergeStrategy)
+    -> Result<Resolution, QueryError> {
+
+    if strategy == MergeStrategy::KeepBoth {
+        return Ok(Resolution::Create);
+    }
+
+    let ref query = format!("
+        SELECT created_at, updated_at FROM main.{table} WHERE {id_column} = :id;
+    ", table = table, id_column = id_column);
+
+    let params: &[(&str, &ToSql)] = &[
+        (":id", &row.id)
+    ];
+
+    let existing: Vec<(i64, i64)> = try!(query_rows(db_conn, query, params, |row| -> (i64, i64) {
+        return (row.get(0), row.get(1));
+    }));
+
+    let resolution: Resolution = match existing.first() {
+        Some(&(created_at, updated_at)) if created_at == row.created_at => {
+            Resolution::Merge(strategy == MergeStrategy::Newer && row.updated_at > updated_at)
+        },
+        _ => Resolution::Create
+    };
+
+    return Ok(resolution);
+}
+
+fn get_merged_rows(db_conn: &Connection, query: &str, is_card: bool) -> Result<Vec<MergedRow>, QueryError> {
+    return query_rows(db_conn, query, &[], |row| -> MergedRow {
+        return MergedRow {
+            id: row.get(0),
+            name: row.get(1),
+            description: row.get(2),
+            created_at: row.get(3),
+            updated_at: row.get(4),
+            parent: row.get(5),
+            front: if is_card { row.get(6) } else { String::new() },
+            back: if is_card { row.get(7) } else { String::new() }
+        };
+    });
+}
+
+fn get_merged_stash_cards(db_conn: &Connection) -> Result<Vec<(i64, i64, i64)>, QueryError> {
+
+    let ref query = format!("
+        SELECT stash, card, added_at FROM {schema}.StashCards ORDER BY stash ASC, card ASC;
+    ", schema = MERGED_SCHEMA);
+
+    return query_rows(db_conn, query, &[], |row| -> (i64, i64, i64) {
+        return (row.get(0), row.get(1), row.get(2));
+    });
+}
+
+fn has_table(db_conn: &Connection, table: &str) -> Result<bool, QueryError> {
+
+    let ref query = format!("
+        SELECT COUNT(1) FROM sqlite_master WHERE type = 'table' AND name = :table;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":table", &table)
+    ];
+
+    let counts: Vec<i64> = try!(query_rows(db_conn, query, params, |row| -> i64 {
+        return row.get(0);
+    }));
+
+    return Ok(counts.first().map(|count| *count > 0).unwrap_or(false));
+}
+
+// keep the timestamps of the imported row.
+// timestamps aren't watched by the update triggers; so they aren't overwritten.
+fn set_timestamps(db_conn: &Connection, table: &str, id_column: &str, id: i64, row: &MergedRow)
+    -> Result<(), QueryError> {
+
+    let ref query = format!("
+        UPDATE main.{table}
+        SET
+            created_at = :created_at,
+            updated_at = :updated_at
+        WHERE {id_column} = :id;
+    ", table = table, id_column = id_column);
+
+    let params: &[(&str, &ToSql)] = &[
+        (":created_at", &row.created_at),
+        (":updated_at", &row.updated_at),
+        (":id", &id)
+    ];
+
+    return execute(db_conn, query, params);
+}
+
+// update the name and description of a deck or stash
+fn update_named(db_conn: &Connection, table: &str, id_column: &str, row: &MergedRow) -> Result<(), QueryError> {
+
+    let ref query = format!("
+        UPDATE main.{table}
+        SET
+            name = :name,
+            description = :description
+        WHERE {id_column} = :id;
+    ", table = table, id_column = id_column);
+
+    let params: &[(&str, &ToSql)] = &[
+        (":name", &row.name),
+        (":description", &row.description),
+        (":id", &row.id)
+    ];
+
+    try!(execute(db_conn, query, params));
+
+    return set_timestamps(db_conn, table, id_column, row.id, row);
+}
+
+// update the content of the card; the card stays in its deck
+fn update_card(db_conn: &Connection, row: &MergedRow) -> Result<(), QueryError> {
+
+    let ref query = format!("
+        UPDATE main.Cards
+        SET
+            title = :title,
+            description = :description,
+            front = :front,
+            back = :back
+        WHERE card_id = :card_id;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":title", &row.name),
+        (":description", &row.description),
+        (":front", &row.front),
+        (":back", &row.back),
+        (":card_id", &row.id)
+    ];
+
+    try!(execute(db_conn, query, params));
+
+    return set_timestamps(db_conn, "Cards", "card_id", row.id, row);
+}
+
+fn create_stash(db_conn: &Connection, row: &MergedRow) -> Result<i64, QueryError> {
+
+    let ref query = format!("
+        INSERT INTO main.Stashes(name, description, created_at, updated_at)
+        VALUES (:name, :description, :created_at, :updated_at);
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":name", &row.name),
+        (":description", &row.description),
+        (":created_at", &row.created_at),
+        (":updated_at", &row.updated_at)
+    ];
+
+    try!(execute(db_conn, query, params));
+
+    return Ok(db_conn.last_insert_rowid());
+}
+
+fn add_stash_card(db_conn: &Connection, stash_id: i64, card_id: i64, added_at: i64) -> Result<(), QueryError> {
+
+    let ref query = format!("
+        INSERT OR IGNORE INTO main.StashCards(stash, card, added_at)
+        VALUES (:stash_id, :card_id, :added_at);
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":stash_id", &stash_id),
+        (":card_id", &card_id),
+        (":added_at", &added_at)
+    ];
+
+    return execute(db_conn, query, params);
+}
+
+// append score history of the card of the other database, and merge its score, if it was
+// reviewed after the card of the same id of this database.
+// returns the number of score history entries appended.
+fn merge_score(db_conn: &Connection, card_id: i64) -> Result<i64, QueryError> {
+
+    if !try!(is_reviewed_since(db_conn, card_id)) {
+        return Ok(0);
+    }
+
+    let num_history: i64 = try!(copy_history(db_conn, card_id, card_id, true));
+
+    try!(copy_score(db_conn, card_id, card_id, true));
+
+    return Ok(num_history);
+}
+
+// whether the card of the other database was reviewed after the card of the same id of this database
+fn is_reviewed_since(db_conn: &Connection, card_id: i64) -> Result<bool, QueryError> {
+
+    let ref query = format!("
+        SELECT
+            (SELECT reviewed_at FROM {schema}.CardsScore WHERE card = :card_id) >
+            (SELECT reviewed_at FROM main.CardsScore WHERE card = :card_id);
+    ", schema = MERGED_SCHEMA);
+
+    let params: &[(&str, &ToSql)] = &[
+        (":card_id", &card_id)
+    ];
+
+    let results: Vec<Option<bool>> = try!(query_rows(db_conn, query, params, |row| -> Option<bool> {
+        return row.get(0);
+    }));
+
+    return Ok(match results.first() {
+        Some(&Some(is_reviewed_since)) => is_reviewed_since,
+        _ => false
+    });
+}
+
+// update the score of the card from the score of the card of the other database.
+// the success/fail score is taken from the other database (it was reviewed since); if merged,
+// review counts and timestamps are the greater of both cards, otherwise they're taken as is.
+//
+// the score is copied rather than reviewed; so the snapshot taken by the update trigger is
+// dropped (the score history is copied separately).
+fn copy_score(db_conn: &Connection, merged_card_id: i64, card_id: i64, is_merged: bool) -> Result<(), QueryError> {
+
+    let merged_value = |column: &str| -> String {
+        return format!("(SELECT {column} FROM {schema}.CardsScore WHERE card = :merged_card_id)",
+            column = column, schema = MERGED_SCHEMA);
+    };
+
+    let greater_value = |column: &str| -> String {
+
+        if is_merged {
+            return format!("MAX({column}, {value})", column = column, value = merged_value(column));
+        }
+
+        return merged_value(column);
+    };
+
+    let ref query = format!("
+        UPDATE main.CardsScore
+        SET
+            changelog = {changelog},
+            success = {success},
+            fail = {fail},
+            times_reviewed = {times_reviewed},
+            times_seen = {times_seen},
+            seen_at = {seen_at},
+            reviewed_at = {reviewed_at}
+        WHERE card = :card_id
+        AND EXISTS (SELECT 1 FROM {schema}.CardsScore WHERE card = :merged_card_id);
+    ",
+        schema = MERGED_SCHEMA,
+        changelog = merged_value("changelog"),
+        success = merged_value("success"),
+        fail = merged_value("fail"),
+        times_reviewed = greater_value("times_reviewed"),
+        times_seen = greater_value("times_seen"),
+        seen_at = greater_value("seen_at"),
+        reviewed_at = greater_value("reviewed_at"));
+
+    let params: &[(&str, &ToSql)] = &[
+        (":card_id", &card_id),
+        (":merged_card_id", &merged_card_id)
+    ];
+
+    let num_updated: i32 = match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(num_updated) => num_updated
+    };
+
+    if num_updated <= 0 {
+        return Ok(());
+    }
+
+    let ref query_snapshot = format!("
+        DELETE FROM main.CardsScoreHistory
+        WHERE history_id = (SELECT MAX(history_id) FROM main.CardsScoreHistory WHERE card = :card_id);
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":card_id", &card_id)
+    ];
+
+    return execute(db_conn, query_snapshot, params);
+}
+
+// copy score history of the card of the other database; returns the number of entries copied.
+// if only_since, only entries after the latest entry of the card are copied.
+fn copy_history(db_conn: &Connection, merged_card_id: i64, card_id: i64, only_since: bool) -> Result<i64, QueryError> {
+
+    let since_cond: &str = if only_since {
+        "AND occurred_at > (SELECT COALESCE(MAX(occurred_at), 0) FROM main.CardsScoreHistory WHERE card = :card_id)"
+    } else {
+        ""
+    };
+
+    let ref query = format!("
+        INSERT INTO main.CardsScoreHistory(occurred_at, is_review_event, success, fail, total_success, total_fail, changelog, card)
+        SELECT occurred_at, is_review_event, success, fail, total_success, total_fail, changelog, :card_id
+        FROM {schema}.CardsScoreHistory
+        WHERE card = :merged_card_id
+        {since_cond}
+        ORDER BY history_id ASC;
+    ", schema = MERGED_SCHEMA, since_cond = since_cond);
+
+    let params: &[(&str, &ToSql)] = &[
+        (":card_id", &card_id),
+        (":merged_card_id", &merged_card_id)
+    ];
+
+    match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(num_copied) => {
+            return Ok(num_copied as i64);
+        }
+    };
+}
+
+fn query_rows<T, F>(db_conn: &Connection, query: &str, params: &[(&str, &ToSql)], f: F) -> Result<Vec<T>, QueryError>
+    where F: Fn(&SqliteRow) -> T {
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.to_string(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.to_string(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut rows: Vec<T> = vec![];
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.to_string(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                rows.push(f(&row));
+            }
+
+            return Ok(rows);
+        }
+    };
+}
+
+fn execute(db_conn: &Connection, query: &str, params: &[(&str, &ToSql)]) -> Result<(), QueryError> {
+
+    match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.to_string(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
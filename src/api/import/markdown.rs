@@ -0,0 +1,599 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, SqliteStatement};
+use rusqlite::types::ToSql;
+
+use ::database::QueryError;
+use ::api::import::{ImportError, create_deck, insert_card, derive_title};
+use ::api::export::markdown::{MarkdownFile, FrontMatterValue, DECK_FILE, MARKDOWN_EXTENSION, split_card_body};
+
+
+// reconcile a directory of markdown files (see export::markdown) back into a deck and its
+// descendents.
+//
+// decks and cards are matched on the id within their front-matter; only decks and cards
+// within the deck being imported into are matched.
+//
+// - matched decks and cards are updated if they've changed; a deck or card that was moved to
+//   another directory is moved to the directory's deck.
+// - directories and files that aren't matched are created as decks and cards; their
+//   front-matter is then updated with the new id. a directory without a _deck.md file is
+//   named after the directory.
+// - cards within the deck that have no file are reported as deleted; they aren't deleted.
+
+#[derive(Debug, RustcEncodable)]
+pub struct DeletedCard {
+    pub card_id: i64,
+    pub title: String
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct MarkdownImportResponse {
+    pub decks_created: i64,
+    pub decks_updated: i64,
+    pub cards_created: i64,
+    pub cards_updated: i64,
+    pub cards_unchanged: i64,
+
+    pub deleted: Vec<DeletedCard>
+}
+
+struct ExistingCard {
+    deck: i64,
+    title: String,
+    description: String,
+    front: String,
+    back: String
+}
+
+struct ExistingDeck {
+    parent: Option<i64>,
+    name: String,
+    description: String
+}
+
+struct Reconciler<'a> {
+    db_conn: &'a Connection,
+
+    decks: HashMap<i64, ExistingDeck>,
+    cards: HashMap<i64, ExistingCard>,
+
+    seen_decks: HashSet<i64>,
+    seen_cards: HashSet<i64>,
+
+    // front-matter of new decks and cards are updated with their id once committed
+    pending_ids: Vec<(PathBuf, MarkdownFile, i64)>,
+
+    response: MarkdownImportResponse
+}
+
+// reconcile dir into the deck.
+// caller should hold the db connection lock; the import is done within a single transaction.
+pub fn import_markdown(db_conn: &Connection, deck_id: i64, dir: &Path) -> Result<MarkdownImportResponse, ImportError> {
+
+    if !try!(fs::metadata(dir)).is_dir() {
+        return Err(ImportError::Invalid(format!("not a directory: {}", dir.display())));
+    }
+
+    let mut reconciler = Reconciler {
+        db_conn: db_conn,
+        decks: try!(get_decks(db_conn, deck_id)),
+        cards: try!(get_cards(db_conn, deck_id)),
+        seen_decks: HashSet::new(),
+        seen_cards: HashSet::new(),
+        pending_ids: vec![],
+        response: MarkdownImportResponse {
+            decks_created: 0,
+            decks_updated: 0,
+            cards_created: 0,
+            cards_updated: 0,
+            cards_unchanged: 0,
+            deleted: vec![]
+        }
+    };
+
+    let tx = match db_conn.transaction() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("creating transaction"),
+            };
+            return Err(ImportError::Query(err));
+        },
+        Ok(tx) => tx
+    };
+
+    try!(reconciler.reconcile_dir(dir, deck_id));
+
+    match tx.commit() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("committing transaction"),
+            };
+            return Err(ImportError::Query(err));
+        },
+        _ => {/* commit successful */}
+    }
+
+    for &mut (ref path, ref mut file, id) in reconciler.pending_ids.iter_mut() {
+        file.set("id", FrontMatterValue::Integer(id));
+        try!(file.write(path));
+    }
+
+    let mut deleted: Vec<DeletedCard> = vec![];
+
+    for (card_id, card) in reconciler.cards.iter() {
+        if !reconciler.seen_cards.contains(card_id) {
+            deleted.push(DeletedCard {
+                card_id: *card_id,
+                title: card.title.clone()
+            });
+        }
+    }
+
+    deleted.sort_by(|a, b| a.card_id.cmp(&b.card_id));
+
+    let mut response = reconciler.response;
+    response.deleted = deleted;
+
+    return Ok(response);
+}
+
+impl<'a> Reconciler<'a> {
+
+    fn reconcile_dir(&mut self, dir: &Path, deck_id: i64) -> Result<(), ImportError> {
+
+        let mut entries: Vec<PathBuf> = vec![];
+
+        for entry in try!(fs::read_dir(dir)) {
+            entries.push(try!(entry).path());
+        }
+
+        entries.sort();
+
+        for path in entries.iter() {
+
+            let name: String = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue
+            };
+
+            // skip hidden files and directories (e.g. .git)
+            if name.starts_with(".") || name == DECK_FILE {
+                continue;
+            }
+
+            if try!(fs::metadata(path)).is_dir() {
+
+                let child_deck_id: i64 = try!(self.reconcile_deck(path, &name, deck_id));
+
+                try!(self.reconcile_dir(path, child_deck_id));
+
+                continue;
+            }
+
+            let is_markdown: bool = match path.extension().and_then(|extension| extension.to_str()) {
+                Some(extension) => extension == MARKDOWN_EXTENSION,
+                None => false
+            };
+
+            if is_markdown {
+                try!(self.reconcile_card(path, deck_id));
+            }
+        }
+
+        return Ok(());
+    }
+
+    // returns id of the deck of the directory
+    fn reconcile_deck(&mut self, dir: &Path, dir_name: &str, parent: i64) -> Result<i64, ImportError> {
+
+        let deck_path: PathBuf = dir.join(DECK_FILE);
+
+        let deck_file: MarkdownFile = match fs::metadata(&deck_path) {
+            Ok(_) => try!(MarkdownFile::read(&deck_path)),
+            Err(_) => MarkdownFile {
+                front_matter: vec![
+                    (format!("name"), FrontMatterValue::Text(dir_name.to_string())),
+                    (format!("description"), FrontMatterValue::Text(String::new()))
+                ],
+                body: String::new()
+            }
+        };
+
+        let name: String = match deck_file.get_text("name") {
+            Some(ref name) if name.trim().len() > 0 => name.trim().to_string(),
+            _ => dir_name.to_string()
+        };
+
+        let description: String = deck_file.get_text("description").unwrap_or(String::new());
+
+        let matched: Option<i64> = match deck_file.get_integer("id") {
+            Some(deck_id) if self.decks.contains_key(&deck_id) && !self.seen_decks.contains(&deck_id) => Some(deck_id),
+            _ => None
+        };
+
+        let deck_id: i64 = match matched {
+            None => {
+
+                let deck_id: i64 = try!(create_deck(self.db_conn, &name, &description, Some(parent)));
+
+                self.response.decks_created = self.response.decks_created + 1;
+                self.pending_ids.push((deck_path, deck_file, deck_id));
+
+                deck_id
+            },
+            Some(deck_id) => {
+
+                let (has_changed, has_moved) = match self.decks.get(&deck_id) {
+                    Some(deck) => (deck.name != name || deck.description != description, deck.parent != Some(parent)),
+                    None => unreachable!()
+                };
+
+                if has_changed {
+                    try!(update_deck(self.db_conn, deck_id, &name, &description));
+                }
+
+                if has_moved {
+                    try!(move_deck(self.db_conn, deck_id, parent));
+                }
+
+                if has_changed || has_moved {
+                    self.response.decks_updated = self.response.decks_updated + 1;
+                }
+
+                deck_id
+            }
+        };
+
+        self.seen_decks.insert(deck_id);
+
+        return Ok(deck_id);
+    }
+
+    fn reconcile_card(&mut self, path: &Path, deck_id: i64) -> Result<(), ImportError> {
+
+        let card_file: MarkdownFile = try!(MarkdownFile::read(path));
+
+        let (front, back) = split_card_body(&card_file.body);
+
+        let title: String = match card_file.get_text("title") {
+            Some(ref title) if title.trim().len() > 0 => title.trim().to_string(),
+            _ => derive_title(&front, "")
+        };
+
+        if title.len() <= 0 {
+            return Err(ImportError::Invalid(format!("card has no title, and its front is empty: {}", path.display())));
+        }
+
+        let description: String = card_file.get_text("description").unwrap_or(String::new());
+
+        // files copied from another card are new cards
+        let matched: Option<i64> = match card_file.get_integer("id") {
+            Some(card_id) if self.cards.contains_key(&card_id) && !self.seen_cards.contains(&card_id) => Some(card_id),
+            _ => None
+        };
+
+        match matched {
+            None => {
+
+                let card_id: i64 = try!(insert_card(self.db_conn, deck_id, &title, &description, &front, &back));
+
+                self.response.cards_created = self.response.cards_created + 1;
+                self.pending_ids.push((path.to_path_buf(), card_file, card_id));
+                self.seen_cards.insert(card_id);
+            },
+            Some(card_id) => {
+
+                let has_changed: bool = match self.cards.get(&card_id) {
+                    Some(card) => {
+                        card.deck != deck_id ||
+                        card.title != title ||
+                        card.description != description ||
+                        card.front != front ||
+                        card.back != back
+                    },
+                    None => unreachable!()
+                };
+
+                if has_changed {
+                    try!(update_card(self.db_conn, card_id, deck_id, &title, &description, &front, &back));
+                    self.response.cards_updated = self.response.cards_updated + 1;
+                } else {
+                    self.response.cards_unchanged = self.response.cards_unchanged + 1;
+                }
+
+                self.seen_cards.insert(card_id);
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+fn update_deck(db_conn: &Connection, deck_id: i64, name: &str, description: &str) -> Result<(), QueryError> {
+
+    let ref query = format!("
+        UPDATE Decks
+        SET
+            name = :name,
+            description = :description
+        WHERE deck_id = :deck_id;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":name", &name),
+        (":description", &description),
+        (":deck_id", &deck_id)
+    ];
+
+    match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
+
+// move the deck (and its descendents) under parent; see DecksAPI::connect_decks
+fn move_deck(db_conn: &Connection, deck_id: i64, parent: i64) -> Result<(), QueryError> {
+
+    let ref query_delete = format!("
+        DELETE FROM DecksClosure
+
+        /* select all descendents of child */
+        WHERE descendent IN (
+            SELECT descendent
+            FROM DecksClosure
+            WHERE ancestor = $1
+        )
+        AND
+
+        /* select all ancestors of child but not child itself */
+        ancestor IN (
+            SELECT ancestor
+            FROM DecksClosure
+            WHERE descendent = $1
+            AND ancestor != descendent
+        )
+        AND descendent != ancestor;
+    ");
+
+    let params: &[&ToSql] = &[
+        &deck_id, // $1
+    ];
+
+    match db_conn.execute(query_delete, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_delete.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */},
+    }
+
+    let ref query_insert = format!("
+        INSERT OR IGNORE INTO DecksClosure(ancestor, descendent, depth)
+        SELECT p.ancestor, c.descendent, p.depth+c.depth+1
+            FROM DecksClosure AS p, DecksClosure AS c
+        WHERE
+            c.ancestor = $1
+            AND p.descendent = $2;
+    ");
+
+    let params: &[&ToSql] = &[
+        &deck_id, // $1
+        &parent, // $2
+    ];
+
+    match db_conn.execute(query_insert, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_insert.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */},
+    }
+
+    return Ok(());
+}
+
+fn update_card(db_conn: &Connection, card_id: i64, deck_id: i64, title: &str, description: &str, front: &str, back: &str)
+    -> Result<(), QueryError> {
+
+    let ref query = format!("
+        UPDATE Cards
+        SET
+            title = :title,
+            description = :description,
+            front = :front,
+            back = :back,
+            deck = :deck
+        WHERE card_id = :card_id;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":title", &title),
+        (":description", &description),
+        (":front", &front),
+        (":back", &back),
+        (":deck", &deck_id),
+        (":card_id", &card_id)
+    ];
+
+    match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
+
+// returns descendents of the deck (but not the deck itself)
+fn get_decks(db_conn: &Connection, deck_id: i64) -> Result<HashMap<i64, ExistingDeck>, QueryError> {
+
+    let ref query = format!("
+        SELECT
+            d.deck_id, d.name, d.description,
+            (SELECT p.ancestor FROM DecksClosure AS p WHERE p.descendent = d.deck_id AND p.depth = 1)
+        FROM DecksClosure AS dc
+
+        INNER JOIN Decks AS d
+        ON d.deck_id = dc.descendent
+
+        WHERE dc.ancestor = :deck_id AND dc.depth > 0;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":deck_id", &deck_id)
+    ];
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut decks: HashMap<i64, ExistingDeck> = HashMap::new();
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                decks.insert(row.get(0), ExistingDeck {
+                    name: row.get(1),
+                    description: row.get(2),
+                    parent: row.get(3)
+                });
+            }
+
+            return Ok(decks);
+        }
+    };
+}
+
+// returns cards within the deck and its descendents
+fn get_cards(db_conn: &Connection, deck_id: i64) -> Result<HashMap<i64, ExistingCard>, QueryError> {
+
+    let ref query = format!("
+        SELECT
+            c.card_id, c.deck, c.title, c.description, c.front, c.back
+        FROM DecksClosure AS dc
+
+        INNER JOIN Cards AS c
+        ON c.deck = dc.descendent
+
+        WHERE dc.ancestor = :deck_id;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":deck_id", &deck_id)
+    ];
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut cards: HashMap<i64, ExistingCard> = HashMap::new();
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                cards.insert(row.get(0), ExistingCard {
+                    deck: row.get(1),
+                    title: row.get(2),
+                    description: row.get(3),
+                    front: row.get(4),
+                    back: row.get(5)
+                });
+            }
+
+            return Ok(cards);
+        }
+    };
+}
@@ -0,0 +1,392 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+extern crate zip;
+extern crate csv;
+
+pub mod anki;
+pub mod delimited;
+pub mod dump;
+pub mod grokdb;
+pub mod markdown;
+pub mod notes;
+mod restify;
+
+use std::error;
+use std::fmt;
+use std::io;
+
+use rusqlite::{Connection, Error as SqliteError};
+use rusqlite::types::ToSql;
+use zip::result::ZipError;
+use csv::Error as CsvError;
+
+use ::database::QueryError;
+use ::api::export::strip_html;
+pub use self::restify::restify;
+
+
+// maximum number of characters of titles derived from a card's content
+pub static MAX_DERIVED_TITLE_LENGTH: usize = 80;
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    Zip(ZipError),
+    Csv(CsvError),
+    Sqlite(SqliteError),
+    Query(QueryError),
+
+    // imported file is malformed or unsupported
+    Invalid(String)
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            ImportError::Io(ref err) => write!(f, "{}", err),
+            ImportError::Zip(ref err) => write!(f, "{}", err),
+            ImportError::Csv(ref err) => write!(f, "{}", err),
+            ImportError::Sqlite(ref err) => write!(f, "{}", err),
+            ImportError::Query(ref err) => write!(f, "{}", err),
+            ImportError::Invalid(ref reason) => write!(f, "{}", reason),
+        };
+    }
+}
+
+impl error::Error for ImportError {
+    fn description(&self) -> &str {
+        return match *self {
+            ImportError::Io(ref err) => err.description(),
+            ImportError::Zip(ref err) => err.description(),
+            ImportError::Csv(ref err) => err.description(),
+            ImportError::Sqlite(ref err) => err.description(),
+            ImportError::Query(ref err) => err.description(),
+            ImportError::Invalid(ref reason) => reason,
+        };
+    }
+}
+
+impl From<io::Error> for ImportError {
+    fn from(err: io::Error) -> ImportError {
+        return ImportError::Io(err);
+    }
+}
+
+impl From<ZipError> for ImportError {
+    fn from(err: ZipError) -> ImportError {
+        return ImportError::Zip(err);
+    }
+}
+
+impl From<CsvError> for ImportError {
+    fn from(err: CsvError) -> ImportError {
+        return ImportError::Csv(err);
+    }
+}
+
+impl From<SqliteError> for ImportError {
+    fn from(err: SqliteError) -> ImportError {
+        return ImportError::Sqlite(err);
+    }
+}
+
+impl From<QueryError> for ImportError {
+    fn from(err: QueryError) -> ImportError {
+        return ImportError::Query(err);
+    }
+}
+
+impl ImportError {
+
+    // whether the error is caused by the imported file, rather than by the database
+    pub fn is_bad_input(&self) -> bool {
+        return match *self {
+            ImportError::Query(_) => false,
+            _ => true
+        };
+    }
+}
+
+/* helpers shared by importers */
+// caller should hold the db connection lock, and should be within a transaction.
+
+// find the deck of the given name directly under parent; or a root deck if no parent is given.
+// returns 0 if there is no such deck.
+pub fn find_deck(db_conn: &Connection, parent: Option<i64>, name: &str) -> Result<i64, QueryError> {
+
+    let ref query = match parent {
+        Some(_) => {
+            format!("
+                SELECT
+                    COALESCE(MIN(d.deck_id), 0)
+                FROM DecksClosure AS dc
+
+                INNER JOIN Decks AS d
+                ON d.deck_id = dc.descendent
+
+                WHERE
+                    dc.ancestor = :parent
+                AND
+                    dc.depth = 1
+                AND
+                    d.name = :name;
+            ")
+        },
+        None => {
+            format!("
+                SELECT
+                    COALESCE(MIN(d.deck_id), 0)
+                FROM Decks AS d
+                WHERE
+                    d.name = :name
+                AND
+                    NOT EXISTS (
+                        SELECT 1 FROM DecksClosure AS dc
+                        WHERE dc.descendent = d.deck_id AND dc.depth > 0
+                    );
+            ")
+        }
+    };
+
+    let parent_id: i64 = parent.unwrap_or(0);
+
+    let mut params: Vec<(&str, &ToSql)> = vec![
+        (":name", &name)
+    ];
+
+    if parent.is_some() {
+        params.push((":parent", &parent_id));
+    }
+
+    let maybe_deck_id = db_conn.query_row_named(query, &params[..], |row| -> i64 {
+        return row.get(0);
+    });
+
+    match maybe_deck_id {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(deck_id) => {
+            return Ok(deck_id);
+        }
+    };
+}
+
+// create a deck under parent (if given); returns id of the new deck
+pub fn create_deck(db_conn: &Connection, name: &str, description: &str, parent: Option<i64>) -> Result<i64, QueryError> {
+
+    let ref query = format!("
+        INSERT INTO Decks(name, description) VALUES (:name, :description);
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":name", &name),
+        (":description", &description)
+    ];
+
+    match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    let deck_id: i64 = db_conn.last_insert_rowid();
+
+    let parent: i64 = match parent {
+        None => {
+            return Ok(deck_id);
+        },
+        Some(parent) => parent
+    };
+
+    // make parent (and its ancestors) be ancestors of the new deck
+    let ref query_connect = format!("
+        INSERT OR IGNORE INTO DecksClosure(ancestor, descendent, depth)
+        SELECT ancestor, :deck_id, depth + 1
+            FROM DecksClosure
+        WHERE
+            descendent = :parent;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":deck_id", &deck_id),
+        (":parent", &parent)
+    ];
+
+    match db_conn.execute_named(query_connect, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_connect.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(deck_id);
+}
+
+// find the deck at the end of path (deck names from outermost to innermost) under parent,
+// creating any missing decks along the way.
+// returns the deck id, and the number of decks created.
+pub fn find_or_create_deck_path(db_conn: &Connection, parent: Option<i64>, path: &[String])
+    -> Result<(i64, i64), QueryError> {
+
+    let mut current: Option<i64> = parent;
+    let mut num_created: i64 = 0;
+
+    for name in path.iter() {
+
+        let name = name.trim();
+
+        if name.len() <= 0 {
+            continue;
+        }
+
+        let deck_id: i64 = match try!(find_deck(db_conn, current, name)) {
+            0 => {
+                num_created = num_created + 1;
+                try!(create_deck(db_conn, name, "", current))
+            },
+            deck_id => deck_id
+        };
+
+        current = Some(deck_id);
+    }
+
+    return match current {
+        Some(deck_id) => Ok((deck_id, num_created)),
+        None => unreachable!() // path should have at least one non-empty deck name, or parent given
+    };
+}
+
+// returns id of the new card
+pub fn insert_card(db_conn: &Connection, deck_id: i64, title: &str, description: &str, front: &str, back: &str)
+    -> Result<i64, QueryError> {
+
+    let ref query = format!("
+        INSERT INTO Cards(title, description, front, back, deck)
+        VALUES (:title, :description, :front, :back, :deck);
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":title", &title),
+        (":description", &description),
+        (":front", &front),
+        (":back", &back),
+        (":deck", &deck_id)
+    ];
+
+    match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(db_conn.last_insert_rowid());
+}
+
+// seed the score of an imported card from its review history elsewhere.
+// the card is marked as last reviewed at reviewed_at (unix timestamp).
+pub fn seed_card_score(db_conn: &Connection, card_id: i64, success: i64, fail: i64, reviewed_at: i64,
+    changelog: &str) -> Result<(), QueryError> {
+
+    // times and timestamps are updated separately from success and fail, so that the entry
+    // in CardsScoreHistory isn't taken as a review event
+
+    let ref query_times = format!("
+        UPDATE CardsScore
+        SET
+            times_reviewed = :times_reviewed,
+            times_seen = :times_reviewed,
+            seen_at = :reviewed_at,
+            reviewed_at = :reviewed_at
+        WHERE card = :card_id;
+    ");
+
+    let times_reviewed: i64 = success + fail;
+
+    let params: &[(&str, &ToSql)] = &[
+        (":times_reviewed", &times_reviewed),
+        (":reviewed_at", &reviewed_at),
+        (":card_id", &card_id)
+    ];
+
+    match db_conn.execute_named(query_times, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_times.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    let ref query_score = format!("
+        UPDATE CardsScore
+        SET
+            success = :success,
+            fail = :fail,
+            changelog = :changelog
+        WHERE card = :card_id;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":success", &success),
+        (":fail", &fail),
+        (":changelog", &changelog),
+        (":card_id", &card_id)
+    ];
+
+    match db_conn.execute_named(query_score, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_score.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
+
+// derive a card title from its content; html tags are stripped and whitespace is collapsed.
+// returns fallback if no title can be derived.
+pub fn derive_title(content: &str, fallback: &str) -> String {
+
+    // tags may separate words
+    let text: String = strip_html(content, " ");
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let text: String = words.join(" ");
+
+    if text.len() <= 0 {
+        return fallback.to_string();
+    }
+
+    if text.chars().count() <= MAX_DERIVED_TITLE_LENGTH {
+        return text;
+    }
+
+    let truncated: String = text.chars().take(MAX_DERIVED_TITLE_LENGTH - 3).collect();
+
+    return format!("{}...", truncated.trim_right());
+}
@@ -0,0 +1,750 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+use std::io::Read;
+use std::ascii::AsciiExt;
+
+use rusqlite::{Connection, SqliteStatement};
+use rusqlite::types::ToSql;
+use rustc_serialize::json;
+
+use ::database::QueryError;
+use ::api::import::{ImportError, find_or_create_deck_path, insert_card, derive_title};
+
+
+// import of plain question and answer notes; either as text files or as Mnemosyne XML exports.
+//
+// text files (SuperMemo style) hold a note per Q: line; an A: line starts its answer, and an
+// optional T: line gives its title. lines without a prefix continue the preceding field.
+//
+//     T: Capital of France
+//     Q: What is the capital of France?
+//     A: Paris
+//
+//     Q: What is 2 + 2?
+//     A: 4
+//
+// Mnemosyne (1.x) XML exports hold a note per <item> element, of which <Q> is the question and
+// <A> is the answer; an item's <cat> category is imported as a deck within the deck imported into.
+//
+// notes without a title are titled after their question.
+//
+// a note is a duplicate if a card with the same question already exists; existing cards are looked
+// up through the full-text search index. duplicates are reported, and are skipped unless asked
+// otherwise.
+//
+// the whole file is imported within a single transaction; if any note is rejected, nothing is
+// imported. in a dry run, the notes that would be rejected or are duplicates are reported, and
+// nothing is imported.
+
+// the category of Mnemosyne items without a category
+static MNEMOSYNE_DEFAULT_CATEGORY: &'static str = "<default>";
+
+#[derive(Debug, Clone, Copy)]
+pub enum NotesFormat {
+    QA,
+    Mnemosyne
+}
+
+impl NotesFormat {
+
+    pub fn parse(format: &str) -> Option<NotesFormat> {
+        return match format.trim().to_lowercase().as_ref() {
+            "qa" | "txt" => Some(NotesFormat::QA),
+            "mnemosyne" | "xml" => Some(NotesFormat::Mnemosyne),
+            _ => None
+        };
+    }
+}
+
+pub struct NotesImportOptions {
+    pub format: NotesFormat,
+
+    // duplicates are reported regardless
+    pub skip_duplicates: bool,
+
+    pub dry_run: bool
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct RejectedNote {
+    // line number within the file, starting at 1
+    line: i64,
+    reason: String
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct DuplicateNote {
+    // line number within the file, starting at 1
+    line: i64,
+    title: String,
+
+    // existing cards with the same question
+    cards: Vec<i64>
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct NotesImportResponse {
+    dry_run: bool,
+
+    // cards and decks were created
+    imported: bool,
+
+    notes: i64,
+    cards_created: i64,
+    decks_created: i64,
+
+    duplicates: Vec<DuplicateNote>,
+    rejected: Vec<RejectedNote>
+}
+
+impl NotesImportResponse {
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+
+    pub fn has_rejected(&self) -> bool {
+        return self.rejected.len() > 0;
+    }
+}
+
+struct Note {
+    line: i64,
+    title: String,
+    front: String,
+    back: String,
+    category: Option<String>
+}
+
+// import the file into the deck.
+// caller should hold the db connection lock; the import is done within a single transaction.
+pub fn import_notes<R: Read>(db_conn: &Connection, deck_id: i64, reader: R, options: &NotesImportOptions)
+    -> Result<NotesImportResponse, ImportError> {
+
+    let mut reader = reader;
+    let mut content: String = String::new();
+
+    try!(reader.read_to_string(&mut content));
+
+    // notepad prepends a byte order mark to utf-8 files
+    let content: &str = content.trim_left_matches('\u{feff}');
+
+    let notes: Vec<Note> = match options.format {
+        NotesFormat::QA => parse_qa(content),
+        NotesFormat::Mnemosyne => try!(parse_mnemosyne(content))
+    };
+
+    let mut response = NotesImportResponse {
+        dry_run: options.dry_run,
+        imported: false,
+        notes: notes.len() as i64,
+        cards_created: 0,
+        decks_created: 0,
+        duplicates: vec![],
+        rejected: vec![]
+    };
+
+    let tx = match db_conn.transaction() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("creating transaction"),
+            };
+            return Err(ImportError::Query(err));
+        },
+        Ok(tx) => tx
+    };
+
+    for note in notes.iter() {
+
+        if note.front.len() <= 0 {
+            response.rejected.push(RejectedNote {
+                line: note.line,
+                reason: format!("note has no question")
+            });
+            continue;
+        }
+
+        let title: String = if note.title.len() > 0 {
+            note.title.clone()
+        } else {
+            derive_title(&note.front, "")
+        };
+
+        if title.len() <= 0 {
+            response.rejected.push(RejectedNote {
+                line: note.line,
+                reason: format!("card title can't be derived from the question")
+            });
+            continue;
+        }
+
+        // imported notes are indexed as they're inserted; so duplicates within the file are found too
+        let duplicates: Vec<i64> = try!(find_duplicates(db_conn, &note.front));
+
+        if duplicates.len() > 0 {
+
+            response.duplicates.push(DuplicateNote {
+                line: note.line,
+                title: title.clone(),
+                cards: duplicates
+            });
+
+            if options.skip_duplicates {
+                continue;
+            }
+        }
+
+        if response.has_rejected() {
+            // nothing is imported; keep looking for notes to reject
+            continue;
+        }
+
+        let card_deck_id: i64 = match note.category {
+            Some(ref category) => {
+                let path: Vec<String> = vec![category.clone()];
+                let (card_deck_id, num_created) = try!(find_or_create_deck_path(db_conn, Some(deck_id), &path[..]));
+                response.decks_created = response.decks_created + num_created;
+                card_deck_id
+            },
+            None => deck_id
+        };
+
+        try!(insert_card(db_conn, card_deck_id, &title, "", &note.front, &note.back));
+
+        response.cards_created = response.cards_created + 1;
+    }
+
+    if response.has_rejected() {
+        // rolled back once dropped
+        response.cards_created = 0;
+        response.decks_created = 0;
+        return Ok(response);
+    }
+
+    if options.dry_run {
+        // rolled back once dropped; report what would have been created
+        return Ok(response);
+    }
+
+    match tx.commit() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("committing transaction"),
+            };
+            return Err(ImportError::Query(err));
+        },
+        _ => {/* commit successful */}
+    }
+
+    response.imported = true;
+
+    return Ok(response);
+}
+
+/* parsers */
+
+#[derive(PartialEq)]
+enum QAField {
+    None,
+    Title,
+    Front,
+    Back
+}
+
+fn parse_qa(content: &str) -> Vec<Note> {
+
+    let mut notes: Vec<Note> = vec![];
+
+    // title given before the question of the next note
+    let mut pending_title: Option<String> = None;
+
+    let mut field = QAField::None;
+
+    for (index, line) in content.lines().enumerate() {
+
+        let line_number: i64 = (index as i64) + 1;
+
+        let (prefix, rest): (Option<char>, &str) = split_prefix(line);
+
+        match prefix {
+            Some('T') => {
+                pending_title = Some(rest.trim().to_string());
+                field = QAField::Title;
+            },
+            Some('Q') => {
+                notes.push(Note {
+                    line: line_number,
+                    title: pending_title.take().unwrap_or(String::new()),
+                    front: rest.to_string(),
+                    back: String::new(),
+                    category: None
+                });
+                field = QAField::Front;
+            },
+            Some('A') if field == QAField::Front || field == QAField::Back => {
+                let note: &mut Note = notes.last_mut().unwrap();
+
+                if field == QAField::Back {
+                    note.back.push_str("\n");
+                }
+
+                note.back.push_str(rest);
+                field = QAField::Back;
+            },
+            _ => {
+                // continuation of the preceding field; an answer before any question is ignored
+                match field {
+                    QAField::Front => {
+                        let note: &mut Note = notes.last_mut().unwrap();
+                        note.front.push_str("\n");
+                        note.front.push_str(line);
+                    },
+                    QAField::Back => {
+                        let note: &mut Note = notes.last_mut().unwrap();
+                        note.back.push_str("\n");
+                        note.back.push_str(line);
+                    },
+                    QAField::Title | QAField::None => {/* skip */}
+                }
+            }
+        }
+    }
+
+    for note in notes.iter_mut() {
+        note.front = note.front.trim().to_string();
+        note.back = note.back.trim().to_string();
+    }
+
+    return notes;
+}
+
+// split a line into its (uppercased) T:, Q:, or A: prefix and the rest of the line
+fn split_prefix(line: &str) -> (Option<char>, &str) {
+
+    let mut chars = line.chars();
+
+    let prefix: char = match (chars.next(), chars.next()) {
+        (Some(prefix), Some(':')) => prefix.to_ascii_uppercase(),
+        _ => {
+            return (None, line);
+        }
+    };
+
+    return match prefix {
+        'T' | 'Q' | 'A' => (Some(prefix), line[2..].trim_left()),
+        _ => (None, line)
+    };
+}
+
+fn parse_mnemosyne(content: &str) -> Result<Vec<Note>, ImportError> {
+
+    if !content.contains("<mnemosyne") {
+
+        if content.contains("<openSM2sync") {
+            return Err(ImportError::Invalid(
+                format!("Mnemosyne 2 exports aren't supported; export as Mnemosyne 1.x XML instead")));
+        }
+
+        return Err(ImportError::Invalid(format!("not a Mnemosyne XML export")));
+    }
+
+    let mut notes: Vec<Note> = vec![];
+    let mut offset: usize = 0;
+
+    loop {
+
+        let start: usize = match find_tag(content, offset, "item") {
+            Some(start) => start,
+            None => {
+                break;
+            }
+        };
+
+        let line: i64 = (content[..start].matches('\n').count() as i64) + 1;
+
+        let end: usize = match content[start..].find("</item>") {
+            Some(end) => start + end,
+            None => {
+                return Err(ImportError::Invalid(format!("line {}: item isn't closed", line)));
+            }
+        };
+
+        let item: &str = &content[start..end];
+
+        let category: Option<String> = match element_text(item, "cat") {
+            Some(ref category) if category.len() > 0 && category != MNEMOSYNE_DEFAULT_CATEGORY => {
+                Some(category.clone())
+            },
+            _ => None
+        };
+
+        notes.push(Note {
+            line: line,
+            title: String::new(),
+            front: element_text(item, "Q").unwrap_or(String::new()),
+            back: element_text(item, "A").unwrap_or(String::new()),
+            category: category
+        });
+
+        offset = end;
+    }
+
+    return Ok(notes);
+}
+
+// returns the offset of the next opening tag of the element, from offset
+fn find_tag(content: &str, offset: usize, name: &str) -> Option<usize> {
+
+    let opening: String = format!("<{}", name);
+
+    let mut offset: usize = offset;
+
+    while let Some(start) = content[offset..].find(&opening[..]) {
+
+        let start: usize = offset + start;
+        let after: usize = start + opening.len();
+
+        // e.g. <item> or <item id="..."> but not <items>
+        match content[after..].chars().next() {
+            Some('>') | Some(' ') | Some('\t') | Some('\r') | Some('\n') => {
+                return Some(start);
+            },
+            _ => {
+                offset = after;
+            }
+        }
+    }
+
+    return None;
+}
+
+// returns the trimmed and unescaped text of the first element of the given name
+fn element_text(content: &str, name: &str) -> Option<String> {
+
+    let start: usize = match find_tag(content, 0, name) {
+        Some(start) => start,
+        None => {
+            return None;
+        }
+    };
+
+    let text_start: usize = match content[start..].find('>') {
+        Some(end) => start + end + 1,
+        None => {
+            return None;
+        }
+    };
+
+    // self-closing element
+    if content[..text_start].ends_with("/>") {
+        return Some(String::new());
+    }
+
+    let closing: String = format!("</{}>", name);
+
+    let text_end: usize = match content[text_start..].find(&closing[..]) {
+        Some(end) => text_start + end,
+        None => {
+            return None;
+        }
+    };
+
+    return Some(unescape_xml(content[text_start..text_end].trim()));
+}
+
+fn unescape_xml(text: &str) -> String {
+
+    let mut unescaped: String = String::new();
+    let mut rest: &str = text;
+
+    while let Some(start) = rest.find('&') {
+
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity: Option<(char, usize)> = match rest.find(';') {
+            Some(end) => {
+                let decoded: Option<char> = match &rest[1..end] {
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "amp" => Some('&'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    reference if reference.starts_with("#x") => {
+                        u32::from_str_radix(&reference[2..], 16).ok().and_then(::std::char::from_u32)
+                    },
+                    reference if reference.starts_with("#") => {
+                        reference[1..].parse::<u32>().ok().and_then(::std::char::from_u32)
+                    },
+                    _ => None
+                };
+                decoded.map(|decoded| (decoded, end + 1))
+            },
+            None => None
+        };
+
+        match entity {
+            Some((decoded, length)) => {
+                unescaped.push(decoded);
+                rest = &rest[length..];
+            },
+            None => {
+                // not an entity; keep as is
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    unescaped.push_str(rest);
+
+    return unescaped;
+}
+
+/* duplicates */
+
+// words of the text, lowercased; html tags and punctuation are skipped
+fn words_of(text: &str) -> Vec<String> {
+
+    let mut plain: String = String::new();
+    let mut in_tag: bool = false;
+
+    for c in text.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+            },
+            '>' if in_tag => {
+                in_tag = false;
+                plain.push(' ');
+            },
+            _ if in_tag => {/* skip */},
+            _ if c.is_alphanumeric() => {
+                plain.extend(c.to_lowercase());
+            },
+            _ => {
+                plain.push(' ');
+            }
+        }
+    }
+
+    return plain.split_whitespace().map(|word| word.to_string()).collect();
+}
+
+// returns ids of cards whose front has the same words as the given front
+fn find_duplicates(db_conn: &Connection, front: &str) -> Result<Vec<i64>, QueryError> {
+
+    let words: Vec<String> = words_of(front);
+
+    if words.len() <= 0 {
+        return Ok(vec![]);
+    }
+
+    // phrase query on the front column; exact matches are filtered below
+    let search_query: String = format!("front:\"{}\"", words.join(" "));
+
+    let ref query = format!("
+        SELECT
+            c.card_id, c.front
+        FROM CardsFTS
+
+        INNER JOIN Cards AS c
+        ON CardsFTS.docid = c.card_id
+
+        WHERE CardsFTS MATCH :search_query
+        ORDER BY c.card_id ASC;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":search_query", &search_query)
+    ];
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut duplicates: Vec<i64> = vec![];
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                let card_id: i64 = row.get(0);
+                let existing_front: String = row.get(1);
+
+                if words_of(&existing_front) == words {
+                    duplicates.push(card_id);
+                }
+            }
+
+            return Ok(duplicates);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{parse_qa, split_prefix, parse_mnemosyne, unescape_xml};
+    use ::api::import::ImportError;
+
+    #[test]
+    fn split_prefix_of_field_lines() {
+        assert_eq!(split_prefix("Q: What is 2 + 2?"), (Some('Q'), "What is 2 + 2?"));
+        assert_eq!(split_prefix("A:4"), (Some('A'), "4"));
+        assert_eq!(split_prefix("t:   Capital"), (Some('T'), "Capital"));
+    }
+
+    #[test]
+    fn split_prefix_of_other_lines() {
+        assert_eq!(split_prefix("X: unknown"), (None, "X: unknown"));
+        assert_eq!(split_prefix("QA: two letters"), (None, "QA: two letters"));
+        assert_eq!(split_prefix("Q"), (None, "Q"));
+        assert_eq!(split_prefix(""), (None, ""));
+    }
+
+    #[test]
+    fn parse_qa_notes() {
+
+        let content = "T: Capital of France\nQ: What is the capital of France?\nA: Paris\n\nQ: What is 2 + 2?\nA: 4\n";
+
+        let notes = parse_qa(content);
+
+        assert_eq!(notes.len(), 2);
+
+        assert_eq!(notes[0].line, 2);
+        assert_eq!(notes[0].title, "Capital of France");
+        assert_eq!(notes[0].front, "What is the capital of France?");
+        assert_eq!(notes[0].back, "Paris");
+
+        assert_eq!(notes[1].line, 5);
+        assert_eq!(notes[1].title, "");
+        assert_eq!(notes[1].front, "What is 2 + 2?");
+        assert_eq!(notes[1].back, "4");
+    }
+
+    #[test]
+    fn parse_qa_continues_fields() {
+
+        let content = "q: first line\nsecond line\na: one\nA: two\nthree\n";
+
+        let notes = parse_qa(content);
+
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].front, "first line\nsecond line");
+        assert_eq!(notes[0].back, "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn parse_qa_ignores_answers_without_questions() {
+
+        let content = "A: orphan\nT: title\nA: also orphan\nQ: question\nA: answer";
+
+        let notes = parse_qa(content);
+
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].title, "title");
+        assert_eq!(notes[0].front, "question");
+        assert_eq!(notes[0].back, "answer");
+    }
+
+    #[test]
+    fn parse_mnemosyne_items() {
+
+        let content = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <mnemosyne core_version=\"1\">\n\
+            <category active=\"1\"><name>&lt;default&gt;</name></category>\n\
+            <item id=\"1\" gr=\"0\"><cat>&lt;default&gt;</cat><Q>2 &amp; 2</Q><A>4</A></item>\n\
+            <item id=\"2\"><cat>Geography</cat>\n\
+            <Q> Capital of France? </Q>\n\
+            <A/>\n\
+            </item>\n\
+            </mnemosyne>\n";
+
+        let notes = parse_mnemosyne(content).unwrap();
+
+        assert_eq!(notes.len(), 2);
+
+        assert_eq!(notes[0].line, 4);
+        assert_eq!(notes[0].front, "2 & 2");
+        assert_eq!(notes[0].back, "4");
+        assert_eq!(notes[0].category, None);
+
+        assert_eq!(notes[1].line, 5);
+        assert_eq!(notes[1].front, "Capital of France?");
+        assert_eq!(notes[1].back, "");
+        assert_eq!(notes[1].category, Some("Geography".to_string()));
+    }
+
+    #[test]
+    fn parse_mnemosyne_rejects_other_documents() {
+
+        match parse_mnemosyne("<openSM2sync number_of_entries=\"1\"></openSM2sync>") {
+            Err(ImportError::Invalid(_)) => {},
+            _ => panic!("expected Mnemosyne 2 exports to be rejected")
+        }
+
+        match parse_mnemosyne("<html></html>") {
+            Err(ImportError::Invalid(_)) => {},
+            _ => panic!("expected other documents to be rejected")
+        }
+    }
+
+    #[test]
+    fn parse_mnemosyne_rejects_unclosed_items() {
+
+        match parse_mnemosyne("<mnemosyne>\n<item><Q>q</Q><A>a</A>\n</mnemosyne>") {
+            Err(ImportError::Invalid(_)) => {},
+            _ => panic!("expected unclosed items to be rejected")
+        }
+    }
+
+    #[test]
+    fn unescape_xml_entities() {
+        assert_eq!(unescape_xml("&lt;b&gt; &amp; &quot;x&quot; &apos;y&apos;"), "<b> & \"x\" 'y'");
+        assert_eq!(unescape_xml("&#65;&#x42;"), "AB");
+    }
+
+    #[test]
+    fn unescape_xml_keeps_other_ampersands() {
+        assert_eq!(unescape_xml("AT&T"), "AT&T");
+        assert_eq!(unescape_xml("&unknown;"), "&unknown;");
+        assert_eq!(unescape_xml("a & b;"), "a & b;");
+        assert_eq!(unescape_xml("&#xZZ;"), "&#xZZ;");
+    }
+}
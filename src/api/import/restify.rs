@@ -0,0 +1,680 @@
+extern crate iron;
+extern crate router;
+extern crate rustc_serialize;
+
+use iron::status;
+use iron::prelude::*;
+use iron::mime::Mime;
+use router::Router;
+use urlencoded::{UrlEncodedQuery, QueryMap, UrlDecodingError};
+
+use std::io::{Cursor, Read};
+use std::sync::Arc;
+use std::ops::Deref;
+use std::error::Error;
+
+use ::database::DB;
+use ::api::{GrokDB, ErrorResponse};
+use ::api::decks::restify::deck_exists;
+use ::api::import::ImportError;
+use ::api::import::anki::{import_apkg, AnkiImportOptions};
+use ::api::import::delimited::{import_delimited, DelimitedImportOptions, Column};
+use ::api::import::notes::{import_notes, NotesImportOptions, NotesFormat};
+use ::api::import::grokdb::{import_grokdb, GrokDBImportOptions, MergeStrategy};
+
+
+// attach import REST endpoints to given router
+pub fn restify(router: &mut Router, grokdb: GrokDB) {
+
+    let grokdb = Arc::new(grokdb);
+
+    // request body is the .apkg file.
+    // queries:
+    // - parent: deck id to import decks under (optional)
+    // - scores: seed card scores from the review log; true or false (default)
+    router.post("/import/anki", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let (parent, with_scores): (Option<i64>, bool) = match req.get_ref::<UrlEncodedQuery>() {
+
+                Ok(ref hashmap) => {
+
+                    let hashmap: &QueryMap = hashmap;
+
+                    let parent: Option<i64> = match get_query(hashmap, "parent") {
+                        None => None,
+                        Some(parent) => {
+                            match parent.parse::<u64>() {
+                                Ok(parent) => Some(parent as i64),
+                                Err(why) => {
+
+                                    let ref reason = format!("invalid parent query");
+                                    let res_code = status::BadRequest;
+
+                                    let err_response = ErrorResponse {
+                                        status: res_code,
+                                        developerMessage: why.description(),
+                                        userMessage: reason,
+                                    }.to_json();
+
+                                    return Ok(Response::with((res_code, err_response)));
+                                }
+                            }
+                        }
+                    };
+
+                    let with_scores: bool = get_bool_query(hashmap, "scores", false);
+
+                    (parent, with_scores)
+                },
+
+                Err(UrlDecodingError::EmptyQuery) => {
+                    (None, false)
+                },
+
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // if given parent deck, ensure it exists
+            if let Some(parent) = parent {
+                match deck_exists(grokdb, parent) {
+                    Err(response) => {
+                        return response;
+                    },
+                    _ => {/* deck exists; continue */}
+                }
+            }
+
+            let mut package: Vec<u8> = vec![];
+
+            match req.body.read_to_end(&mut package) {
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* package read */}
+            }
+
+            if package.len() <= 0 {
+
+                let reason = "no Anki package given";
+                let res_code = status::BadRequest;
+
+                let err_response = ErrorResponse {
+                    status: res_code,
+                    developerMessage: reason,
+                    userMessage: reason,
+                }.to_json();
+
+                return Ok(Response::with((res_code, err_response)));
+            }
+
+            // media files aren't extracted when importing over http
+            let ref options = AnkiImportOptions {
+                parent: parent,
+                with_scores: with_scores,
+                media_dir: None
+            };
+
+            let db_conn_guard = grokdb.decks.db.lock().unwrap();
+            let ref db_conn = *db_conn_guard;
+
+            match DB::prepare_query(db_conn) {
+                Err(why) => {
+                    return import_error_response(ImportError::Query(why));
+                },
+                _ => {/* continue */}
+            }
+
+            let response = match import_apkg(db_conn, Cursor::new(package), options) {
+                Err(why) => {
+                    return import_error_response(why);
+                },
+                Ok(response) => response
+            };
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response.to_json())));
+        }
+    });
+
+    // request body is the csv or tsv file.
+    // queries:
+    // - format: csv (default) or tsv
+    // - headers: first row is a header row; true (default) or false
+    // - title, description, front, back, deck_path: column index or header name (optional)
+    // - dry_run: report rejected rows without importing; true or false (default)
+    router.post("/decks/:deck_id/cards/import", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let options: DelimitedImportOptions = match req.get_ref::<UrlEncodedQuery>() {
+
+                Ok(ref hashmap) => {
+
+                    let hashmap: &QueryMap = hashmap;
+
+                    let delimiter: u8 = match get_query(hashmap, "format") {
+                        None => b',',
+                        Some(format) => {
+                            match format.to_lowercase().as_ref() {
+                                "csv" => b',',
+                                "tsv" => b'\t',
+                                _ => {
+
+                                    let ref reason = format!("invalid format query; expected csv or tsv");
+                                    let res_code = status::BadRequest;
+
+                                    let err_response = ErrorResponse {
+                                        status: res_code,
+                                        developerMessage: reason,
+                                        userMessage: reason,
+                                    }.to_json();
+
+                                    return Ok(Response::with((res_code, err_response)));
+                                }
+                            }
+                        }
+                    };
+
+                    DelimitedImportOptions {
+                        delimiter: delimiter,
+                        has_headers: get_bool_query(hashmap, "headers", true),
+                        title: get_query(hashmap, "title").map(|column| Column::parse(&column)),
+                        description: get_query(hashmap, "description").map(|column| Column::parse(&column)),
+                        front: get_query(hashmap, "front").map(|column| Column::parse(&column)),
+                        back: get_query(hashmap, "back").map(|column| Column::parse(&column)),
+                        deck_path: get_query(hashmap, "deck_path").map(|column| Column::parse(&column)),
+                        dry_run: get_bool_query(hashmap, "dry_run", false)
+                    }
+                },
+
+                Err(UrlDecodingError::EmptyQuery) => {
+                    DelimitedImportOptions {
+                        delimiter: b',',
+                        has_headers: true,
+                        title: None,
+                        description: None,
+                        front: None,
+                        back: None,
+                        deck_path: None,
+                        dry_run: false
+                    }
+                },
+
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // fetch and parse requested deck id
+
+            let deck_id: &str = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists; otherwise bail early
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
+            }
+
+            let mut file: Vec<u8> = vec![];
+
+            match req.body.read_to_end(&mut file) {
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* file read */}
+            }
+
+            let db_conn_guard = grokdb.decks.db.lock().unwrap();
+            let ref db_conn = *db_conn_guard;
+
+            match DB::prepare_query(db_conn) {
+                Err(why) => {
+                    return import_error_response(ImportError::Query(why));
+                },
+                _ => {/* continue */}
+            }
+
+            let response = match import_delimited(db_conn, deck_id, Cursor::new(file), &options) {
+                Err(why) => {
+                    return import_error_response(why);
+                },
+                Ok(response) => response
+            };
+
+            // rejected rows are reported along with the response
+            let res_code = if response.has_rejected() && !options.dry_run {
+                status::BadRequest
+            } else {
+                status::Ok
+            };
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, res_code, response.to_json())));
+        }
+    });
+
+    // request body is the Q/A text file or the Mnemosyne XML export.
+    // queries:
+    // - format: qa (default) or mnemosyne
+    // - skip_duplicates: skip notes whose question is already a card; true (default) or false
+    // - dry_run: report rejected and duplicate notes without importing; true or false (default)
+    router.post("/decks/:deck_id/cards/import/notes", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let options: NotesImportOptions = match req.get_ref::<UrlEncodedQuery>() {
+
+                Ok(ref hashmap) => {
+
+                    let hashmap: &QueryMap = hashmap;
+
+                    let format: NotesFormat = match get_query(hashmap, "format") {
+                        None => NotesFormat::QA,
+                        Some(format) => {
+                            match NotesFormat::parse(&format) {
+                                Some(format) => format,
+                                None => {
+
+                                    let ref reason = format!("invalid format query; expected qa or mnemosyne");
+                                    let res_code = status::BadRequest;
+
+                                    let err_response = ErrorResponse {
+                                        status: res_code,
+                                        developerMessage: reason,
+                                        userMessage: reason,
+                                    }.to_json();
+
+                                    return Ok(Response::with((res_code, err_response)));
+                                }
+                            }
+                        }
+                    };
+
+                    NotesImportOptions {
+                        format: format,
+                        skip_duplicates: get_bool_query(hashmap, "skip_duplicates", true),
+                        dry_run: get_bool_query(hashmap, "dry_run", false)
+                    }
+                },
+
+                Err(UrlDecodingError::EmptyQuery) => {
+                    NotesImportOptions {
+                        format: NotesFormat::QA,
+                        skip_duplicates: true,
+                        dry_run: false
+                    }
+                },
+
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // fetch and parse requested deck id
+
+            let deck_id: &str = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists; otherwise bail early
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
+            }
+
+            let mut file: Vec<u8> = vec![];
+
+            match req.body.read_to_end(&mut file) {
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* file read */}
+            }
+
+            let db_conn_guard = grokdb.decks.db.lock().unwrap();
+            let ref db_conn = *db_conn_guard;
+
+            match DB::prepare_query(db_conn) {
+                Err(why) => {
+                    return import_error_response(ImportError::Query(why));
+                },
+                _ => {/* continue */}
+            }
+
+            let response = match import_notes(db_conn, deck_id, Cursor::new(file), &options) {
+                Err(why) => {
+                    return import_error_response(why);
+                },
+                Ok(response) => response
+            };
+
+            // rejected notes are reported along with the response
+            let res_code = if response.has_rejected() && !options.dry_run {
+                status::BadRequest
+            } else {
+                status::Ok
+            };
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, res_code, response.to_json())));
+        }
+    });
+
+    // request body is the grokdb database (.db) file.
+    // queries:
+    // - parent: deck id to import root decks under (optional)
+    // - strategy: how decks, cards, and stashes that already exist are merged;
+    //   keep_both (default), newer, or skip
+    router.post("/import/grokdb", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let options: GrokDBImportOptions = match req.get_ref::<UrlEncodedQuery>() {
+
+                Ok(ref hashmap) => {
+
+                    let hashmap: &QueryMap = hashmap;
+
+                    let parent: Option<i64> = match get_query(hashmap, "parent") {
+                        None => None,
+                        Some(parent) => {
+                            match parent.parse::<u64>() {
+                                Ok(parent) => Some(parent as i64),
+                                Err(why) => {
+
+                                    let ref reason = format!("invalid parent query");
+                                    let res_code = status::BadRequest;
+
+                                    let err_response = ErrorResponse {
+                                        status: res_code,
+                                        developerMessage: why.description(),
+                                        userMessage: reason,
+                                    }.to_json();
+
+                                    return Ok(Response::with((res_code, err_response)));
+                                }
+                            }
+                        }
+                    };
+
+                    let strategy: MergeStrategy = match get_query(hashmap, "strategy") {
+                        None => MergeStrategy::KeepBoth,
+                        Some(strategy) => {
+                            match MergeStrategy::parse(&strategy) {
+                                Some(strategy) => strategy,
+                                None => {
+
+                                    let ref reason = format!("invalid strategy query; expected keep_both, newer, or skip");
+                                    let res_code = status::BadRequest;
+
+                                    let err_response = ErrorResponse {
+                                        status: res_code,
+                                        developerMessage: reason,
+                                        userMessage: reason,
+                                    }.to_json();
+
+                                    return Ok(Response::with((res_code, err_response)));
+                                }
+                            }
+                        }
+                    };
+
+                    GrokDBImportOptions {
+                        parent: parent,
+                        strategy: strategy
+                    }
+                },
+
+                Err(UrlDecodingError::EmptyQuery) => {
+                    GrokDBImportOptions {
+                        parent: None,
+                        strategy: MergeStrategy::KeepBoth
+                    }
+                },
+
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // if given parent deck, ensure it exists
+            if let Some(parent) = options.parent {
+                match deck_exists(grokdb, parent) {
+                    Err(response) => {
+                        return response;
+                    },
+                    _ => {/* deck exists; continue */}
+                }
+            }
+
+            let mut database: Vec<u8> = vec![];
+
+            match req.body.read_to_end(&mut database) {
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* database read */}
+            }
+
+            if database.len() <= 0 {
+
+                let reason = "no grokdb database given";
+                let res_code = status::BadRequest;
+
+                let err_response = ErrorResponse {
+                    status: res_code,
+                    developerMessage: reason,
+                    userMessage: reason,
+                }.to_json();
+
+                return Ok(Response::with((res_code, err_response)));
+            }
+
+            let db_conn_guard = grokdb.decks.db.lock().unwrap();
+            let ref db_conn = *db_conn_guard;
+
+            match DB::prepare_query(db_conn) {
+                Err(why) => {
+                    return import_error_response(ImportError::Query(why));
+                },
+                _ => {/* continue */}
+            }
+
+            let response = match import_grokdb(db_conn, Cursor::new(database), &options) {
+                Err(why) => {
+                    return import_error_response(why);
+                },
+                Ok(response) => response
+            };
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response.to_json())));
+        }
+    });
+}
+
+/* helpers */
+
+// returns the trimmed value of the query (if given)
+fn get_query(hashmap: &QueryMap, name: &str) -> Option<String> {
+
+    if !hashmap.contains_key(name) {
+        return None;
+    }
+
+    let values: &Vec<String> = hashmap.get(name).unwrap();
+
+    if values.len() <= 0 {
+        return None;
+    }
+
+    let value: String = values[0].trim().to_string();
+
+    if value.len() <= 0 {
+        return None;
+    }
+
+    return Some(value);
+}
+
+// returns default if the query isn't given
+fn get_bool_query(hashmap: &QueryMap, name: &str, default: bool) -> bool {
+    return match get_query(hashmap, name) {
+        None => default,
+        Some(value) => {
+            match value.to_lowercase().as_ref() {
+                "true" | "1" | "yes" => true,
+                _ => false
+            }
+        }
+    };
+}
+
+fn import_error_response(why: ImportError) -> IronResult<Response> {
+
+    let ref reason = format!("{:?}", why);
+
+    let res_code = if why.is_bad_input() {
+        status::BadRequest
+    } else {
+        status::InternalServerError
+    };
+
+    let ref user_message = format!("{}", why);
+
+    let err_response = ErrorResponse {
+        status: res_code,
+        developerMessage: reason,
+        userMessage: user_message,
+    }.to_json();
+
+    return Ok(Response::with((res_code, err_response)));
+}
@@ -6,9 +6,13 @@ extern crate rustc_serialize;
 pub mod decks;
 pub mod cards;
 pub mod stashes;
+pub mod tags;
 pub mod review;
 pub mod configs;
-mod backup;
+pub mod import;
+pub mod export;
+pub mod backup;
+mod tempfile;
 
 use iron::status;
 use router::Router;
@@ -19,6 +23,7 @@ use std::sync::Arc;
 use self::decks::DecksAPI;
 use self::cards::CardsAPI;
 use self::stashes::StashesAPI;
+use self::tags::TagsAPI;
 use self::review::ReviewAPI;
 use self::configs::ConfigsAPI;
 use super::database::{DB, BootstrapError};
@@ -84,19 +89,38 @@ pub struct GrokDB {
     pub base_db_name: String,
     pub backup_base_dest: Option<String>,
 
+    // backups requested over http may be written anywhere (see backup::sandbox)
+    pub unsafe_backup_paths: bool,
+
+    // default seed for choosing cards to review (see review::get_review_card)
+    pub review_seed: Option<u64>,
+
+    // schema version the database was migrated to on startup (if it was migrated)
+    pub migrated_to: Option<i64>,
+
     pub decks: DecksAPI,
     pub cards: CardsAPI,
     pub stashes: StashesAPI,
+    pub tags: TagsAPI,
     pub review: ReviewAPI,
     pub configs: ConfigsAPI,
 }
 
-pub fn new(database_name: String) -> Result<GrokDB, BootstrapError> {
+pub fn new(database_name: String, backup_base_dest: Option<String>) -> Result<GrokDB, BootstrapError> {
+
+    // open db connection and bootstrap it; the database is backed up before any migration
+    let db_conn: Result<(DB, Option<i64>), BootstrapError> = {
+        let backup_dir: String = match backup_base_dest {
+            Some(ref backup_base_dest) => format!("{}", backup_base_dest),
+            None => format!("./")
+        };
+
+        super::database::bootstrap(database_name.clone(), &backup_dir)
+    };
 
-    // open db connection and bootstrap it
-    let db_conn: Result<DB, BootstrapError> = super::database::bootstrap(database_name.clone());
+    let (db, migrated_to) = try!(db_conn);
 
-    let db: Arc<DB> = Arc::new(try!(db_conn));
+    let db: Arc<DB> = Arc::new(db);
 
     // fetch base db name
     let mut base_db_name: String = format!("{}", database_name);
@@ -111,7 +135,10 @@ pub fn new(database_name: String) -> Result<GrokDB, BootstrapError> {
 
     let api = GrokDB {
         base_db_name: base_db_name,
-        backup_base_dest: None,
+        backup_base_dest: backup_base_dest,
+        unsafe_backup_paths: false,
+        review_seed: None,
+        migrated_to: migrated_to,
 
         decks: DecksAPI {
             db: db.clone()
@@ -122,6 +149,9 @@ pub fn new(database_name: String) -> Result<GrokDB, BootstrapError> {
         stashes: StashesAPI {
             db: db.clone()
         },
+        tags: TagsAPI {
+            db: db.clone()
+        },
         review: ReviewAPI {
             db: db.clone()
         },
@@ -143,8 +173,14 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
 
     stashes::restify(router, grokdb.clone());
 
+    tags::restify(router, grokdb.clone());
+
     review::restify(router, grokdb.clone());
 
     configs::restify(router, grokdb.clone());
+
+    import::restify(router, grokdb.clone());
+
+    export::restify(router, grokdb.clone());
 }
 
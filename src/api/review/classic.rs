@@ -0,0 +1,271 @@
+use rand::{Rng, StdRng};
+
+use ::database::QueryError;
+use ::api::review::{ReviewableSelection, Scheduler};
+use ::api::review::settings::{get_review_settings, ReviewSettings};
+
+
+// the original grokdb scheduler.
+//
+// the next card is chosen by randomly picking one of the following methods:
+// - new cards
+// - cards old enough to be reviewed (sorted by rank score)
+// - least recently reviewed cards (purgatory)
+//
+// the probabilities of each method, and the cutoffs used by each method, are given
+// by the review settings of the selection.
+pub struct GrokdbClassic;
+
+impl Scheduler for GrokdbClassic {
+
+    fn name(&self) -> &'static str {
+        return "grokdb-classic";
+    }
+
+    fn choose_card(&self, selection: &ReviewableSelection, rng: &mut StdRng) -> Result<i64, QueryError> {
+
+        let settings: ReviewSettings = match get_review_settings(selection) {
+            Err(why) => {
+                return Err(why);
+            },
+            Ok(settings) => settings
+        };
+
+        // decide method for choosing the next card
+
+        let method = match choose_method(selection, &settings, rng) {
+            Err(why) => {
+                return Err(why);
+            },
+            Ok(method) => method
+        };
+
+        let card_id: i64 = match method {
+            Method::NewCards => {
+                match selection.get_new_card(0) {
+                    Err(why) => {
+                        return Err(why);
+                    },
+                    Ok(card_id) => card_id
+                }
+            },
+
+            Method::OldEnough => {
+
+                // randomly decide to discard low scoring cards
+                let min_score: f64 = {
+
+                    let cutoff = settings.min_score;
+
+                    let num_cards = match selection.number_of_reviewable_cards(settings.age_in_hours, cutoff) {
+                        Err(why) => {
+                            return Err(why);
+                        },
+                        Ok(num_cards) => num_cards
+                    };
+
+                    // flip a coin.
+                    if rng.gen_weighted_bool(2) && num_cards >= 2 {
+                        cutoff
+                    } else {
+                        0f64
+                    }
+                };
+
+                let num_cards: i64 = match selection.number_of_reviewable_cards(settings.age_in_hours, min_score) {
+                    Err(why) => {
+                        return Err(why);
+                    },
+                    Ok(num_cards) => num_cards
+                };
+
+                assert!(num_cards > 0);
+
+                let card_idx: i64 = match rng.gen_range(0f64, 1f64) {
+
+                    pin if pin < 0.3 => {
+                        // random card
+                        rng.gen_range(0, num_cards)
+                    },
+
+                    _ => { // 70% probability
+                        // Random card from top 50% of highest rank score
+                        let min_idx: i64 = ((num_cards as f64) / 2f64).ceil() as i64;
+                        assert!(min_idx > 0);
+                        rng.gen_range(0, min_idx)
+                    }
+                };
+
+                match selection.get_reviewable_card(settings.age_in_hours, min_score, card_idx) {
+                    Err(why) => {
+                        return Err(why);
+                    },
+                    Ok(card_id) => card_id
+                }
+            },
+
+            Method::LeastRecentlyReviewed => {
+
+                // calculate the purgatory size
+                let purgatory_size = {
+
+                    let num_cards: i64 = match selection.number_of_cards() {
+                        Err(why) => {
+                            return Err(why);
+                        },
+                        Ok(num_cards) => num_cards
+                    };
+
+                    (settings.purgatory_size * (num_cards as f64)).ceil() as i64
+                };
+
+                // randomly decide to discard low scoring cards
+                let min_score: f64 = {
+
+                    let cutoff = settings.min_score;
+
+                    let num_cards = match selection.number_of_old_cards(purgatory_size, cutoff, false) {
+                        Err(why) => {
+                            return Err(why);
+                        },
+                        Ok(num_cards) => num_cards
+                    };
+
+                    if rng.gen_weighted_bool(2) && num_cards >= 2 {
+                        cutoff
+                    } else {
+                        0f64
+                    }
+                };
+
+                let (card_idx, sort_by_score): (i64, bool) = match rng.gen_range(0f64, 1f64) {
+
+                    pin if pin < 0.2 => {
+                        // random card (20% prob)
+
+                        let num_cards = match selection.number_of_old_cards(purgatory_size, min_score, false) {
+                            Err(why) => {
+                                return Err(why);
+                            },
+                            Ok(num_cards) => num_cards
+                        };
+
+                        assert!(num_cards > 0);
+
+                        (rng.gen_range(0, num_cards), true)
+                    },
+
+                    pin if pin < (0.2 + 0.75) => {
+                        // Random card from top 50% of highest rank score (75% prob)
+
+                        let num_cards = match selection.number_of_old_cards(purgatory_size, min_score, true) {
+                            Err(why) => {
+                                return Err(why);
+                            },
+                            Ok(num_cards) => num_cards
+                        };
+
+                        let min_idx: i64 = ((num_cards as f64) / 2f64).ceil() as i64;
+
+                        assert!(min_idx > 0);
+
+                        (rng.gen_range(0, min_idx), true)
+                    },
+
+                    _ => { // Oldest card
+                        (0, false)
+                    }
+                };
+
+                match selection.get_old_card(purgatory_size, min_score, card_idx, sort_by_score) {
+                    Err(why) => {
+                        return Err(why);
+                    },
+                    Ok(card_id) => card_id
+                }
+            }
+        };
+
+        return Ok(card_id);
+    }
+}
+
+enum Method {
+    NewCards,
+    OldEnough, // old enough to be reviewed
+    LeastRecentlyReviewed,
+}
+
+fn choose_method(selection: &ReviewableSelection, settings: &ReviewSettings, rng: &mut StdRng) -> Result<Method, QueryError> {
+
+    let mut max_pin: f64 = 1f64;
+
+    // if there are no new cards, adjust pin to exclude 'new cards' method.
+    let has_new_cards: bool = match selection.has_new_cards() {
+        Err(why) => {
+            return Err(why);
+        },
+        Ok(false) => {
+            max_pin = max_pin - settings.new_cards;
+            false
+        },
+        Ok(true) => { true }
+    };
+
+    // check if there are cards that haven't been reviewed for at least age_in_hours
+    // and have a minimum score of 0.
+    //
+    // if there are no such cards that meet the above criteria, then exclude
+    // this method.
+    let has_reviewable_cards: bool = match selection.has_reviewable_cards(settings.age_in_hours, 0f64) {
+        Err(why) => {
+            return Err(why);
+        },
+        Ok(false) => {
+            max_pin = max_pin - settings.old_enough;
+            false
+        },
+        Ok(true) => { true }
+    };
+
+
+    let max_pin = max_pin;
+
+    // if there is only one method to choose from.
+    // faster code path.
+    if !has_new_cards && !has_reviewable_cards {
+        return Ok(Method::LeastRecentlyReviewed);
+    }
+
+    // invariant: max_pin > least_recent
+
+    assert!(max_pin > 0f64);
+
+    if !has_new_cards {
+
+        let method = match rng.gen_range(0f64, max_pin) {
+            pin if pin < settings.least_recent => Method::LeastRecentlyReviewed,
+            _ => Method::OldEnough
+        };
+
+        return Ok(method);
+    }
+
+    if !has_reviewable_cards {
+
+        let method = match rng.gen_range(0f64, max_pin) {
+            pin if pin < settings.least_recent => Method::LeastRecentlyReviewed,
+            _ => Method::NewCards
+        };
+
+        return Ok(method);
+    }
+
+    let method = match rng.gen_range(0f64, max_pin) {
+        pin if pin < settings.least_recent => Method::LeastRecentlyReviewed,
+        pin if pin < (settings.least_recent + settings.old_enough) => Method::OldEnough,
+        _ => Method::NewCards
+    };
+
+    return Ok(method);
+}
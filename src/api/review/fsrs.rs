@@ -0,0 +1,931 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+use rand::StdRng;
+use rusqlite::{Connection, SqliteStatement};
+use rusqlite::types::ToSql;
+use rustc_serialize::json;
+
+use ::database::{DB, QueryError, get_config};
+use ::api::review::{ReviewableSelection, Scheduler};
+use ::api::review::classic::GrokdbClassic;
+
+
+// FSRS (Free Spaced Repetition Scheduler) memory model.
+// src: https://github.com/open-spaced-repetition/fsrs4anki/wiki/The-Algorithm
+//
+// every reviewed card has a memory state (stability and difficulty), from which the
+// probability of recalling the card (retrievability) is predicted.
+//
+// cards whose retrievability fell below the desired retention are reviewed first
+// (least retrievable first); otherwise, the next card is chosen by the grokdb-classic
+// scheduler.
+pub struct FSRS;
+
+impl Scheduler for FSRS {
+
+    fn name(&self) -> &'static str {
+        return "fsrs";
+    }
+
+    fn choose_card(&self, selection: &ReviewableSelection, rng: &mut StdRng) -> Result<i64, QueryError> {
+
+        match selection.has_fading_cards(DEFAULT_DESIRED_RETENTION) {
+            Err(why) => {
+                return Err(why);
+            },
+            Ok(true) => {
+                return selection.get_fading_card(DEFAULT_DESIRED_RETENTION, 0);
+            },
+            Ok(false) => {
+                // no card is below desired retention
+            }
+        }
+
+        return GrokdbClassic.choose_card(selection, rng);
+    }
+}
+
+// FSRS v4.5 default weights
+pub static DEFAULT_WEIGHTS: [f64; 17] = [
+    0.4872, 1.4003, 3.7145, 13.8206,
+    5.1618, 1.2298, 0.8975, 0.031,
+    1.6474, 0.1367, 1.0461,
+    2.1072, 0.0793, 0.3246, 1.587,
+    0.2272, 2.8755
+];
+
+// lower and upper bounds of each weight when fitting
+static WEIGHT_BOUNDS: [(f64, f64); 17] = [
+    (0.1, 100.0), (0.1, 100.0), (0.1, 100.0), (0.1, 100.0),
+    (1.0, 10.0), (0.1, 5.0), (0.1, 5.0), (0.0, 0.75),
+    (0.0, 4.0), (0.0, 0.8), (0.01, 3.0),
+    (0.5, 5.0), (0.01, 0.2), (0.01, 0.9), (0.01, 2.0),
+    (0.0, 1.0), (1.0, 6.0)
+];
+
+pub static DEFAULT_DESIRED_RETENTION: f64 = 0.9;
+
+// config setting of the fitted weights (stored as JSON array)
+pub static WEIGHTS_CONFIG: &'static str = "fsrs_weights";
+
+static DECAY: f64 = -0.5;
+// chosen such that retrievability is 90% when elapsed time is equal to stability:
+// FACTOR = 0.9^(1 / DECAY) - 1
+static FACTOR: f64 = 19.0 / 81.0;
+
+static MIN_DIFFICULTY: f64 = 1.0;
+static MAX_DIFFICULTY: f64 = 10.0;
+static MIN_STABILITY: f64 = 0.1;
+
+// ratings
+pub static AGAIN: i64 = 1;
+pub static HARD: i64 = 2;
+pub static GOOD: i64 = 3;
+pub static EASY: i64 = 4;
+
+// map a grade (0 to 5; see sm2) onto a FSRS rating
+pub fn rating_from_grade(grade: i64) -> i64 {
+    return match grade {
+        0 | 1 | 2 => AGAIN,
+        3 => HARD,
+        4 => GOOD,
+        _ => EASY
+    };
+}
+
+// probability of recalling a card with the given stability after elapsed_days
+pub fn retrievability(elapsed_days: f64, stability: f64) -> f64 {
+
+    let elapsed_days: f64 = if elapsed_days < 0f64 {
+        0f64
+    } else {
+        elapsed_days
+    };
+
+    return (1f64 + FACTOR * elapsed_days / stability).powf(DECAY);
+}
+
+// number of days after which retrievability drops to desired_retention
+pub fn next_interval(stability: f64, desired_retention: f64) -> f64 {
+    return stability / FACTOR * (desired_retention.powf(1f64 / DECAY) - 1f64);
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryState {
+    pub stability: f64,
+    pub difficulty: f64
+}
+
+pub struct Model {
+    weights: Vec<f64>
+}
+
+impl Model {
+
+    pub fn new(weights: Vec<f64>) -> Model {
+        assert!(weights.len() == DEFAULT_WEIGHTS.len());
+        return Model {
+            weights: weights
+        };
+    }
+
+    pub fn default() -> Model {
+        return Model::new(DEFAULT_WEIGHTS.to_vec());
+    }
+
+    fn init_stability(&self, rating: i64) -> f64 {
+        let ref w = self.weights;
+        return w[(rating - 1) as usize].max(MIN_STABILITY);
+    }
+
+    fn init_difficulty(&self, rating: i64) -> f64 {
+        let ref w = self.weights;
+        return w[4] - w[5] * ((rating - 3) as f64);
+    }
+
+    fn next_difficulty(&self, difficulty: f64, rating: i64) -> f64 {
+        let ref w = self.weights;
+
+        let next: f64 = difficulty - w[6] * ((rating - 3) as f64);
+
+        // mean reversion towards the initial difficulty of a 'good' rating
+        let next: f64 = w[7] * self.init_difficulty(GOOD) + (1f64 - w[7]) * next;
+
+        return clamp(next, MIN_DIFFICULTY, MAX_DIFFICULTY);
+    }
+
+    fn next_recall_stability(&self, state: &MemoryState, retrievability: f64, rating: i64) -> f64 {
+        let ref w = self.weights;
+
+        let hard_penalty: f64 = if rating == HARD { w[15] } else { 1f64 };
+        let easy_bonus: f64 = if rating == EASY { w[16] } else { 1f64 };
+
+        return state.stability * (
+            w[8].exp() *
+            (11f64 - state.difficulty) *
+            state.stability.powf(-w[9]) *
+            ((w[10] * (1f64 - retrievability)).exp() - 1f64) *
+            hard_penalty *
+            easy_bonus + 1f64
+        );
+    }
+
+    fn next_forget_stability(&self, state: &MemoryState, retrievability: f64) -> f64 {
+        let ref w = self.weights;
+
+        let stability: f64 = w[11] *
+            state.difficulty.powf(-w[12]) *
+            ((state.stability + 1f64).powf(w[13]) - 1f64) *
+            (w[14] * (1f64 - retrievability)).exp();
+
+        // forgetting a card should never increase its stability
+        return stability.min(state.stability);
+    }
+
+    // returns the memory state after a card is reviewed with the given rating
+    pub fn next_state(&self, state: Option<&MemoryState>, elapsed_days: f64, rating: i64) -> MemoryState {
+
+        assert!(rating >= AGAIN && rating <= EASY);
+
+        let state: &MemoryState = match state {
+            None => {
+                // first review
+                return MemoryState {
+                    stability: self.init_stability(rating),
+                    difficulty: clamp(self.init_difficulty(rating), MIN_DIFFICULTY, MAX_DIFFICULTY)
+                };
+            },
+            Some(state) => state
+        };
+
+        let r: f64 = retrievability(elapsed_days, state.stability);
+
+        let stability: f64 = if rating == AGAIN {
+            self.next_forget_stability(state, r)
+        } else {
+            self.next_recall_stability(state, r, rating)
+        };
+
+        return MemoryState {
+            stability: stability.max(MIN_STABILITY),
+            difficulty: self.next_difficulty(state.difficulty, rating)
+        };
+    }
+
+    // mean log loss of predicted retrievability over the given review log.
+    // returns None if there are no reviews to predict.
+    pub fn loss(&self, review_log: &Vec<Vec<Review>>) -> Option<f64> {
+
+        let mut total_loss: f64 = 0f64;
+        let mut num_predictions: i64 = 0;
+
+        for reviews in review_log {
+
+            let mut state: Option<MemoryState> = None;
+            let mut last_reviewed_at: i64 = 0;
+
+            for review in reviews {
+
+                let elapsed_days: f64 = ((review.reviewed_at - last_reviewed_at) as f64) / 86400f64;
+
+                if let Some(ref state) = state {
+
+                    let r: f64 = clamp(retrievability(elapsed_days, state.stability), 0.0001f64, 0.9999f64);
+
+                    let y: f64 = if review.passed { 1f64 } else { 0f64 };
+
+                    total_loss = total_loss - (y * r.ln() + (1f64 - y) * (1f64 - r).ln());
+                    num_predictions = num_predictions + 1;
+                }
+
+                state = Some(self.next_state(state.as_ref(), elapsed_days, review.rating));
+                last_reviewed_at = review.reviewed_at;
+            }
+        }
+
+        if num_predictions <= 0 {
+            return None;
+        }
+
+        return Some(total_loss / (num_predictions as f64));
+    }
+}
+
+fn clamp(value: f64, min: f64, max: f64) -> f64 {
+    return value.max(min).min(max);
+}
+
+/* review log */
+
+// review event of a card from CardsScoreHistory
+#[derive(Debug, Clone)]
+pub struct Review {
+    pub card: i64,
+    pub reviewed_at: i64,
+    pub passed: bool,
+
+    // rating the card was reviewed with
+    pub rating: i64
+}
+
+// fetch review events of every card, grouped by card, in chronological order.
+// undone review events are excluded.
+//
+// the rating of a review event is the one recorded in its context (see history::record_context);
+// review events without a recorded rating (e.g. from before ratings were recorded) are taken
+// as rated good if passed, or again otherwise.
+// caller should hold the db connection lock.
+pub fn get_review_log(db_conn: &Connection) -> Result<Vec<Vec<Review>>, QueryError> {
+
+    let ref query = format!("
+        SELECT
+            h.card, h.occurred_at, h.success, h.fail, ctx.fsrs_rating
+        FROM CardsScoreHistory AS h
+
+        LEFT JOIN CardsScoreHistoryContext AS ctx
+        ON ctx.history = h.history_id
+
+        WHERE
+            h.is_review_event = 1
+        AND
+            (h.success > 0 OR h.fail > 0 OR ctx.fsrs_rating IS NOT NULL)
+        AND
+            h.history_id NOT IN (SELECT history FROM CardsScoreUndo WHERE reverted = 1)
+        ORDER BY
+            h.card ASC, h.occurred_at ASC, h.history_id ASC;
+    ");
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query(&[]);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut review_log: Vec<Vec<Review>> = Vec::new();
+            let mut current_card: Option<i64> = None;
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                let card_id: i64 = row.get(0);
+                let success: i64 = row.get(2);
+                let fail: i64 = row.get(3);
+                let rating: Option<i64> = row.get(4);
+
+                let rating: i64 = match rating {
+                    Some(rating) => rating,
+                    None => {
+                        if success > 0 && fail <= 0 { GOOD } else { AGAIN }
+                    }
+                };
+
+                let review = Review {
+                    card: card_id,
+                    reviewed_at: row.get(1),
+                    passed: rating != AGAIN,
+                    rating: rating
+                };
+
+                if current_card != Some(card_id) {
+                    review_log.push(Vec::new());
+                    current_card = Some(card_id);
+                }
+
+                review_log.last_mut().unwrap().push(review);
+            }
+
+            return Ok(review_log);
+        }
+    };
+}
+
+/* optimizer */
+
+#[derive(Debug, RustcEncodable)]
+pub struct OptimizeResponse {
+    weights: Vec<f64>,
+    loss: Option<f64>,
+    num_cards: i64,
+    num_reviews: i64
+}
+
+impl OptimizeResponse {
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+}
+
+static MAX_ITERATIONS: i64 = 200;
+static INITIAL_LEARNING_RATE: f64 = 0.05;
+static MIN_LEARNING_RATE: f64 = 0.000001;
+static GRADIENT_STEP: f64 = 0.0001;
+
+// fit weights to the review log by projected gradient descent on the log loss,
+// starting from the given weights.
+// returns fitted weights, and their loss (if there are reviews to fit to).
+pub fn fit_weights(review_log: &Vec<Vec<Review>>, weights: Vec<f64>) -> (Vec<f64>, Option<f64>) {
+
+    let mut weights: Vec<f64> = weights;
+
+    let mut loss: f64 = match Model::new(weights.clone()).loss(review_log) {
+        None => {
+            // nothing to fit to
+            return (weights, None);
+        },
+        Some(loss) => loss
+    };
+
+    let mut learning_rate: f64 = INITIAL_LEARNING_RATE;
+    let mut iteration: i64 = 0;
+
+    while iteration < MAX_ITERATIONS && learning_rate > MIN_LEARNING_RATE {
+
+        iteration = iteration + 1;
+
+        // numerical gradient by central differences
+        let mut gradient: Vec<f64> = vec![0f64; weights.len()];
+
+        for idx in 0..weights.len() {
+
+            let mut forward: Vec<f64> = weights.clone();
+            forward[idx] = forward[idx] + GRADIENT_STEP;
+
+            let mut backward: Vec<f64> = weights.clone();
+            backward[idx] = backward[idx] - GRADIENT_STEP;
+
+            let forward_loss: f64 = Model::new(forward).loss(review_log).unwrap();
+            let backward_loss: f64 = Model::new(backward).loss(review_log).unwrap();
+
+            gradient[idx] = (forward_loss - backward_loss) / (2f64 * GRADIENT_STEP);
+        }
+
+        let candidate: Vec<f64> = weights.iter().enumerate().map(|(idx, weight)| -> f64 {
+            let (lower, upper) = WEIGHT_BOUNDS[idx];
+            return clamp(weight - learning_rate * gradient[idx], lower, upper);
+        }).collect();
+
+        let candidate_loss: f64 = Model::new(candidate.clone()).loss(review_log).unwrap();
+
+        if candidate_loss < loss {
+            weights = candidate;
+            loss = candidate_loss;
+            learning_rate = learning_rate * 1.2f64;
+        } else {
+            learning_rate = learning_rate * 0.5f64;
+        }
+    }
+
+    return (weights, Some(loss));
+}
+
+// re-fit weights from the database's review log, store them, and recompute the memory
+// state of every card.
+//
+// weights are fitted on a copy of the review log, without holding the db connection lock;
+// memory states are then recomputed from the review log at the time the weights are stored
+// (i.e. including any review since the copy was taken).
+pub fn optimize(db: &DB) -> Result<OptimizeResponse, QueryError> {
+
+    let (review_log, initial_weights) = {
+
+        let db_conn_guard = db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let review_log: Vec<Vec<Review>> = match get_review_log(db_conn) {
+            Err(why) => {
+                return Err(why);
+            },
+            Ok(review_log) => review_log
+        };
+
+        let initial_weights: Vec<f64> = match get_weights(db_conn) {
+            Err(why) => {
+                return Err(why);
+            },
+            Ok(weights) => weights
+        };
+
+        (review_log, initial_weights)
+    };
+
+    let (weights, loss) = fit_weights(&review_log, initial_weights);
+
+    let db_conn_guard = db.lock().unwrap();
+    let ref db_conn = *db_conn_guard;
+
+    try!(DB::prepare_query(db_conn));
+
+    let tx = match db_conn.transaction() {
+
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("creating transaction"),
+            };
+            return Err(err);
+        },
+
+        Ok(tx) => {
+            /* new transaction created */
+            tx
+        }
+    };
+
+    match set_weights(db_conn, &weights) {
+        Err(why) => {
+            return Err(why);
+        },
+        _ => {/* weights stored */}
+    }
+
+    let latest_review_log: Vec<Vec<Review>> = match get_review_log(db_conn) {
+        Err(why) => {
+            return Err(why);
+        },
+        Ok(review_log) => review_log
+    };
+
+    match rebuild_memory_states(db_conn, &Model::new(weights.clone()), &latest_review_log) {
+        Err(why) => {
+            return Err(why);
+        },
+        _ => {/* memory states recomputed */}
+    }
+
+    match tx.commit() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("committing transaction"),
+            };
+            return Err(err);
+        },
+        _ => {/* commit successful */}
+    }
+
+    let num_reviews: usize = review_log.iter().fold(0, |acc, reviews| acc + reviews.len());
+
+    let response = OptimizeResponse {
+        weights: weights,
+        loss: loss,
+        num_cards: review_log.len() as i64,
+        num_reviews: num_reviews as i64
+    };
+
+    return Ok(response);
+}
+
+/* weights */
+
+// fetch fitted weights from configs; fallback to default weights.
+// caller should hold the db connection lock.
+pub fn get_weights(db_conn: &Connection) -> Result<Vec<f64>, QueryError> {
+
+    let value: String = match try!(get_config(db_conn, WEIGHTS_CONFIG)) {
+        None => {
+            return Ok(DEFAULT_WEIGHTS.to_vec());
+        },
+        Some(value) => value
+    };
+
+    // ignore malformed weights
+    return match json::decode::<Vec<f64>>(&value) {
+        Ok(ref weights) if weights.len() == DEFAULT_WEIGHTS.len() => Ok(weights.clone()),
+        _ => Ok(DEFAULT_WEIGHTS.to_vec())
+    };
+}
+
+// caller should hold the db connection lock.
+fn set_weights(db_conn: &Connection, weights: &Vec<f64>) -> Result<(), QueryError> {
+
+    let value: String = json::encode(weights).unwrap();
+
+    let ref query = format!("
+        INSERT OR REPLACE INTO Configs (setting, value)
+        VALUES (:setting, :value);
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":setting", &WEIGHTS_CONFIG),
+        (":value", &value),
+    ];
+
+    match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */},
+    }
+
+    return Ok(());
+}
+
+/* memory states */
+
+// update the card's memory state after it is reviewed with the given rating.
+// caller should hold the db connection lock.
+pub fn review_card(db_conn: &Connection, card_id: i64, rating: i64) -> Result<(), QueryError> {
+
+    let model: Model = match get_weights(db_conn) {
+        Err(why) => {
+            return Err(why);
+        },
+        Ok(weights) => Model::new(weights)
+    };
+
+    let params: &[(&str, &ToSql)] = &[
+        (":card_id", &card_id)
+    ];
+
+    // TODO: can be simplified if this is fixed: https://github.com/jgallagher/rusqlite/issues/79
+    let ref query_count = format!("
+        SELECT COUNT(1)
+        FROM CardsFSRS
+        WHERE card = :card_id LIMIT 1;
+    ");
+
+    let has_state = db_conn.query_row_named(query_count, params, |row| -> bool {
+        let count: i64 = row.get(0);
+        return count >= 1;
+    });
+
+    let has_state: bool = match has_state {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_count.clone(),
+            };
+            return Err(err);
+        },
+        Ok(has_state) => has_state
+    };
+
+    let (state, elapsed_days): (Option<MemoryState>, f64) = if !has_state {
+        (None, 0f64)
+    } else {
+
+        let ref query = format!("
+            SELECT
+                stability, difficulty, strftime('%s', 'now') - reviewed_at
+            FROM CardsFSRS
+            WHERE card = :card_id
+            LIMIT 1;
+        ");
+
+        let results = db_conn.query_row_named(query, params, |row| -> (MemoryState, i64) {
+            let state = MemoryState {
+                stability: row.get(0),
+                difficulty: row.get(1)
+            };
+            return (state, row.get(2));
+        });
+
+        match results {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok((state, elapsed_seconds)) => {
+                (Some(state), (elapsed_seconds as f64) / 86400f64)
+            }
+        }
+    };
+
+    let next_state: MemoryState = model.next_state(state.as_ref(), elapsed_days, rating);
+
+    let lapse: i64 = if rating == AGAIN && has_state { 1 } else { 0 };
+
+    let ref query_insert = format!("
+        INSERT OR REPLACE INTO CardsFSRS(card, stability, difficulty, reviews, lapses, reviewed_at)
+        VALUES (
+            :card_id,
+            :stability,
+            :difficulty,
+            COALESCE((SELECT reviews FROM CardsFSRS WHERE card = :card_id), 0) + 1,
+            COALESCE((SELECT lapses FROM CardsFSRS WHERE card = :card_id), 0) + :lapse,
+            strftime('%s', 'now')
+        );
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":card_id", &card_id),
+        (":stability", &(next_state.stability)),
+        (":difficulty", &(next_state.difficulty)),
+        (":lapse", &lapse)
+    ];
+
+    match db_conn.execute_named(query_insert, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_insert.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
+
+// forget the card's memory state.
+// caller should hold the db connection lock.
+pub fn reset_card(db_conn: &Connection, card_id: i64) -> Result<(), QueryError> {
+
+    let ref query_delete = format!("
+        DELETE FROM CardsFSRS WHERE card = :card_id;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":card_id", &card_id)
+    ];
+
+    match db_conn.execute_named(query_delete, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_delete.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
+
+// recompute memory states of every card by replaying the review log.
+// caller should hold the db connection lock.
+fn rebuild_memory_states(db_conn: &Connection, model: &Model, review_log: &Vec<Vec<Review>>) -> Result<(), QueryError> {
+
+    let ref query_delete = format!("
+        DELETE FROM CardsFSRS;
+    ");
+
+    match db_conn.execute_batch(query_delete) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_delete.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    let ref query_insert = format!("
+        INSERT OR REPLACE INTO CardsFSRS(card, stability, difficulty, reviews, lapses, reviewed_at)
+        VALUES (:card_id, :stability, :difficulty, :reviews, :lapses, :reviewed_at);
+    ");
+
+    for reviews in review_log.iter() {
+
+        let card_id: i64 = reviews[0].card;
+
+        let mut state: Option<MemoryState> = None;
+        let mut lapses: i64 = 0;
+        let mut last_reviewed_at: i64 = 0;
+
+        for review in reviews {
+
+            let elapsed_days: f64 = ((review.reviewed_at - last_reviewed_at) as f64) / 86400f64;
+
+            if !review.passed && state.is_some() {
+                lapses = lapses + 1;
+            }
+
+            state = Some(model.next_state(state.as_ref(), elapsed_days, review.rating));
+            last_reviewed_at = review.reviewed_at;
+        }
+
+        let state: MemoryState = state.unwrap();
+        let num_reviews: i64 = reviews.len() as i64;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":card_id", &card_id),
+            (":stability", &(state.stability)),
+            (":difficulty", &(state.difficulty)),
+            (":reviews", &num_reviews),
+            (":lapses", &lapses),
+            (":reviewed_at", &last_reviewed_at)
+        ];
+
+        match db_conn.execute_named(query_insert, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_insert.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */}
+        }
+    }
+
+    return Ok(());
+}
+
+/* retrievability */
+
+#[derive(Debug, RustcEncodable)]
+pub struct RetrievabilityResponse {
+    card: i64,
+
+    // null if the card was never reviewed
+    stability: Option<f64>, // in days
+    difficulty: Option<f64>,
+    reviewed_at: Option<i64>, // unix timestamp
+    retrievability: Option<f64>,
+
+    // when retrievability drops to the desired retention
+    due_at: Option<i64> // unix timestamp
+}
+
+// predicted retrievability of every card within the deck or the deck's descendents.
+// sorted by retrievability (least retrievable first); cards never reviewed are last.
+// caller should hold the db connection lock.
+pub fn get_deck_retrievability(db_conn: &Connection, deck_id: i64) -> Result<Vec<RetrievabilityResponse>, QueryError> {
+
+    let ref query = format!("
+        SELECT
+            sub.card_id, sub.stability, sub.difficulty, sub.reviewed_at, sub.retrievability
+        FROM
+        (
+            SELECT
+                c.card_id,
+                f.stability,
+                f.difficulty,
+                f.reviewed_at,
+                CASE
+                    WHEN f.card IS NULL THEN NULL
+                    ELSE fsrs_retrievability(CAST(strftime('%s','now') - f.reviewed_at AS REAL), f.stability)
+                END AS retrievability
+            FROM DecksClosure AS dc
+
+            INNER JOIN Cards AS c
+            ON c.deck = dc.descendent
+
+            LEFT JOIN CardsFSRS AS f
+            ON f.card = c.card_id
+
+            WHERE
+                dc.ancestor = :deck_id
+        )
+        AS sub
+        ORDER BY
+            sub.retrievability IS NULL ASC,
+            sub.retrievability ASC,
+            sub.card_id ASC;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":deck_id", &deck_id)
+    ];
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            let mut list: Vec<RetrievabilityResponse> = Vec::new();
+
+            for result_row in iter {
+
+                let row = match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => row
+                };
+
+                let stability: Option<f64> = row.get(1);
+                let reviewed_at: Option<i64> = row.get(3);
+
+                let due_at: Option<i64> = match (stability, reviewed_at) {
+                    (Some(stability), Some(reviewed_at)) => {
+                        let interval: f64 = next_interval(stability, DEFAULT_DESIRED_RETENTION);
+                        Some(reviewed_at + (interval * 86400f64).round() as i64)
+                    },
+                    _ => None
+                };
+
+                list.push(RetrievabilityResponse {
+                    card: row.get(0),
+                    stability: stability,
+                    difficulty: row.get(2),
+                    reviewed_at: reviewed_at,
+                    retrievability: row.get(4),
+                    due_at: due_at
+                });
+            }
+
+            return Ok(list);
+        }
+    };
+}
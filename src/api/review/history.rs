@@ -0,0 +1,215 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+use rusqlite::{Connection, SqliteStatement};
+use rusqlite::types::ToSql;
+
+use ::database::QueryError;
+use ::api::review::{ReviewAPI, UpdateCardScore};
+use ::api::review::fsrs;
+
+
+// review log of a card.
+//
+// each review action creates a CardsScoreHistory entry (see SNAPSHOT_CARDS_SCORE_ON_UPDATED_TRIGGER).
+// the context of the action (i.e. where the card was reviewed, and how long it took to answer)
+// is stored alongside the entry in CardsScoreHistoryContext.
+
+#[derive(Debug, Clone, RustcEncodable)]
+pub struct ReviewLogResponse {
+    history_id: i64,
+    occurred_at: i64, // unix timestamp
+    is_review_event: bool,
+
+    success: i64,
+    fail: i64,
+    total_success: i64,
+    total_fail: i64,
+    changelog: String,
+
+    // container the card was reviewed in (if any)
+    deck: Option<i64>,
+    stash: Option<i64>,
+    session: Option<i64>,
+
+    // null if not given
+    answer_time_ms: Option<i64>,
+    front_time_ms: Option<i64>,
+
+    // review action was undone
+    reverted: bool
+}
+
+// history_id of the card's latest CardsScoreHistory entry; i.e. of the entry created by the review
+// action that was just taken. entries are inserted by a trigger, which last_insert_rowid() doesn't
+// report outside of.
+// caller should hold the db connection lock.
+pub fn latest_history_id(db_conn: &Connection, card_id: i64) -> Result<i64, QueryError> {
+
+    let ref query = format!("
+        SELECT history_id
+        FROM CardsScoreHistory
+        WHERE card = :card_id
+        ORDER BY history_id DESC
+        LIMIT 1;
+    ");
+
+    let results = db_conn.query_row_named(query, &[(":card_id", &card_id)], |row| -> i64 {
+        return row.get(0);
+    });
+
+    match results {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(history_id) => {
+            return Ok(history_id);
+        }
+    };
+}
+
+// record the context of the review action for the CardsScoreHistory entry (history_id) it created.
+// caller should hold the db connection lock.
+pub fn record_context(db_conn: &Connection, history_id: i64, card_id: i64, request: &UpdateCardScore)
+    -> Result<(), QueryError> {
+
+    let ref query = format!("
+        INSERT INTO CardsScoreHistoryContext(
+            history, card, deck, stash, session, answer_time_ms, front_time_ms, fsrs_rating
+        )
+        VALUES (
+            :history_id, :card_id,
+            :deck_id, :stash_id, :session_id, :answer_time_ms, :front_time_ms, :fsrs_rating
+        );
+    ");
+
+    // FSRS rating the card was reviewed with; replayed when re-fitting FSRS weights
+    let fsrs_rating: Option<i64> = request.get_grade().map(fsrs::rating_from_grade);
+
+    let params: &[(&str, &ToSql)] = &[
+        (":history_id", &history_id),
+        (":card_id", &card_id),
+        (":deck_id", &(request.deck)),
+        (":stash_id", &(request.stash)),
+        (":session_id", &(request.session)),
+        (":answer_time_ms", &(request.answer_time_ms)),
+        (":front_time_ms", &(request.front_time_ms)),
+        (":fsrs_rating", &fsrs_rating)
+    ];
+
+    match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
+
+impl ReviewAPI {
+
+    // list review log of a card; most recent first
+    pub fn get_review_log(&self, card_id: i64) -> Result<Vec<ReviewLogResponse>, QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                h.history_id, h.occurred_at, h.is_review_event,
+                h.success, h.fail, h.total_success, h.total_fail, h.changelog,
+                ctx.deck, ctx.stash, ctx.session, ctx.answer_time_ms, ctx.front_time_ms,
+                u.reverted
+            FROM CardsScoreHistory AS h
+
+            LEFT JOIN CardsScoreHistoryContext AS ctx
+            ON ctx.history = h.history_id
+
+            LEFT JOIN CardsScoreUndo AS u
+            ON u.history = h.history_id
+
+            WHERE h.card = :card_id
+            ORDER BY h.occurred_at DESC, h.history_id DESC;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":card_id", &card_id)
+        ];
+
+        let maybe_stmt = db_conn.prepare(query);
+
+        if maybe_stmt.is_err() {
+
+            let why = maybe_stmt.unwrap_err();
+
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        }
+
+        let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+        let maybe_iter = stmt.query_named(params);
+
+        match maybe_iter {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(iter) => {
+
+                let mut list: Vec<ReviewLogResponse> = Vec::new();
+
+                for result_row in iter {
+
+                    let row = match result_row {
+                        Err(why) => {
+                            let err = QueryError {
+                                sqlite_error: why,
+                                query: query.clone(),
+                            };
+                            return Err(err);
+                        },
+                        Ok(row) => row
+                    };
+
+                    let is_review_event: i64 = row.get(2);
+                    let reverted: Option<i64> = row.get(13);
+
+                    list.push(ReviewLogResponse {
+                        history_id: row.get(0),
+                        occurred_at: row.get(1),
+                        is_review_event: is_review_event != 0,
+                        success: row.get(3),
+                        fail: row.get(4),
+                        total_success: row.get(5),
+                        total_fail: row.get(6),
+                        changelog: row.get(7),
+                        deck: row.get(8),
+                        stash: row.get(9),
+                        session: row.get(10),
+                        answer_time_ms: row.get(11),
+                        front_time_ms: row.get(12),
+                        reverted: reverted.unwrap_or(0) != 0
+                    });
+                }
+
+                return Ok(list);
+            }
+        };
+    }
+}
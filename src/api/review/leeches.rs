@@ -0,0 +1,364 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+use rusqlite::{Connection, SqliteStatement};
+use rusqlite::types::ToSql;
+
+use ::database::{DB, QueryError, get_config, execute_named};
+use ::api::review::ReviewAPI;
+
+
+// a leech is a card that is failed over and over.
+//
+// lapses of a card are counted from its review log (CardsScoreHistory); a lapse is a review
+// event that isn't a success (i.e. fail, forgot, or a failing grade). undone review actions
+// aren't counted. when the lapses of a card reach the leech threshold, the card is marked
+// as a leech, and is optionally suspended.
+//
+// once a leech is cleared, only lapses after it was cleared are counted.
+
+pub static DEFAULT_LEECH_THRESHOLD: i64 = 8;
+
+// config settings.
+// a leech threshold of 0 disables leech detection.
+pub static LEECH_THRESHOLD_CONFIG: &'static str = "leech_threshold";
+pub static LEECH_SUSPEND_CONFIG: &'static str = "leech_suspend";
+
+// reason of a card suspended for being a leech
+pub static LEECH_SUSPEND_REASON: &'static str = "leech";
+
+#[derive(Debug, Clone, RustcEncodable)]
+pub struct LeechResponse {
+    card: i64,
+    lapses: i64,
+    marked_at: i64, // unix timestamp
+    suspended: bool
+}
+
+// re-evaluate whether the card is a leech after its review log has changed.
+// caller should hold the db connection lock.
+pub fn detect_leech(db_conn: &Connection, card_id: i64) -> Result<(), QueryError> {
+
+    let threshold: i64 = match try!(get_config(db_conn, LEECH_THRESHOLD_CONFIG)) {
+        None => DEFAULT_LEECH_THRESHOLD,
+        Some(value) => {
+            // ignore malformed threshold
+            match value.trim().parse::<i64>() {
+                Ok(threshold) if threshold >= 0 => threshold,
+                _ => DEFAULT_LEECH_THRESHOLD
+            }
+        }
+    };
+
+    if threshold <= 0 {
+        // leech detection disabled
+        return Ok(());
+    }
+
+    let should_suspend: bool = match try!(get_config(db_conn, LEECH_SUSPEND_CONFIG)) {
+        None => false,
+        Some(value) => {
+            match value.trim().to_lowercase().as_ref() {
+                "true" | "1" | "yes" => true,
+                _ => false
+            }
+        }
+    };
+
+    let params: &[(&str, &ToSql)] = &[
+        (":card_id", &card_id)
+    ];
+
+    let ref query_lapses = format!("
+        SELECT
+            COUNT(1)
+        FROM CardsScoreHistory AS h
+
+        LEFT JOIN CardsLeech AS l
+        ON l.card = h.card
+
+        WHERE
+            h.card = :card_id
+        AND
+            h.is_review_event = 1
+        AND
+            h.success = 0
+        AND
+            (l.cleared_at IS NULL OR h.occurred_at > l.cleared_at)
+        AND
+            h.history_id NOT IN (SELECT history FROM CardsScoreUndo WHERE card = :card_id AND reverted = 1);
+    ");
+
+    let lapses = db_conn.query_row_named(query_lapses, params, |row| -> i64 {
+        return row.get(0);
+    });
+
+    let lapses: i64 = match lapses {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_lapses.clone(),
+            };
+            return Err(err);
+        },
+        Ok(lapses) => lapses
+    };
+
+    let ref query_is_leech = format!("
+        SELECT COUNT(1)
+        FROM CardsLeech
+        WHERE card = :card_id AND is_leech = 1
+        LIMIT 1;
+    ");
+
+    let is_leech = db_conn.query_row_named(query_is_leech, params, |row| -> bool {
+        let count: i64 = row.get(0);
+        return count >= 1;
+    });
+
+    let is_leech: bool = match is_leech {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_is_leech.clone(),
+            };
+            return Err(err);
+        },
+        Ok(is_leech) => is_leech
+    };
+
+    if !is_leech && lapses >= threshold {
+
+        // mark card as a leech
+
+        let ref query_mark = format!("
+            INSERT OR REPLACE INTO CardsLeech(card, is_leech, lapses, marked_at, cleared_at)
+            VALUES (
+                :card_id, 1, :lapses, strftime('%s', 'now'),
+                (SELECT cleared_at FROM CardsLeech WHERE card = :card_id)
+            );
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":card_id", &card_id),
+            (":lapses", &lapses)
+        ];
+
+        try!(execute_named(db_conn, query_mark, params));
+
+        if should_suspend {
+
+            let ref query_suspend = format!("
+                INSERT OR IGNORE INTO SuspendedCards(card, reason)
+                VALUES (:card_id, :reason);
+            ");
+
+            let params: &[(&str, &ToSql)] = &[
+                (":card_id", &card_id),
+                (":reason", &LEECH_SUSPEND_REASON)
+            ];
+
+            try!(execute_named(db_conn, query_suspend, params));
+        }
+
+    } else if is_leech && lapses < threshold {
+
+        // lapses that marked the card as a leech were undone
+
+        try!(unmark_leech(db_conn, card_id, false));
+
+    } else if is_leech {
+
+        let ref query_update = format!("
+            UPDATE CardsLeech
+            SET
+                lapses = :lapses
+            WHERE card = :card_id;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":card_id", &card_id),
+            (":lapses", &lapses)
+        ];
+
+        try!(execute_named(db_conn, query_update, params));
+    }
+
+    return Ok(());
+}
+
+// remove the leech mark of the card, and resume the card if it was suspended for being a leech.
+// if clear is true, lapses before now are no longer counted.
+// caller should hold the db connection lock.
+fn unmark_leech(db_conn: &Connection, card_id: i64, clear: bool) -> Result<(), QueryError> {
+
+    let ref query_unmark = format!("
+        UPDATE CardsLeech
+        SET
+            is_leech = 0
+            {cleared_at}
+        WHERE card = :card_id;
+    ", cleared_at = {
+        if clear {
+            ", cleared_at = strftime('%s', 'now')"
+        } else {
+            ""
+        }
+    });
+
+    let params: &[(&str, &ToSql)] = &[
+        (":card_id", &card_id)
+    ];
+
+    try!(execute_named(db_conn, query_unmark, params));
+
+    let ref query_resume = format!("
+        DELETE FROM SuspendedCards
+        WHERE card = :card_id AND reason = :reason;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":card_id", &card_id),
+        (":reason", &LEECH_SUSPEND_REASON)
+    ];
+
+    try!(execute_named(db_conn, query_resume, params));
+
+    return Ok(());
+}
+
+impl ReviewAPI {
+
+    pub fn is_leech(&self, card_id: i64) -> Result<bool, QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT COUNT(1)
+            FROM CardsLeech
+            WHERE card = :card_id AND is_leech = 1
+            LIMIT 1;
+        ");
+
+        let is_leech = db_conn.query_row_named(query, &[(":card_id", &card_id)], |row| -> bool {
+            let count: i64 = row.get(0);
+            return count >= 1;
+        });
+
+        match is_leech {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(is_leech) => {
+                return Ok(is_leech);
+            }
+        };
+    }
+
+    // clear the leech mark of the card; the card is resumed if it was suspended for being a leech
+    pub fn clear_leech(&self, card_id: i64) -> Result<(), QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        return unmark_leech(db_conn, card_id, true);
+    }
+
+    // list leeches within the deck and the deck's descendents; most lapses first
+    pub fn get_deck_leeches(&self, deck_id: i64) -> Result<Vec<LeechResponse>, QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                l.card, l.lapses, l.marked_at,
+                CASE WHEN s.card IS NULL THEN 0 ELSE 1 END AS suspended
+            FROM DecksClosure AS dc
+
+            INNER JOIN Cards AS c
+            ON c.deck = dc.descendent
+
+            INNER JOIN CardsLeech AS l
+            ON l.card = c.card_id
+
+            LEFT JOIN SuspendedCards AS s
+            ON s.card = c.card_id
+
+            WHERE
+                dc.ancestor = :deck_id
+            AND
+                l.is_leech = 1
+            ORDER BY
+                l.lapses DESC,
+                l.marked_at DESC;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &deck_id)
+        ];
+
+        let maybe_stmt = db_conn.prepare(query);
+
+        if maybe_stmt.is_err() {
+
+            let why = maybe_stmt.unwrap_err();
+
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        }
+
+        let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+        let maybe_iter = stmt.query_named(params);
+
+        match maybe_iter {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(iter) => {
+
+                let mut list: Vec<LeechResponse> = Vec::new();
+
+                for result_row in iter {
+
+                    let row = match result_row {
+                        Err(why) => {
+                            let err = QueryError {
+                                sqlite_error: why,
+                                query: query.clone(),
+                            };
+                            return Err(err);
+                        },
+                        Ok(row) => row
+                    };
+
+                    let suspended: i64 = row.get(3);
+
+                    list.push(LeechResponse {
+                        card: row.get(0),
+                        lapses: row.get(1),
+                        marked_at: row.get(2),
+                        suspended: suspended != 0
+                    });
+                }
+
+                return Ok(list);
+            }
+        };
+    }
+}
@@ -2,16 +2,54 @@ extern crate rusqlite;
 extern crate rustc_serialize;
 
 mod restify;
-
-use rand::{thread_rng, Rng};
+mod classic;
+pub mod sm2;
+pub mod fsrs;
+pub mod settings;
+pub mod sessions;
+pub mod undo;
+pub mod history;
+pub mod leeches;
 
 use std::sync::Arc;
 
+use rand::{thread_rng, Rng, SeedableRng, StdRng};
 use rusqlite::types::ToSql;
+use rustc_serialize::json;
 
 use ::database::{DB, QueryError};
+use self::classic::GrokdbClassic;
+use self::sm2::SM2;
+use self::fsrs::FSRS;
+use self::settings::ReviewSettings;
+use self::sessions::ReviewSession;
 pub use self::restify::restify;
 
+// excludes cards that shouldn't be reviewed (i.e. suspended or buried cards) from a selection.
+// the card should be aliased as c within the query.
+pub const REVIEWABLE_CARDS_FILTER: &'static str = "
+    c.card_id NOT IN (SELECT card FROM SuspendedCards)
+    AND
+    c.card_id NOT IN (SELECT card FROM BuriedCards WHERE buried_until > strftime('%s', 'now'))
+";
+
+// excludes cards that were never reviewed (i.e. new cards) from a selection.
+// the card should be aliased as c within the query.
+pub const REVIEWED_CARDS_FILTER: &'static str = "
+    c.card_id IN (SELECT card FROM CardsScore WHERE times_reviewed > 0)
+";
+
+// filter of cards that may be reviewed within a selection; new cards are excluded unless allowed
+// (e.g. when the daily limit of new cards of a review session is reached).
+pub fn reviewable_cards_filter(allow_new_cards: bool) -> String {
+
+    if allow_new_cards {
+        return REVIEWABLE_CARDS_FILTER.to_string();
+    }
+
+    return format!("{} AND {}", REVIEWABLE_CARDS_FILTER, REVIEWED_CARDS_FILTER);
+}
+
 
 pub trait ReviewableSelection {
 
@@ -31,6 +69,12 @@ pub trait ReviewableSelection {
 
     /* new cards */
 
+    // if false, new cards are excluded from every sub-selection
+    fn allows_new_cards(&self) -> bool;
+
+    // check if the card was never reviewed
+    fn is_new_card(&self, card_id: i64) -> Result<bool, QueryError>;
+
     fn has_new_cards(&self) -> Result<bool, QueryError>;
 
     fn number_of_new_cards(&self) -> Result<i64, QueryError>;
@@ -64,11 +108,57 @@ pub trait ReviewableSelection {
     // - discards less than min_score
     // - sort by score (desc) [optional; if false, cards are implicitly sorted by age]
     fn get_old_card(&self, purgatory_size: i64, min_score: f64, index: i64, sort_by_score: bool) -> Result<i64, QueryError>;
+
+    /* Cards scheduled by SM-2 that are due for review */
+
+    fn has_due_cards(&self) -> Result<bool, QueryError>;
+
+    fn number_of_due_cards(&self) -> Result<i64, QueryError>;
+
+    // returns card id
+    // - sorted by due date (most overdue first)
+    fn get_due_card(&self, index: i64) -> Result<i64, QueryError>;
+
+    /* Cards modelled by FSRS whose predicted retrievability is below the given retention */
+
+    fn has_fading_cards(&self, retention: f64) -> Result<bool, QueryError>;
+
+    fn number_of_fading_cards(&self, retention: f64) -> Result<i64, QueryError>;
+
+    // returns card id
+    // - sorted by retrievability (least retrievable first)
+    fn get_fading_card(&self, retention: f64, index: i64) -> Result<i64, QueryError>;
+
+    /* scheduler */
+
+    // name of the scheduler chosen for this selection (if any)
+    fn get_scheduler_name(&self) -> Result<Option<String>, QueryError>;
+
+    /* settings */
+
+    // review settings chosen for this selection (if any)
+    fn get_review_settings(&self) -> Result<Option<ReviewSettings>, QueryError>;
+}
+
+// a scheduler decides which card of a selection should be reviewed next.
+//
+// schedulers are registered by name (see schedulers()), and may be chosen per deck or stash.
+pub trait Scheduler {
+
+    // unique name of the scheduler
+    fn name(&self) -> &'static str;
+
+    // returns card id.
+    // every random decision should be drawn from rng, so that the choice is
+    // reproducible when rng is seeded.
+    // invariant: selection has cards
+    fn choose_card(&self, selection: &ReviewableSelection, rng: &mut StdRng) -> Result<i64, QueryError>;
 }
 
 pub enum Action {
     Success,
     Fail,
+    Grade,
     Reset,
     Forgot,
     Skip,
@@ -84,8 +174,11 @@ pub struct UpdateCardScore {
     // - reset
     // - forgot
     // - skip
+    // - grade
     action: String,
 
+    // for grade action, value is the quality of the answer from 0 (complete blackout)
+    // to 5 (perfect response).
     value: Option<i64>,
 
     // description of the action on the card being reviewed
@@ -95,7 +188,17 @@ pub struct UpdateCardScore {
     // either stash or deck is given (but not both),
     // or neither stash nor deck is selected (case when reviewing the card individually)
     stash: Option<i64>, // stash id
-    deck: Option<i64> // deck id
+    deck: Option<i64>, // deck id
+
+    // review session the card was reviewed in (if any).
+    // the session should be active, and be of the given stash or deck.
+    session: Option<i64>, // session id
+
+    // time taken to answer the card since it was shown, in milliseconds
+    answer_time_ms: Option<i64>,
+
+    // how long the front of the card was shown before the back was revealed, in milliseconds
+    front_time_ms: Option<i64>
 }
 
 static DEFAULT_VALUE: i64 = 1;
@@ -112,6 +215,7 @@ impl UpdateCardScore {
             "reset" => Action::Reset,
             "forgot" => Action::Forgot,
             "skip" => Action::Skip,
+            "grade" => Action::Grade,
             _ => Action::Invalid
         };
     }
@@ -140,6 +244,16 @@ impl UpdateCardScore {
                     }
                 }
             },
+            Action::Grade => {
+                match self.value {
+                    Some(value) => {
+                        return value >= sm2::MIN_GRADE && value <= sm2::MAX_GRADE;
+                    },
+                    None => {
+                        return false;
+                    }
+                }
+            },
             _ => {
                 // value is ignored for all other actions
                 return true;
@@ -165,8 +279,61 @@ impl UpdateCardScore {
         }
     }
 
+    // SM-2 grade of the answer.
+    // success and fail actions are mapped onto grades so that cards reviewed
+    // without grading are still scheduled.
+    pub fn get_grade(&self) -> Option<i64> {
+
+        return match self.get_action() {
+            Action::Grade => self.value,
+            Action::Success => Some(4),
+            Action::Fail => Some(1),
+            Action::Forgot => Some(0),
+            _ => None
+        };
+    }
+
+    pub fn get_session(&self) -> Option<i64> {
+        return self.session;
+    }
+
+    // check if the card was reviewed within the container of the given session
+    pub fn is_in_session(&self, session: &ReviewSession) -> bool {
+        return session.is_for(self.deck, self.stash);
+    }
+
+    // actions that count as reviewing the card
+    pub fn is_review_action(&self) -> bool {
+        return match self.get_action() {
+            Action::Success | Action::Fail | Action::Grade | Action::Forgot => true,
+            _ => false
+        };
+    }
+
+    pub fn should_reset_schedule(&self) -> bool {
+        return match self.get_action() {
+            Action::Reset => true,
+            _ => false
+        };
+    }
+
+    // latencies, if given, should be non-negative
+    pub fn is_valid_latency(&self) -> bool {
+
+        for latency in [self.answer_time_ms, self.front_time_ms].iter() {
+            match *latency {
+                Some(ms) if ms < 0 => {
+                    return false;
+                },
+                _ => {/* latency is valid or not given */}
+            }
+        }
+
+        return true;
+    }
+
     pub fn should_update(&self) -> bool {
-        return self.is_valid_action() && self.is_valid_value()
+        return self.is_valid_action() && self.is_valid_value() && self.is_valid_latency()
             && (!self.stash.is_some() || !self.deck.is_some());
             // long-form:
             // ((self.stash.is_some() && !self.deck.is_some()) ||
@@ -185,7 +352,7 @@ impl UpdateCardScore {
                 return false;
             },
 
-            Action::Success | Action::Fail | Action::Forgot => {
+            Action::Success | Action::Fail | Action::Grade | Action::Forgot => {
                 return true;
             }
         }
@@ -228,6 +395,25 @@ impl UpdateCardScore {
                 values.push(tuple);
             },
 
+            Action::Grade => {
+
+                fields.push(format!("times_reviewed = times_reviewed + 1"));
+                fields.push(format!("times_seen = times_seen + 1"));
+                fields.push(format!("seen_at = strftime('%s', 'now')"));
+                fields.push(format!("reviewed_at = strftime('%s', 'now')"));
+
+                // a passing grade is a success; otherwise, it is a fail
+                if self.value.unwrap() >= sm2::PASSING_GRADE {
+                    fields.push(format!("success = success + :success"));
+                    let tuple: (&str, &ToSql) = (":success", &DEFAULT_VALUE);
+                    values.push(tuple);
+                } else {
+                    fields.push(format!("fail = fail + :fail"));
+                    let tuple: (&str, &ToSql) = (":fail", &DEFAULT_VALUE);
+                    values.push(tuple);
+                }
+            },
+
             Action::Forgot => {
 
                 fields.push(format!("times_reviewed = times_reviewed + 1"));
@@ -285,7 +471,49 @@ pub struct ReviewResponse {
     times_reviewed: i64,
     times_seen: i64,
     reviewed_at: i64, // unix timestamp
-    seen_at: i64 // unix timestamp
+    seen_at: i64, // unix timestamp
+
+    // SM-2 schedule; null if the card was never graded
+    ease_factor: Option<f64>,
+    interval: Option<i64>, // in days
+    due_at: Option<i64> // unix timestamp
+}
+
+impl ReviewResponse {
+
+    // the card was never reviewed
+    pub fn is_new(&self) -> bool {
+        return self.times_reviewed <= 0;
+    }
+}
+
+#[derive(Debug, Clone, RustcDecodable)]
+pub struct SetSchedulerRequest {
+    scheduler: String
+}
+
+impl SetSchedulerRequest {
+
+    pub fn get_scheduler_name(&self) -> String {
+        return self.scheduler.trim().to_string();
+    }
+
+    // scheduler should be registered
+    pub fn is_valid(&self) -> bool {
+        return get_scheduler(&self.get_scheduler_name()).is_some();
+    }
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct SchedulerResponse {
+    scheduler: String
+}
+
+impl SchedulerResponse {
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -302,9 +530,14 @@ impl ReviewAPI {
 
         let ref query = format!("
             SELECT
-                success, fail, times_reviewed, times_seen, reviewed_at, seen_at
-            FROM CardsScore
-            WHERE card = :card_id
+                cs.success, cs.fail, cs.times_reviewed, cs.times_seen, cs.reviewed_at, cs.seen_at,
+                sm.ease_factor, sm.interval_days, sm.due_at
+            FROM CardsScore AS cs
+
+            LEFT JOIN CardsSM2 AS sm
+            ON sm.card = cs.card
+
+            WHERE cs.card = :card_id
             LIMIT 1;
         ");
 
@@ -321,7 +554,10 @@ impl ReviewAPI {
                 times_reviewed: row.get(2),
                 times_seen: row.get(3),
                 reviewed_at: row.get(4),
-                seen_at: row.get(5)
+                seen_at: row.get(5),
+                ease_factor: row.get(6),
+                interval: row.get(7),
+                due_at: row.get(8)
             };
         });
 
@@ -376,6 +612,22 @@ impl ReviewAPI {
             }
         };
 
+        // take snapshot of the card for undoing this action
+
+        let snapshot = undo::take_snapshot(db_conn, card_id,
+            update_review_request.deck, update_review_request.stash,
+            update_review_request.should_update_card_container());
+
+        let mut snapshot: undo::Snapshot = match snapshot {
+            Err(why) => {
+                return Err(why);
+            },
+            Ok(snapshot) => snapshot
+        };
+
+        // a new card is a card that was never reviewed
+        let is_new_card: bool = snapshot.times_reviewed <= 0;
+
         match db_conn.execute_named(query_update, &values[..]) {
             Err(why) => {
                 let err = QueryError {
@@ -387,6 +639,52 @@ impl ReviewAPI {
             _ => {/* query sucessfully executed */}
         }
 
+        // review log entry created by this action
+
+        let history_id: i64 = match history::latest_history_id(db_conn, card_id) {
+            Err(why) => {
+                return Err(why);
+            },
+            Ok(history_id) => history_id
+        };
+
+        // update SM-2 schedule and FSRS memory state of the card
+
+        if update_review_request.should_reset_schedule() {
+            match sm2::reset_card(db_conn, card_id) {
+                Err(why) => {
+                    return Err(why);
+                },
+                _ => {/* schedule removed */}
+            }
+
+            match fsrs::reset_card(db_conn, card_id) {
+                Err(why) => {
+                    return Err(why);
+                },
+                _ => {/* memory state removed */}
+            }
+        }
+
+        match update_review_request.get_grade() {
+            None => {/* card is not graded */},
+            Some(grade) => {
+                match sm2::grade_card(db_conn, card_id, grade) {
+                    Err(why) => {
+                        return Err(why);
+                    },
+                    _ => {/* card rescheduled */}
+                }
+
+                match fsrs::review_card(db_conn, card_id, fsrs::rating_from_grade(grade)) {
+                    Err(why) => {
+                        return Err(why);
+                    },
+                    _ => {/* memory state updated */}
+                }
+            }
+        }
+
         if update_review_request.should_update_card_container() && update_review_request.deck.is_some() {
 
             let deck_id: i64 = update_review_request.deck.unwrap();
@@ -432,6 +730,48 @@ impl ReviewAPI {
             }
         }
 
+        // record progress of review session
+
+        match update_review_request.get_session() {
+            Some(session_id) if update_review_request.is_review_action() => {
+                match sessions::record_review(db_conn, session_id, is_new_card) {
+                    Err(why) => {
+                        return Err(why);
+                    },
+                    _ => {/* progress recorded */}
+                }
+
+                snapshot.session = Some(session_id);
+                snapshot.is_new_card = is_new_card;
+            },
+            _ => {/* not reviewed within a session */}
+        }
+
+        // record context of the review action in the review log
+
+        match history::record_context(db_conn, history_id, card_id, &update_review_request) {
+            Err(why) => {
+                return Err(why);
+            },
+            _ => {/* context recorded */}
+        }
+
+        // check if the card became a leech
+
+        match leeches::detect_leech(db_conn, card_id) {
+            Err(why) => {
+                return Err(why);
+            },
+            _ => {/* leech checked */}
+        }
+
+        match undo::save_snapshot(db_conn, history_id, &snapshot) {
+            Err(why) => {
+                return Err(why);
+            },
+            _ => {/* snapshot saved */}
+        }
+
         match tx.commit() {
             Err(why) => {
                 let err = QueryError {
@@ -445,9 +785,23 @@ impl ReviewAPI {
 
         return Ok(());
     }
+
+    // re-fit FSRS weights from the review log, and recompute memory states of every card
+    pub fn optimize_fsrs(&self) -> Result<fsrs::OptimizeResponse, QueryError> {
+        return fsrs::optimize(&self.db);
+    }
+
+    pub fn get_deck_retrievability(&self, deck_id: i64) -> Result<Vec<fsrs::RetrievabilityResponse>, QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        return fsrs::get_deck_retrievability(db_conn, deck_id);
+    }
 }
 
-pub fn get_review_card<T>(selection: &T) -> Result<Option<i64>, QueryError>
+// if seed is given, the same card is chosen for the same selection and database state.
+pub fn get_review_card<T>(selection: &T, seed: Option<u64>) -> Result<Option<i64>, QueryError>
     where T: ReviewableSelection {
 
     match selection.get_cached_card() {
@@ -455,7 +809,24 @@ pub fn get_review_card<T>(selection: &T) -> Result<Option<i64>, QueryError>
             return Err(why);
         },
         Ok(Some(card_id)) => {
-            return Ok(Some(card_id));
+
+            // a cached new card is only reviewed if new cards are still allowed
+            // (e.g. daily limit of new cards was reached since it was cached)
+
+            let is_new_card: bool = if selection.allows_new_cards() {
+                false
+            } else {
+                match selection.is_new_card(card_id) {
+                    Err(why) => {
+                        return Err(why);
+                    },
+                    Ok(is_new_card) => is_new_card
+                }
+            };
+
+            if !is_new_card {
+                return Ok(Some(card_id));
+            }
         }
         Ok(None) => {
             // no cached card for review
@@ -475,165 +846,29 @@ pub fn get_review_card<T>(selection: &T) -> Result<Option<i64>, QueryError>
         }
     }
 
-
-    // decide method for choosing the next card
-
-    let method = match choose_method(selection) {
+    // fetch the scheduler chosen for this selection.
+    // fallback to the default scheduler if none was chosen, or if the chosen
+    // scheduler is no longer registered.
+    let scheduler: Box<Scheduler> = match selection.get_scheduler_name() {
         Err(why) => {
             return Err(why);
         },
-        Ok(method) => method
-    };
-
-
-    let card_id: i64 = match method {
-        Method::NewCards => {
-            match selection.get_new_card(0) {
-                Err(why) => {
-                    return Err(why);
-                },
-                Ok(card_id) => card_id
+        Ok(None) => default_scheduler(),
+        Ok(Some(scheduler_name)) => {
+            match get_scheduler(&scheduler_name) {
+                None => default_scheduler(),
+                Some(scheduler) => scheduler
             }
-        },
-
-        Method::OldEnough => {
-
-            let mut rng = thread_rng();
-
-            // randomly decide to discard low scoring cards
-            let min_score: f64 = {
-
-                let cutoff = 0.3f64; // TODO: move this
-
-                let num_cards = match selection.number_of_reviewable_cards(3, cutoff) {
-                    Err(why) => {
-                        return Err(why);
-                    },
-                    Ok(num_cards) => num_cards
-                };
-
-                // flip a coin.
-                if rng.gen_weighted_bool(2) && num_cards >= 2 {
-                    cutoff
-                } else {
-                    0f64
-                }
-            };
-
-            let num_cards: i64 = match selection.number_of_reviewable_cards(3, min_score) {
-                Err(why) => {
-                    return Err(why);
-                },
-                Ok(num_cards) => num_cards
-            };
-
-            assert!(num_cards > 0);
+        }
+    };
 
-            let card_idx: i64 = match rng.gen_range(0f64, 1f64) {
+    let mut rng: StdRng = review_rng(seed);
 
-                pin if pin < 0.3 => {
-                    // random card
-                    rng.gen_range(0, num_cards)
-                },
-
-                _ => { // 70% probability
-                    // Random card from top 50% of highest rank score
-                    let min_idx: i64 = ((num_cards as f64) / 2f64).ceil() as i64;
-                    assert!(min_idx > 0);
-                    rng.gen_range(0, min_idx)
-                }
-            };
-
-            match selection.get_reviewable_card(3, min_score, card_idx) {
-                Err(why) => {
-                    return Err(why);
-                },
-                Ok(card_id) => card_id
-            }
+    let card_id: i64 = match scheduler.choose_card(selection, &mut rng) {
+        Err(why) => {
+            return Err(why);
         },
-
-        Method::LeastRecentlyReviewed => {
-
-            let mut rng = thread_rng();
-
-            // calculate the purgatory size
-            let purgatory_size = {
-
-                let num_cards: i64 = match selection.number_of_cards() {
-                    Err(why) => {
-                        return Err(why);
-                    },
-                    Ok(num_cards) => num_cards
-                };
-
-                (0.2 * (num_cards as f64)).ceil() as i64
-            };
-
-            // randomly decide to discard low scoring cards
-            let min_score: f64 = {
-
-                let cutoff = 0.3f64; // TODO: move this
-
-                let num_cards = match selection.number_of_old_cards(purgatory_size, cutoff, false) {
-                    Err(why) => {
-                        return Err(why);
-                    },
-                    Ok(num_cards) => num_cards
-                };
-
-                if rng.gen_weighted_bool(2) && num_cards >= 2 {
-                    cutoff
-                } else {
-                    0f64
-                }
-            };
-
-            let (card_idx, sort_by_score): (i64, bool) = match rng.gen_range(0f64, 1f64) {
-
-                pin if pin < 0.2 => {
-                    // random card (20% prob)
-
-                    let num_cards = match selection.number_of_old_cards(purgatory_size, min_score, false) {
-                        Err(why) => {
-                            return Err(why);
-                        },
-                        Ok(num_cards) => num_cards
-                    };
-
-                    assert!(num_cards > 0);
-
-                    (rng.gen_range(0, num_cards), true)
-                },
-
-                pin if pin < (0.2 + 0.75) => {
-                    // Random card from top 50% of highest rank score (75% prob)
-
-                    let num_cards = match selection.number_of_old_cards(purgatory_size, min_score, true) {
-                        Err(why) => {
-                            return Err(why);
-                        },
-                        Ok(num_cards) => num_cards
-                    };
-
-                    let min_idx: i64 = ((num_cards as f64) / 2f64).ceil() as i64;
-
-                    assert!(min_idx > 0);
-
-                    (rng.gen_range(0, min_idx), true)
-                },
-
-                _ => { // Oldest card
-                    (0, false)
-                }
-            };
-
-            match selection.get_old_card(purgatory_size, min_score, card_idx, sort_by_score) {
-                Err(why) => {
-                    return Err(why);
-                },
-                Ok(card_id) => card_id
-            }
-        }
+        Ok(card_id) => card_id
     };
 
     // remove any cached entry
@@ -659,90 +894,51 @@ pub fn get_review_card<T>(selection: &T) -> Result<Option<i64>, QueryError>
     return Ok(Some(card_id));
 }
 
-enum Method {
-    NewCards,
-    OldEnough, // old enough to be reviewed
-    LeastRecentlyReviewed,
-}
-
-// these should add up to 1
-static NEW_CARDS: f64 = 0.15;
-static OLD_ENOUGH: f64 = 0.3;
-static LEAST_RECENT: f64 = 0.55;
-
-fn choose_method<T>(selection: &T) -> Result<Method, QueryError>
-    where T: ReviewableSelection {
-
-    let mut max_pin: f64 = 1f64;
+// config setting of the seed for choosing cards to review
+pub static SEED_CONFIG: &'static str = "review_seed";
 
-    // if there are no new cards, adjust pin to exclude 'new cards' method.
-    let has_new_cards: bool = match selection.has_new_cards() {
-        Err(why) => {
-            return Err(why);
+// random number generator for choosing cards to review.
+// if no seed is given, the generator is seeded randomly.
+pub fn review_rng(seed: Option<u64>) -> StdRng {
+    return match seed {
+        Some(seed) => {
+            let seed: &[usize] = &[seed as usize, (seed >> 32) as usize];
+            StdRng::from_seed(seed)
         },
-        Ok(false) => {
-            max_pin = max_pin - NEW_CARDS;
-            false
-        },
-        Ok(true) => { true }
+        None => thread_rng().gen::<StdRng>()
     };
+}
 
-    // check if there are cards that haven't been reviewed for at least 3 hours
-    // and have a minimum score of 0.
-    //
-    // if there are no such cards that meet the above criteria, then exclude
-    // this method.
-    let has_reviewable_cards: bool = match selection.has_reviewable_cards(3, 0f64) {
-        Err(why) => {
-            return Err(why);
-        },
-        Ok(false) => {
-            max_pin = max_pin - OLD_ENOUGH;
-            false
-        },
-        Ok(true) => { true }
-    };
-
-
-    let max_pin = max_pin;
-
-    // if there is only one method to choose from.
-    // faster code path.
-    if !has_new_cards && !has_reviewable_cards {
-        return Ok(Method::LeastRecentlyReviewed);
-    }
-
-    // invariant: max_pin > LEAST_RECENT
+/* schedulers */
 
-    let mut rng = thread_rng();
+// due and overdue cards are reviewed first (see sm2 module); decks and stashes may opt into
+// grokdb-classic for choosing every card by score
+pub static DEFAULT_SCHEDULER: &'static str = "sm2";
 
-    assert!(max_pin > 0f64);
+// all registered schedulers.
+// to add a new scheduler, implement the Scheduler trait and register it here.
+pub fn schedulers() -> Vec<Box<Scheduler>> {
+    return vec![
+        Box::new(GrokdbClassic) as Box<Scheduler>,
+        Box::new(SM2) as Box<Scheduler>,
+        Box::new(FSRS) as Box<Scheduler>,
+    ];
+}
 
-    if !has_new_cards {
+pub fn get_scheduler(scheduler_name: &str) -> Option<Box<Scheduler>> {
 
-        let method = match rng.gen_range(0f64, max_pin) {
-            pin if pin < LEAST_RECENT => Method::LeastRecentlyReviewed,
-            _ => Method::OldEnough
-        };
-
-        return Ok(method);
+    for scheduler in schedulers() {
+        if scheduler.name() == scheduler_name {
+            return Some(scheduler);
+        }
     }
 
-    if !has_reviewable_cards {
-
-        let method = match rng.gen_range(0f64, max_pin) {
-            pin if pin < LEAST_RECENT => Method::LeastRecentlyReviewed,
-            _ => Method::NewCards
-        };
-
-        return Ok(method);
-    }
+    return None;
+}
 
-    let method = match rng.gen_range(0f64, max_pin) {
-        pin if pin < LEAST_RECENT => Method::LeastRecentlyReviewed,
-        pin if pin < (LEAST_RECENT + OLD_ENOUGH) => Method::OldEnough,
-        _ => Method::NewCards
+pub fn default_scheduler() -> Box<Scheduler> {
+    return match get_scheduler(DEFAULT_SCHEDULER) {
+        Some(scheduler) => scheduler,
+        None => unreachable!() // default scheduler is always registered
     };
-
-    return Ok(method);
 }
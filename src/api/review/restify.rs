@@ -5,7 +5,10 @@ extern crate rustc_serialize;
 
 use iron::status;
 use iron::prelude::*;
+use iron::mime::Mime;
 use router::Router;
+use urlencoded::{UrlEncodedQuery, QueryMap, UrlDecodingError};
+use rustc_serialize::json;
 
 use std::sync::Arc;
 use std::ops::Deref;
@@ -16,8 +19,13 @@ use ::api::decks::reviewable::{ReviewableDeck};
 use ::api::decks::restify::{deck_exists};
 use ::api::stashes::reviewable::{ReviewableStash};
 use ::api::stashes::restify::{stash_exists};
+use ::api::tags::reviewable::{ReviewableTag};
+use ::api::tags::restify::{tag_exists};
 use ::api::cards::restify::{get_card_by_id, card_exists};
-use ::api::review::{get_review_card, UpdateCardScore, ReviewableSelection};
+use ::api::review::{get_review_card, get_scheduler, schedulers, UpdateCardScore, ReviewableSelection};
+use ::api::review::{SetSchedulerRequest, SchedulerResponse, DEFAULT_SCHEDULER, SEED_CONFIG};
+use ::api::review::settings::{get_review_settings, ReviewSettings, SetReviewSettingsRequest};
+use ::api::review::sessions::{CreateReviewSession, ReviewSession, SessionStatus};
 
 
 // attach review REST endpoints to given router
@@ -145,6 +153,31 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                 // TODO: is this necessary?
             }
 
+            // if given review session, ensure it's active and of the given deck or stash
+            if let Some(session_id) = update_card_score_request.get_session() {
+
+                let session: ReviewSession = match get_active_session(grokdb, session_id) {
+                    Err(response) => {
+                        return response;
+                    },
+                    Ok(session) => session
+                };
+
+                if !update_card_score_request.is_in_session(&session) {
+
+                    let ref reason = format!("card should be reviewed within the deck or stash of review session: {}", session_id);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            }
+
             // update card score
             match grokdb.review.update_reviewed_card(card_id, update_card_score_request) {
                 Err(why) => {
@@ -175,11 +208,18 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
             // then that same card will be shown for review.
             let deck_selection = ReviewableDeck {
                 deck_id: 0, // doesn't matter which deck
-                grokdb: grokdb_arc.clone()
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
             };
             let stash_selection = ReviewableStash {
                 stash_id: 0, // doesn't matter which stash
-                grokdb: grokdb_arc.clone()
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+            let tag_selection = ReviewableTag {
+                tag_id: 0, // doesn't matter which tag
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
             };
 
             match deck_selection.remove_cached_card(card_id) {
@@ -220,16 +260,177 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                 }
             }
 
+            match tag_selection.remove_cached_card(card_id) {
+                Err(err) => {
+
+                    let ref reason = format!("{:?}", err);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: err.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {
+                    // cache removed
+                }
+            }
+
+            return get_card_by_id(grokdb.clone(), card_id);
+        }
+    });
+
+    router.post("/cards/:card_id/review/undo", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let card_id = req.extensions.get::<Router>().unwrap().find("card_id").unwrap();
+
+            let card_id: i64 = match card_id.parse::<u64>() {
+                Ok(card_id) => card_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure card exists
+            match card_exists(grokdb, card_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* card exists; continue */}
+            }
+
+            match grokdb.review.undo_review(card_id) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(false) => {
+
+                    let ref reason = format!("No review action to undo");
+                    let res_code = status::NotFound;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(true) => {/* review action undone */}
+            }
+
             return get_card_by_id(grokdb.clone(), card_id);
         }
     });
 
+    router.get("/cards/:card_id/review/history", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let card_id = req.extensions.get::<Router>().unwrap().find("card_id").unwrap();
+
+            let card_id: i64 = match card_id.parse::<u64>() {
+                Ok(card_id) => card_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure card exists
+            match card_exists(grokdb, card_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* card exists; continue */}
+            }
+
+            let list = match grokdb.review.get_review_log(card_id) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(list) => list
+            };
+
+            let ref list = list;
+
+            let response = json::encode(list).unwrap();
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response)));
+        }
+    });
+
     router.get("/decks/:deck_id/review", {
         let grokdb = grokdb.clone();
         let grokdb_arc = grokdb.clone();
         move |req: &mut Request| -> IronResult<Response> {
             let ref grokdb = grokdb.deref();
 
+            // parse queries before capturing :deck_id
+            let seed: Option<u64> = match get_review_seed(grokdb, req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(seed) => seed
+            };
+
+            let session_id: Option<i64> = match get_session_query(req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(session_id) => session_id
+            };
+
             let deck_id = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
 
             let deck_id: i64 = match deck_id.parse::<u64>() {
@@ -257,12 +458,39 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                 _ => {/* deck exists; continue */}
             }
 
+            // if given review session, honour its daily limits
+            let allow_new_cards: bool = match get_review_session(grokdb, session_id, Some(deck_id), None) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(None) => true,
+                Ok(Some(session)) => {
+
+                    if !session.can_review() {
+
+                        let ref reason = format!("Daily review limit reached");
+                        let res_code = status::NotFound;
+
+                        let err_response = ErrorResponse {
+                            status: res_code,
+                            developerMessage: reason,
+                            userMessage: reason,
+                        }.to_json();
+
+                        return Ok(Response::with((res_code, err_response)));
+                    }
+
+                    session.can_review_new_cards()
+                }
+            };
+
             let deck_selection = ReviewableDeck {
                 deck_id: deck_id,
-                grokdb: grokdb_arc.clone()
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: allow_new_cards
             };
 
-            match get_review_card(&deck_selection) {
+            match get_review_card(&deck_selection, seed) {
                 Err(why) => {
 
                     let ref reason = format!("{:?}", why);
@@ -304,6 +532,21 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
         move |req: &mut Request| -> IronResult<Response> {
             let ref grokdb = grokdb.deref();
 
+            // parse queries before capturing :stash_id
+            let seed: Option<u64> = match get_review_seed(grokdb, req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(seed) => seed
+            };
+
+            let session_id: Option<i64> = match get_session_query(req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(session_id) => session_id
+            };
+
             let stash_id = req.extensions.get::<Router>().unwrap().find("stash_id").unwrap();
 
             let stash_id: i64 = match stash_id.parse::<u64>() {
@@ -331,12 +574,39 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
                 _ => {/* stash exists; continue */}
             }
 
+            // if given review session, honour its daily limits
+            let allow_new_cards: bool = match get_review_session(grokdb, session_id, None, Some(stash_id)) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(None) => true,
+                Ok(Some(session)) => {
+
+                    if !session.can_review() {
+
+                        let ref reason = format!("Daily review limit reached");
+                        let res_code = status::NotFound;
+
+                        let err_response = ErrorResponse {
+                            status: res_code,
+                            developerMessage: reason,
+                            userMessage: reason,
+                        }.to_json();
+
+                        return Ok(Response::with((res_code, err_response)));
+                    }
+
+                    session.can_review_new_cards()
+                }
+            };
+
             let stash_selection = ReviewableStash {
                 stash_id: stash_id,
-                grokdb: grokdb_arc.clone()
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: allow_new_cards
             };
 
-            match get_review_card(&stash_selection) {
+            match get_review_card(&stash_selection, seed) {
                 Err(why) => {
 
                     let ref reason = format!("{:?}", why);
@@ -371,4 +641,1992 @@ pub fn restify(router: &mut Router, grokdb: GrokDB) {
             }
         }
     });
+
+    // tags have no review sessions; cards are reviewed under the default scheduler and settings
+    router.get("/tags/:tag/review", {
+        let grokdb = grokdb.clone();
+        let grokdb_arc = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            // parse queries before capturing :tag
+            let seed: Option<u64> = match get_review_seed(grokdb, req) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(seed) => seed
+            };
+
+            let tag: String = req.extensions.get::<Router>().unwrap().find("tag").unwrap().trim().to_string();
+
+            // ensure tag exists
+            let tag_id: i64 = match tag_exists(grokdb, &tag) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(tag_id) => tag_id
+            };
+
+            let tag_selection = ReviewableTag {
+                tag_id: tag_id,
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+
+            match get_review_card(&tag_selection, seed) {
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+
+                Ok(None) => {
+
+                    let ref reason = format!("No card to review");
+                    let res_code = status::NotFound;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+
+                Ok(Some(card_id)) => {
+                    return get_card_by_id(grokdb.clone(), card_id);
+                }
+            }
+        }
+    });
+
+    router.get("/review/schedulers", {
+        move |_: &mut Request| -> IronResult<Response> {
+
+            let scheduler_names: Vec<String> = schedulers().iter().map(|scheduler| -> String {
+                return scheduler.name().to_string();
+            }).collect();
+
+            let ref scheduler_names = scheduler_names;
+
+            let response = json::encode(scheduler_names).unwrap();
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response)));
+        }
+    });
+
+    router.get("/decks/:deck_id/review/scheduler", {
+        let grokdb = grokdb.clone();
+        let grokdb_arc = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let deck_id = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
+            }
+
+            let deck_selection = ReviewableDeck {
+                deck_id: deck_id,
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+
+            return get_scheduler_response(&deck_selection);
+        }
+    });
+
+    router.put("/decks/:deck_id/review/scheduler", {
+        let grokdb = grokdb.clone();
+        let grokdb_arc = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let set_scheduler_request = req.get::<bodyparser::Struct<SetSchedulerRequest>>();
+
+            let deck_id: &str = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
+            }
+
+            // parse json
+
+            let set_scheduler_request: SetSchedulerRequest = match set_scheduler_request {
+
+                Ok(Some(set_scheduler_request)) => set_scheduler_request,
+
+                Ok(None) => {
+
+                    let reason = "no JSON given";
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+
+                Err(err) => {
+
+                    let ref reason = format!("{:?}", err);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: err.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure scheduler is registered
+            if !set_scheduler_request.is_valid() {
+
+                let ref reason = format!("unknown scheduler: {}", set_scheduler_request.get_scheduler_name());
+                let res_code = status::BadRequest;
+
+                let err_response = ErrorResponse {
+                    status: res_code,
+                    developerMessage: reason,
+                    userMessage: reason,
+                }.to_json();
+
+                return Ok(Response::with((res_code, err_response)));
+            }
+
+            let deck_selection = ReviewableDeck {
+                deck_id: deck_id,
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+
+            match deck_selection.set_scheduler_name(&set_scheduler_request.get_scheduler_name()) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* scheduler chosen */}
+            }
+
+            return get_scheduler_response(&deck_selection);
+        }
+    });
+
+    router.delete("/decks/:deck_id/review/scheduler", {
+        let grokdb = grokdb.clone();
+        let grokdb_arc = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let deck_id = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
+            }
+
+            let deck_selection = ReviewableDeck {
+                deck_id: deck_id,
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+
+            match deck_selection.remove_scheduler_name() {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* scheduler removed */}
+            }
+
+            return get_scheduler_response(&deck_selection);
+        }
+    });
+
+    router.get("/stashes/:stash_id/review/scheduler", {
+        let grokdb = grokdb.clone();
+        let grokdb_arc = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let stash_id = req.extensions.get::<Router>().unwrap().find("stash_id").unwrap();
+
+            let stash_id: i64 = match stash_id.parse::<u64>() {
+                Ok(stash_id) => stash_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure stash exists
+            match stash_exists(grokdb, stash_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* stash exists; continue */}
+            }
+
+            let stash_selection = ReviewableStash {
+                stash_id: stash_id,
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+
+            return get_scheduler_response(&stash_selection);
+        }
+    });
+
+    router.put("/stashes/:stash_id/review/scheduler", {
+        let grokdb = grokdb.clone();
+        let grokdb_arc = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let set_scheduler_request = req.get::<bodyparser::Struct<SetSchedulerRequest>>();
+
+            let stash_id: &str = req.extensions.get::<Router>().unwrap().find("stash_id").unwrap();
+
+            let stash_id: i64 = match stash_id.parse::<u64>() {
+                Ok(stash_id) => stash_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure stash exists
+            match stash_exists(grokdb, stash_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* stash exists; continue */}
+            }
+
+            // parse json
+
+            let set_scheduler_request: SetSchedulerRequest = match set_scheduler_request {
+
+                Ok(Some(set_scheduler_request)) => set_scheduler_request,
+
+                Ok(None) => {
+
+                    let reason = "no JSON given";
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+
+                Err(err) => {
+
+                    let ref reason = format!("{:?}", err);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: err.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure scheduler is registered
+            if !set_scheduler_request.is_valid() {
+
+                let ref reason = format!("unknown scheduler: {}", set_scheduler_request.get_scheduler_name());
+                let res_code = status::BadRequest;
+
+                let err_response = ErrorResponse {
+                    status: res_code,
+                    developerMessage: reason,
+                    userMessage: reason,
+                }.to_json();
+
+                return Ok(Response::with((res_code, err_response)));
+            }
+
+            let stash_selection = ReviewableStash {
+                stash_id: stash_id,
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+
+            match stash_selection.set_scheduler_name(&set_scheduler_request.get_scheduler_name()) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* scheduler chosen */}
+            }
+
+            return get_scheduler_response(&stash_selection);
+        }
+    });
+
+    router.delete("/stashes/:stash_id/review/scheduler", {
+        let grokdb = grokdb.clone();
+        let grokdb_arc = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let stash_id = req.extensions.get::<Router>().unwrap().find("stash_id").unwrap();
+
+            let stash_id: i64 = match stash_id.parse::<u64>() {
+                Ok(stash_id) => stash_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure stash exists
+            match stash_exists(grokdb, stash_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* stash exists; continue */}
+            }
+
+            let stash_selection = ReviewableStash {
+                stash_id: stash_id,
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+
+            match stash_selection.remove_scheduler_name() {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* scheduler removed */}
+            }
+
+            return get_scheduler_response(&stash_selection);
+        }
+    });
+
+    router.get("/decks/:deck_id/review/settings", {
+        let grokdb = grokdb.clone();
+        let grokdb_arc = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let deck_id = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
+            }
+
+            let deck_selection = ReviewableDeck {
+                deck_id: deck_id,
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+
+            return get_review_settings_response(&deck_selection);
+        }
+    });
+
+    router.put("/decks/:deck_id/review/settings", {
+        let grokdb = grokdb.clone();
+        let grokdb_arc = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let set_settings_request = req.get::<bodyparser::Struct<SetReviewSettingsRequest>>();
+
+            let deck_id: &str = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
+            }
+
+            // parse json
+
+            let set_settings_request: SetReviewSettingsRequest = match set_settings_request {
+
+                Ok(Some(set_settings_request)) => set_settings_request,
+
+                Ok(None) => {
+
+                    let reason = "no JSON given";
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+
+                Err(err) => {
+
+                    let ref reason = format!("{:?}", err);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: err.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            let deck_selection = ReviewableDeck {
+                deck_id: deck_id,
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+
+            // settings not given are taken from the current settings of the deck
+            let current_settings: ReviewSettings = match get_review_settings(&deck_selection) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(settings) => settings
+            };
+
+            let settings: ReviewSettings = set_settings_request.merge(&current_settings);
+
+            // validate settings
+            match settings.validate() {
+                Err(reason) => {
+
+                    let ref reason = reason;
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* settings are valid */}
+            }
+
+            match deck_selection.set_review_settings(&settings) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* settings stored */}
+            }
+
+            return get_review_settings_response(&deck_selection);
+        }
+    });
+
+    router.delete("/decks/:deck_id/review/settings", {
+        let grokdb = grokdb.clone();
+        let grokdb_arc = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let deck_id = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
+            }
+
+            let deck_selection = ReviewableDeck {
+                deck_id: deck_id,
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+
+            match deck_selection.remove_review_settings() {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* settings removed */}
+            }
+
+            return get_review_settings_response(&deck_selection);
+        }
+    });
+
+    router.get("/stashes/:stash_id/review/settings", {
+        let grokdb = grokdb.clone();
+        let grokdb_arc = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let stash_id = req.extensions.get::<Router>().unwrap().find("stash_id").unwrap();
+
+            let stash_id: i64 = match stash_id.parse::<u64>() {
+                Ok(stash_id) => stash_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure stash exists
+            match stash_exists(grokdb, stash_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* stash exists; continue */}
+            }
+
+            let stash_selection = ReviewableStash {
+                stash_id: stash_id,
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+
+            return get_review_settings_response(&stash_selection);
+        }
+    });
+
+    router.put("/stashes/:stash_id/review/settings", {
+        let grokdb = grokdb.clone();
+        let grokdb_arc = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let set_settings_request = req.get::<bodyparser::Struct<SetReviewSettingsRequest>>();
+
+            let stash_id: &str = req.extensions.get::<Router>().unwrap().find("stash_id").unwrap();
+
+            let stash_id: i64 = match stash_id.parse::<u64>() {
+                Ok(stash_id) => stash_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure stash exists
+            match stash_exists(grokdb, stash_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* stash exists; continue */}
+            }
+
+            // parse json
+
+            let set_settings_request: SetReviewSettingsRequest = match set_settings_request {
+
+                Ok(Some(set_settings_request)) => set_settings_request,
+
+                Ok(None) => {
+
+                    let reason = "no JSON given";
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+
+                Err(err) => {
+
+                    let ref reason = format!("{:?}", err);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: err.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            let stash_selection = ReviewableStash {
+                stash_id: stash_id,
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+
+            // settings not given are taken from the current settings of the stash
+            let current_settings: ReviewSettings = match get_review_settings(&stash_selection) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(settings) => settings
+            };
+
+            let settings: ReviewSettings = set_settings_request.merge(&current_settings);
+
+            // validate settings
+            match settings.validate() {
+                Err(reason) => {
+
+                    let ref reason = reason;
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* settings are valid */}
+            }
+
+            match stash_selection.set_review_settings(&settings) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* settings stored */}
+            }
+
+            return get_review_settings_response(&stash_selection);
+        }
+    });
+
+    router.delete("/stashes/:stash_id/review/settings", {
+        let grokdb = grokdb.clone();
+        let grokdb_arc = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let stash_id = req.extensions.get::<Router>().unwrap().find("stash_id").unwrap();
+
+            let stash_id: i64 = match stash_id.parse::<u64>() {
+                Ok(stash_id) => stash_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure stash exists
+            match stash_exists(grokdb, stash_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* stash exists; continue */}
+            }
+
+            let stash_selection = ReviewableStash {
+                stash_id: stash_id,
+                grokdb: grokdb_arc.clone(),
+                allow_new_cards: true
+            };
+
+            match stash_selection.remove_review_settings() {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* settings removed */}
+            }
+
+            return get_review_settings_response(&stash_selection);
+        }
+    });
+
+    router.post("/decks/:deck_id/review/sessions", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let create_session_request = req.get::<bodyparser::Struct<CreateReviewSession>>();
+
+            let deck_id: &str = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
+            }
+
+            // parse json
+
+            let create_session_request: CreateReviewSession = match create_session_request {
+
+                Ok(Some(create_session_request)) => create_session_request,
+
+                Ok(None) => {
+                    // limits are optional
+                    CreateReviewSession::new()
+                },
+
+                Err(err) => {
+
+                    let ref reason = format!("{:?}", err);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: err.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            if !create_session_request.is_valid() {
+
+                let ref reason = format!("daily limits should be at least 0");
+                let res_code = status::BadRequest;
+
+                let err_response = ErrorResponse {
+                    status: res_code,
+                    developerMessage: reason,
+                    userMessage: reason,
+                }.to_json();
+
+                return Ok(Response::with((res_code, err_response)));
+            }
+
+            let session_id: i64 = match grokdb.review.create_session(Some(deck_id), None, &create_session_request) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(session_id) => session_id
+            };
+
+            return get_session_response(grokdb, session_id);
+        }
+    });
+
+    router.post("/stashes/:stash_id/review/sessions", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let create_session_request = req.get::<bodyparser::Struct<CreateReviewSession>>();
+
+            let stash_id: &str = req.extensions.get::<Router>().unwrap().find("stash_id").unwrap();
+
+            let stash_id: i64 = match stash_id.parse::<u64>() {
+                Ok(stash_id) => stash_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure stash exists
+            match stash_exists(grokdb, stash_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* stash exists; continue */}
+            }
+
+            // parse json
+
+            let create_session_request: CreateReviewSession = match create_session_request {
+
+                Ok(Some(create_session_request)) => create_session_request,
+
+                Ok(None) => {
+                    // limits are optional
+                    CreateReviewSession::new()
+                },
+
+                Err(err) => {
+
+                    let ref reason = format!("{:?}", err);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: err.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            if !create_session_request.is_valid() {
+
+                let ref reason = format!("daily limits should be at least 0");
+                let res_code = status::BadRequest;
+
+                let err_response = ErrorResponse {
+                    status: res_code,
+                    developerMessage: reason,
+                    userMessage: reason,
+                }.to_json();
+
+                return Ok(Response::with((res_code, err_response)));
+            }
+
+            let session_id: i64 = match grokdb.review.create_session(None, Some(stash_id), &create_session_request) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(session_id) => session_id
+            };
+
+            return get_session_response(grokdb, session_id);
+        }
+    });
+
+    router.get("/review/sessions/:session_id", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let session_id = req.extensions.get::<Router>().unwrap().find("session_id").unwrap();
+
+            let session_id: i64 = match session_id.parse::<u64>() {
+                Ok(session_id) => session_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure session exists
+            match session_exists(grokdb, session_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* session exists; continue */}
+            }
+
+            return get_session_response(grokdb, session_id);
+        }
+    });
+
+    router.post("/review/sessions/:session_id/finish", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let session_id = req.extensions.get::<Router>().unwrap().find("session_id").unwrap();
+
+            let session_id: i64 = match session_id.parse::<u64>() {
+                Ok(session_id) => session_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure session exists
+            match session_exists(grokdb, session_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* session exists; continue */}
+            }
+
+            match grokdb.review.end_session(session_id, SessionStatus::Finished) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(false) => {
+
+                    let ref reason = format!("review session is no longer active: {}", session_id);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(true) => {/* session ended */}
+            }
+
+            return get_session_response(grokdb, session_id);
+        }
+    });
+
+    router.post("/review/sessions/:session_id/abandon", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let session_id = req.extensions.get::<Router>().unwrap().find("session_id").unwrap();
+
+            let session_id: i64 = match session_id.parse::<u64>() {
+                Ok(session_id) => session_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure session exists
+            match session_exists(grokdb, session_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* session exists; continue */}
+            }
+
+            match grokdb.review.end_session(session_id, SessionStatus::Abandoned) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(false) => {
+
+                    let ref reason = format!("review session is no longer active: {}", session_id);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(true) => {/* session ended */}
+            }
+
+            return get_session_response(grokdb, session_id);
+        }
+    });
+
+    router.post("/review/fsrs/optimize", {
+        let grokdb = grokdb.clone();
+        move |_: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let response = match grokdb.review.optimize_fsrs() {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(response) => response.to_json()
+            };
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response)));
+        }
+    });
+
+    router.get("/decks/:deck_id/retrievability", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let deck_id = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
+            }
+
+            let list = match grokdb.review.get_deck_retrievability(deck_id) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(list) => list
+            };
+
+            let ref list = list;
+
+            let response = json::encode(list).unwrap();
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response)));
+        }
+    });
+
+    router.get("/decks/:deck_id/leeches", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let deck_id = req.extensions.get::<Router>().unwrap().find("deck_id").unwrap();
+
+            let deck_id: i64 = match deck_id.parse::<u64>() {
+                Ok(deck_id) => deck_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure deck exists
+            match deck_exists(grokdb, deck_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* deck exists; continue */}
+            }
+
+            let list = match grokdb.review.get_deck_leeches(deck_id) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(list) => list
+            };
+
+            let ref list = list;
+
+            let response = json::encode(list).unwrap();
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response)));
+        }
+    });
+
+    router.delete("/cards/:card_id/leech", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let card_id = req.extensions.get::<Router>().unwrap().find("card_id").unwrap();
+
+            let card_id: i64 = match card_id.parse::<u64>() {
+                Ok(card_id) => card_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure card exists
+            match card_exists(grokdb, card_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* card exists; continue */}
+            }
+
+            match grokdb.review.is_leech(card_id) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(false) => {
+
+                    let ref reason = format!("Card is not a leech");
+                    let res_code = status::NotFound;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(true) => {/* card is a leech */}
+            }
+
+            match grokdb.review.clear_leech(card_id) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* leech cleared */}
+            }
+
+            return get_card_by_id(grokdb.clone(), card_id);
+        }
+    });
+}
+
+/* helpers */
+
+fn get_review_settings_response<T>(selection: &T) -> IronResult<Response>
+    where T: ReviewableSelection {
+
+    let response = match get_review_settings(selection) {
+        Err(why) => {
+            // why: QueryError
+
+            let ref reason = format!("{:?}", why);
+            let res_code = status::InternalServerError;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: why.description(),
+            }.to_json();
+
+            return Ok(Response::with((res_code, err_response)));
+        },
+        Ok(settings) => settings.to_json()
+    };
+
+    let content_type = "application/json".parse::<Mime>().unwrap();
+
+    return Ok(Response::with((content_type, status::Ok, response)));
+}
+
+fn get_scheduler_response<T>(selection: &T) -> IronResult<Response>
+    where T: ReviewableSelection {
+
+    let scheduler_name: String = match selection.get_scheduler_name() {
+        Err(why) => {
+            // why: QueryError
+
+            let ref reason = format!("{:?}", why);
+            let res_code = status::InternalServerError;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: why.description(),
+            }.to_json();
+
+            return Ok(Response::with((res_code, err_response)));
+        },
+        Ok(None) => DEFAULT_SCHEDULER.to_string(),
+        Ok(Some(scheduler_name)) => {
+
+            // fallback to default scheduler if chosen scheduler is no longer registered
+            match get_scheduler(&scheduler_name) {
+                None => DEFAULT_SCHEDULER.to_string(),
+                Some(_) => scheduler_name
+            }
+        }
+    };
+
+    let response = SchedulerResponse {
+        scheduler: scheduler_name
+    }.to_json();
+
+    let content_type = "application/json".parse::<Mime>().unwrap();
+
+    return Ok(Response::with((content_type, status::Ok, response)));
+}
+
+// fetch seed for choosing the card to review.
+// the seed is chosen from (in order of precedence):
+// - seed query (e.g. /decks/:deck_id/review?seed=42)
+// - review_seed config setting
+// - --seed server flag
+// if none of these are given, the card is chosen randomly.
+fn get_review_seed(grokdb: &GrokDB, req: &mut Request) -> Result<Option<u64>, IronResult<Response>> {
+
+    match req.get_ref::<UrlEncodedQuery>() {
+
+        Ok(ref hashmap) => {
+
+            let hashmap: &QueryMap = hashmap;
+
+            if hashmap.contains_key("seed") {
+
+                let maybe_seed: &Vec<String> = hashmap.get("seed").unwrap();
+
+                if maybe_seed.len() > 0 {
+
+                    let ref seed: String = maybe_seed[0];
+
+                    match seed.trim().parse::<u64>() {
+                        Ok(seed) => {
+                            return Ok(Some(seed));
+                        },
+                        Err(why) => {
+                            let ref reason = format!("invalid seed query");
+                            let res_code = status::BadRequest;
+
+                            let err_response = ErrorResponse {
+                                status: res_code,
+                                developerMessage: why.description(),
+                                userMessage: reason,
+                            }.to_json();
+
+                            return Err(Ok(Response::with((res_code, err_response))));
+                        }
+                    }
+                }
+            }
+        },
+
+        Err(UrlDecodingError::EmptyQuery) => {/* no seed query */},
+
+        Err(why) => {
+
+            let ref reason = format!("{:?}", why);
+            let res_code = status::BadRequest;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: why.description(),
+            }.to_json();
+
+            return Err(Ok(Response::with((res_code, err_response))));
+        }
+    };
+
+    match grokdb.configs.exists(SEED_CONFIG) {
+        Err(why) => {
+            // why: QueryError
+
+            let ref reason = format!("{:?}", why);
+            let res_code = status::InternalServerError;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: why.description(),
+            }.to_json();
+
+            return Err(Ok(Response::with((res_code, err_response))));
+        },
+        Ok(false) => {/* no seed config */},
+        Ok(true) => {
+
+            let config = match grokdb.configs.get(SEED_CONFIG) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Err(Ok(Response::with((res_code, err_response))));
+                },
+                Ok(config) => config
+            };
+
+            match config.get_value().trim().parse::<u64>() {
+                Ok(seed) => {
+                    return Ok(Some(seed));
+                },
+                Err(why) => {
+                    let ref reason = format!("invalid {} config setting", SEED_CONFIG);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: why.description(),
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Err(Ok(Response::with((res_code, err_response))));
+                }
+            }
+        }
+    }
+
+    return Ok(grokdb.review_seed);
+}
+
+pub fn session_exists(grokdb: &GrokDB, session_id: i64) -> Result<(), IronResult<Response>> {
+
+    match grokdb.review.session_exists(session_id) {
+
+        Err(why) => {
+            // why: QueryError
+
+            let ref reason = format!("{:?}", why);
+            let res_code = status::InternalServerError;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: why.description(),
+            }.to_json();
+
+            let res = Ok(Response::with((res_code, err_response)));
+            return Err(res);
+        },
+
+        Ok(false) => {
+            let ref reason = format!("given review session id does not exist: {}", session_id);
+            let res_code = status::NotFound;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: reason,
+            }.to_json();
+
+            let res = Ok(Response::with((res_code, err_response)));
+            return Err(res);
+        },
+
+        _ => {
+            return Ok(());
+        }
+    }
+}
+
+fn get_session_response(grokdb: &GrokDB, session_id: i64) -> IronResult<Response> {
+
+    let response = match grokdb.review.get_session(session_id) {
+        Err(why) => {
+            // why: QueryError
+
+            let ref reason = format!("{:?}", why);
+            let res_code = status::InternalServerError;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: why.description(),
+            }.to_json();
+
+            return Ok(Response::with((res_code, err_response)));
+        },
+        Ok(session) => session.to_json()
+    };
+
+    let content_type = "application/json".parse::<Mime>().unwrap();
+
+    return Ok(Response::with((content_type, status::Ok, response)));
+}
+
+// fetch review session, and ensure it's still active
+fn get_active_session(grokdb: &GrokDB, session_id: i64) -> Result<ReviewSession, IronResult<Response>> {
+
+    // ensure session exists
+    match session_exists(grokdb, session_id) {
+        Err(response) => {
+            return Err(response);
+        },
+        _ => {/* session exists; continue */}
+    }
+
+    let session: ReviewSession = match grokdb.review.get_session(session_id) {
+        Err(why) => {
+            // why: QueryError
+
+            let ref reason = format!("{:?}", why);
+            let res_code = status::InternalServerError;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: why.description(),
+            }.to_json();
+
+            return Err(Ok(Response::with((res_code, err_response))));
+        },
+        Ok(session) => session
+    };
+
+    if !session.is_active() {
+
+        let ref reason = format!("review session is no longer active: {}", session_id);
+        let res_code = status::BadRequest;
+
+        let err_response = ErrorResponse {
+            status: res_code,
+            developerMessage: reason,
+            userMessage: reason,
+        }.to_json();
+
+        return Err(Ok(Response::with((res_code, err_response))));
+    }
+
+    return Ok(session);
+}
+
+// parse session query (e.g. /decks/:deck_id/review?session=1)
+fn get_session_query(req: &mut Request) -> Result<Option<i64>, IronResult<Response>> {
+
+    match req.get_ref::<UrlEncodedQuery>() {
+
+        Ok(ref hashmap) => {
+
+            let hashmap: &QueryMap = hashmap;
+
+            if !hashmap.contains_key("session") {
+                return Ok(None);
+            }
+
+            let maybe_session: &Vec<String> = hashmap.get("session").unwrap();
+
+            if maybe_session.len() <= 0 {
+                return Ok(None);
+            }
+
+            let ref session_id: String = maybe_session[0];
+
+            match session_id.trim().parse::<u64>() {
+                Ok(session_id) => {
+                    return Ok(Some(session_id as i64));
+                },
+                Err(why) => {
+                    let ref reason = format!("invalid session query");
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: why.description(),
+                        userMessage: reason,
+                    }.to_json();
+
+                    return Err(Ok(Response::with((res_code, err_response))));
+                }
+            }
+        },
+
+        Err(UrlDecodingError::EmptyQuery) => {
+            return Ok(None);
+        },
+
+        Err(why) => {
+
+            let ref reason = format!("{:?}", why);
+            let res_code = status::BadRequest;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: why.description(),
+            }.to_json();
+
+            return Err(Ok(Response::with((res_code, err_response))));
+        }
+    };
+}
+
+// fetch the given review session (if any).
+// the session should be active, and be of the given deck or stash.
+fn get_review_session(grokdb: &GrokDB, session_id: Option<i64>,
+    deck_id: Option<i64>, stash_id: Option<i64>) -> Result<Option<ReviewSession>, IronResult<Response>> {
+
+    let session_id: i64 = match session_id {
+        None => {
+            return Ok(None);
+        },
+        Some(session_id) => session_id
+    };
+
+    let session: ReviewSession = match get_active_session(grokdb, session_id) {
+        Err(response) => {
+            return Err(response);
+        },
+        Ok(session) => session
+    };
+
+    if !session.is_for(deck_id, stash_id) {
+
+        let ref reason = format!("review session is not of the deck or stash being reviewed: {}", session_id);
+        let res_code = status::BadRequest;
+
+        let err_response = ErrorResponse {
+            status: res_code,
+            developerMessage: reason,
+            userMessage: reason,
+        }.to_json();
+
+        return Err(Ok(Response::with((res_code, err_response))));
+    }
+
+    return Ok(Some(session));
 }
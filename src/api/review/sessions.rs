@@ -0,0 +1,439 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+use rusqlite::Connection;
+use rusqlite::types::ToSql;
+use rustc_serialize::json;
+
+use ::database::{DB, QueryError, get_config};
+use ::api::review::ReviewAPI;
+
+
+// a review session is a study session of a deck or a stash.
+//
+// each session has daily limits on the number of new cards and the total number of cards
+// reviewed. the limits apply to every review of the cards of the deck or stash on the same day,
+// whether or not they were reviewed within a session (see CardsScoreHistory).
+// a new card is a card that was never reviewed before that day.
+
+pub static DEFAULT_NEW_CARDS_PER_DAY: i64 = 20;
+pub static DEFAULT_REVIEWS_PER_DAY: i64 = 200;
+
+// config settings of the default daily limits
+pub static NEW_CARDS_PER_DAY_CONFIG: &'static str = "new_cards_per_day";
+pub static REVIEWS_PER_DAY_CONFIG: &'static str = "reviews_per_day";
+
+pub enum SessionStatus {
+    Active,
+    Finished,
+    Abandoned
+}
+
+impl SessionStatus {
+
+    pub fn to_str(&self) -> &'static str {
+        return match *self {
+            SessionStatus::Active => "active",
+            SessionStatus::Finished => "finished",
+            SessionStatus::Abandoned => "abandoned"
+        };
+    }
+}
+
+#[derive(Debug, Clone, RustcDecodable)]
+pub struct CreateReviewSession {
+    new_cards_per_day: Option<i64>,
+    reviews_per_day: Option<i64>
+}
+
+impl CreateReviewSession {
+
+    pub fn new() -> CreateReviewSession {
+        return CreateReviewSession {
+            new_cards_per_day: None,
+            reviews_per_day: None
+        };
+    }
+
+    pub fn is_valid(&self) -> bool {
+
+        if let Some(new_cards_per_day) = self.new_cards_per_day {
+            if new_cards_per_day < 0 {
+                return false;
+            }
+        }
+
+        if let Some(reviews_per_day) = self.reviews_per_day {
+            if reviews_per_day < 0 {
+                return false;
+            }
+        }
+
+        return true;
+    }
+}
+
+#[derive(Debug, Clone, RustcEncodable)]
+pub struct ReviewSession {
+    session_id: i64,
+
+    // either deck or stash is given (but not both)
+    deck: Option<i64>,
+    stash: Option<i64>,
+
+    // active, finished or abandoned
+    status: String,
+
+    // daily limits
+    new_cards_per_day: i64,
+    reviews_per_day: i64,
+
+    // progress of this session
+    new_cards_reviewed: i64,
+    cards_reviewed: i64,
+
+    // remaining for today, for all reviews of the deck or stash
+    new_cards_left: i64,
+    reviews_left: i64,
+
+    created_at: i64, // unix timestamp
+    updated_at: i64, // unix timestamp
+    finished_at: Option<i64> // unix timestamp
+}
+
+impl ReviewSession {
+
+    pub fn is_active(&self) -> bool {
+        return self.status == SessionStatus::Active.to_str();
+    }
+
+    // check if the session is for the given deck or stash
+    pub fn is_for(&self, deck_id: Option<i64>, stash_id: Option<i64>) -> bool {
+        return self.deck == deck_id && self.stash == stash_id;
+    }
+
+    pub fn can_review(&self) -> bool {
+        return self.reviews_left > 0;
+    }
+
+    pub fn can_review_new_cards(&self) -> bool {
+        return self.new_cards_left > 0 && self.reviews_left > 0;
+    }
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+}
+
+impl ReviewAPI {
+
+    pub fn session_exists(&self, session_id: i64) -> Result<bool, QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT COUNT(1)
+            FROM ReviewSessions
+            WHERE session_id = $1 LIMIT 1;
+        ");
+
+        let session_exists = db_conn.query_row(query, &[&session_id], |row| -> bool {
+            let count: i64 = row.get(0);
+            return count >= 1;
+        });
+
+        match session_exists {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(session_exists) => {
+                return Ok(session_exists);
+            }
+        };
+    }
+
+    // create a session for either a deck or a stash.
+    // limits not given are taken from configs; otherwise, from defaults.
+    pub fn create_session(&self, deck_id: Option<i64>, stash_id: Option<i64>,
+        request: &CreateReviewSession) -> Result<i64, QueryError> {
+
+        assert!(deck_id.is_some() != stash_id.is_some());
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let new_cards_per_day: i64 = match request.new_cards_per_day {
+            Some(limit) => limit,
+            None => {
+                match get_config_limit(db_conn, NEW_CARDS_PER_DAY_CONFIG) {
+                    Err(why) => {
+                        return Err(why);
+                    },
+                    Ok(limit) => limit.unwrap_or(DEFAULT_NEW_CARDS_PER_DAY)
+                }
+            }
+        };
+
+        let reviews_per_day: i64 = match request.reviews_per_day {
+            Some(limit) => limit,
+            None => {
+                match get_config_limit(db_conn, REVIEWS_PER_DAY_CONFIG) {
+                    Err(why) => {
+                        return Err(why);
+                    },
+                    Ok(limit) => limit.unwrap_or(DEFAULT_REVIEWS_PER_DAY)
+                }
+            }
+        };
+
+        let ref query = format!("
+            INSERT INTO ReviewSessions(deck, stash, status, new_cards_per_day, reviews_per_day)
+            VALUES (:deck_id, :stash_id, :status, :new_cards_per_day, :reviews_per_day);
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":deck_id", &deck_id),
+            (":stash_id", &stash_id),
+            (":status", &(SessionStatus::Active.to_str())),
+            (":new_cards_per_day", &new_cards_per_day),
+            (":reviews_per_day", &reviews_per_day)
+        ];
+
+        match db_conn.execute_named(query, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        let rowid = db_conn.last_insert_rowid();
+
+        return Ok(rowid);
+    }
+
+    pub fn get_session(&self, session_id: i64) -> Result<ReviewSession, QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                session_id, deck, stash, status, new_cards_per_day, reviews_per_day,
+                new_cards_reviewed, cards_reviewed, created_at, updated_at, finished_at
+            FROM ReviewSessions
+            WHERE session_id = :session_id
+            LIMIT 1;
+        ");
+
+        let results = db_conn.query_row_named(query, &[(":session_id", &session_id)], |row| -> ReviewSession {
+            return ReviewSession {
+                session_id: row.get(0),
+                deck: row.get(1),
+                stash: row.get(2),
+                status: row.get(3),
+                new_cards_per_day: row.get(4),
+                reviews_per_day: row.get(5),
+                new_cards_reviewed: row.get(6),
+                cards_reviewed: row.get(7),
+                new_cards_left: 0,
+                reviews_left: 0,
+                created_at: row.get(8),
+                updated_at: row.get(9),
+                finished_at: row.get(10)
+            };
+        });
+
+        let session: ReviewSession = match results {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(session) => session
+        };
+
+        // count cards of the deck or stash reviewed today (undone reviews are excluded)
+
+        let cards_query: &str = match session.deck {
+            Some(_) => {
+                "
+                SELECT c.card_id
+                FROM DecksClosure AS dc
+
+                INNER JOIN Cards AS c
+                ON c.deck = dc.descendent
+
+                WHERE dc.ancestor = :container_id
+                "
+            },
+            None => {
+                "
+                SELECT card FROM StashCards WHERE stash = :container_id
+                "
+            }
+        };
+
+        let ref query_today = format!("
+            SELECT
+                COUNT(1),
+                COUNT(DISTINCT CASE WHEN h.card NOT IN (
+                    SELECT card
+                    FROM CardsScoreHistory
+                    WHERE
+                        is_review_event = 1
+                    AND
+                        occurred_at < {start_of_day}
+                    AND
+                        history_id NOT IN (SELECT history FROM CardsScoreUndo WHERE reverted = 1)
+                ) THEN h.card END)
+            FROM CardsScoreHistory AS h
+            WHERE
+                h.card IN ({cards_query})
+            AND
+                h.is_review_event = 1
+            AND
+                h.occurred_at >= {start_of_day}
+            AND
+                h.history_id NOT IN (SELECT history FROM CardsScoreUndo WHERE reverted = 1);
+        ",
+        start_of_day = "strftime('%s', 'now', 'localtime', 'start of day', 'utc')",
+        cards_query = cards_query);
+
+        let container_id: i64 = match session.deck {
+            Some(deck_id) => deck_id,
+            None => session.stash.unwrap()
+        };
+
+        let params: &[(&str, &ToSql)] = &[
+            (":container_id", &container_id)
+        ];
+
+        let results = db_conn.query_row_named(query_today, params, |row| -> (i64, i64) {
+            return (row.get(1), row.get(0));
+        });
+
+        let (new_cards_today, cards_today): (i64, i64) = match results {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_today.clone(),
+                };
+                return Err(err);
+            },
+            Ok(counts) => counts
+        };
+
+        let mut session = session;
+
+        session.new_cards_left = (session.new_cards_per_day - new_cards_today).max(0);
+        session.reviews_left = (session.reviews_per_day - cards_today).max(0);
+
+        return Ok(session);
+    }
+
+    // end an active session.
+    // returns false if the session is no longer active.
+    pub fn end_session(&self, session_id: i64, status: SessionStatus) -> Result<bool, QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query = format!("
+            UPDATE ReviewSessions
+            SET
+                status = :status,
+                updated_at = strftime('%s', 'now'),
+                finished_at = strftime('%s', 'now')
+            WHERE
+                session_id = :session_id
+            AND
+                status = :active;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":status", &(status.to_str())),
+            (":session_id", &session_id),
+            (":active", &(SessionStatus::Active.to_str()))
+        ];
+
+        match db_conn.execute_named(query, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(num_rows) => {
+                return Ok(num_rows > 0);
+            }
+        }
+    }
+}
+
+// record a reviewed card towards the progress of an active session.
+// caller should hold the db connection lock.
+pub fn record_review(db_conn: &Connection, session_id: i64, is_new_card: bool) -> Result<(), QueryError> {
+
+    let new_card: i64 = if is_new_card { 1 } else { 0 };
+
+    let ref query = format!("
+        UPDATE ReviewSessions
+        SET
+            cards_reviewed = cards_reviewed + 1,
+            new_cards_reviewed = new_cards_reviewed + :new_card,
+            updated_at = strftime('%s', 'now')
+        WHERE
+            session_id = :session_id
+        AND
+            status = :active;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":new_card", &new_card),
+        (":session_id", &session_id),
+        (":active", &(SessionStatus::Active.to_str()))
+    ];
+
+    match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
+
+// ignore malformed limits.
+// caller should hold the db connection lock.
+fn get_config_limit(db_conn: &Connection, setting: &str) -> Result<Option<i64>, QueryError> {
+    return match try!(get_config(db_conn, setting)) {
+        None => Ok(None),
+        Some(value) => {
+            match value.trim().parse::<i64>() {
+                Ok(limit) if limit >= 0 => Ok(Some(limit)),
+                _ => Ok(None)
+            }
+        }
+    };
+}
@@ -0,0 +1,135 @@
+extern crate rustc_serialize;
+
+use rustc_serialize::json;
+
+use ::database::QueryError;
+use ::api::review::ReviewableSelection;
+
+
+// default review settings.
+// new_cards, old_enough and least_recent are the probabilities of choosing the next
+// card by the respective method; these should add up to 1.
+pub static DEFAULT_NEW_CARDS: f64 = 0.15;
+pub static DEFAULT_OLD_ENOUGH: f64 = 0.3;
+pub static DEFAULT_LEAST_RECENT: f64 = 0.55;
+pub static DEFAULT_MIN_SCORE: f64 = 0.3;
+pub static DEFAULT_AGE_IN_HOURS: i64 = 3;
+pub static DEFAULT_PURGATORY_SIZE: f64 = 0.2;
+
+// tolerance when checking that the method probabilities add up to 1
+static EPSILON: f64 = 0.000001;
+
+// tuning of how cards are chosen for review (see review::classic).
+// chosen per deck (inherited by the deck's descendents) or per stash.
+#[derive(Debug, Clone, RustcEncodable)]
+pub struct ReviewSettings {
+
+    // probabilities of each method for choosing the next card
+    pub new_cards: f64,
+    pub old_enough: f64,
+    pub least_recent: f64,
+
+    // score cutoff for randomly discarding low scoring cards
+    pub min_score: f64,
+
+    // minimum hours since a card was last seen for it to be old enough to be reviewed
+    pub age_in_hours: i64,
+
+    // fraction of the least recently reviewed cards to choose from
+    pub purgatory_size: f64
+}
+
+impl ReviewSettings {
+
+    pub fn new() -> ReviewSettings {
+        return ReviewSettings {
+            new_cards: DEFAULT_NEW_CARDS,
+            old_enough: DEFAULT_OLD_ENOUGH,
+            least_recent: DEFAULT_LEAST_RECENT,
+            min_score: DEFAULT_MIN_SCORE,
+            age_in_hours: DEFAULT_AGE_IN_HOURS,
+            purgatory_size: DEFAULT_PURGATORY_SIZE
+        };
+    }
+
+    // returns reason if settings are invalid
+    pub fn validate(&self) -> Result<(), String> {
+
+        let probabilities: [(&str, f64); 3] = [
+            ("new_cards", self.new_cards),
+            ("old_enough", self.old_enough),
+            ("least_recent", self.least_recent)
+        ];
+
+        for &(name, probability) in probabilities.iter() {
+            if probability < 0f64 || probability > 1f64 {
+                return Err(format!("{} should be within 0 and 1", name));
+            }
+        }
+
+        // least recently reviewed cards is the fallback method when there are no new cards
+        // nor cards old enough to be reviewed; it should always have a chance to be chosen.
+        if self.least_recent <= 0f64 {
+            return Err(format!("least_recent should be greater than 0"));
+        }
+
+        let total: f64 = self.new_cards + self.old_enough + self.least_recent;
+
+        if (total - 1f64).abs() > EPSILON {
+            return Err(format!("new_cards, old_enough and least_recent should add up to 1"));
+        }
+
+        if self.min_score < 0f64 || self.min_score > 1f64 {
+            return Err(format!("min_score should be within 0 and 1"));
+        }
+
+        if self.age_in_hours < 0 {
+            return Err(format!("age_in_hours should be at least 0"));
+        }
+
+        if self.purgatory_size <= 0f64 || self.purgatory_size > 1f64 {
+            return Err(format!("purgatory_size should be greater than 0 and at most 1"));
+        }
+
+        return Ok(());
+    }
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+}
+
+#[derive(Debug, Clone, RustcDecodable)]
+pub struct SetReviewSettingsRequest {
+    new_cards: Option<f64>,
+    old_enough: Option<f64>,
+    least_recent: Option<f64>,
+    min_score: Option<f64>,
+    age_in_hours: Option<i64>,
+    purgatory_size: Option<f64>
+}
+
+impl SetReviewSettingsRequest {
+
+    // settings not given are taken from current settings
+    pub fn merge(&self, current: &ReviewSettings) -> ReviewSettings {
+        return ReviewSettings {
+            new_cards: self.new_cards.unwrap_or(current.new_cards),
+            old_enough: self.old_enough.unwrap_or(current.old_enough),
+            least_recent: self.least_recent.unwrap_or(current.least_recent),
+            min_score: self.min_score.unwrap_or(current.min_score),
+            age_in_hours: self.age_in_hours.unwrap_or(current.age_in_hours),
+            purgatory_size: self.purgatory_size.unwrap_or(current.purgatory_size)
+        };
+    }
+}
+
+// fetch the review settings of the selection; fallback to default settings
+pub fn get_review_settings(selection: &ReviewableSelection) -> Result<ReviewSettings, QueryError> {
+
+    return match selection.get_review_settings() {
+        Err(why) => Err(why),
+        Ok(None) => Ok(ReviewSettings::new()),
+        Ok(Some(settings)) => Ok(settings)
+    };
+}
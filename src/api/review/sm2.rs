@@ -0,0 +1,314 @@
+extern crate rusqlite;
+
+use rand::StdRng;
+use rusqlite::Connection;
+use rusqlite::types::ToSql;
+
+use ::database::QueryError;
+use ::api::review::{ReviewableSelection, Scheduler};
+use ::api::review::classic::GrokdbClassic;
+
+
+// SM-2 spaced repetition algorithm.
+// src: https://www.supermemo.com/english/ol/sm2.htm
+//
+// cards that have been graded at least once have a due date. overdue cards are
+// reviewed first (most overdue first); otherwise, the next card is chosen by the
+// grokdb-classic scheduler. this is the default scheduler.
+pub struct SM2;
+
+impl Scheduler for SM2 {
+
+    fn name(&self) -> &'static str {
+        return "sm2";
+    }
+
+    fn choose_card(&self, selection: &ReviewableSelection, rng: &mut StdRng) -> Result<i64, QueryError> {
+
+        match selection.has_due_cards() {
+            Err(why) => {
+                return Err(why);
+            },
+            Ok(true) => {
+                return selection.get_due_card(0);
+            },
+            Ok(false) => {
+                // no overdue cards
+            }
+        }
+
+        return GrokdbClassic.choose_card(selection, rng);
+    }
+}
+
+pub static DEFAULT_EASE_FACTOR: f64 = 2.5;
+pub static MIN_EASE_FACTOR: f64 = 1.3;
+
+// grades are within [MIN_GRADE, MAX_GRADE].
+// a grade of at least PASSING_GRADE is a correct response.
+pub static MIN_GRADE: i64 = 0;
+pub static MAX_GRADE: i64 = 5;
+pub static PASSING_GRADE: i64 = 3;
+
+#[derive(Debug, Clone)]
+pub struct SM2State {
+    pub ease_factor: f64,
+    pub interval: i64, // in days
+    pub repetitions: i64
+}
+
+impl SM2State {
+
+    pub fn new() -> SM2State {
+        return SM2State {
+            ease_factor: DEFAULT_EASE_FACTOR,
+            interval: 0,
+            repetitions: 0
+        };
+    }
+
+    // returns the state after the card is graded.
+    // invariant: MIN_GRADE <= grade <= MAX_GRADE
+    pub fn next(&self, grade: i64) -> SM2State {
+
+        assert!(grade >= MIN_GRADE && grade <= MAX_GRADE);
+
+        if grade < PASSING_GRADE {
+            // incorrect response; start repetitions from the beginning without
+            // changing the ease factor
+            return SM2State {
+                ease_factor: self.ease_factor,
+                interval: 1,
+                repetitions: 0
+            };
+        }
+
+        let interval: i64 = match self.repetitions {
+            0 => 1,
+            1 => 6,
+            _ => ((self.interval as f64) * self.ease_factor).round() as i64
+        };
+
+        let repetitions: i64 = self.repetitions + 1;
+
+        let q: f64 = (MAX_GRADE - grade) as f64;
+
+        let ease_factor: f64 = self.ease_factor + (0.1f64 - q * (0.08f64 + q * 0.02f64));
+
+        let ease_factor: f64 = if ease_factor < MIN_EASE_FACTOR {
+            MIN_EASE_FACTOR
+        } else {
+            ease_factor
+        };
+
+        return SM2State {
+            ease_factor: ease_factor,
+            interval: interval,
+            repetitions: repetitions
+        };
+    }
+}
+
+// apply a graded answer to the card's SM-2 state, and reschedule the card.
+// caller should hold the db connection lock.
+pub fn grade_card(db_conn: &Connection, card_id: i64, grade: i64) -> Result<(), QueryError> {
+
+    let params: &[(&str, &ToSql)] = &[
+        (":card_id", &card_id)
+    ];
+
+    // cards that were never graded start at the initial state
+    let ref query_init = format!("
+        INSERT OR IGNORE INTO CardsSM2(card)
+        VALUES (:card_id);
+    ");
+
+    match db_conn.execute_named(query_init, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_init.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    let ref query = format!("
+        SELECT
+            ease_factor, interval_days, repetitions
+        FROM CardsSM2
+        WHERE card = :card_id
+        LIMIT 1;
+    ");
+
+    let state = db_conn.query_row_named(query, params, |row| -> SM2State {
+        return SM2State {
+            ease_factor: row.get(0),
+            interval: row.get(1),
+            repetitions: row.get(2)
+        };
+    });
+
+    let state: SM2State = match state {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(state) => state
+    };
+
+    let next_state: SM2State = state.next(grade);
+
+    let interval_in_seconds: i64 = next_state.interval * 86400;
+
+    let ref query_update = format!("
+        UPDATE CardsSM2
+        SET
+            ease_factor = :ease_factor,
+            interval_days = :interval_days,
+            repetitions = :repetitions,
+            reviewed_at = strftime('%s', 'now'),
+            due_at = strftime('%s', 'now') + :interval_in_seconds
+        WHERE card = :card_id;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":ease_factor", &(next_state.ease_factor)),
+        (":interval_days", &(next_state.interval)),
+        (":repetitions", &(next_state.repetitions)),
+        (":interval_in_seconds", &interval_in_seconds),
+        (":card_id", &card_id)
+    ];
+
+    match db_conn.execute_named(query_update, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_update.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
+
+// forget the card's SM-2 state; the card is no longer scheduled.
+// caller should hold the db connection lock.
+pub fn reset_card(db_conn: &Connection, card_id: i64) -> Result<(), QueryError> {
+
+    let ref query_delete = format!("
+        DELETE FROM CardsSM2 WHERE card = :card_id;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":card_id", &card_id)
+    ];
+
+    match db_conn.execute_named(query_delete, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_delete.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{SM2State, DEFAULT_EASE_FACTOR, MIN_EASE_FACTOR};
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+    }
+
+    fn reviewed_twice() -> SM2State {
+        return SM2State {
+            ease_factor: DEFAULT_EASE_FACTOR,
+            interval: 6,
+            repetitions: 2
+        };
+    }
+
+    #[test]
+    fn failing_grades_restart_repetitions_without_changing_ease_factor() {
+        for grade in 0..3 {
+            let next = reviewed_twice().next(grade);
+
+            assert_eq!(next.interval, 1);
+            assert_eq!(next.repetitions, 0);
+            assert_close(next.ease_factor, DEFAULT_EASE_FACTOR);
+        }
+    }
+
+    #[test]
+    fn grade_3_lowers_ease_factor() {
+        let next = reviewed_twice().next(3);
+
+        assert_eq!(next.interval, 15);
+        assert_eq!(next.repetitions, 3);
+        assert_close(next.ease_factor, 2.36);
+    }
+
+    #[test]
+    fn grade_4_keeps_ease_factor() {
+        let next = reviewed_twice().next(4);
+
+        assert_eq!(next.interval, 15);
+        assert_eq!(next.repetitions, 3);
+        assert_close(next.ease_factor, DEFAULT_EASE_FACTOR);
+    }
+
+    #[test]
+    fn grade_5_raises_ease_factor() {
+        let next = reviewed_twice().next(5);
+
+        assert_eq!(next.interval, 15);
+        assert_eq!(next.repetitions, 3);
+        assert_close(next.ease_factor, 2.6);
+    }
+
+    #[test]
+    fn first_intervals_are_1_and_6_days() {
+        let first = SM2State::new().next(4);
+
+        assert_eq!(first.interval, 1);
+        assert_eq!(first.repetitions, 1);
+
+        let second = first.next(4);
+
+        assert_eq!(second.interval, 6);
+        assert_eq!(second.repetitions, 2);
+    }
+
+    #[test]
+    fn ease_factor_is_at_least_the_minimum() {
+        let state = SM2State {
+            ease_factor: MIN_EASE_FACTOR,
+            interval: 6,
+            repetitions: 2
+        };
+
+        let next = state.next(3);
+
+        assert_close(next.ease_factor, MIN_EASE_FACTOR);
+        assert_eq!(next.interval, 8);
+    }
+
+    #[test]
+    #[should_panic]
+    fn grade_above_maximum_is_rejected() {
+        SM2State::new().next(6);
+    }
+}
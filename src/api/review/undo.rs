@@ -0,0 +1,596 @@
+extern crate rusqlite;
+
+use rusqlite::Connection;
+use rusqlite::types::ToSql;
+
+use ::database::{DB, QueryError, execute_named};
+use ::api::review::ReviewAPI;
+use ::api::review::leeches;
+
+
+// undoing a review action restores the card to its state before the action.
+//
+// every review action (i.e. every update of CardsScore) takes a snapshot of the card
+// before it's updated. the snapshot is keyed by the CardsScoreHistory entry that the
+// action created. undone entries are marked as reverted, and are never deleted.
+
+static UNDO_CHANGELOG: &'static str = "Undo review action.";
+
+// state of a reviewed card before a review action
+pub struct Snapshot {
+    pub card: i64,
+
+    // CardsScore
+    pub success: i64,
+    pub fail: i64,
+    pub times_reviewed: i64,
+    pub times_seen: i64,
+    pub seen_at: i64,
+    pub reviewed_at: i64,
+    pub changelog: String,
+
+    // CardsSM2; null if the card had no SM-2 schedule
+    pub sm2_ease_factor: Option<f64>,
+    pub sm2_interval_days: Option<i64>,
+    pub sm2_repetitions: Option<i64>,
+    pub sm2_reviewed_at: Option<i64>,
+    pub sm2_due_at: Option<i64>,
+
+    // CardsFSRS; null if the card had no memory state
+    pub fsrs_stability: Option<f64>,
+    pub fsrs_difficulty: Option<f64>,
+    pub fsrs_reviews: Option<i64>,
+    pub fsrs_lapses: Option<i64>,
+    pub fsrs_reviewed_at: Option<i64>,
+
+    // the deck or stash the card was reviewed in (if any)
+    pub deck: Option<i64>,
+    pub stash: Option<i64>,
+
+    // reviewed_at of the deck or stash; null if it wasn't updated by the action
+    pub container_reviewed_at: Option<i64>,
+
+    // review session the action was counted towards (if any)
+    pub session: Option<i64>,
+    pub is_new_card: bool
+}
+
+// take a snapshot of the card before a review action.
+// if update_container is true, reviewed_at of the given deck or stash is also taken.
+// caller should hold the db connection lock.
+pub fn take_snapshot(db_conn: &Connection, card_id: i64, deck_id: Option<i64>, stash_id: Option<i64>,
+    update_container: bool) -> Result<Snapshot, QueryError> {
+
+    let ref query = format!("
+        SELECT
+            cs.success, cs.fail, cs.times_reviewed, cs.times_seen, cs.seen_at, cs.reviewed_at, cs.changelog,
+            sm.ease_factor, sm.interval_days, sm.repetitions, sm.reviewed_at, sm.due_at,
+            f.stability, f.difficulty, f.reviews, f.lapses, f.reviewed_at
+        FROM CardsScore AS cs
+
+        LEFT JOIN CardsSM2 AS sm
+        ON sm.card = cs.card
+
+        LEFT JOIN CardsFSRS AS f
+        ON f.card = cs.card
+
+        WHERE cs.card = :card_id
+        LIMIT 1;
+    ");
+
+    let results = db_conn.query_row_named(query, &[(":card_id", &card_id)], |row| -> Snapshot {
+        return Snapshot {
+            card: card_id,
+
+            success: row.get(0),
+            fail: row.get(1),
+            times_reviewed: row.get(2),
+            times_seen: row.get(3),
+            seen_at: row.get(4),
+            reviewed_at: row.get(5),
+            changelog: row.get(6),
+
+            sm2_ease_factor: row.get(7),
+            sm2_interval_days: row.get(8),
+            sm2_repetitions: row.get(9),
+            sm2_reviewed_at: row.get(10),
+            sm2_due_at: row.get(11),
+
+            fsrs_stability: row.get(12),
+            fsrs_difficulty: row.get(13),
+            fsrs_reviews: row.get(14),
+            fsrs_lapses: row.get(15),
+            fsrs_reviewed_at: row.get(16),
+
+            deck: deck_id,
+            stash: stash_id,
+            container_reviewed_at: None,
+
+            session: None,
+            is_new_card: false
+        };
+    });
+
+    let mut snapshot: Snapshot = match results {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(snapshot) => snapshot
+    };
+
+    if !update_container {
+        return Ok(snapshot);
+    }
+
+    let (ref query_container, container_id): (String, i64) = match (deck_id, stash_id) {
+        (Some(deck_id), _) => {
+            (format!("SELECT reviewed_at FROM Decks WHERE deck_id = :container_id LIMIT 1;"), deck_id)
+        },
+        (_, Some(stash_id)) => {
+            (format!("SELECT reviewed_at FROM Stashes WHERE stash_id = :container_id LIMIT 1;"), stash_id)
+        },
+        _ => {
+            return Ok(snapshot);
+        }
+    };
+
+    let results = db_conn.query_row_named(query_container, &[(":container_id", &container_id)], |row| -> i64 {
+        return row.get(0);
+    });
+
+    match results {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_container.clone(),
+            };
+            return Err(err);
+        },
+        Ok(reviewed_at) => {
+            snapshot.container_reviewed_at = Some(reviewed_at);
+        }
+    }
+
+    return Ok(snapshot);
+}
+
+// store the snapshot for the CardsScoreHistory entry (history_id) created by the review action.
+// caller should hold the db connection lock.
+pub fn save_snapshot(db_conn: &Connection, history_id: i64, snapshot: &Snapshot) -> Result<(), QueryError> {
+
+    let ref query = format!("
+        INSERT INTO CardsScoreUndo(
+            history, card,
+            success, fail, times_reviewed, times_seen, seen_at, reviewed_at, changelog,
+            sm2_ease_factor, sm2_interval_days, sm2_repetitions, sm2_reviewed_at, sm2_due_at,
+            fsrs_stability, fsrs_difficulty, fsrs_reviews, fsrs_lapses, fsrs_reviewed_at,
+            deck, stash, container_reviewed_at,
+            session, is_new_card
+        )
+        VALUES (
+            :history_id, :card_id,
+            :success, :fail, :times_reviewed, :times_seen, :seen_at, :reviewed_at, :changelog,
+            :sm2_ease_factor, :sm2_interval_days, :sm2_repetitions, :sm2_reviewed_at, :sm2_due_at,
+            :fsrs_stability, :fsrs_difficulty, :fsrs_reviews, :fsrs_lapses, :fsrs_reviewed_at,
+            :deck_id, :stash_id, :container_reviewed_at,
+            :session_id, :is_new_card
+        );
+    ");
+
+    let is_new_card: i64 = if snapshot.is_new_card { 1 } else { 0 };
+
+    let params: &[(&str, &ToSql)] = &[
+        (":history_id", &history_id),
+        (":card_id", &(snapshot.card)),
+
+        (":success", &(snapshot.success)),
+        (":fail", &(snapshot.fail)),
+        (":times_reviewed", &(snapshot.times_reviewed)),
+        (":times_seen", &(snapshot.times_seen)),
+        (":seen_at", &(snapshot.seen_at)),
+        (":reviewed_at", &(snapshot.reviewed_at)),
+        (":changelog", &(snapshot.changelog)),
+
+        (":sm2_ease_factor", &(snapshot.sm2_ease_factor)),
+        (":sm2_interval_days", &(snapshot.sm2_interval_days)),
+        (":sm2_repetitions", &(snapshot.sm2_repetitions)),
+        (":sm2_reviewed_at", &(snapshot.sm2_reviewed_at)),
+        (":sm2_due_at", &(snapshot.sm2_due_at)),
+
+        (":fsrs_stability", &(snapshot.fsrs_stability)),
+        (":fsrs_difficulty", &(snapshot.fsrs_difficulty)),
+        (":fsrs_reviews", &(snapshot.fsrs_reviews)),
+        (":fsrs_lapses", &(snapshot.fsrs_lapses)),
+        (":fsrs_reviewed_at", &(snapshot.fsrs_reviewed_at)),
+
+        (":deck_id", &(snapshot.deck)),
+        (":stash_id", &(snapshot.stash)),
+        (":container_reviewed_at", &(snapshot.container_reviewed_at)),
+
+        (":session_id", &(snapshot.session)),
+        (":is_new_card", &is_new_card)
+    ];
+
+    match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
+    }
+
+    return Ok(());
+}
+
+impl ReviewAPI {
+
+    // undo the latest review action of the card that wasn't undone.
+    // returns false if there is no review action to undo.
+    pub fn undo_review(&self, card_id: i64) -> Result<bool, QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let params: &[(&str, &ToSql)] = &[
+            (":card_id", &card_id)
+        ];
+
+        // TODO: can be simplified if this is fixed: https://github.com/jgallagher/rusqlite/issues/79
+        let ref query_count = format!("
+            SELECT COUNT(1)
+            FROM CardsScoreUndo
+            WHERE card = :card_id AND reverted = 0
+            LIMIT 1;
+        ");
+
+        let has_entry = db_conn.query_row_named(query_count, params, |row| -> bool {
+            let count: i64 = row.get(0);
+            return count >= 1;
+        });
+
+        match has_entry {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_count.clone(),
+                };
+                return Err(err);
+            },
+            Ok(has_entry) => {
+                if !has_entry {
+                    return Ok(false);
+                }
+            }
+        }
+
+        let ref query = format!("
+            SELECT
+                history,
+                success, fail, times_reviewed, times_seen, seen_at, reviewed_at, changelog,
+                sm2_ease_factor, sm2_interval_days, sm2_repetitions, sm2_reviewed_at, sm2_due_at,
+                fsrs_stability, fsrs_difficulty, fsrs_reviews, fsrs_lapses, fsrs_reviewed_at,
+                deck, stash, container_reviewed_at,
+                session, is_new_card
+            FROM CardsScoreUndo
+            WHERE card = :card_id AND reverted = 0
+            ORDER BY history DESC
+            LIMIT 1;
+        ");
+
+        let results = db_conn.query_row_named(query, params, |row| -> (i64, Snapshot) {
+
+            let is_new_card: i64 = row.get(22);
+
+            let snapshot = Snapshot {
+                card: card_id,
+
+                success: row.get(1),
+                fail: row.get(2),
+                times_reviewed: row.get(3),
+                times_seen: row.get(4),
+                seen_at: row.get(5),
+                reviewed_at: row.get(6),
+                changelog: row.get(7),
+
+                sm2_ease_factor: row.get(8),
+                sm2_interval_days: row.get(9),
+                sm2_repetitions: row.get(10),
+                sm2_reviewed_at: row.get(11),
+                sm2_due_at: row.get(12),
+
+                fsrs_stability: row.get(13),
+                fsrs_difficulty: row.get(14),
+                fsrs_reviews: row.get(15),
+                fsrs_lapses: row.get(16),
+                fsrs_reviewed_at: row.get(17),
+
+                deck: row.get(18),
+                stash: row.get(19),
+                container_reviewed_at: row.get(20),
+
+                session: row.get(21),
+                is_new_card: is_new_card > 0
+            };
+
+            return (row.get(0), snapshot);
+        });
+
+        let (history_id, snapshot): (i64, Snapshot) = match results {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(results) => results
+        };
+
+        let tx = match db_conn.transaction() {
+
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: format!("creating transaction"),
+                };
+                return Err(err);
+            },
+
+            Ok(tx) => {
+                /* new transaction created */
+                tx
+            }
+        };
+
+        // restore CardsScore.
+        //
+        // reviewed_at is restored separately from success and fail, so that the entry of
+        // CardsScoreHistory created by this undo isn't considered to be a review event.
+
+        let ref query_restore_times = format!("
+            UPDATE CardsScore
+            SET
+                times_reviewed = :times_reviewed,
+                times_seen = :times_seen,
+                seen_at = :seen_at,
+                reviewed_at = :reviewed_at
+            WHERE card = :card_id;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":times_reviewed", &(snapshot.times_reviewed)),
+            (":times_seen", &(snapshot.times_seen)),
+            (":seen_at", &(snapshot.seen_at)),
+            (":reviewed_at", &(snapshot.reviewed_at)),
+            (":card_id", &card_id)
+        ];
+
+        try!(execute_named(db_conn, query_restore_times, params));
+
+        let ref query_restore_score = format!("
+            UPDATE CardsScore
+            SET
+                success = :success,
+                fail = :fail,
+                changelog = :changelog
+            WHERE card = :card_id;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":success", &(snapshot.success)),
+            (":fail", &(snapshot.fail)),
+            (":changelog", &UNDO_CHANGELOG),
+            (":card_id", &card_id)
+        ];
+
+        try!(execute_named(db_conn, query_restore_score, params));
+
+        // restore SM-2 schedule
+
+        if snapshot.sm2_ease_factor.is_some() {
+
+            let ref query_restore_sm2 = format!("
+                INSERT OR REPLACE INTO CardsSM2(card, ease_factor, interval_days, repetitions, reviewed_at, due_at)
+                VALUES (:card_id, :ease_factor, :interval_days, :repetitions, :reviewed_at, :due_at);
+            ");
+
+            let params: &[(&str, &ToSql)] = &[
+                (":card_id", &card_id),
+                (":ease_factor", &(snapshot.sm2_ease_factor)),
+                (":interval_days", &(snapshot.sm2_interval_days)),
+                (":repetitions", &(snapshot.sm2_repetitions)),
+                (":reviewed_at", &(snapshot.sm2_reviewed_at)),
+                (":due_at", &(snapshot.sm2_due_at))
+            ];
+
+            try!(execute_named(db_conn, query_restore_sm2, params));
+
+        } else {
+
+            let ref query_delete_sm2 = format!("
+                DELETE FROM CardsSM2 WHERE card = :card_id;
+            ");
+
+            let params: &[(&str, &ToSql)] = &[
+                (":card_id", &card_id)
+            ];
+
+            try!(execute_named(db_conn, query_delete_sm2, params));
+        }
+
+        // restore FSRS memory state
+
+        if snapshot.fsrs_stability.is_some() {
+
+            let ref query_restore_fsrs = format!("
+                INSERT OR REPLACE INTO CardsFSRS(card, stability, difficulty, reviews, lapses, reviewed_at)
+                VALUES (:card_id, :stability, :difficulty, :reviews, :lapses, :reviewed_at);
+            ");
+
+            let params: &[(&str, &ToSql)] = &[
+                (":card_id", &card_id),
+                (":stability", &(snapshot.fsrs_stability)),
+                (":difficulty", &(snapshot.fsrs_difficulty)),
+                (":reviews", &(snapshot.fsrs_reviews)),
+                (":lapses", &(snapshot.fsrs_lapses)),
+                (":reviewed_at", &(snapshot.fsrs_reviewed_at))
+            ];
+
+            try!(execute_named(db_conn, query_restore_fsrs, params));
+
+        } else {
+
+            let ref query_delete_fsrs = format!("
+                DELETE FROM CardsFSRS WHERE card = :card_id;
+            ");
+
+            let params: &[(&str, &ToSql)] = &[
+                (":card_id", &card_id)
+            ];
+
+            try!(execute_named(db_conn, query_delete_fsrs, params));
+        }
+
+        // tags have no reviewed_at to restore; the card is chosen afresh when reviewing a tag
+
+        let ref query_uncache_tag = format!("
+            DELETE FROM CachedTagReview WHERE card = :card_id;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":card_id", &card_id)
+        ];
+
+        try!(execute_named(db_conn, query_uncache_tag, params));
+
+        // restore reviewed_at of the deck or stash, and put the card back up for review
+        // within the deck or stash
+
+        if let Some(deck_id) = snapshot.deck {
+
+            if let Some(ref container_reviewed_at) = snapshot.container_reviewed_at {
+
+                let ref query_restore_deck = format!("
+                    UPDATE Decks
+                    SET
+                    reviewed_at = :reviewed_at
+                    WHERE deck_id = :deck_id;
+                ");
+
+                let params: &[(&str, &ToSql)] = &[
+                    (":reviewed_at", container_reviewed_at),
+                    (":deck_id", &deck_id)
+                ];
+
+                try!(execute_named(db_conn, query_restore_deck, params));
+            }
+
+            let ref query_cache_deck = format!("
+                INSERT OR REPLACE INTO CachedDeckReview(deck, card)
+                VALUES (:deck_id, :card_id);
+            ");
+
+            let params: &[(&str, &ToSql)] = &[
+                (":deck_id", &deck_id),
+                (":card_id", &card_id)
+            ];
+
+            try!(execute_named(db_conn, query_cache_deck, params));
+
+        } else if let Some(stash_id) = snapshot.stash {
+
+            if let Some(ref container_reviewed_at) = snapshot.container_reviewed_at {
+
+                let ref query_restore_stash = format!("
+                    UPDATE Stashes
+                    SET
+                    reviewed_at = :reviewed_at
+                    WHERE stash_id = :stash_id;
+                ");
+
+                let params: &[(&str, &ToSql)] = &[
+                    (":reviewed_at", container_reviewed_at),
+                    (":stash_id", &stash_id)
+                ];
+
+                try!(execute_named(db_conn, query_restore_stash, params));
+            }
+
+            let ref query_cache_stash = format!("
+                INSERT OR REPLACE INTO CachedStashReview(stash, card)
+                VALUES (:stash_id, :card_id);
+            ");
+
+            let params: &[(&str, &ToSql)] = &[
+                (":stash_id", &stash_id),
+                (":card_id", &card_id)
+            ];
+
+            try!(execute_named(db_conn, query_cache_stash, params));
+        }
+
+        // revert progress of the review session
+
+        if let Some(session_id) = snapshot.session {
+
+            let new_card: i64 = if snapshot.is_new_card { 1 } else { 0 };
+
+            let ref query_revert_session = format!("
+                UPDATE ReviewSessions
+                SET
+                    cards_reviewed = max(cards_reviewed - 1, 0),
+                    new_cards_reviewed = max(new_cards_reviewed - :new_card, 0),
+                    updated_at = strftime('%s', 'now')
+                WHERE session_id = :session_id;
+            ");
+
+            let params: &[(&str, &ToSql)] = &[
+                (":new_card", &new_card),
+                (":session_id", &session_id)
+            ];
+
+            try!(execute_named(db_conn, query_revert_session, params));
+        }
+
+        // mark the undone entry as reverted
+
+        let ref query_revert = format!("
+            UPDATE CardsScoreUndo
+            SET
+                reverted = 1,
+                reverted_at = strftime('%s', 'now')
+            WHERE history = :history_id;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":history_id", &history_id)
+        ];
+
+        try!(execute_named(db_conn, query_revert, params));
+
+        // the undone review action may have marked the card as a leech
+        try!(leeches::detect_leech(db_conn, card_id));
+
+        match tx.commit() {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: format!("committing transaction"),
+                };
+                return Err(err);
+            },
+            _ => {/* commit successful */}
+        }
+
+        return Ok(true);
+    }
+}
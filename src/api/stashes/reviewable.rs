@@ -7,11 +7,15 @@ use rusqlite::types::ToSql;
 
 use ::database::{DB, QueryError};
 use ::api::GrokDB;
-use ::api::review::ReviewableSelection;
+use ::api::review::{ReviewableSelection, reviewable_cards_filter};
+use ::api::review::settings::ReviewSettings;
 
 pub struct ReviewableStash {
     pub stash_id: i64,
-    pub grokdb: Arc<GrokDB>
+    pub grokdb: Arc<GrokDB>,
+
+    // if false, new cards are hidden (e.g. daily limit of new cards of a review session is reached)
+    pub allow_new_cards: bool
 }
 
 impl ReviewableStash {
@@ -130,6 +134,142 @@ impl ReviewableStash {
             }
         };
     }
+
+    // choose the scheduler for this stash
+    pub fn set_scheduler_name(&self, scheduler_name: &str) -> Result<(), QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.stashes.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query = format!("
+            INSERT OR REPLACE INTO StashesScheduler(stash, scheduler)
+            VALUES (:stash_id, :scheduler);
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":stash_id", &(self.stash_id)),
+            (":scheduler", &scheduler_name)
+        ];
+
+        match db_conn.execute_named(query, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        return Ok(());
+    }
+
+    // remove the scheduler chosen for this stash
+    pub fn remove_scheduler_name(&self) -> Result<(), QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.stashes.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query_delete = format!("
+            DELETE FROM StashesScheduler WHERE stash = :stash_id;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":stash_id", &(self.stash_id))
+        ];
+
+        match db_conn.execute_named(query_delete, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_delete.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        return Ok(());
+    }
+    // choose the review settings for this stash
+    pub fn set_review_settings(&self, settings: &ReviewSettings) -> Result<(), QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.stashes.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query = format!("
+            INSERT OR REPLACE INTO StashesReviewSettings(stash, new_cards, old_enough, least_recent, min_score, age_in_hours, purgatory_size)
+            VALUES (:stash_id, :new_cards, :old_enough, :least_recent, :min_score, :age_in_hours, :purgatory_size);
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":stash_id", &(self.stash_id)),
+            (":new_cards", &(settings.new_cards)),
+            (":old_enough", &(settings.old_enough)),
+            (":least_recent", &(settings.least_recent)),
+            (":min_score", &(settings.min_score)),
+            (":age_in_hours", &(settings.age_in_hours)),
+            (":purgatory_size", &(settings.purgatory_size))
+        ];
+
+        match db_conn.execute_named(query, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        return Ok(());
+    }
+
+    // remove the review settings chosen for this stash
+    pub fn remove_review_settings(&self) -> Result<(), QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.stashes.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query_delete = format!("
+            DELETE FROM StashesReviewSettings WHERE stash = :stash_id;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":stash_id", &(self.stash_id))
+        ];
+
+        match db_conn.execute_named(query_delete, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_delete.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        return Ok(());
+    }
 }
 
 impl ReviewableSelection for ReviewableStash {
@@ -149,14 +289,46 @@ impl ReviewableSelection for ReviewableStash {
 
     fn number_of_cards(&self) -> Result<i64, QueryError> {
 
-        match self.grokdb.deref().cards.count_by_stash(self.stash_id) {
-            Err(err) => {
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.stashs.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":stash_id", &(self.stash_id))
+        ];
+
+        // suspended cards are excluded
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM StashCards AS sc
+
+            INNER JOIN Cards AS c
+            ON c.card_id = sc.card
+
+            WHERE
+                sc.stash = :stash_id
+            AND
+                {reviewable};
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
                 return Err(err);
             },
-            Ok(num_cards) => {
-                return Ok(num_cards);
+            Ok(count) => {
+                return Ok(count);
             }
-        }
+        };
     }
 
     fn cache_card(&self, card_id: i64) -> Result<(), QueryError> {
@@ -290,6 +462,24 @@ impl ReviewableSelection for ReviewableStash {
         return Ok(());
     }
 
+    fn allows_new_cards(&self) -> bool {
+        return self.allow_new_cards;
+    }
+
+    fn is_new_card(&self, card_id: i64) -> Result<bool, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        match grokdb.review.get_review_stat(card_id) {
+            Err(why) => {
+                return Err(why);
+            },
+            Ok(review_stat) => {
+                return Ok(review_stat.is_new());
+            }
+        }
+    }
+
     fn has_new_cards(&self) -> Result<bool, QueryError> {
 
         match self.number_of_new_cards() {
@@ -304,6 +494,10 @@ impl ReviewableSelection for ReviewableStash {
 
     fn number_of_new_cards(&self) -> Result<i64, QueryError> {
 
+        if !self.allow_new_cards {
+            return Ok(0);
+        }
+
         let ref grokdb = self.grokdb.deref();
 
         let db_conn_guard = grokdb.stashes.db.lock().unwrap();
@@ -326,9 +520,11 @@ impl ReviewableSelection for ReviewableStash {
 
             WHERE
                 sc.stash = :stash_id
+            AND
+                {reviewable}
             AND
                 (c.created_at - cs.seen_at) = 0;
-        ");
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
 
         let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
             return row.get(0);
@@ -351,6 +547,8 @@ impl ReviewableSelection for ReviewableStash {
     // returns card id
     fn get_new_card(&self, index: i64) -> Result<i64, QueryError> {
 
+        assert!(self.allow_new_cards);
+
         let ref grokdb = self.grokdb.deref();
 
         let db_conn_guard = grokdb.stashes.db.lock().unwrap();
@@ -369,11 +567,13 @@ impl ReviewableSelection for ReviewableStash {
 
             WHERE
                 sc.stash = :stash_id
+            AND
+                {reviewable}
             AND
                 (c.created_at - cs.seen_at) = 0
             LIMIT 1
             OFFSET :offset;
-        ");
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
 
         let params: &[(&str, &ToSql)] = &[
             (":stash_id", &(self.stash_id)),
@@ -430,11 +630,13 @@ impl ReviewableSelection for ReviewableStash {
 
             WHERE
                 sc.stash = :stash_id
+            AND
+                {reviewable}
             AND
                 (strftime('%s','now') - cs.seen_at) >= :age_of_consent
             AND
                 raw_score(cs.success, cs.fail) >= :min_score;
-        ");
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
 
         let age_in_seconds: i64 = age_in_hours * 3600;
 
@@ -483,6 +685,8 @@ impl ReviewableSelection for ReviewableStash {
 
             WHERE
                 sc.stash = :stash_id
+            AND
+                {reviewable}
             AND
                 (strftime('%s','now') - cs.seen_at) >= :age_of_consent
             AND
@@ -491,7 +695,7 @@ impl ReviewableSelection for ReviewableStash {
                 rank_score(cs.success, cs.fail, strftime('%s','now') - cs.seen_at, cs.times_reviewed) DESC
             LIMIT 1
             OFFSET :index;
-        ");
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
 
         let age_in_seconds: i64 = age_in_hours * 3600;
 
@@ -556,6 +760,8 @@ impl ReviewableSelection for ReviewableStash {
 
                 WHERE
                     sc.stash = :stash_id
+                AND
+                    {reviewable}
                 ORDER BY
                     (strftime('%s','now') - cs.seen_at) DESC
                 LIMIT :purgatory_size
@@ -565,7 +771,7 @@ impl ReviewableSelection for ReviewableStash {
                 raw_score(sub.success, sub.fail) >= :min_score
             {sort_by_score}
             ;
-        ", sort_by_score = {
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards), sort_by_score = {
             if sort_by_score {
                 "ORDER BY rank_score(sub.success, sub.fail, strftime('%s','now') - sub.seen_at, sub.times_reviewed) DESC"
             } else {
@@ -622,6 +828,8 @@ impl ReviewableSelection for ReviewableStash {
 
                 WHERE
                     sc.stash = :stash_id
+                AND
+                    {reviewable}
                 ORDER BY
                     (strftime('%s','now') - cs.seen_at) DESC
                 LIMIT :purgatory_size
@@ -631,7 +839,7 @@ impl ReviewableSelection for ReviewableStash {
                 raw_score(sub.success, sub.fail) >= :min_score
             {sort_by_score}
             LIMIT 1 OFFSET :index;
-        ", sort_by_score = {
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards), sort_by_score = {
             if sort_by_score {
                 "ORDER BY rank_score(sub.success, sub.fail, strftime('%s','now') - sub.seen_at, sub.times_reviewed) DESC"
             } else {
@@ -663,4 +871,377 @@ impl ReviewableSelection for ReviewableStash {
             }
         };
     }
+
+    fn has_due_cards(&self) -> Result<bool, QueryError> {
+
+        match self.number_of_due_cards() {
+            Err(err) => {
+                return Err(err);
+            },
+            Ok(num_cards) => {
+                return Ok(num_cards > 0);
+            }
+        }
+    }
+
+    fn number_of_due_cards(&self) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.stashes.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":stash_id", &(self.stash_id))
+        ];
+
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM StashCards AS sc
+
+            INNER JOIN Cards AS c
+            ON c.card_id = sc.card
+
+            INNER JOIN CardsSM2 AS sm
+            ON sm.card = c.card_id
+
+            WHERE
+                sc.stash = :stash_id
+            AND
+                {reviewable}
+            AND
+                sm.due_at <= strftime('%s','now');
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(count) => {
+                return Ok(count);
+            }
+        };
+    }
+
+    // returns card id
+    fn get_due_card(&self, index: i64) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.stashes.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                c.card_id
+            FROM StashCards AS sc
+
+            INNER JOIN Cards AS c
+            ON c.card_id = sc.card
+
+            INNER JOIN CardsSM2 AS sm
+            ON sm.card = c.card_id
+
+            WHERE
+                sc.stash = :stash_id
+            AND
+                {reviewable}
+            AND
+                sm.due_at <= strftime('%s','now')
+            ORDER BY
+                sm.due_at ASC
+            LIMIT 1
+            OFFSET :index;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let params: &[(&str, &ToSql)] = &[
+            (":stash_id", &(self.stash_id)),
+            (":index", &index)
+        ];
+
+        let maybe_card_id = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_card_id {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(card_id) => {
+                return Ok(card_id);
+            }
+        };
+    }
+
+    fn has_fading_cards(&self, retention: f64) -> Result<bool, QueryError> {
+
+        match self.number_of_fading_cards(retention) {
+            Err(err) => {
+                return Err(err);
+            },
+            Ok(num_cards) => {
+                return Ok(num_cards > 0);
+            }
+        }
+    }
+
+    fn number_of_fading_cards(&self, retention: f64) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.stashes.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":stash_id", &(self.stash_id)),
+            (":retention", &retention)
+        ];
+
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM StashCards AS sc
+
+            INNER JOIN Cards AS c
+            ON c.card_id = sc.card
+
+            INNER JOIN CardsFSRS AS f
+            ON f.card = c.card_id
+
+            WHERE
+                sc.stash = :stash_id
+            AND
+                {reviewable}
+            AND
+                fsrs_retrievability(CAST(strftime('%s','now') - f.reviewed_at AS REAL), f.stability) < :retention;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(count) => {
+                return Ok(count);
+            }
+        };
+    }
+
+    // returns card id
+    fn get_fading_card(&self, retention: f64, index: i64) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.stashes.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                c.card_id
+            FROM StashCards AS sc
+
+            INNER JOIN Cards AS c
+            ON c.card_id = sc.card
+
+            INNER JOIN CardsFSRS AS f
+            ON f.card = c.card_id
+
+            WHERE
+                sc.stash = :stash_id
+            AND
+                {reviewable}
+            AND
+                fsrs_retrievability(CAST(strftime('%s','now') - f.reviewed_at AS REAL), f.stability) < :retention
+            ORDER BY
+                fsrs_retrievability(CAST(strftime('%s','now') - f.reviewed_at AS REAL), f.stability) ASC
+            LIMIT 1
+            OFFSET :index;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let params: &[(&str, &ToSql)] = &[
+            (":stash_id", &(self.stash_id)),
+            (":retention", &retention),
+            (":index", &index)
+        ];
+
+        let maybe_card_id = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_card_id {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(card_id) => {
+                return Ok(card_id);
+            }
+        };
+    }
+
+    fn get_scheduler_name(&self) -> Result<Option<String>, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.stashes.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":stash_id", &(self.stash_id))
+        ];
+
+        // TODO: can be simplified if this is fixed: https://github.com/jgallagher/rusqlite/issues/79
+        let ref query_count = format!("
+            SELECT
+                COUNT(1)
+            FROM StashesScheduler
+            WHERE
+                stash = :stash_id
+            LIMIT 1;
+        ");
+
+        let has_entry = db_conn.query_row_named(query_count, params, |row| -> bool {
+            let count: i64 = row.get(0);
+            return count >= 1;
+        });
+
+        match has_entry {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_count.clone(),
+                };
+                return Err(err);
+            },
+            Ok(has_entry) => {
+                if !has_entry {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let ref query = format!("
+            SELECT
+                scheduler
+            FROM StashesScheduler
+            WHERE
+                stash = :stash_id
+            LIMIT 1;
+        ");
+
+        let scheduler_name = db_conn.query_row_named(query, params, |row| -> String {
+            return row.get(0);
+        });
+
+        match scheduler_name {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(scheduler_name) => {
+                return Ok(Some(scheduler_name));
+            }
+        };
+    }
+
+    fn get_review_settings(&self) -> Result<Option<ReviewSettings>, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.stashes.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":stash_id", &(self.stash_id))
+        ];
+
+        // TODO: can be simplified if this is fixed: https://github.com/jgallagher/rusqlite/issues/79
+        let ref query_count = format!("
+            SELECT
+                COUNT(1)
+            FROM StashesReviewSettings AS rs
+            WHERE
+                rs.stash = :stash_id
+            LIMIT 1;
+        ");
+
+        let has_entry = db_conn.query_row_named(query_count, params, |row| -> bool {
+            let count: i64 = row.get(0);
+            return count >= 1;
+        });
+
+        match has_entry {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_count.clone(),
+                };
+                return Err(err);
+            },
+            Ok(has_entry) => {
+                if !has_entry {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let ref query = format!("
+            SELECT
+                rs.new_cards, rs.old_enough, rs.least_recent, rs.min_score, rs.age_in_hours, rs.purgatory_size
+            FROM StashesReviewSettings AS rs
+            WHERE
+                rs.stash = :stash_id
+            LIMIT 1;
+        ");
+
+        let settings = db_conn.query_row_named(query, params, |row| -> ReviewSettings {
+            return ReviewSettings {
+                new_cards: row.get(0),
+                old_enough: row.get(1),
+                least_recent: row.get(2),
+                min_score: row.get(3),
+                age_in_hours: row.get(4),
+                purgatory_size: row.get(5)
+            };
+        });
+
+        match settings {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(settings) => {
+                return Ok(Some(settings));
+            }
+        };
+    }
 }
@@ -0,0 +1,328 @@
+extern crate rusqlite;
+extern crate rustc_serialize;
+
+pub mod restify;
+pub mod reviewable;
+
+use std::sync::Arc;
+
+use rusqlite::types::ToSql;
+use rusqlite::{Connection, SqliteStatement, SqliteRow};
+use rustc_serialize::json;
+
+use ::database::{DB, QueryError};
+pub use self::restify::restify;
+
+
+// tags are labels on cards across decks (e.g. exam-2, formula, needs-rewrite).
+// a tag is created once a card is tagged with it; tags are matched case-insensitively.
+
+#[derive(Debug, RustcEncodable)]
+pub struct TagResponse {
+    id: i64,
+    name: String,
+    created_at: i64, // unix timestamp
+    num_of_cards: i64
+}
+
+impl TagResponse {
+
+    pub fn to_json(&self) -> String {
+        return json::encode(self).unwrap();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TagsAPI {
+    pub db: Arc<DB>,
+}
+
+impl TagsAPI {
+
+    // every tag, sorted by name
+    pub fn get_list(&self) -> Result<Vec<TagResponse>, QueryError> {
+
+        let ref query = format!("
+            SELECT
+                t.tag_id, t.name, t.created_at, COUNT(ct.card)
+            FROM Tags AS t
+
+            LEFT JOIN CardsTags AS ct
+            ON ct.tag = t.tag_id
+
+            GROUP BY t.tag_id
+            ORDER BY t.name ASC;
+        ");
+
+        return self.query_tags(query, &[]);
+    }
+
+    // tags of the card, sorted by name
+    pub fn get_by_card(&self, card_id: i64) -> Result<Vec<TagResponse>, QueryError> {
+
+        let ref query = format!("
+            SELECT
+                t.tag_id, t.name, t.created_at,
+                (SELECT COUNT(1) FROM CardsTags WHERE tag = t.tag_id)
+            FROM CardsTags AS ct
+
+            INNER JOIN Tags AS t
+            ON t.tag_id = ct.tag
+
+            WHERE
+                ct.card = :card_id
+            ORDER BY t.name ASC;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":card_id", &card_id)
+        ];
+
+        return self.query_tags(query, params);
+    }
+
+    // returns the tag id of the tag (if exists)
+    pub fn get_id(&self, name: &str) -> Result<Option<i64>, QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        return get_tag_id(db_conn, name);
+    }
+
+    // tag the card; the tag is created if it doesn't exist. returns the tag id.
+    pub fn tag_card(&self, card_id: i64, name: &str) -> Result<i64, QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query_tag = format!("
+            INSERT OR IGNORE INTO Tags(name) VALUES (:name);
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":name", &name)
+        ];
+
+        match db_conn.execute_named(query_tag, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_tag.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        let tag_id: i64 = match try!(get_tag_id(db_conn, name)) {
+            Some(tag_id) => tag_id,
+            None => unreachable!() // tag was just created
+        };
+
+        let ref query_insert = format!("
+            INSERT OR IGNORE INTO CardsTags(card, tag) VALUES (:card_id, :tag_id);
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":card_id", &card_id),
+            (":tag_id", &tag_id)
+        ];
+
+        match db_conn.execute_named(query_insert, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_insert.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        return Ok(tag_id);
+    }
+
+    // remove the tag from the card; the tag is deleted once no card has it
+    pub fn untag_card(&self, card_id: i64, tag_id: i64) -> Result<(), QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query_delete = format!("
+            DELETE FROM CardsTags WHERE card = :card_id AND tag = :tag_id;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":card_id", &card_id),
+            (":tag_id", &tag_id)
+        ];
+
+        match db_conn.execute_named(query_delete, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_delete.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        let ref query_delete_tag = format!("
+            DELETE FROM Tags
+            WHERE
+                tag_id = :tag_id
+            AND
+                NOT EXISTS (SELECT 1 FROM CardsTags WHERE tag = :tag_id);
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":tag_id", &tag_id)
+        ];
+
+        match db_conn.execute_named(query_delete_tag, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_delete_tag.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        return Ok(());
+    }
+
+    fn query_tags(&self, query: &String, params: &[(&str, &ToSql)]) -> Result<Vec<TagResponse>, QueryError> {
+
+        let db_conn_guard = self.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let maybe_stmt = db_conn.prepare(query);
+
+        if maybe_stmt.is_err() {
+
+            let why = maybe_stmt.unwrap_err();
+
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        }
+
+        let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+        let maybe_iter = stmt.query_named(params);
+
+        match maybe_iter {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(iter) => {
+
+                let mut tags: Vec<TagResponse> = vec![];
+
+                for result_row in iter {
+
+                    let row: SqliteRow = match result_row {
+                        Err(why) => {
+                            let err = QueryError {
+                                sqlite_error: why,
+                                query: query.clone(),
+                            };
+                            return Err(err);
+                        },
+                        Ok(row) => row
+                    };
+
+                    tags.push(TagResponse {
+                        id: row.get(0),
+                        name: row.get(1),
+                        created_at: row.get(2),
+                        num_of_cards: row.get(3)
+                    });
+                }
+
+                return Ok(tags);
+            }
+        };
+    }
+}
+
+// a tag name is a non-empty word of letters, digits, and any of - _ . :
+pub fn valid_tag_name(name: &str) -> bool {
+
+    if name.len() <= 0 {
+        return false;
+    }
+
+    return name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.' || c == ':');
+}
+
+fn get_tag_id(db_conn: &Connection, name: &str) -> Result<Option<i64>, QueryError> {
+
+    let ref query = format!("
+        SELECT tag_id FROM Tags WHERE name = :name LIMIT 1;
+    ");
+
+    let params: &[(&str, &ToSql)] = &[
+        (":name", &name)
+    ];
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            for result_row in iter {
+                match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => {
+                        return Ok(Some(row.get(0)));
+                    }
+                }
+            }
+
+            return Ok(None);
+        }
+    };
+}
@@ -0,0 +1,305 @@
+extern crate iron;
+extern crate router;
+extern crate rustc_serialize;
+
+use iron::status;
+use iron::prelude::*;
+use iron::mime::Mime;
+use router::Router;
+use rustc_serialize::json;
+
+use std::sync::Arc;
+use std::ops::Deref;
+use std::error::Error;
+
+use ::api::{GrokDB, ErrorResponse};
+use ::api::cards::restify::card_exists;
+use ::api::tags::{TagResponse, valid_tag_name};
+
+// attach tags REST endpoints to given router
+pub fn restify(router: &mut Router, grokdb: GrokDB) {
+
+    let grokdb = Arc::new(grokdb);
+
+    router.get("/tags", {
+        let grokdb = grokdb.clone();
+        move |_: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let tags: Vec<TagResponse> = match grokdb.tags.get_list() {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(tags) => tags
+            };
+
+            let ref tags = tags;
+
+            let response: String = json::encode(tags).unwrap();
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response)));
+        }
+    });
+
+    router.get("/cards/:card_id/tags", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            // fetch and parse requested card id
+
+            let card_id = req.extensions.get::<Router>().unwrap().find("card_id").unwrap();
+
+            let card_id: i64 = match card_id.parse::<u64>() {
+                Ok(card_id) => card_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure card exists
+            match card_exists(grokdb, card_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* card exists; continue */}
+            }
+
+            let tags: Vec<TagResponse> = match grokdb.tags.get_by_card(card_id) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                Ok(tags) => tags
+            };
+
+            let ref tags = tags;
+
+            let response: String = json::encode(tags).unwrap();
+
+            let content_type = "application/json".parse::<Mime>().unwrap();
+
+            return Ok(Response::with((content_type, status::Ok, response)));
+        }
+    });
+
+    // tag a card; the tag is created if it doesn't exist
+    router.put("/cards/:card_id/tags/:tag", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            // fetch and validate requested tag
+
+            let tag: String = req.extensions.get::<Router>().unwrap().find("tag").unwrap().trim().to_string();
+
+            if !valid_tag_name(&tag) {
+
+                let ref reason = format!("invalid tag: {}; a tag is a word of letters, digits, and any of - _ . :", tag);
+                let res_code = status::BadRequest;
+
+                let err_response = ErrorResponse {
+                    status: res_code,
+                    developerMessage: reason,
+                    userMessage: reason,
+                }.to_json();
+
+                return Ok(Response::with((res_code, err_response)));
+            }
+
+            // fetch and parse requested card id
+
+            let card_id = req.extensions.get::<Router>().unwrap().find("card_id").unwrap();
+
+            let card_id: i64 = match card_id.parse::<u64>() {
+                Ok(card_id) => card_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure card exists
+            match card_exists(grokdb, card_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* card exists; continue */}
+            }
+
+            match grokdb.tags.tag_card(card_id, &tag) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* card tagged */}
+            }
+
+            let res_code = status::Ok;
+            return Ok(Response::with((res_code, "")));
+        }
+    });
+
+    // remove a tag from a card
+    router.delete("/cards/:card_id/tags/:tag", {
+        let grokdb = grokdb.clone();
+        move |req: &mut Request| -> IronResult<Response> {
+            let ref grokdb = grokdb.deref();
+
+            let tag: String = req.extensions.get::<Router>().unwrap().find("tag").unwrap().trim().to_string();
+
+            // fetch and parse requested card id
+
+            let card_id = req.extensions.get::<Router>().unwrap().find("card_id").unwrap();
+
+            let card_id: i64 = match card_id.parse::<u64>() {
+                Ok(card_id) => card_id as i64,
+                Err(why) => {
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::BadRequest;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                }
+            };
+
+            // ensure card exists
+            match card_exists(grokdb, card_id) {
+                Err(response) => {
+                    return response;
+                },
+                _ => {/* card exists; continue */}
+            }
+
+            // ensure tag exists
+            let tag_id: i64 = match tag_exists(grokdb, &tag) {
+                Err(response) => {
+                    return response;
+                },
+                Ok(tag_id) => tag_id
+            };
+
+            match grokdb.tags.untag_card(card_id, tag_id) {
+                Err(why) => {
+                    // why: QueryError
+
+                    let ref reason = format!("{:?}", why);
+                    let res_code = status::InternalServerError;
+
+                    let err_response = ErrorResponse {
+                        status: res_code,
+                        developerMessage: reason,
+                        userMessage: why.description(),
+                    }.to_json();
+
+                    return Ok(Response::with((res_code, err_response)));
+                },
+                _ => {/* tag removed from card */}
+            }
+
+            let res_code = status::Ok;
+            return Ok(Response::with((res_code, "")));
+        }
+    });
+}
+
+/* helpers */
+
+// returns the tag id of the tag
+pub fn tag_exists(grokdb: &GrokDB, tag: &str) -> Result<i64, IronResult<Response>> {
+
+    match grokdb.tags.get_id(tag) {
+
+        Err(why) => {
+            // why: QueryError
+
+            let ref reason = format!("{:?}", why);
+            let res_code = status::InternalServerError;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: why.description(),
+            }.to_json();
+
+            let res = Ok(Response::with((res_code, err_response)));
+            return Err(res);
+        },
+
+        Ok(None) => {
+            let ref reason = format!("given tag does not exist: {}", tag);
+            let res_code = status::NotFound;
+
+            let err_response = ErrorResponse {
+                status: res_code,
+                developerMessage: reason,
+                userMessage: reason,
+            }.to_json();
+
+            let res = Ok(Response::with((res_code, err_response)));
+            return Err(res);
+        },
+
+        Ok(Some(tag_id)) => {
+            return Ok(tag_id);
+        }
+    }
+}
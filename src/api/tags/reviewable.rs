@@ -0,0 +1,978 @@
+extern crate rusqlite;
+
+use std::sync::Arc;
+use std::ops::Deref;
+
+use rusqlite::types::ToSql;
+
+use ::database::{DB, QueryError};
+use ::api::GrokDB;
+use ::api::review::{ReviewableSelection, reviewable_cards_filter};
+use ::api::review::settings::ReviewSettings;
+
+pub struct ReviewableTag {
+    pub tag_id: i64,
+    pub grokdb: Arc<GrokDB>,
+
+    // if false, new cards are hidden (e.g. daily limit of new cards of a review session is reached)
+    pub allow_new_cards: bool
+}
+
+impl ReviewableTag {
+
+    // check if the card has the tag
+    pub fn has_card(&self, card_id: i64) -> Result<bool, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM
+                CardsTags
+            WHERE
+                tag = :tag_id
+            AND
+                card = :card_id
+            LIMIT 1;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":card_id", &card_id),
+            (":tag_id", &(self.tag_id))
+        ];
+
+        let has_tag = db_conn.query_row_named(query, params, |row| -> bool {
+            let count: i64 = row.get(0);
+            return count >= 1;
+        });
+
+        match has_tag {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(has_tag) => {
+                return Ok(has_tag);
+            }
+        };
+
+    }
+
+    // returns card id (if exists)
+    fn __get_cached_card(&self) -> Result<Option<i64>, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":tag_id", &(self.tag_id))
+        ];
+
+        // TODO: can be simplified if this is fixed: https://github.com/jgallagher/rusqlite/issues/79
+        // ensure a cache entry exists for this tag
+        let ref query_count = format!("
+            SELECT
+                COUNT(1)
+            FROM CachedTagReview
+            WHERE
+                tag = :tag_id
+            LIMIT 1;
+        ");
+
+        let has_entry = db_conn.query_row_named(query_count, params, |row| -> bool {
+            let count: i64 = row.get(0);
+            return count >= 1;
+        });
+
+        match has_entry {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_count.clone(),
+                };
+                return Err(err);
+            },
+            Ok(has_entry) => {
+                if !has_entry {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let ref query = format!("
+            SELECT
+                tag, card, created_at
+            FROM CachedTagReview
+            WHERE
+                tag = :tag_id
+            LIMIT 1;
+        ");
+
+        let cached_card = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(1);
+        });
+
+        match cached_card {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(card_id) => {
+                return Ok(Some(card_id));
+            }
+        };
+    }
+}
+
+impl ReviewableSelection for ReviewableTag {
+
+    // faster version
+    fn has_cards(&self) -> Result<bool, QueryError> {
+
+        match self.number_of_cards() {
+            Err(err) => {
+                return Err(err);
+            },
+            Ok(num_cards) => {
+                return Ok(num_cards > 0);
+            }
+        }
+    }
+
+    fn number_of_cards(&self) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":tag_id", &(self.tag_id))
+        ];
+
+        // suspended cards are excluded
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM CardsTags AS ct
+
+            INNER JOIN Cards AS c
+            ON c.card_id = ct.card
+
+            WHERE
+                ct.tag = :tag_id
+            AND
+                {reviewable};
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(count) => {
+                return Ok(count);
+            }
+        };
+    }
+
+    fn cache_card(&self, card_id: i64) -> Result<(), QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query = format!("
+            INSERT OR REPLACE INTO CachedTagReview(tag, card)
+            VALUES (:tag_id, :card_id);
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":tag_id", &(self.tag_id)),
+            (":card_id", &card_id)
+        ];
+
+
+        match db_conn.execute_named(query, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        return Ok(());
+    }
+
+    fn get_cached_card(&self) -> Result<Option<i64>, QueryError> {
+
+        match self.__get_cached_card() {
+            Err(why) => {
+                return Err(why);
+            },
+            Ok(None) => {
+                return Ok(None);
+            },
+            Ok(Some(card_id)) => {
+
+                match self.has_card(card_id) {
+                    Err(why) => {
+                        return Err(why);
+                    },
+                    Ok(true) => {
+                        return Ok(Some(card_id));
+                    },
+                    Ok(false) => {
+
+                        match self.remove_cache() {
+                            Err(why) => {
+                                return Err(why);
+                            },
+                            Ok(_) => {
+                                return Ok(None);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // remove tag/card review entry by tag
+    fn remove_cache(&self) -> Result<(), QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query_delete = format!("
+            DELETE FROM CachedTagReview WHERE tag = :tag_id;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":tag_id", &(self.tag_id))
+        ];
+
+        match db_conn.execute_named(query_delete, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_delete.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        return Ok(());
+    }
+
+    fn remove_cached_card(&self, card_id: i64) -> Result<(), QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        try!(DB::prepare_query(db_conn));
+
+        let ref query_delete = format!("
+            DELETE FROM CachedTagReview WHERE card = :card_id;
+        ");
+
+        let params: &[(&str, &ToSql)] = &[
+            (":card_id", &card_id)
+        ];
+
+        match db_conn.execute_named(query_delete, params) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query_delete.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+
+        return Ok(());
+    }
+
+    fn allows_new_cards(&self) -> bool {
+        return self.allow_new_cards;
+    }
+
+    fn is_new_card(&self, card_id: i64) -> Result<bool, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        match grokdb.review.get_review_stat(card_id) {
+            Err(why) => {
+                return Err(why);
+            },
+            Ok(review_stat) => {
+                return Ok(review_stat.is_new());
+            }
+        }
+    }
+
+    fn has_new_cards(&self) -> Result<bool, QueryError> {
+
+        match self.number_of_new_cards() {
+            Err(err) => {
+                return Err(err);
+            },
+            Ok(num_cards) => {
+                return Ok(num_cards > 0);
+            }
+        }
+    }
+
+    fn number_of_new_cards(&self) -> Result<i64, QueryError> {
+
+        if !self.allow_new_cards {
+            return Ok(0);
+        }
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":tag_id", &(self.tag_id))
+        ];
+
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM CardsTags AS ct
+
+            INNER JOIN Cards AS c
+            ON c.card_id = ct.card
+
+            INNER JOIN CardsScore AS cs
+            ON cs.card = c.card_id
+
+            WHERE
+                ct.tag = :tag_id
+            AND
+                {reviewable}
+            AND
+                (c.created_at - cs.seen_at) = 0;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(count) => {
+                return Ok(count);
+            }
+        };
+    }
+
+    // returns card id
+    fn get_new_card(&self, index: i64) -> Result<i64, QueryError> {
+
+        assert!(self.allow_new_cards);
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                c.card_id
+            FROM CardsTags AS ct
+
+            INNER JOIN Cards AS c
+            ON c.card_id = ct.card
+
+            INNER JOIN CardsScore AS cs
+            ON cs.card = c.card_id
+
+            WHERE
+                ct.tag = :tag_id
+            AND
+                {reviewable}
+            AND
+                (c.created_at - cs.seen_at) = 0
+            LIMIT 1
+            OFFSET :offset;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let params: &[(&str, &ToSql)] = &[
+            (":tag_id", &(self.tag_id)),
+            (":offset", &index),
+        ];
+
+        let maybe_card_id = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_card_id {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(card_id) => {
+                return Ok(card_id);
+            }
+        };
+    }
+
+    fn has_reviewable_cards(&self, age_in_hours: i64, min_score: f64) -> Result<bool, QueryError> {
+
+        match self.number_of_reviewable_cards(age_in_hours, min_score) {
+            Err(err) => {
+                return Err(err);
+            },
+            Ok(num_cards) => {
+                return Ok(num_cards > 0);
+            }
+        }
+    }
+
+    fn number_of_reviewable_cards(&self, age_in_hours: i64, min_score: f64) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM CardsTags AS ct
+
+            INNER JOIN Cards AS c
+            ON c.card_id = ct.card
+
+            INNER JOIN CardsScore AS cs
+            ON cs.card = c.card_id
+
+            WHERE
+                ct.tag = :tag_id
+            AND
+                {reviewable}
+            AND
+                (strftime('%s','now') - cs.seen_at) >= :age_of_consent
+            AND
+                raw_score(cs.success, cs.fail) >= :min_score;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let age_in_seconds: i64 = age_in_hours * 3600;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":age_of_consent", &age_in_seconds),
+            (":min_score", &min_score),
+            (":tag_id", &(self.tag_id))
+        ];
+
+        let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(count) => {
+                return Ok(count);
+            }
+        };
+    }
+
+    fn get_reviewable_card(&self, age_in_hours: i64, min_score: f64, index: i64) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        // TODO: use http://blog.ssokolow.com/archives/2009/12/23/sql-pagination-without-offset/
+        let ref query = format!("
+            SELECT
+                c.card_id
+            FROM CardsTags AS ct
+
+            INNER JOIN Cards AS c
+            ON c.card_id = ct.card
+
+            INNER JOIN CardsScore AS cs
+            ON cs.card = c.card_id
+
+            WHERE
+                ct.tag = :tag_id
+            AND
+                {reviewable}
+            AND
+                (strftime('%s','now') - cs.seen_at) >= :age_of_consent
+            AND
+                raw_score(cs.success, cs.fail) >= :min_score
+            ORDER BY
+                rank_score(cs.success, cs.fail, strftime('%s','now') - cs.seen_at, cs.times_reviewed) DESC
+            LIMIT 1
+            OFFSET :index;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let age_in_seconds: i64 = age_in_hours * 3600;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":age_of_consent", &age_in_seconds),
+            (":min_score", &min_score),
+            (":index", &index),
+            (":tag_id", &(self.tag_id))
+        ];
+
+        let maybe_card_id = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_card_id {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(card_id) => {
+                return Ok(card_id);
+            }
+        };
+    }
+
+    fn has_old_cards(&self, purgatory_size: i64, min_score: f64) -> Result<bool, QueryError> {
+
+        match self.number_of_old_cards(purgatory_size, min_score, false) {
+            Err(err) => {
+                return Err(err);
+            },
+            Ok(num_cards) => {
+                return Ok(num_cards > 0);
+            }
+        }
+    }
+
+    fn number_of_old_cards(&self, purgatory_size: i64, min_score: f64, sort_by_score: bool) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM
+            (
+                SELECT
+                    c.card_id, cs.success, cs.fail, cs.seen_at, cs.times_reviewed
+                FROM CardsTags AS ct
+
+                INNER JOIN Cards AS c
+                ON c.card_id = ct.card
+
+                INNER JOIN CardsScore AS cs
+                ON cs.card = c.card_id
+
+                WHERE
+                    ct.tag = :tag_id
+                AND
+                    {reviewable}
+                ORDER BY
+                    (strftime('%s','now') - cs.seen_at) DESC
+                LIMIT :purgatory_size
+            )
+            AS sub
+            WHERE
+                raw_score(sub.success, sub.fail) >= :min_score
+            {sort_by_score}
+            ;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards), sort_by_score = {
+            if sort_by_score {
+                "ORDER BY rank_score(sub.success, sub.fail, strftime('%s','now') - sub.seen_at, sub.times_reviewed) DESC"
+            } else {
+                ""
+            }
+        });
+
+        let params: &[(&str, &ToSql)] = &[
+            (":purgatory_size", &purgatory_size),
+            (":min_score", &min_score),
+            (":tag_id", &(self.tag_id))
+        ];
+
+        let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(count) => {
+                return Ok(count);
+            }
+        };
+    }
+
+    // returns card id
+    fn get_old_card(&self, purgatory_size: i64, min_score: f64, index: i64, sort_by_score: bool) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                sub.card_id
+            FROM
+            (
+                SELECT
+                    c.card_id, cs.success, cs.fail, cs.seen_at, cs.times_reviewed
+                FROM CardsTags AS ct
+
+                INNER JOIN Cards AS c
+                ON c.card_id = ct.card
+
+                INNER JOIN CardsScore AS cs
+                ON cs.card = c.card_id
+
+                WHERE
+                    ct.tag = :tag_id
+                AND
+                    {reviewable}
+                ORDER BY
+                    (strftime('%s','now') - cs.seen_at) DESC
+                LIMIT :purgatory_size
+            )
+            AS sub
+            WHERE
+                raw_score(sub.success, sub.fail) >= :min_score
+            {sort_by_score}
+            LIMIT 1 OFFSET :index;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards), sort_by_score = {
+            if sort_by_score {
+                "ORDER BY rank_score(sub.success, sub.fail, strftime('%s','now') - sub.seen_at, sub.times_reviewed) DESC"
+            } else {
+                ""
+            }
+        });
+
+        let params: &[(&str, &ToSql)] = &[
+            (":purgatory_size", &purgatory_size),
+            (":min_score", &min_score),
+            (":index", &index),
+            (":tag_id", &(self.tag_id))
+        ];
+
+        let maybe_card_id = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_card_id {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(card_id) => {
+                return Ok(card_id);
+            }
+        };
+    }
+
+    fn has_due_cards(&self) -> Result<bool, QueryError> {
+
+        match self.number_of_due_cards() {
+            Err(err) => {
+                return Err(err);
+            },
+            Ok(num_cards) => {
+                return Ok(num_cards > 0);
+            }
+        }
+    }
+
+    fn number_of_due_cards(&self) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":tag_id", &(self.tag_id))
+        ];
+
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM CardsTags AS ct
+
+            INNER JOIN Cards AS c
+            ON c.card_id = ct.card
+
+            INNER JOIN CardsSM2 AS sm
+            ON sm.card = c.card_id
+
+            WHERE
+                ct.tag = :tag_id
+            AND
+                {reviewable}
+            AND
+                sm.due_at <= strftime('%s','now');
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(count) => {
+                return Ok(count);
+            }
+        };
+    }
+
+    // returns card id
+    fn get_due_card(&self, index: i64) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                c.card_id
+            FROM CardsTags AS ct
+
+            INNER JOIN Cards AS c
+            ON c.card_id = ct.card
+
+            INNER JOIN CardsSM2 AS sm
+            ON sm.card = c.card_id
+
+            WHERE
+                ct.tag = :tag_id
+            AND
+                {reviewable}
+            AND
+                sm.due_at <= strftime('%s','now')
+            ORDER BY
+                sm.due_at ASC
+            LIMIT 1
+            OFFSET :index;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let params: &[(&str, &ToSql)] = &[
+            (":tag_id", &(self.tag_id)),
+            (":index", &index)
+        ];
+
+        let maybe_card_id = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_card_id {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(card_id) => {
+                return Ok(card_id);
+            }
+        };
+    }
+
+    fn has_fading_cards(&self, retention: f64) -> Result<bool, QueryError> {
+
+        match self.number_of_fading_cards(retention) {
+            Err(err) => {
+                return Err(err);
+            },
+            Ok(num_cards) => {
+                return Ok(num_cards > 0);
+            }
+        }
+    }
+
+    fn number_of_fading_cards(&self, retention: f64) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let params: &[(&str, &ToSql)] = &[
+            (":tag_id", &(self.tag_id)),
+            (":retention", &retention)
+        ];
+
+        let ref query = format!("
+            SELECT
+                COUNT(1)
+            FROM CardsTags AS ct
+
+            INNER JOIN Cards AS c
+            ON c.card_id = ct.card
+
+            INNER JOIN CardsFSRS AS f
+            ON f.card = c.card_id
+
+            WHERE
+                ct.tag = :tag_id
+            AND
+                {reviewable}
+            AND
+                fsrs_retrievability(CAST(strftime('%s','now') - f.reviewed_at AS REAL), f.stability) < :retention;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let maybe_count = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_count {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(count) => {
+                return Ok(count);
+            }
+        };
+    }
+
+    // returns card id
+    fn get_fading_card(&self, retention: f64, index: i64) -> Result<i64, QueryError> {
+
+        let ref grokdb = self.grokdb.deref();
+
+        let db_conn_guard = grokdb.tags.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        let ref query = format!("
+            SELECT
+                c.card_id
+            FROM CardsTags AS ct
+
+            INNER JOIN Cards AS c
+            ON c.card_id = ct.card
+
+            INNER JOIN CardsFSRS AS f
+            ON f.card = c.card_id
+
+            WHERE
+                ct.tag = :tag_id
+            AND
+                {reviewable}
+            AND
+                fsrs_retrievability(CAST(strftime('%s','now') - f.reviewed_at AS REAL), f.stability) < :retention
+            ORDER BY
+                fsrs_retrievability(CAST(strftime('%s','now') - f.reviewed_at AS REAL), f.stability) ASC
+            LIMIT 1
+            OFFSET :index;
+        ", reviewable = reviewable_cards_filter(self.allow_new_cards));
+
+        let params: &[(&str, &ToSql)] = &[
+            (":tag_id", &(self.tag_id)),
+            (":retention", &retention),
+            (":index", &index)
+        ];
+
+        let maybe_card_id = db_conn.query_row_named(query, params, |row| -> i64 {
+            return row.get(0);
+        });
+
+        match maybe_card_id {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            Ok(card_id) => {
+                return Ok(card_id);
+            }
+        };
+    }
+
+    // tags have no scheduler of their own; the default scheduler is used
+    fn get_scheduler_name(&self) -> Result<Option<String>, QueryError> {
+        return Ok(None);
+    }
+
+    // tags have no review settings of their own; the default settings are used
+    fn get_review_settings(&self) -> Result<Option<ReviewSettings>, QueryError> {
+        return Ok(None);
+    }
+}
@@ -0,0 +1,61 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rand::{thread_rng, Rng};
+
+
+// a file within the system's temporary directory (or the given directory); the file is deleted
+// once dropped
+pub struct TempFile {
+    pub path: PathBuf
+}
+
+impl TempFile {
+
+    pub fn new(prefix: &str, extension: &str) -> TempFile {
+        return TempFile::new_in(&env::temp_dir(), prefix, extension);
+    }
+
+    // e.g. to be renamed into place; a rename doesn't cross filesystems
+    pub fn new_in(dir: &Path, prefix: &str, extension: &str) -> TempFile {
+        return TempFile {
+            path: dir.join(format!("{}-{}.{}", prefix, thread_rng().gen::<u64>(), extension))
+        };
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// a directory within the system's temporary directory; the directory and its contents are
+// removed once dropped
+#[cfg(test)]
+pub struct TempDir {
+    pub path: PathBuf
+}
+
+#[cfg(test)]
+impl TempDir {
+
+    pub fn new(prefix: &str) -> Result<TempDir, ::std::io::Error> {
+
+        let path: PathBuf = env::temp_dir().join(format!("{}-{}", prefix, thread_rng().gen::<u64>()));
+
+        try!(fs::create_dir_all(&path));
+
+        return Ok(TempDir {
+            path: path
+        });
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -4,17 +4,22 @@ extern crate rusqlite;
 use std::error;
 use std::fmt;
 use std::ops::Deref;
+use std::path::Path;
 use std::sync::{Arc, Mutex, LockResult, MutexGuard};
 use libc::{c_int, c_double};
 
-use rusqlite::{Connection, Error, Result as SqliteResult};
+use rusqlite::{Connection, Error, Result as SqliteResult, SqliteStatement};
 use rusqlite::functions::{Context};
+use rusqlite::types::ToSql;
 
-use queries::tables;
+use migrations;
 
 #[derive(Debug)]
 pub struct DB {
-    pub db_conn: Arc<Mutex<Connection>>
+    pub db_conn: Arc<Mutex<Connection>>,
+
+    // path of the database file
+    pub database_name: String
 }
 
 impl DB {
@@ -77,6 +82,9 @@ impl error::Error for QueryError {
 pub enum BootstrapError {
     Query(QueryError),
     Sqlite(Error),
+
+    // schema version of the database, and latest version supported (see migrations)
+    UnsupportedVersion(i64, i64),
 }
 
 impl fmt::Display for BootstrapError {
@@ -84,6 +92,9 @@ impl fmt::Display for BootstrapError {
         return match *self {
             BootstrapError::Query(ref err) => write!(f, "{}", err),
             BootstrapError::Sqlite(ref err) => write!(f, "{}", err),
+            BootstrapError::UnsupportedVersion(version, supported) => {
+                write!(f, "database has schema version {}; this grokdb supports up to version {}", version, supported)
+            },
         };
     }
 }
@@ -93,6 +104,7 @@ impl error::Error for BootstrapError {
         return match *self {
             BootstrapError::Query(ref err) => err.description(),
             BootstrapError::Sqlite(ref err) => err.description(),
+            BootstrapError::UnsupportedVersion(_, _) => "database is newer than this grokdb",
         };
     }
 }
@@ -109,7 +121,32 @@ impl From<Error> for BootstrapError {
     }
 }
 
-pub fn bootstrap(database_name: String) -> Result<DB, BootstrapError> {
+// open the database, and migrate it to the latest schema version.
+// unless the database is new, it's backed up into backup_dir before migrating.
+// returns the schema version the database was migrated to (if it was migrated).
+pub fn bootstrap(database_name: String, backup_dir: &str) -> Result<(DB, Option<i64>), BootstrapError> {
+
+    let db_conn: Connection = try!(open(&database_name));
+
+    let backup_path: String = migrations::get_backup_path(&database_name, backup_dir);
+
+    let applied = try!(migrations::migrate(&db_conn, Some(Path::new(&backup_path))));
+
+    let migrated_to: Option<i64> = applied.last().map(|migration| migration.version);
+
+    let lock = Mutex::new(db_conn);
+    let arc = Arc::new(lock).clone();
+
+    let db_wrap = DB {
+        db_conn: arc,
+        database_name: database_name
+    };
+
+    return Ok((db_wrap, migrated_to));
+}
+
+// open db connection, and load the custom scalar functions used by queries
+pub fn open(database_name: &str) -> Result<Connection, BootstrapError> {
 
     // open db connection
     let db_conn = Connection::open(database_name);
@@ -178,52 +215,122 @@ pub fn bootstrap(database_name: String) -> Result<DB, BootstrapError> {
                 }
             }
 
-            let lock = Mutex::new(db_conn);
-            let arc = Arc::new(lock).clone();
+            // TODO: move this somewhere
+            match db_conn.create_scalar_function("fsrs_retrievability", 2, true, fsrs_retrievability) {
+                Err(why) => {
+                    return Err(BootstrapError::Sqlite(why));
+                },
+                _ => {
 
-            let db_wrap = DB {
-                db_conn: arc
-            };
+                    // ensure custom scalar function was loaded
 
-            {
-                let db = &db_wrap;
+                    let ref query = format!("
+                        SELECT fsrs_retrievability(0.0, 1.0);
+                    ");
 
-                match create_tables(db) {
-                    Err(why) => {
-                        // why: QueryError
-                        return Err(BootstrapError::Query(why));
-                    },
-                    _ => {/* queries sucessfully executed */},
+                    let maybe_result = db_conn.query_row(query, &[], |row| -> f64 {
+                        return row.get(0);
+                    });
+
+                    match maybe_result {
+                        Err(why) => {
+                            return Err(BootstrapError::Sqlite(why));
+                        },
+                        Ok(_/*result*/) => {
+                            // TODO: assert result is 1.0, otherwise panic
+                        }
+                    };
                 }
             }
 
-            return Ok(db_wrap);
+            return Ok(db_conn);
         }
     };
 }
 
-fn create_tables(db: &DB) -> Result<(), QueryError> {
+// bring a database without a backup (e.g. a new, or just restored, database) to the latest
+// schema version
+pub fn create_schema(db_conn: &Connection) -> Result<(), BootstrapError> {
 
-    let db_conn_guard = db.lock().unwrap();
-    let ref db_conn = *db_conn_guard;
+    try!(migrations::migrate(db_conn, None));
 
-    try!(DB::prepare_query(db_conn));
+    return Ok(());
+}
 
-    // execute every table setup query
-    for query in tables::SETUP.into_iter() {
+// value of the setting of Configs (if any).
+// caller should hold the db connection lock.
+pub fn get_config(db_conn: &Connection, setting: &str) -> Result<Option<String>, QueryError> {
 
-        let ref query = query.to_string();
+    let ref query = format!("
+        SELECT value
+        FROM Configs
+        WHERE setting = :setting LIMIT 1;
+    ");
 
-        match db_conn.execute_batch(query) {
-            Err(why) => {
-                let err = QueryError {
-                    sqlite_error: why,
-                    query: query.clone(),
-                };
-                return Err(err);
-            },
-            _ => {/* query sucessfully executed */},
+    let params: &[(&str, &ToSql)] = &[
+        (":setting", &setting)
+    ];
+
+    let maybe_stmt = db_conn.prepare(query);
+
+    if maybe_stmt.is_err() {
+
+        let why = maybe_stmt.unwrap_err();
+
+        let err = QueryError {
+            sqlite_error: why,
+            query: query.clone(),
+        };
+        return Err(err);
+    }
+
+    let mut stmt: SqliteStatement = maybe_stmt.unwrap();
+
+    let maybe_iter = stmt.query_named(params);
+
+    match maybe_iter {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(iter) => {
+
+            for result_row in iter {
+                match result_row {
+                    Err(why) => {
+                        let err = QueryError {
+                            sqlite_error: why,
+                            query: query.clone(),
+                        };
+                        return Err(err);
+                    },
+                    Ok(row) => {
+                        let value: Option<String> = row.get(0);
+                        return Ok(value);
+                    }
+                }
+            }
+
+            return Ok(None);
         }
+    };
+}
+
+// caller should hold the db connection lock.
+pub fn execute_named(db_conn: &Connection, query: &str, params: &[(&str, &ToSql)]) -> Result<(), QueryError> {
+
+    match db_conn.execute_named(query, params) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.to_string(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */}
     }
 
     return Ok(());
@@ -266,3 +373,17 @@ fn rank_score(ctx: &Context) -> SqliteResult<c_double> {
 
     return Ok(_rank_score);
 }
+
+// TODO: move this somewhere
+fn fsrs_retrievability(ctx: &Context) -> SqliteResult<c_double> {
+
+    // fsrs_retrievability(elapsed_seconds: real, stability: real) -> f64
+    assert!(ctx.len() == 2, "called with unexpected number of arguments");
+
+    let elapsed_seconds = try!(ctx.get::<c_double>(0));
+    let stability = try!(ctx.get::<c_double>(1));
+
+    let elapsed_days: c_double = elapsed_seconds / 86400f64;
+
+    return Ok(::api::review::fsrs::retrievability(elapsed_days, stability));
+}
@@ -18,16 +18,22 @@ extern crate urlencoded;
 // [end] iron framework plugins
 extern crate rusqlite;
 extern crate rustc_serialize;
+extern crate zip;
+extern crate sha1;
+extern crate sha2;
+extern crate libflate;
+extern crate csv;
 
 // local modules
 mod database;
+mod migrations;
 mod queries;
 mod api;
 
 // local scoped names
 use api::GrokDB;
 
-use clap::{Arg, App};
+use clap::{Arg, App, SubCommand, ArgMatches};
 // [begin] iron framework
 use iron::{Iron, Chain, AfterMiddleware, Response, Request, IronResult};
 use iron::error::{IronError};
@@ -39,6 +45,7 @@ use iron::middleware::Handler;
 // [end] iron framework
 
 use std::path::{Path};
+use std::fs::File;
 
 
 struct Custom404 {
@@ -142,6 +149,49 @@ fn main() {
                 }
             })
         )
+        .arg(
+            Arg::with_name("unsafe_backup_paths")
+            .long("unsafe-backup-paths")
+            .help("Allow backups requested over http to be written to any directory. By default, they're confined to the backup directory.")
+            .required(false)
+        )
+        .arg(
+            Arg::with_name("backup_interval")
+            .long("backup-interval")
+            .help("Take scheduled backups within the backup directory: hourly, daily, weekly, or every given number of minutes. Overrides the backup_interval setting.")
+            .takes_value(true)
+            .multiple(false)
+            .required(false)
+            .validator(|interval| {
+                match api::backup::scheduler::parse_interval(&interval) {
+                    Some(_) => {
+                        return Ok(());
+                    },
+                    None => {
+                        return Err(String::from("invalid backup interval; expected hourly, daily, weekly, or a number of minutes"));
+                    }
+                };
+            })
+        )
+        .arg(
+            Arg::with_name("seed")
+            .short("s")
+            .long("seed")
+            .help("Seed for choosing cards to review. Cards are chosen randomly by default.")
+            .takes_value(true)
+            .multiple(false)
+            .required(false)
+            .validator(|seed| {
+                match seed.trim().parse::<u64>() {
+                    Ok(_) => {
+                        return Ok(());
+                    },
+                    _ => {
+                        return Err(String::from("invalid seed"));
+                    }
+                };
+            })
+        )
         .arg(
             Arg::with_name("database_name")
             .help("Database name to store your flashcards")
@@ -155,6 +205,174 @@ fn main() {
                     return Ok(());
                 }
             })
+        )
+        .subcommand(
+            SubCommand::with_name("import-anki")
+            .about("Import an Anki package (.apkg) into decks and cards, then exit")
+            .arg(
+                Arg::with_name("file")
+                .help("Path to the .apkg file")
+                .required(true)
+                .index(1)
+            )
+            .arg(
+                Arg::with_name("parent")
+                .long("parent")
+                .help("Deck id to import decks under. Decks are imported as root decks by default.")
+                .takes_value(true)
+                .required(false)
+                .validator(|parent| {
+                    match parent.trim().parse::<u64>() {
+                        Ok(_) => {
+                            return Ok(());
+                        },
+                        _ => {
+                            return Err(String::from("invalid parent deck id"));
+                        }
+                    };
+                })
+            )
+            .arg(
+                Arg::with_name("scores")
+                .long("scores")
+                .help("Seed card scores from the Anki review log")
+                .required(false)
+            )
+            .arg(
+                Arg::with_name("media")
+                .long("media")
+                .help("Directory to extract media files to")
+                .takes_value(true)
+                .required(false)
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("export-anki")
+            .about("Export a deck and its descendents as an Anki package (.apkg), then exit")
+            .arg(
+                Arg::with_name("deck")
+                .help("Deck id to export")
+                .required(true)
+                .index(1)
+                .validator(|deck| {
+                    match deck.trim().parse::<u64>() {
+                        Ok(_) => {
+                            return Ok(());
+                        },
+                        _ => {
+                            return Err(String::from("invalid deck id"));
+                        }
+                    };
+                })
+            )
+            .arg(
+                Arg::with_name("file")
+                .help("Path to write the .apkg file to")
+                .required(true)
+                .index(2)
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("dump")
+            .about("Dump the whole database as a json document, then exit")
+            .arg(
+                Arg::with_name("file")
+                .help("Path to write the dump to")
+                .required(true)
+                .index(1)
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("restore-dump")
+            .about("Restore a json dump into the database, which should be empty, then exit")
+            .arg(
+                Arg::with_name("file")
+                .help("Path to the dump")
+                .required(true)
+                .index(1)
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("restore")
+            .about("Restore the database from a backup, then exit. A safety backup of the database is taken beforehand.")
+            .arg(
+                Arg::with_name("backup")
+                .help("Path to the backup, or name of a backup within the backup directory")
+                .required(true)
+                .index(1)
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+            .about("Migrate the database to the latest schema version, then exit. The database is backed up beforehand.")
+            .arg(
+                Arg::with_name("dry_run")
+                .long("dry-run")
+                .help("List the pending migrations without applying them")
+                .required(false)
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+            .about("Verify a backup (integrity, schema, and checksum within its manifest), then exit")
+            .arg(
+                Arg::with_name("backup")
+                .help("Path to the backup, or name of a backup within the backup directory")
+                .required(true)
+                .index(1)
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("export-markdown")
+            .about("Export a deck and its descendents as a directory of Markdown files, then exit")
+            .arg(
+                Arg::with_name("deck")
+                .help("Deck id to export")
+                .required(true)
+                .index(1)
+                .validator(|deck| {
+                    match deck.trim().parse::<u64>() {
+                        Ok(_) => {
+                            return Ok(());
+                        },
+                        _ => {
+                            return Err(String::from("invalid deck id"));
+                        }
+                    };
+                })
+            )
+            .arg(
+                Arg::with_name("dir")
+                .help("Directory to write the deck to")
+                .required(true)
+                .index(2)
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("import-markdown")
+            .about("Reconcile a directory of Markdown files into a deck, then exit")
+            .arg(
+                Arg::with_name("deck")
+                .help("Deck id to import into")
+                .required(true)
+                .index(1)
+                .validator(|deck| {
+                    match deck.trim().parse::<u64>() {
+                        Ok(_) => {
+                            return Ok(());
+                        },
+                        _ => {
+                            return Err(String::from("invalid deck id"));
+                        }
+                    };
+                })
+            )
+            .arg(
+                Arg::with_name("dir")
+                .help("Directory of the exported deck")
+                .required(true)
+                .index(2)
+            )
         ).get_matches();
 
     // fetch database name
@@ -172,8 +390,18 @@ fn main() {
 
     let database_name = database_name;
 
+    let backup_base_dest: Option<String> = cmd_matches.value_of("backup").map(|backup_path| {
+        format!("{}", backup_path.trim())
+    });
+
+    // migrate the database, then exit; the database isn't opened otherwise
+    if let Some(ref migrate_matches) = cmd_matches.subcommand_matches("migrate") {
+        migrate(&database_name, backup_base_dest, migrate_matches);
+        return;
+    }
+
     // set up api
-    let grokdb = api::new(database_name);
+    let grokdb = api::new(database_name, backup_base_dest);
 
     let grokdb: GrokDB = match grokdb {
 
@@ -187,15 +415,93 @@ fn main() {
 
     let mut grokdb = grokdb;
 
-    if let Some(ref backup_path) = cmd_matches.value_of("backup") {
-        let backup_path = backup_path.trim();
-        grokdb.backup_base_dest = Some(format!("{}", backup_path));
+    if let Some(version) = grokdb.migrated_to {
+        println!("Migrated database to schema version {}", version);
+    }
 
+    if let Some(ref backup_path) = grokdb.backup_base_dest {
         println!("Default back up path at: {}", backup_path);
     }
 
+    if cmd_matches.is_present("unsafe_backup_paths") {
+        grokdb.unsafe_backup_paths = true;
+
+        println!("Backups may be written to any directory");
+    }
+
+    if let Some(ref seed) = cmd_matches.value_of("seed") {
+
+        let seed: u64 = match seed.trim().parse::<u64>() {
+            Ok(seed) => seed,
+            _ => unreachable!() // should already be validated to be u64
+        };
+
+        grokdb.review_seed = Some(seed);
+
+        println!("Reviewing cards with seed: {}", seed);
+    }
+
     let grokdb = grokdb;
 
+    /* subcommands */
+
+    if let Some(ref import_matches) = cmd_matches.subcommand_matches("import-anki") {
+        import_anki(&grokdb, import_matches);
+        return;
+    }
+
+    if let Some(ref export_matches) = cmd_matches.subcommand_matches("export-anki") {
+        export_anki(&grokdb, export_matches);
+        return;
+    }
+
+    if let Some(ref dump_matches) = cmd_matches.subcommand_matches("dump") {
+        dump(&grokdb, dump_matches);
+        return;
+    }
+
+    if let Some(ref restore_matches) = cmd_matches.subcommand_matches("restore-dump") {
+        restore_dump(&grokdb, restore_matches);
+        return;
+    }
+
+    if let Some(ref restore_matches) = cmd_matches.subcommand_matches("restore") {
+        restore(&grokdb, restore_matches);
+        return;
+    }
+
+    if let Some(ref verify_matches) = cmd_matches.subcommand_matches("verify") {
+        verify(&grokdb, verify_matches);
+        return;
+    }
+
+    if let Some(ref export_matches) = cmd_matches.subcommand_matches("export-markdown") {
+        export_markdown(&grokdb, export_matches);
+        return;
+    }
+
+    if let Some(ref import_matches) = cmd_matches.subcommand_matches("import-markdown") {
+        import_markdown(&grokdb, import_matches);
+        return;
+    }
+
+    /* scheduled backups */
+
+    let backup_interval: Option<i64> = match cmd_matches.value_of("backup_interval") {
+        Some(interval) => {
+            let interval = api::backup::scheduler::parse_interval(interval);
+            // should already be validated
+            assert!(interval.is_some());
+
+            println!("Scheduled backups every {} minutes", interval.unwrap() / 60);
+
+            interval
+        },
+        None => None
+    };
+
+    api::backup::scheduler::start(grokdb.clone(), backup_interval);
+
     /* iron middleware */
     let mut router = Router::new();
     let mut mount = Mount::new();
@@ -279,3 +585,438 @@ fn main() {
     }
 
 }
+
+fn import_anki(grokdb: &GrokDB, import_matches: &ArgMatches) {
+
+    use api::import::anki::{import_apkg, AnkiImportOptions};
+    use database::DB;
+
+    let file_path = import_matches.value_of("file").unwrap().trim();
+
+    let parent: Option<i64> = match import_matches.value_of("parent") {
+        None => None,
+        Some(ref parent) => {
+            match parent.trim().parse::<u64>() {
+                Ok(parent) => Some(parent as i64),
+                _ => unreachable!() // should already be validated to be u64
+            }
+        }
+    };
+
+    if let Some(parent) = parent {
+        match grokdb.decks.exists(parent) {
+            Ok(true) => {/* continue */},
+            Ok(false) => {
+                println!("FATAL ERROR:\nparent deck #{} does not exist", parent);
+                std::process::exit(1);
+            },
+            Err(why) => {
+                println!("FATAL ERROR:\n{}", why);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let options = AnkiImportOptions {
+        parent: parent,
+        with_scores: import_matches.is_present("scores"),
+        media_dir: import_matches.value_of("media").map(|media_dir| media_dir.trim().to_string())
+    };
+
+    let file = match File::open(file_path) {
+        Ok(file) => file,
+        Err(why) => {
+            println!("FATAL ERROR:\nunable to open {}: {}", file_path, why);
+            std::process::exit(1);
+        }
+    };
+
+    let db_conn_guard = grokdb.decks.db.lock().unwrap();
+    let ref db_conn = *db_conn_guard;
+
+    match DB::prepare_query(db_conn) {
+        Err(why) => {
+            println!("FATAL ERROR:\n{}", why);
+            std::process::exit(1);
+        },
+        _ => {/* continue */}
+    }
+
+    match import_apkg(db_conn, file, &options) {
+        Err(why) => {
+            println!("FATAL ERROR:\nunable to import {}: {}", file_path, why);
+            std::process::exit(1);
+        },
+        Ok(response) => {
+            println!("Imported {} cards ({} with scores) into {} new decks from: {}",
+                response.cards_created, response.cards_scored, response.decks_created, file_path);
+
+            if response.media_files > 0 {
+                println!("Extracted {} media files", response.media_files);
+            }
+        }
+    }
+}
+
+fn export_anki(grokdb: &GrokDB, export_matches: &ArgMatches) {
+
+    use api::export::anki::export_apkg;
+
+    let file_path = export_matches.value_of("file").unwrap().trim();
+
+    let deck_id: i64 = match export_matches.value_of("deck").unwrap().trim().parse::<u64>() {
+        Ok(deck_id) => deck_id as i64,
+        _ => unreachable!() // should already be validated to be u64
+    };
+
+    match grokdb.decks.exists(deck_id) {
+        Ok(true) => {/* continue */},
+        Ok(false) => {
+            println!("FATAL ERROR:\ndeck #{} does not exist", deck_id);
+            std::process::exit(1);
+        },
+        Err(why) => {
+            println!("FATAL ERROR:\n{}", why);
+            std::process::exit(1);
+        }
+    }
+
+    let file = match File::create(file_path) {
+        Ok(file) => file,
+        Err(why) => {
+            println!("FATAL ERROR:\nunable to create {}: {}", file_path, why);
+            std::process::exit(1);
+        }
+    };
+
+    let db_conn_guard = grokdb.decks.db.lock().unwrap();
+    let ref db_conn = *db_conn_guard;
+
+    match export_apkg(db_conn, deck_id, &format!("grokdb:{}", grokdb.base_db_name), file) {
+        Err(why) => {
+            println!("FATAL ERROR:\nunable to export deck #{}: {}", deck_id, why);
+            std::process::exit(1);
+        },
+        Ok(response) => {
+            println!("Exported {} decks, {} cards and {} reviews to: {}",
+                response.decks_exported, response.cards_exported, response.reviews_exported, file_path);
+        }
+    }
+}
+
+fn dump(grokdb: &GrokDB, dump_matches: &ArgMatches) {
+
+    use std::io::Write;
+    use api::export::dump::dump_database;
+
+    let file_path = dump_matches.value_of("file").unwrap().trim();
+
+    let dump: String = {
+        let db_conn_guard = grokdb.decks.db.lock().unwrap();
+        let ref db_conn = *db_conn_guard;
+
+        match dump_database(db_conn) {
+            Ok(dump) => dump,
+            Err(why) => {
+                println!("FATAL ERROR:\nunable to dump database: {}", why);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    let mut file = match File::create(file_path) {
+        Ok(file) => file,
+        Err(why) => {
+            println!("FATAL ERROR:\nunable to create {}: {}", file_path, why);
+            std::process::exit(1);
+        }
+    };
+
+    match file.write_all(dump.as_bytes()) {
+        Ok(_) => {
+            println!("Dumped database to: {}", file_path);
+        },
+        Err(why) => {
+            println!("FATAL ERROR:\nunable to write {}: {}", file_path, why);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn restore_dump(grokdb: &GrokDB, restore_matches: &ArgMatches) {
+
+    use api::import::dump::restore_dump;
+    use database::DB;
+
+    let file_path = restore_matches.value_of("file").unwrap().trim();
+
+    let file = match File::open(file_path) {
+        Ok(file) => file,
+        Err(why) => {
+            println!("FATAL ERROR:\nunable to open {}: {}", file_path, why);
+            std::process::exit(1);
+        }
+    };
+
+    let db_conn_guard = grokdb.decks.db.lock().unwrap();
+    let ref db_conn = *db_conn_guard;
+
+    match DB::prepare_query(db_conn) {
+        Err(why) => {
+            println!("FATAL ERROR:\n{}", why);
+            std::process::exit(1);
+        },
+        _ => {/* continue */}
+    }
+
+    match restore_dump(db_conn, file) {
+        Err(why) => {
+            println!("FATAL ERROR:\nunable to restore {}: {}", file_path, why);
+            std::process::exit(1);
+        },
+        Ok(response) => {
+            println!("Restored {} rows from: {}", response.rows_restored, file_path);
+        }
+    }
+}
+
+fn export_markdown(grokdb: &GrokDB, export_matches: &ArgMatches) {
+
+    use std::path::Path;
+    use api::export::markdown::export_markdown;
+
+    let dir_path = export_matches.value_of("dir").unwrap().trim();
+
+    let deck_id: i64 = match export_matches.value_of("deck").unwrap().trim().parse::<u64>() {
+        Ok(deck_id) => deck_id as i64,
+        _ => unreachable!() // should already be validated to be u64
+    };
+
+    match grokdb.decks.exists(deck_id) {
+        Ok(true) => {/* continue */},
+        Ok(false) => {
+            println!("FATAL ERROR:\ndeck #{} does not exist", deck_id);
+            std::process::exit(1);
+        },
+        Err(why) => {
+            println!("FATAL ERROR:\n{}", why);
+            std::process::exit(1);
+        }
+    }
+
+    let db_conn_guard = grokdb.decks.db.lock().unwrap();
+    let ref db_conn = *db_conn_guard;
+
+    match export_markdown(db_conn, deck_id, Path::new(dir_path)) {
+        Err(why) => {
+            println!("FATAL ERROR:\nunable to export deck #{}: {}", deck_id, why);
+            std::process::exit(1);
+        },
+        Ok(response) => {
+            println!("Exported {} decks and {} cards to: {}",
+                response.decks_exported, response.cards_exported, dir_path);
+        }
+    }
+}
+
+fn import_markdown(grokdb: &GrokDB, import_matches: &ArgMatches) {
+
+    use std::path::Path;
+    use api::import::markdown::import_markdown;
+    use database::DB;
+
+    let dir_path = import_matches.value_of("dir").unwrap().trim();
+
+    let deck_id: i64 = match import_matches.value_of("deck").unwrap().trim().parse::<u64>() {
+        Ok(deck_id) => deck_id as i64,
+        _ => unreachable!() // should already be validated to be u64
+    };
+
+    match grokdb.decks.exists(deck_id) {
+        Ok(true) => {/* continue */},
+        Ok(false) => {
+            println!("FATAL ERROR:\ndeck #{} does not exist", deck_id);
+            std::process::exit(1);
+        },
+        Err(why) => {
+            println!("FATAL ERROR:\n{}", why);
+            std::process::exit(1);
+        }
+    }
+
+    let db_conn_guard = grokdb.decks.db.lock().unwrap();
+    let ref db_conn = *db_conn_guard;
+
+    match DB::prepare_query(db_conn) {
+        Err(why) => {
+            println!("FATAL ERROR:\n{}", why);
+            std::process::exit(1);
+        },
+        _ => {/* continue */}
+    }
+
+    match import_markdown(db_conn, deck_id, Path::new(dir_path)) {
+        Err(why) => {
+            println!("FATAL ERROR:\nunable to import {}: {}", dir_path, why);
+            std::process::exit(1);
+        },
+        Ok(response) => {
+            println!("Created {} decks and {} cards; updated {} decks and {} cards; {} cards unchanged",
+                response.decks_created, response.cards_created,
+                response.decks_updated, response.cards_updated, response.cards_unchanged);
+
+            for deleted in response.deleted.iter() {
+                println!("Card #{} ({}) has no file; it was not deleted", deleted.card_id, deleted.title);
+            }
+        }
+    }
+}
+
+fn restore(grokdb: &GrokDB, restore_matches: &ArgMatches) {
+
+    use std::path::{Path, PathBuf};
+    use api::backup::get_backup_dir;
+    use api::backup::restore::{find_backup, restore_backup};
+
+    let backup = restore_matches.value_of("backup").unwrap().trim();
+
+    let backup_dir: String = get_backup_dir(grokdb);
+
+    // a path to the backup, otherwise a name of a backup within the backup directory
+    let backup_path: PathBuf = if Path::new(backup).is_file() {
+        Path::new(backup).to_path_buf()
+    } else {
+        match find_backup(&backup_dir, backup) {
+            Ok(backup_path) => backup_path,
+            Err(why) => {
+                println!("FATAL ERROR:\n{}", why);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    match restore_backup(&grokdb.decks.db, &backup_path, &backup_dir, &grokdb.base_db_name) {
+        Err(why) => {
+            println!("FATAL ERROR:\nunable to restore {}: {}", backup_path.display(), why);
+            std::process::exit(1);
+        },
+        Ok(response) => {
+            println!("Safety backup of the database at: {}", response.safety_backup);
+            println!("Restored database from: {}", response.restored_from);
+        }
+    }
+}
+
+fn migrate(database_name: &String, backup_base_dest: Option<String>, migrate_matches: &ArgMatches) {
+
+    let db_conn = match database::open(database_name) {
+        Ok(db_conn) => db_conn,
+        Err(why) => {
+            println!("FATAL ERROR:\n{}", why);
+            std::process::exit(1);
+        }
+    };
+
+    let version: i64 = match migrations::get_version(&db_conn) {
+        Ok(version) => version,
+        Err(why) => {
+            println!("FATAL ERROR:\n{}", why);
+            std::process::exit(1);
+        }
+    };
+
+    let pending = match migrations::pending_migrations(&db_conn) {
+        Ok(pending) => pending,
+        Err(why) => {
+            println!("FATAL ERROR:\n{}", why);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Schema version: {} (latest: {})", version, migrations::latest_version());
+
+    if pending.len() <= 0 {
+        println!("Database is up to date");
+        return;
+    }
+
+    for migration in pending.iter() {
+        println!("Pending migration {}: {}", migration.version, migration.description);
+    }
+
+    if migrate_matches.is_present("dry_run") {
+        return;
+    }
+
+    let backup_dir: String = match backup_base_dest {
+        Some(backup_base_dest) => backup_base_dest,
+        None => format!("./")
+    };
+
+    let backup_path: String = migrations::get_backup_path(database_name, &backup_dir);
+
+    match migrations::migrate(&db_conn, Some(Path::new(&backup_path))) {
+        Err(why) => {
+            println!("FATAL ERROR:\nunable to migrate {}: {}", database_name, why);
+            std::process::exit(1);
+        },
+        Ok(_) => {
+            if Path::new(&backup_path).is_file() {
+                println!("Backup of the database at: {}", backup_path);
+            }
+            println!("Migrated database to schema version {}", migrations::latest_version());
+        }
+    }
+}
+
+fn verify(grokdb: &GrokDB, verify_matches: &ArgMatches) {
+
+    use std::path::{Path, PathBuf};
+    use api::backup::get_backup_dir;
+    use api::backup::restore::find_backup;
+    use api::backup::verify::verify_backup;
+
+    let backup = verify_matches.value_of("backup").unwrap().trim();
+
+    // a path to the backup, otherwise a name of a backup within the backup directory
+    let backup_path: PathBuf = if Path::new(backup).is_file() {
+        Path::new(backup).to_path_buf()
+    } else {
+        match find_backup(&get_backup_dir(grokdb), backup) {
+            Ok(backup_path) => backup_path,
+            Err(why) => {
+                println!("FATAL ERROR:\n{}", why);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    let response = match verify_backup(&backup_path) {
+        Err(why) => {
+            println!("FATAL ERROR:\nunable to verify {}: {}", backup_path.display(), why);
+            std::process::exit(1);
+        },
+        Ok(response) => response
+    };
+
+    println!("SHA-256: {}", response.sha256);
+
+    match response.manifest_sha256 {
+        Some(_) => {},
+        None => {
+            println!("No manifest found for {}", response.file);
+        }
+    }
+
+    if !response.valid {
+        println!("FATAL ERROR:\n{} failed verification:", response.file);
+
+        for problem in response.problems.iter() {
+            println!("- {}", problem);
+        }
+
+        std::process::exit(1);
+    }
+
+    println!("Verified backup: {}", response.file);
+}
@@ -0,0 +1,219 @@
+extern crate rusqlite;
+extern crate chrono;
+
+use std::path::Path;
+
+use chrono::*;
+use rusqlite::{Connection, DatabaseName};
+
+use database::{DB, QueryError, BootstrapError};
+use queries::tables;
+use api::backup::BACKUP_TIMESTAMP_FORMAT;
+
+
+// versioned schema migrations.
+//
+// the schema version of a database is kept in PRAGMA user_version. migrations are applied in
+// order of version, each within its own transaction along with the bump of user_version; so a
+// failed migration leaves the database at the previous version.
+//
+// databases from before migrations have version 0; the baseline migration creates any table,
+// index or trigger they don't have yet. new schema changes (e.g. a new column) are added as a new
+// migration at the end of MIGRATIONS; existing migrations should never be changed.
+//
+// databases with a version newer than the latest migration are refused.
+
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub queries: &'static [&'static str]
+}
+
+pub static MIGRATIONS: [Migration; 4] = [
+    Migration {
+        version: 1,
+        description: "baseline schema",
+        queries: &tables::SETUP
+    },
+    Migration {
+        version: 2,
+        description: "tags of cards",
+        queries: &tables::TAGS_SETUP
+    },
+    Migration {
+        version: 3,
+        description: "FSRS ratings of the review log",
+        queries: &tables::FSRS_RATING_SETUP
+    },
+    Migration {
+        version: 4,
+        description: "explicit ids of the review log",
+        queries: &tables::HISTORY_ID_SETUP
+    }
+];
+
+// latest schema version this grokdb supports
+pub fn latest_version() -> i64 {
+    return MIGRATIONS[MIGRATIONS.len() - 1].version;
+}
+
+pub fn get_version(db_conn: &Connection) -> Result<i64, QueryError> {
+
+    let ref query = format!("PRAGMA user_version;");
+
+    let version = db_conn.query_row(query, &[], |row| -> i64 {
+        return row.get(0);
+    });
+
+    match version {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(version) => {
+            return Ok(version);
+        }
+    };
+}
+
+// migrations that the database doesn't have yet, in order
+pub fn pending_migrations(db_conn: &Connection) -> Result<Vec<&'static Migration>, BootstrapError> {
+
+    let version: i64 = try!(get_version(db_conn));
+
+    if version > latest_version() {
+        return Err(BootstrapError::UnsupportedVersion(version, latest_version()));
+    }
+
+    let pending: Vec<&'static Migration> = MIGRATIONS.iter()
+        .filter(|migration| migration.version > version)
+        .collect();
+
+    return Ok(pending);
+}
+
+// apply pending migrations; returns the applied migrations.
+// unless the database is empty, it's backed up to backup_path beforehand (if given).
+pub fn migrate(db_conn: &Connection, backup_path: Option<&Path>) -> Result<Vec<&'static Migration>, BootstrapError> {
+
+    let pending: Vec<&'static Migration> = try!(pending_migrations(db_conn));
+
+    if pending.len() <= 0 {
+        return Ok(pending);
+    }
+
+    match backup_path {
+        Some(backup_path) => {
+            if !try!(is_empty(db_conn)) {
+                try!(db_conn.backup(DatabaseName::Main, backup_path, None));
+            }
+        },
+        None => {}
+    }
+
+    try!(DB::prepare_query(db_conn));
+
+    for migration in pending.iter() {
+        try!(apply(db_conn, migration));
+    }
+
+    return Ok(pending);
+}
+
+// path of the backup taken before migrating the database, within backup_dir
+pub fn get_backup_path(database_name: &str, backup_dir: &str) -> String {
+
+    let base_name: String = match Path::new(database_name).file_stem() {
+        Some(base_name) => base_name.to_string_lossy().into_owned(),
+        None => format!("grokdb")
+    };
+
+    let file_name: String = format!("{}-before-migration-v{}-{}.db",
+        base_name,
+        latest_version(),
+        UTC::now().format(BACKUP_TIMESTAMP_FORMAT).to_string());
+
+    return Path::new(backup_dir).join(file_name).to_string_lossy().into_owned();
+}
+
+fn apply(db_conn: &Connection, migration: &Migration) -> Result<(), QueryError> {
+
+    let tx = match db_conn.transaction() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("creating transaction"),
+            };
+            return Err(err);
+        },
+        Ok(tx) => tx
+    };
+
+    for query in migration.queries.iter() {
+
+        let ref query = query.to_string();
+
+        match db_conn.execute_batch(query) {
+            Err(why) => {
+                let err = QueryError {
+                    sqlite_error: why,
+                    query: query.clone(),
+                };
+                return Err(err);
+            },
+            _ => {/* query sucessfully executed */},
+        }
+    }
+
+    // pragma arguments can't be bound
+    let ref query_version = format!("PRAGMA user_version = {};", migration.version);
+
+    match db_conn.execute_batch(query_version) {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query_version.clone(),
+            };
+            return Err(err);
+        },
+        _ => {/* query sucessfully executed */},
+    }
+
+    match tx.commit() {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: format!("committing transaction"),
+            };
+            return Err(err);
+        },
+        _ => {/* commit successful */}
+    }
+
+    return Ok(());
+}
+
+fn is_empty(db_conn: &Connection) -> Result<bool, QueryError> {
+
+    let ref query = format!("SELECT COUNT(1) FROM sqlite_master;");
+
+    let count = db_conn.query_row(query, &[], |row| -> i64 {
+        return row.get(0);
+    });
+
+    match count {
+        Err(why) => {
+            let err = QueryError {
+                sqlite_error: why,
+                query: query.clone(),
+            };
+            return Err(err);
+        },
+        Ok(count) => {
+            return Ok(count <= 0);
+        }
+    };
+}
@@ -1,4 +1,4 @@
-pub const SETUP: [&'static str; 26] = [
+pub const SETUP: [&'static str; 45] = [
 
     // configs
 
@@ -69,6 +69,41 @@ pub const SETUP: [&'static str; 26] = [
     CACHED_DECK_REVIEW,
     CACHED_STASH_REVIEW,
 
+    // review/schedulers
+    DECKS_SCHEDULER,
+    STASHES_SCHEDULER,
+
+    // review/sm2
+    CARDS_SM2,
+    CARDS_SM2_DUE_AT_INDEX,
+
+    // review/fsrs
+    CARDS_FSRS,
+
+    // review/settings
+    DECKS_REVIEW_SETTINGS,
+    STASHES_REVIEW_SETTINGS,
+
+    // review/sessions
+    REVIEW_SESSIONS,
+    REVIEW_SESSIONS_CREATED_AT_INDEX,
+
+    // review/undo
+    CARDS_SCORE_UNDO,
+    CARDS_SCORE_UNDO_CARD_INDEX,
+
+    // review/history
+    CARDS_SCORE_HISTORY_CONTEXT,
+    CARDS_SCORE_HISTORY_CONTEXT_CARD_INDEX,
+
+    // review/leeches
+    CARDS_LEECH,
+
+    // review/suspension
+    SUSPENDED_CARDS,
+    BURIED_CARDS,
+    BURIED_CARDS_BURIED_UNTIL_INDEX,
+
     // FTS3/4 full-text searching sqlite module
     CARD_SEARCH_INDEX,
     CARD_SEARCH_FIRST_INDEX_TRIGGER,
@@ -375,6 +410,346 @@ CREATE TABLE IF NOT EXISTS CachedStashReview (
 );
 ";
 
+/* review/schedulers */
+
+// scheduler chosen for a deck.
+// decks without a chosen scheduler inherit the scheduler of their nearest ancestor.
+const DECKS_SCHEDULER: &'static str = "
+CREATE TABLE IF NOT EXISTS DecksScheduler (
+    deck INTEGER NOT NULL,
+    scheduler TEXT NOT NULL,
+
+    PRIMARY KEY(deck),
+
+    CHECK (scheduler <> ''),
+    FOREIGN KEY (deck) REFERENCES Decks(deck_id) ON DELETE CASCADE
+);
+";
+
+// scheduler chosen for a stash.
+const STASHES_SCHEDULER: &'static str = "
+CREATE TABLE IF NOT EXISTS StashesScheduler (
+    stash INTEGER NOT NULL,
+    scheduler TEXT NOT NULL,
+
+    PRIMARY KEY(stash),
+
+    CHECK (scheduler <> ''),
+    FOREIGN KEY (stash) REFERENCES Stashes(stash_id) ON DELETE CASCADE
+);
+";
+
+/* review/sm2 */
+
+// SM-2 schedule of cards.
+// a card only has a schedule after it is graded for the first time.
+// interval_days is the number of days between reviewed_at and due_at.
+const CARDS_SM2: &'static str = "
+CREATE TABLE IF NOT EXISTS CardsSM2 (
+
+    ease_factor REAL NOT NULL DEFAULT 2.5,
+    interval_days INT NOT NULL DEFAULT 0,
+    repetitions INT NOT NULL DEFAULT 0,
+
+    reviewed_at INT NOT NULL DEFAULT (strftime('%s', 'now')),
+    due_at INT NOT NULL DEFAULT (strftime('%s', 'now')),
+
+    card INTEGER NOT NULL,
+
+    PRIMARY KEY(card),
+
+    FOREIGN KEY (card) REFERENCES Cards(card_id) ON DELETE CASCADE
+);
+";
+
+const CARDS_SM2_DUE_AT_INDEX: &'static str = "
+CREATE INDEX IF NOT EXISTS CARDS_SM2_DUE_AT_INDEX
+ON CardsSM2 (due_at ASC);
+";
+
+/* review/fsrs */
+
+// FSRS memory state of cards.
+// a card only has a memory state after it is reviewed for the first time.
+// stability is in days; difficulty is within [1, 10].
+// lapses is the number of times the card was forgotten after its first review.
+const CARDS_FSRS: &'static str = "
+CREATE TABLE IF NOT EXISTS CardsFSRS (
+
+    stability REAL NOT NULL,
+    difficulty REAL NOT NULL,
+
+    reviews INT NOT NULL DEFAULT 0,
+    lapses INT NOT NULL DEFAULT 0,
+
+    reviewed_at INT NOT NULL DEFAULT (strftime('%s', 'now')),
+
+    card INTEGER NOT NULL,
+
+    PRIMARY KEY(card),
+
+    FOREIGN KEY (card) REFERENCES Cards(card_id) ON DELETE CASCADE
+);
+";
+
+/* review/settings */
+
+// review settings chosen for a deck.
+// a deck inherits the review settings of its nearest ancestor (see DecksClosure).
+const DECKS_REVIEW_SETTINGS: &'static str = "
+CREATE TABLE IF NOT EXISTS DecksReviewSettings (
+    deck INTEGER NOT NULL,
+
+    new_cards REAL NOT NULL,
+    old_enough REAL NOT NULL,
+    least_recent REAL NOT NULL,
+    min_score REAL NOT NULL,
+    age_in_hours INT NOT NULL,
+    purgatory_size REAL NOT NULL,
+
+    PRIMARY KEY(deck),
+
+    CHECK (new_cards >= 0 AND old_enough >= 0 AND least_recent > 0),
+    CHECK (min_score >= 0 AND min_score <= 1),
+    CHECK (age_in_hours >= 0),
+    CHECK (purgatory_size > 0 AND purgatory_size <= 1),
+    FOREIGN KEY (deck) REFERENCES Decks(deck_id) ON DELETE CASCADE
+);
+";
+
+// review settings chosen for a stash.
+const STASHES_REVIEW_SETTINGS: &'static str = "
+CREATE TABLE IF NOT EXISTS StashesReviewSettings (
+    stash INTEGER NOT NULL,
+
+    new_cards REAL NOT NULL,
+    old_enough REAL NOT NULL,
+    least_recent REAL NOT NULL,
+    min_score REAL NOT NULL,
+    age_in_hours INT NOT NULL,
+    purgatory_size REAL NOT NULL,
+
+    PRIMARY KEY(stash),
+
+    CHECK (new_cards >= 0 AND old_enough >= 0 AND least_recent > 0),
+    CHECK (min_score >= 0 AND min_score <= 1),
+    CHECK (age_in_hours >= 0),
+    CHECK (purgatory_size > 0 AND purgatory_size <= 1),
+    FOREIGN KEY (stash) REFERENCES Stashes(stash_id) ON DELETE CASCADE
+);
+";
+
+/* review/sessions */
+
+// study session of either a deck or a stash (but not both).
+// new_cards_per_day and reviews_per_day are the daily limits for all sessions of the deck or stash.
+const REVIEW_SESSIONS: &'static str = "
+CREATE TABLE IF NOT EXISTS ReviewSessions (
+    session_id INTEGER PRIMARY KEY NOT NULL,
+
+    deck INTEGER,
+    stash INTEGER,
+
+    status TEXT NOT NULL DEFAULT 'active',
+
+    new_cards_per_day INT NOT NULL,
+    reviews_per_day INT NOT NULL,
+
+    new_cards_reviewed INT NOT NULL DEFAULT 0,
+    cards_reviewed INT NOT NULL DEFAULT 0,
+
+    created_at INT NOT NULL DEFAULT (strftime('%s', 'now')),
+    updated_at INT NOT NULL DEFAULT (strftime('%s', 'now')),
+    finished_at INT,
+
+    CHECK ((deck IS NULL) <> (stash IS NULL)),
+    CHECK (status IN ('active', 'finished', 'abandoned')),
+    CHECK (new_cards_per_day >= 0 AND reviews_per_day >= 0),
+    FOREIGN KEY (deck) REFERENCES Decks(deck_id) ON DELETE CASCADE,
+    FOREIGN KEY (stash) REFERENCES Stashes(stash_id) ON DELETE CASCADE
+);
+";
+
+const REVIEW_SESSIONS_CREATED_AT_INDEX: &'static str = "
+CREATE INDEX IF NOT EXISTS REVIEW_SESSIONS_CREATED_AT_INDEX
+ON ReviewSessions (created_at DESC);
+";
+
+/* review/undo */
+
+// snapshot of a card before a review action, for undoing the action.
+// history is the history_id of the CardsScoreHistory entry created by the review action.
+// sm2_* and fsrs_* columns are null if the card had no SM-2 schedule or FSRS memory state.
+// container_reviewed_at is null if the deck or stash wasn't updated by the review action.
+// undone review actions are marked as reverted.
+const CARDS_SCORE_UNDO: &'static str = "
+CREATE TABLE IF NOT EXISTS CardsScoreUndo (
+    history INTEGER NOT NULL,
+    card INTEGER NOT NULL,
+
+    success INTEGER NOT NULL,
+    fail INTEGER NOT NULL,
+    times_reviewed INT NOT NULL,
+    times_seen INT NOT NULL,
+    seen_at INT NOT NULL,
+    reviewed_at INT NOT NULL,
+    changelog TEXT NOT NULL,
+
+    sm2_ease_factor REAL,
+    sm2_interval_days INT,
+    sm2_repetitions INT,
+    sm2_reviewed_at INT,
+    sm2_due_at INT,
+
+    fsrs_stability REAL,
+    fsrs_difficulty REAL,
+    fsrs_reviews INT,
+    fsrs_lapses INT,
+    fsrs_reviewed_at INT,
+
+    deck INTEGER,
+    stash INTEGER,
+    container_reviewed_at INT,
+
+    session INTEGER,
+    is_new_card INT NOT NULL DEFAULT 0,
+
+    reverted INT NOT NULL DEFAULT 0,
+    reverted_at INT,
+
+    PRIMARY KEY(history),
+
+    FOREIGN KEY (card) REFERENCES Cards(card_id) ON DELETE CASCADE,
+    FOREIGN KEY (deck) REFERENCES Decks(deck_id) ON DELETE SET NULL,
+    FOREIGN KEY (stash) REFERENCES Stashes(stash_id) ON DELETE SET NULL,
+    FOREIGN KEY (session) REFERENCES ReviewSessions(session_id) ON DELETE SET NULL
+);
+";
+
+const CARDS_SCORE_UNDO_CARD_INDEX: &'static str = "
+CREATE INDEX IF NOT EXISTS CARDS_SCORE_UNDO_CARD_INDEX
+ON CardsScoreUndo (card, reverted);
+";
+
+/* review/history */
+
+// context of the review action of a CardsScoreHistory entry.
+// history is the history_id of the CardsScoreHistory entry created by the review action.
+// deck, stash and session are the container the card was reviewed in (if any).
+// answer_time_ms and front_time_ms are null if they weren't given.
+const CARDS_SCORE_HISTORY_CONTEXT: &'static str = "
+CREATE TABLE IF NOT EXISTS CardsScoreHistoryContext (
+    history INTEGER NOT NULL,
+    card INTEGER NOT NULL,
+
+    deck INTEGER,
+    stash INTEGER,
+    session INTEGER,
+
+    answer_time_ms INTEGER CHECK (answer_time_ms IS NULL OR answer_time_ms >= 0),
+    front_time_ms INTEGER CHECK (front_time_ms IS NULL OR front_time_ms >= 0),
+
+    PRIMARY KEY(history),
+
+    FOREIGN KEY (card) REFERENCES Cards(card_id) ON DELETE CASCADE,
+    FOREIGN KEY (deck) REFERENCES Decks(deck_id) ON DELETE SET NULL,
+    FOREIGN KEY (stash) REFERENCES Stashes(stash_id) ON DELETE SET NULL,
+    FOREIGN KEY (session) REFERENCES ReviewSessions(session_id) ON DELETE SET NULL
+);
+";
+
+const CARDS_SCORE_HISTORY_CONTEXT_CARD_INDEX: &'static str = "
+CREATE INDEX IF NOT EXISTS CARDS_SCORE_HISTORY_CONTEXT_CARD_INDEX
+ON CardsScoreHistoryContext (card);
+";
+
+/* review/leeches */
+
+// lapses is the number of lapses counted when the card was last checked.
+// cleared_at is when the leech was last cleared; lapses before it aren't counted.
+const CARDS_LEECH: &'static str = "
+CREATE TABLE IF NOT EXISTS CardsLeech (
+    card INTEGER NOT NULL,
+
+    is_leech INT NOT NULL DEFAULT 1,
+    lapses INT NOT NULL DEFAULT 0 CHECK (lapses >= 0),
+
+    marked_at INT NOT NULL DEFAULT (strftime('%s', 'now')),
+    cleared_at INT,
+
+    PRIMARY KEY(card),
+
+    FOREIGN KEY (card) REFERENCES Cards(card_id) ON DELETE CASCADE
+);
+";
+
+/* review/suspension */
+
+// cards excluded from review until they're resumed.
+// reason is why the card was suspended (e.g. leech); empty if it was suspended manually.
+const SUSPENDED_CARDS: &'static str = "
+CREATE TABLE IF NOT EXISTS SuspendedCards (
+    card INTEGER NOT NULL,
+
+    reason TEXT NOT NULL DEFAULT '',
+    suspended_at INT NOT NULL DEFAULT (strftime('%s', 'now')),
+
+    PRIMARY KEY(card),
+
+    FOREIGN KEY (card) REFERENCES Cards(card_id) ON DELETE CASCADE
+);
+";
+
+// cards excluded from review until buried_until.
+// entries whose buried_until has passed are ignored.
+const BURIED_CARDS: &'static str = "
+CREATE TABLE IF NOT EXISTS BuriedCards (
+    card INTEGER NOT NULL,
+
+    buried_until INT NOT NULL,
+    buried_at INT NOT NULL DEFAULT (strftime('%s', 'now')),
+
+    PRIMARY KEY(card),
+
+    FOREIGN KEY (card) REFERENCES Cards(card_id) ON DELETE CASCADE
+);
+";
+
+const BURIED_CARDS_BURIED_UNTIL_INDEX: &'static str = "
+CREATE INDEX IF NOT EXISTS BURIED_CARDS_BURIED_UNTIL_INDEX
+ON BuriedCards (buried_until);
+";
+
+/* backup/log */
+
+// a backup run; either scheduled or requested (PUT /backup).
+// error is empty unless the backup failed.
+// pruned_at is when the backup file was deleted by the retention policy (scheduled backups only).
+const BACKUP_LOG: &'static str = "
+CREATE TABLE IF NOT EXISTS BackupLog (
+    backup_id INTEGER PRIMARY KEY NOT NULL,
+
+    kind TEXT NOT NULL,
+    dest_file TEXT NOT NULL,
+
+    status TEXT NOT NULL,
+    error TEXT NOT NULL DEFAULT '',
+    size INT,
+
+    started_at INT NOT NULL,
+    finished_at INT NOT NULL,
+    pruned_at INT,
+
+    CHECK (kind IN ('scheduled', 'manual')),
+    CHECK (status IN ('success', 'failed'))
+);
+";
+
+const BACKUP_LOG_STARTED_AT_INDEX: &'static str = "
+CREATE INDEX IF NOT EXISTS BACKUP_LOG_STARTED_AT_INDEX
+ON BackupLog (started_at DESC);
+";
+
 const CARD_SEARCH_INDEX: &'static str = "
 CREATE VIRTUAL TABLE IF NOT EXISTS
     CardsFTS
@@ -415,3 +790,131 @@ BEGIN
     VALUES (NEW.card_id, NEW.title, NEW.description, NEW.front, NEW.back);
 END;
 ";
+
+/* tags (migration 2) */
+
+// tables added after the baseline schema; see migrations.
+// these aren't replayed, so they aren't created IF NOT EXISTS.
+pub const TAGS_SETUP: [&'static str; 4] = [
+    TAGS,
+    CARDS_TAGS,
+    CARDS_TAGS_TAG_INDEX,
+    CACHED_TAG_REVIEW
+];
+
+// note: CHECK (name <> '') ensures name is non-empty string
+const TAGS: &'static str = "
+CREATE TABLE Tags (
+    tag_id INTEGER PRIMARY KEY NOT NULL,
+
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+
+    created_at INT NOT NULL DEFAULT (strftime('%s', 'now')),
+
+    CHECK (name <> '')
+);
+";
+
+// tags of a card
+const CARDS_TAGS: &'static str = "
+CREATE TABLE CardsTags (
+
+    card INTEGER NOT NULL,
+    tag INTEGER NOT NULL,
+
+    tagged_at INT NOT NULL DEFAULT (strftime('%s', 'now')),
+
+    PRIMARY KEY(card, tag),
+
+    FOREIGN KEY (card) REFERENCES Cards(card_id) ON DELETE CASCADE,
+    FOREIGN KEY (tag) REFERENCES Tags(tag_id) ON DELETE CASCADE
+);
+";
+
+const CARDS_TAGS_TAG_INDEX: &'static str = "
+CREATE INDEX CARDS_TAGS_TAG_INDEX
+ON CardsTags (tag);
+";
+
+const CACHED_TAG_REVIEW: &'static str = "
+CREATE TABLE CachedTagReview (
+    tag INTEGER NOT NULL,
+    card INTEGER NOT NULL,
+    created_at INT NOT NULL DEFAULT (strftime('%s', 'now')),
+
+    PRIMARY KEY(tag),
+
+    FOREIGN KEY (tag) REFERENCES Tags(tag_id) ON DELETE CASCADE,
+    FOREIGN KEY (card) REFERENCES Cards(card_id) ON DELETE CASCADE
+);
+";
+
+// rating each review was made with under FSRS (see review::fsrs::rating_from_grade);
+// null if the review action wasn't graded, or if it was made before ratings were recorded.
+pub const FSRS_RATING_SETUP: [&'static str; 1] = [
+    CARDS_SCORE_HISTORY_CONTEXT_FSRS_RATING
+];
+
+const CARDS_SCORE_HISTORY_CONTEXT_FSRS_RATING: &'static str = "
+ALTER TABLE CardsScoreHistoryContext
+ADD COLUMN fsrs_rating INTEGER CHECK (fsrs_rating IS NULL OR (fsrs_rating >= 1 AND fsrs_rating <= 4));
+";
+
+/* review history ids (migration 4) */
+
+// CardsScoreHistory entries are given an explicit id; entries were keyed by their implicit
+// rowid, which isn't kept by VACUUM. the table is rebuilt keeping the rowid of every entry as
+// its id; so CardsScoreUndo and CardsScoreHistoryContext still refer to the same entries.
+// the snapshot trigger is recreated, since it refers to the table.
+pub const HISTORY_ID_SETUP: [&'static str; 8] = [
+    DROP_SNAPSHOT_CARDS_SCORE_ON_UPDATED_TRIGGER,
+    CARDS_SCORE_HISTORY_WITH_ID,
+    COPY_CARDS_SCORE_HISTORY,
+    DROP_CARDS_SCORE_HISTORY,
+    RENAME_CARDS_SCORE_HISTORY,
+    CARDS_SCORE_HISTORY_CARD_INDEX,
+    CARDS_SCORE_HISTORY_OCCURRED_AT_INDEX,
+    SNAPSHOT_CARDS_SCORE_ON_UPDATED_TRIGGER
+];
+
+const DROP_SNAPSHOT_CARDS_SCORE_ON_UPDATED_TRIGGER: &'static str = "
+DROP TRIGGER IF EXISTS SNAPSHOT_CARDS_SCORE_ON_UPDATED_TRIGGER;
+";
+
+const CARDS_SCORE_HISTORY_WITH_ID: &'static str = "
+CREATE TABLE CardsScoreHistoryWithId (
+    history_id INTEGER PRIMARY KEY NOT NULL,
+
+    occurred_at INT NOT NULL DEFAULT (strftime('%s', 'now')),
+
+    is_review_event INT NOT NULL DEFAULT 0,
+
+    success INTEGER NOT NULL DEFAULT 0,
+    fail INTEGER NOT NULL DEFAULT 0,
+
+    total_success INTEGER NOT NULL DEFAULT 0,
+    total_fail INTEGER NOT NULL DEFAULT 0,
+
+    changelog TEXT NOT NULL DEFAULT '',
+
+    card INTEGER NOT NULL,
+
+    FOREIGN KEY (card) REFERENCES Cards(card_id) ON DELETE CASCADE
+);
+";
+
+const COPY_CARDS_SCORE_HISTORY: &'static str = "
+INSERT INTO CardsScoreHistoryWithId(
+    history_id, occurred_at, is_review_event, success, fail, total_success, total_fail, changelog, card
+)
+SELECT oid, occurred_at, is_review_event, success, fail, total_success, total_fail, changelog, card
+FROM CardsScoreHistory;
+";
+
+const DROP_CARDS_SCORE_HISTORY: &'static str = "
+DROP TABLE CardsScoreHistory;
+";
+
+const RENAME_CARDS_SCORE_HISTORY: &'static str = "
+ALTER TABLE CardsScoreHistoryWithId RENAME TO CardsScoreHistory;
+";